@@ -13,7 +13,7 @@ use std::slice;
 
 
 /// A set of variable indices.
-#[derive(Default,Debug)]
+#[derive(Default,Debug,PartialEq,Eq)]
 pub struct Bitset {
     head: usize,
     // You can take out the Box here and it will still compile (and, with more
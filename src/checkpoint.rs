@@ -0,0 +1,127 @@
+//! Binary cache of parsed segments, for fast cold starts.
+//!
+//! Parsing is the one pass which must look at every byte of the database, so
+//! it dominates the time to load a large database in a fresh process.  A
+//! checkpoint file records the parsed segments of every source slice which
+//! parsed without errors, keyed by the slice's text; loading it fills the
+//! second cache of the `SegmentSet` (see the `segment_set` module comment), so
+//! the next `read` only compares each slice with its cached text instead of
+//! parsing it.  Slices whose text changed in the meantime are simply parsed
+//! again, so a stale checkpoint is harmless.
+//!
+//! The later passes are derived from the segments and are recomputed as usual.
+//!
+//! The format is private to this version of the crate: a magic number and
+//! `VERSION`, which must be incremented whenever the layout of `Segment`
+//! changes, followed by the slices.  All integers are little-endian `u32`.
+
+use parser::Buffer;
+use parser::BufferRef;
+use parser::Segment;
+use parser::Span;
+use std::io;
+use std::io::Write;
+use std::sync::Arc;
+
+const MAGIC: &[u8; 8] = b"MMKNIFE\0";
+
+/// Version of the checkpoint format.
+pub const VERSION: u32 = 1;
+
+/// Appends an integer to an encoding buffer.
+pub fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&[value as u8, (value >> 8) as u8, (value >> 16) as u8,
+                            (value >> 24) as u8]);
+}
+
+/// Appends a length-prefixed byte string to an encoding buffer.
+pub fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    put_u32(out, bytes.len() as u32);
+    out.extend_from_slice(bytes);
+}
+
+/// Appends a span to an encoding buffer.
+pub fn put_span(out: &mut Vec<u8>, span: Span) {
+    put_u32(out, span.start);
+    put_u32(out, span.end);
+}
+
+/// Reads an integer, advancing `input` past it.
+pub fn get_u32(input: &mut &[u8]) -> Option<u32> {
+    if input.len() < 4 {
+        return None;
+    }
+    let value = input[0] as u32 | (input[1] as u32) << 8 | (input[2] as u32) << 16 |
+                (input[3] as u32) << 24;
+    *input = &input[4..];
+    Some(value)
+}
+
+/// Reads a length-prefixed byte string, advancing `input` past it.
+pub fn get_bytes<'a>(input: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = get_u32(input)? as usize;
+    if input.len() < len {
+        return None;
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Some(bytes)
+}
+
+/// Reads a span, advancing `input` past it.
+pub fn get_span(input: &mut &[u8]) -> Option<Span> {
+    let start = get_u32(input)?;
+    let end = get_u32(input)?;
+    Some(Span::new(start as usize, end as usize))
+}
+
+/// Writes a checkpoint of slices and the segments parsed from them.  Slices
+/// with parse errors are skipped, since diagnostics are not recorded.
+pub fn write_checkpoint<W: Write>(slices: &[(BufferRef, Vec<Arc<Segment>>)],
+                                  out: &mut W)
+                                  -> io::Result<()> {
+    let slices: Vec<_> = slices.iter()
+        .filter(|slice| slice.1.iter().all(|seg| seg.diagnostics.is_empty()))
+        .collect();
+    let mut buf = MAGIC.to_vec();
+    put_u32(&mut buf, VERSION);
+    put_u32(&mut buf, slices.len() as u32);
+    for slice in slices {
+        put_bytes(&mut buf, &slice.0);
+        put_u32(&mut buf, slice.1.len() as u32);
+        for seg in &slice.1 {
+            seg.encode(&mut buf);
+        }
+    }
+    out.write_all(&buf)
+}
+
+/// Reads the slices and segments of a checkpoint.
+///
+/// Fails with `InvalidData` if the data is not a checkpoint of the current
+/// version, is truncated, or describes segments which are not consistent.
+pub fn read_checkpoint(data: &[u8]) -> io::Result<Vec<(BufferRef, Vec<Arc<Segment>>)>> {
+    fn read(mut input: &[u8]) -> Option<Vec<(BufferRef, Vec<Arc<Segment>>)>> {
+        if !input.starts_with(MAGIC) {
+            return None;
+        }
+        input = &input[MAGIC.len()..];
+        if get_u32(&mut input)? != VERSION {
+            return None;
+        }
+        let count = get_u32(&mut input)?;
+        let mut out = Vec::new();
+        for _ in 0..count {
+            let text: BufferRef = Arc::new(Buffer::from(get_bytes(&mut input)?.to_vec()));
+            let mut segments = Vec::new();
+            for _ in 0..get_u32(&mut input)? {
+                segments.push(Arc::new(Segment::decode(&text, &mut input)?));
+            }
+            out.push((text, segments));
+        }
+        if input.is_empty() { Some(out) } else { None }
+    }
+    read(data).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "not a checkpoint of this version")
+    })
+}
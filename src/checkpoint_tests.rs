@@ -0,0 +1,64 @@
+use checkpoint::read_checkpoint;
+use database::Database;
+use database::DbOptions;
+use diag::DiagnosticClass;
+use std::env;
+use std::fs;
+use std::io;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_checkpoint() {
+    let text = b"$c X $.\n$v x $.\nvx $f X x $.\nax $a X x $.\n$( $x $)\n";
+    let path = env::temp_dir().join(format!("metamath-knife-ckpt-{}", ::std::process::id()));
+    let path = path.to_str().unwrap();
+    mkdb(text).save_checkpoint(path).unwrap();
+    // a segment with parse errors is not recorded
+    mkdb(b"$c X $q $.\n").save_checkpoint(&format!("{}.err", path)).unwrap();
+
+    let mut db = Database::new(DbOptions::default());
+    assert_eq!(db.load_checkpoint(path).unwrap(), 1);
+    assert_eq!(db.load_checkpoint(&format!("{}.err", path)).unwrap(), 0);
+    fs::remove_file(path).unwrap();
+    fs::remove_file(format!("{}.err", path)).unwrap();
+    db.parse("test.mm".to_owned(), vec![("test.mm".to_owned(), text.to_vec())]);
+    let sset = db.parse_result().clone();
+    let stmt = db.statement("ax").unwrap();
+    assert_eq!(stmt.math_len(), 2);
+    assert_eq!(sset.statement(stmt.address()).label(), b"ax");
+    assert!(db.diag_notations(vec![DiagnosticClass::Parse, DiagnosticClass::Scope]).is_empty());
+
+    assert_eq!(read_checkpoint(b"MMKNIFE").unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_corrupted_checkpoint() {
+    let text = b"$c X $.\n$v x $.\nvx $f X x $.\nax $a X x $.\n";
+    let mut data = Vec::new();
+    mkdb(text).parse_result().write_checkpoint(&mut data).unwrap();
+    assert_eq!(read_checkpoint(&data).unwrap().len(), 1);
+    // magic, version, slice count, slice text, segment count, statement count
+    let stmt = 8 + 4 + 4 + 4 + text.len() + 4 + 4;
+    // type, span and label come before the statement indices and token ranges
+    for &field in &[20, 24, 36] {
+        let mut bad = data.clone();
+        bad[stmt + field..stmt + field + 4].copy_from_slice(&[0, 1, 0, 0]);
+        assert_eq!(read_checkpoint(&bad).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
+
+#[test]
+fn test_corrupted_checkpoint_ordinal() {
+    let text = b"$c X $.\n$v x $.\nvx $f X x $.\n${ $v y $. $}\nax $a X x $.\n";
+    let mut data = Vec::new();
+    mkdb(text).parse_result().write_checkpoint(&mut data).unwrap();
+    assert_eq!(read_checkpoint(&data).unwrap().len(), 1);
+    // the variable x, followed by its start and ordinal, then the local
+    // variable y as a count, an index and an ordinal
+    let var = data.windows(9).position(|w| w == b"\x01\0\0\0x\0\0\0\0").unwrap() + 9;
+    for &ordinal in &[var + 4, var + 16] {
+        let mut bad = data.clone();
+        bad[ordinal..ordinal + 4].copy_from_slice(&[0, 1, 0, 0]);
+        assert_eq!(read_checkpoint(&bad).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,260 @@
+//! Splitting of comments into their markup elements.
+//!
+//! Comments use the light markup of metamath.exe's HTML generator: a `~`
+//! followed by a label is a reference to a statement (or, if the label starts
+//! with `http://` or `https://`, a link), math text between backquotes is
+//! typeset using the `$t` definitions, and a word in brackets such as
+//! `[Monk1]` refers to the bibliography.  Doubled markup characters (`~~`,
+//! ` `` ` and `[[`) stand for the character itself.
+//!
+//! Backquotes and tildes are only recognized as separate words, as in
+//! metamath.exe; a closing backquote may however be directly followed by
+//! punctuation, as in `` ` ph `, ``.  Items are returned with spans into the
+//! segment buffer, so they can be used both for diagnostics and for edits.
+//!
+//! Some conventions of set.mm which metamath.exe renders as plain text are
+//! recognized as well:
+//!
+//! * List markers at the start of a line: `-` and `*` for bulleted lists, and
+//!   `1.`, `1)`, `(1)`, `(a)` or `(iv)` for numbered ones.  The column of the
+//!   marker is returned with it, so that nested lists can be told apart by
+//!   their indentation.
+//! * The `(Contributed by NM, 5-Aug-1993.)`, `(Revised by ...)` and
+//!   `(Proof shortened by ...)` parentheticals which record the history of a
+//!   statement, with their dates parsed.
+
+use history::Date;
+use history::RevisionKind;
+use parser::Span;
+use parser::StatementRef;
+use std::str;
+
+/// A markup element of a comment.
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum CommentItem {
+    /// A run of ordinary words, including escaped markup characters.
+    Text(Span),
+    /// The label of a `~ label` reference.
+    Label(Span),
+    /// The address of a `~ http://...` link.
+    Url(Span),
+    /// The backquote opening math text.
+    StartMath(Span),
+    /// A math symbol.
+    MathToken(Span),
+    /// The backquote closing math text.
+    EndMath(Span),
+    /// The tag of a `[tag]` bibliographic reference, without the brackets.
+    BibTag(Span),
+    /// A list marker at the start of a line, with its column (zero-based, in
+    /// bytes) as an indication of the nesting depth.
+    ListItem(Span, u32),
+    /// A contributor parenthetical.
+    Contribution(Contribution),
+}
+
+const CONTRIBUTION_PHRASES: [(&[&[u8]], RevisionKind); 3] =
+    [(&[b"Contributed", b"by"], RevisionKind::Contributed),
+     (&[b"Revised", b"by"], RevisionKind::Revised),
+     (&[b"Proof", b"shortened", b"by"], RevisionKind::ProofShortened)];
+
+/// A `(Contributed by NM, 5-Aug-1993.)` parenthetical and its parts.
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub struct Contribution {
+    /// The whole parenthetical, including the parentheses.
+    pub span: Span,
+    /// What the parenthetical records.
+    pub kind: RevisionKind,
+    /// The name of the contributor, between `by` and the last comma.
+    pub author: Span,
+    /// The date after the last comma, without the final period; empty if
+    /// there is no comma.
+    pub date_span: Span,
+    /// The parsed date, if it is in the `DD-Mon-YYYY` format.
+    pub date: Option<Date>,
+}
+
+/// Returns the column of a word if it is the first on its line, or `None` if
+/// it follows other text (or the `$(` of the comment).
+fn line_column(buf: &[u8], start: usize, word_start: usize) -> Option<u32> {
+    let mut pos = word_start;
+    while pos > start && (buf[pos - 1] == b' ' || buf[pos - 1] == b'\t') {
+        pos -= 1;
+    }
+    if pos > start && buf[pos - 1] == b'\n' {
+        Some((word_start - pos) as u32)
+    } else {
+        None
+    }
+}
+
+/// Returns true if a word is a list marker.
+fn is_list_marker(word: &[u8]) -> bool {
+    let alnum = |text: &[u8], digits: bool| {
+        !text.is_empty() && text.len() <= 4 &&
+        text.iter().all(|ch| if digits { ch.is_ascii_digit() } else { ch.is_ascii_alphanumeric() })
+    };
+    match word.len() {
+        0 => false,
+        1 => word == b"-" || word == b"*",
+        len => {
+            match (word[0], word[len - 1]) {
+                (b'(', b')') => alnum(&word[1..len - 1], false),
+                (_, b'.') | (_, b')') => alnum(&word[..len - 1], true),
+                _ => false,
+            }
+        }
+    }
+}
+
+/// Parses a contributor parenthetical starting at `open`, which must be the
+/// position of a `(`, and ending before `end`.
+fn parse_contribution(buf: &[u8], open: usize, end: usize) -> Option<Contribution> {
+    let close = open + buf[open..end].iter().position(|&ch| ch == b')')?;
+    let (kind, mut pos) = CONTRIBUTION_PHRASES.iter()
+        .filter_map(|&(phrase, kind)| {
+            let mut pos = open + 1;
+            for &word in phrase {
+                while pos < close && buf[pos] <= b' ' {
+                    pos += 1;
+                }
+                if !buf[pos..close].starts_with(word) {
+                    return None;
+                }
+                pos += word.len();
+                if pos >= close || buf[pos] > b' ' {
+                    return None;
+                }
+            }
+            Some((kind, pos))
+        })
+        .next()?;
+    while buf[pos] <= b' ' {
+        pos += 1;
+    }
+    let trim = |mut start: usize, mut end: usize| {
+        while start < end && buf[start] <= b' ' {
+            start += 1;
+        }
+        while end > start && buf[end - 1] <= b' ' {
+            end -= 1;
+        }
+        Span::new(start, end)
+    };
+    let (author, date_span) = match buf[pos..close].iter().rposition(|&ch| ch == b',') {
+        Some(comma) => {
+            let date = trim(pos + comma + 1, close);
+            let date_end = if date.as_ref(buf).ends_with(b".") {
+                date.end as usize - 1
+            } else {
+                date.end as usize
+            };
+            (trim(pos, pos + comma), Span::new(date.start as usize, date_end))
+        }
+        None => (trim(pos, close), Span::new(close, close)),
+    };
+    Some(Contribution {
+        span: Span::new(open, close + 1),
+        kind: kind,
+        author: author,
+        date_span: date_span,
+        date: str::from_utf8(date_span.as_ref(buf)).ok().and_then(Date::parse),
+    })
+}
+
+/// Splits the text of a comment into markup elements; `span` is the extent of
+/// the comment in `buf`, including the `$(` and `$)` delimiters.
+///
+/// Math text which is still open at the end of the comment has no `EndMath`
+/// item.
+pub fn parse_comment(buf: &[u8], span: Span) -> Vec<CommentItem> {
+    let mut out = Vec::new();
+    let start = span.start as usize + 2;
+    let end = span.end as usize - 2;
+    let mut math = false;
+    let mut reference = false;
+    let mut text: Option<Span> = None;
+    let mut pos = start;
+    while pos < end {
+        if buf[pos] <= b' ' {
+            pos += 1;
+            continue;
+        }
+        let word_start = pos;
+        while pos < end && buf[pos] > b' ' {
+            pos += 1;
+        }
+        let word = &buf[word_start..pos];
+        let mut item = None;
+        if !math && !reference && word[0] == b'(' {
+            if let Some(contribution) = parse_contribution(buf, word_start, end) {
+                out.extend(text.take().map(CommentItem::Text));
+                out.push(CommentItem::Contribution(contribution));
+                pos = contribution.span.end as usize;
+                continue;
+            }
+        }
+        if !math && !reference && is_list_marker(word) {
+            if let Some(column) = line_column(buf, start, word_start) {
+                out.extend(text.take().map(CommentItem::Text));
+                out.push(CommentItem::ListItem(Span::new(word_start, pos), column));
+                continue;
+            }
+        }
+        if reference {
+            reference = false;
+            let span = Span::new(word_start, pos);
+            item = Some(if word.starts_with(b"http://") || word.starts_with(b"https://") {
+                CommentItem::Url(span)
+            } else {
+                CommentItem::Label(span)
+            });
+        } else if math {
+            if word == b"`" || word[0] == b'`' && word.get(1) != Some(&b'`') {
+                math = false;
+                out.extend(text.take().map(CommentItem::Text));
+                out.push(CommentItem::EndMath(Span::new(word_start, word_start + 1)));
+                if word.len() > 1 {
+                    text = Some(Span::new(word_start + 1, pos));
+                }
+                continue;
+            }
+            item = Some(CommentItem::MathToken(Span::new(word_start, pos)));
+        } else if word == b"`" {
+            math = true;
+            item = Some(CommentItem::StartMath(Span::new(word_start, pos)));
+        } else if word == b"~" {
+            reference = true;
+            out.extend(text.take().map(CommentItem::Text));
+            continue;
+        } else if word[0] == b'[' && word.get(1) != Some(&b'[') {
+            if let Some(close) = word.iter().position(|&ch| ch == b']') {
+                if close > 1 {
+                    out.extend(text.take().map(CommentItem::Text));
+                    out.push(CommentItem::BibTag(Span::new(word_start + 1, word_start + close)));
+                    if close + 1 < word.len() {
+                        text = Some(Span::new(word_start + close + 1, pos));
+                    }
+                    continue;
+                }
+            }
+        }
+        match item {
+            Some(item) => {
+                out.extend(text.take().map(CommentItem::Text));
+                out.push(item);
+            }
+            None => {
+                let run = text.map_or(word_start, |run| run.start as usize);
+                text = Some(Span::new(run, pos));
+            }
+        }
+    }
+    out.extend(text.map(CommentItem::Text));
+    out
+}
+
+/// Splits a comment statement into markup elements.
+pub fn comment_items(stmt: StatementRef) -> Vec<CommentItem> {
+    parse_comment(&stmt.segment().segment.buffer, stmt.span())
+}
@@ -0,0 +1,40 @@
+use comment_parser::CommentItem;
+use comment_parser::parse_comment;
+use history::Date;
+use history::RevisionKind;
+use parser::Span;
+
+#[test]
+fn test_comment_structure() {
+    let buf = b"$( See ` A ` :\n  - one\n    (ii) two\n(Contributed by NM, \
+                5-Aug-1993.) (Revised by A B, 30-Fbr-2001.) $)";
+    let items = parse_comment(buf, Span::new(0, buf.len()));
+    let text = |span: Span| String::from_utf8_lossy(span.as_ref(buf)).into_owned();
+    assert_eq!(items[3], CommentItem::EndMath(Span::new(11, 12)));
+    match (items[5], items[7]) {
+        (CommentItem::ListItem(bullet, 2), CommentItem::ListItem(number, 4)) => {
+            assert_eq!(text(bullet), "-");
+            assert_eq!(text(number), "(ii)");
+        }
+        other => panic!("expected list items, found {:?}", other),
+    }
+    let contributions: Vec<_> = items.iter()
+        .filter_map(|item| match *item {
+            CommentItem::Contribution(c) => Some(c),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(contributions.len(), 2);
+    assert_eq!(contributions[0].kind, RevisionKind::Contributed);
+    assert_eq!(text(contributions[0].author), "NM");
+    assert_eq!(contributions[0].date,
+               Some(Date {
+                   year: 1993,
+                   month: 8,
+                   day: 5,
+               }));
+    assert_eq!(contributions[1].kind, RevisionKind::Revised);
+    assert_eq!(text(contributions[1].author), "A B");
+    assert_eq!(text(contributions[1].date_span), "30-Fbr-2001");
+    assert_eq!(contributions[1].date, None);
+}
@@ -0,0 +1,254 @@
+//! Equality of formulas modulo asserted equalities.
+//!
+//! `CongruenceClosure` keeps parse trees of formulas (see
+//! `Formula::parse`) in a shared graph where each distinct subtree is a node,
+//! and partitions the nodes into classes of formulas known to be equal.  When
+//! two classes are merged, applications of the same syntax axiom to equal
+//! arguments are merged as well, so asserting `ph = ps` makes
+//! `( ph -> ch )` equal to `( ps -> ch )`.  This is the classic congruence
+//! closure algorithm, with the bookkeeping done eagerly at each merge.
+//!
+//! Equalities with variables to be substituted, such as those stated by
+//! definitions or commutativity theorems, are applied by `saturate`, which
+//! instantiates them on the formulas already in the graph (e-matching) for a
+//! bounded number of rounds.  What "equal" means is up to the client; the
+//! closure only assumes it is a congruence.
+
+use grammar::SyntaxTree;
+use parser::StatementAddress;
+use parser::Token;
+use std::mem;
+use util::HashMap;
+use util::new_map;
+
+/// The head of a node: a variable, or the syntax axiom applied.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+enum Head {
+    Var(Token),
+    App(StatementAddress),
+}
+
+/// A node as its head and the classes of its arguments.
+type Signature = (Head, Vec<usize>);
+
+/// A congruence closure over the subtrees of the formulas added to it.
+///
+/// Classes are identified by one of their nodes, which changes when classes
+/// are merged; use `equal` rather than comparing identifiers from earlier
+/// calls.
+#[derive(Clone,Debug,Default)]
+pub struct CongruenceClosure {
+    /// The signature of each node when it was added.
+    nodes: Vec<Signature>,
+    /// Nodes by signature with the arguments' current classes.  Entries with
+    /// outdated classes are left in place, since they can no longer match.
+    table: HashMap<Signature, usize>,
+    /// Union-find forest over the nodes; the roots identify the classes.
+    parent: Vec<usize>,
+    /// For each class, the nodes of the class.
+    members: Vec<Vec<usize>>,
+    /// For each class, the nodes with an argument in the class.
+    uses: Vec<Vec<usize>>,
+}
+
+impl CongruenceClosure {
+    /// Creates an empty closure.
+    pub fn new() -> CongruenceClosure {
+        CongruenceClosure {
+            nodes: Vec::new(),
+            table: new_map(),
+            parent: Vec::new(),
+            members: Vec::new(),
+            uses: Vec::new(),
+        }
+    }
+
+    /// Returns the class of a node.
+    fn find(&self, mut node: usize) -> usize {
+        while self.parent[node] != node {
+            node = self.parent[node];
+        }
+        node
+    }
+
+    fn canonical(&self, sig: &Signature) -> Signature {
+        (sig.0.clone(), sig.1.iter().map(|&arg| self.find(arg)).collect())
+    }
+
+    /// Returns the class of the node with a head and argument classes,
+    /// adding the node if there is none.
+    fn add_node(&mut self, head: Head, args: Vec<usize>) -> usize {
+        let sig = self.canonical(&(head, args));
+        if let Some(&node) = self.table.get(&sig) {
+            return self.find(node);
+        }
+        let node = self.nodes.len();
+        for &arg in &sig.1 {
+            self.uses[arg].push(node);
+        }
+        self.nodes.push(sig.clone());
+        self.table.insert(sig, node);
+        self.parent.push(node);
+        self.members.push(vec![node]);
+        self.uses.push(Vec::new());
+        node
+    }
+
+    /// Adds a formula and its subformulas, returning the class of the
+    /// formula.
+    pub fn add(&mut self, tree: &SyntaxTree) -> usize {
+        match *tree {
+            SyntaxTree::Var(ref name) => self.add_node(Head::Var(name.clone()), Vec::new()),
+            SyntaxTree::App(addr, ref args) => {
+                let args = args.iter().map(|arg| self.add(arg)).collect();
+                self.add_node(Head::App(addr), args)
+            }
+        }
+    }
+
+    /// Merges two classes and the classes of the nodes which become
+    /// congruent.  Returns false if they were already the same.
+    fn union(&mut self, first: usize, second: usize) -> bool {
+        if self.find(first) == self.find(second) {
+            return false;
+        }
+        let mut pending = vec![(first, second)];
+        while let Some((a, b)) = pending.pop() {
+            let (a, b) = (self.find(a), self.find(b));
+            if a == b {
+                continue;
+            }
+            let (keep, gone) = if self.members[a].len() >= self.members[b].len() {
+                (a, b)
+            } else {
+                (b, a)
+            };
+            self.parent[gone] = keep;
+            let members = mem::take(&mut self.members[gone]);
+            self.members[keep].extend(members);
+            // the signatures of the nodes using the merged class change
+            let uses = mem::take(&mut self.uses[gone]);
+            for &node in &uses {
+                let sig = self.canonical(&self.nodes[node]);
+                match self.table.get(&sig) {
+                    Some(&other) => {
+                        if self.find(other) != self.find(node) {
+                            pending.push((other, node));
+                        }
+                    }
+                    None => {
+                        self.table.insert(sig, node);
+                    }
+                }
+            }
+            self.uses[keep].extend(uses);
+        }
+        true
+    }
+
+    /// Asserts that two formulas are equal.
+    pub fn assert_equal(&mut self, first: &SyntaxTree, second: &SyntaxTree) {
+        let first = self.add(first);
+        let second = self.add(second);
+        self.union(first, second);
+    }
+
+    /// Returns true if two formulas are equal modulo the equalities asserted
+    /// so far.  The formulas are added if they are not yet in the closure,
+    /// which does not change the answer for other formulas.
+    pub fn equal(&mut self, first: &SyntaxTree, second: &SyntaxTree) -> bool {
+        let first = self.add(first);
+        let second = self.add(second);
+        self.find(first) == self.find(second)
+    }
+
+    /// Finds the substitutions, mapping variables of a pattern to classes,
+    /// under which the pattern is in a class, extending `subst`.
+    fn ematch(&self,
+              pattern: &SyntaxTree,
+              class: usize,
+              subst: HashMap<Token, usize>,
+              out: &mut Vec<HashMap<Token, usize>>) {
+        match *pattern {
+            SyntaxTree::Var(ref var) => {
+                match subst.get(var).cloned() {
+                    Some(bound) if self.find(bound) != class => {}
+                    Some(_) => out.push(subst),
+                    None => {
+                        let mut subst = subst;
+                        subst.insert(var.clone(), class);
+                        out.push(subst);
+                    }
+                }
+            }
+            SyntaxTree::App(addr, ref args) => {
+                for &node in &self.members[class] {
+                    let (ref head, ref children) = self.nodes[node];
+                    if *head != Head::App(addr) || children.len() != args.len() {
+                        continue;
+                    }
+                    let mut partial = vec![subst.clone()];
+                    for (arg, &child) in args.iter().zip(children) {
+                        let mut next = Vec::new();
+                        for subst in partial {
+                            self.ematch(arg, self.find(child), subst, &mut next);
+                        }
+                        partial = next;
+                    }
+                    out.extend(partial);
+                }
+            }
+        }
+    }
+
+    /// Adds a formula with some of its variables replaced by classes.
+    fn instantiate(&mut self, tree: &SyntaxTree, subst: &HashMap<Token, usize>) -> usize {
+        match *tree {
+            SyntaxTree::Var(ref name) => {
+                match subst.get(name) {
+                    Some(&class) => class,
+                    None => self.add_node(Head::Var(name.clone()), Vec::new()),
+                }
+            }
+            SyntaxTree::App(addr, ref args) => {
+                let args = args.iter().map(|arg| self.instantiate(arg, subst)).collect();
+                self.add_node(Head::App(addr), args)
+            }
+        }
+    }
+
+    /// Applies equational lemmas `lhs = rhs`, whose variables may be
+    /// substituted, to the formulas in the closure.
+    ///
+    /// Each round finds all the instances of the left-hand sides among the
+    /// classes, then adds the corresponding instances of the right-hand sides
+    /// and merges them with the instances of the left-hand sides.  As for
+    /// `Formula::rewrite`, variables match any class regardless of typecode.
+    /// Returns true if a round merged nothing, meaning that the closure is
+    /// closed under the lemmas, or false if `max_rounds` rounds were not
+    /// enough.
+    pub fn saturate(&mut self, lemmas: &[(SyntaxTree, SyntaxTree)], max_rounds: usize) -> bool {
+        for _ in 0..max_rounds {
+            let mut matches = Vec::new();
+            for class in 0..self.nodes.len() {
+                if self.parent[class] != class {
+                    continue;
+                }
+                for (ix, (lhs, _)) in lemmas.iter().enumerate() {
+                    let mut found = Vec::new();
+                    self.ematch(lhs, class, new_map(), &mut found);
+                    matches.extend(found.into_iter().map(|subst| (ix, class, subst)));
+                }
+            }
+            let mut merged = false;
+            for (ix, class, subst) in matches {
+                let rhs = self.instantiate(&lemmas[ix].1, &subst);
+                merged |= self.union(class, rhs);
+            }
+            if !merged {
+                return true;
+            }
+        }
+        false
+    }
+}
@@ -0,0 +1,28 @@
+use congruence::CongruenceClosure;
+use grammar::global_variables;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_congruence_closure() {
+    let mut db = mkdb(DB.as_bytes());
+    let parser = db.syntax_parser().clone();
+    let vars = global_variables(db.parse_result());
+    let mut tree = |text: &str| {
+        let formula = db.parse_formula_str(text, "wff").unwrap();
+        formula.parse(&parser, &vars).unwrap().1
+    };
+    let mut cc = CongruenceClosure::new();
+    cc.assert_equal(&tree("ph"), &tree("( ps -> ps )"));
+    assert!(cc.equal(&tree("( ph -> ps )"), &tree("( ( ps -> ps ) -> ps )")));
+    assert!(!cc.equal(&tree("ph"), &tree("ps")));
+    // commutativity, applied to subformulas as well
+    let mut cc = CongruenceClosure::new();
+    let lemma = (tree("( ph -> ps )"), tree("( ps -> ph )"));
+    let (first, second) = (tree("( ( ph -> ps ) -> ph )"), tree("( ph -> ( ps -> ph ) )"));
+    cc.add(&first);
+    assert!(!cc.equal(&first, &second));
+    assert!(cc.saturate(&[lemma], 10));
+    assert!(cc.equal(&first, &second));
+    assert!(!cc.equal(&first, &tree("( ph -> ph )")));
+}
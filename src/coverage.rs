@@ -0,0 +1,185 @@
+//! Reporting of which statements were covered by proof verification.
+//!
+//! A clean verifier run only says that no proof failed; it does not say how
+//! many proofs were actually checked.  A `$p` statement whose frame could not
+//! be constructed because of an earlier scope error is silently skipped by the
+//! verifier, and an incomplete proof is reported only as a single
+//! diagnostic.  This report classifies every assertion, counts the classes
+//! per section, and lists the statements which were not verified, so that a
+//! continuous integration job can insist that every provable statement was
+//! checked.
+//!
+//! Sections are delimited by the heading comments used in set.mm, which start
+//! with a line of `####`, `#*#*`, `=-=-` or `-.-.` followed by the title; see
+//! `outline`.
+
+use diag::Diagnostic;
+use extra_info::JConfig;
+use outline::heading;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::as_str;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use std::io;
+use std::io::Write;
+use util::HashMap;
+use verify::VerifyResult;
+
+/// The verification status of an assertion.
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum CoverageStatus {
+    /// A `$p` statement whose proof was checked without error.
+    Verified,
+    /// A `$a` statement with a provable typecode, such as `|-`; there is
+    /// nothing to verify.
+    Axiom,
+    /// A `$a` statement with another typecode; there is nothing to verify.
+    Syntax,
+    /// A `$p` statement which was not checked because it has no valid frame,
+    /// usually as a consequence of an earlier error.
+    Skipped,
+    /// A `$p` statement whose proof contains `?` steps.
+    Incomplete,
+    /// A `$p` statement whose proof failed to verify.
+    Failed,
+}
+
+const ALL_STATUSES: [CoverageStatus; 6] = [CoverageStatus::Verified,
+                                           CoverageStatus::Axiom,
+                                           CoverageStatus::Syntax,
+                                           CoverageStatus::Skipped,
+                                           CoverageStatus::Incomplete,
+                                           CoverageStatus::Failed];
+
+/// Counts of assertions by status for one section of the database.
+#[derive(Clone,Debug,Default)]
+pub struct SectionCoverage {
+    /// The heading comment which starts the section, or `None` for the
+    /// statements before the first heading.
+    pub heading: Option<StatementAddress>,
+    /// The title of the section.
+    pub title: String,
+    counts: [usize; 6],
+}
+
+impl SectionCoverage {
+    /// Returns the number of assertions in the section with a given status.
+    pub fn count(&self, status: CoverageStatus) -> usize {
+        self.counts[status as usize]
+    }
+}
+
+/// Verification coverage of a database.
+#[derive(Clone,Debug,Default)]
+pub struct CoverageReport {
+    /// Per-section counts, in database order.
+    pub sections: Vec<SectionCoverage>,
+    /// Every assertion which is not `Verified`, in database order.
+    pub uncovered: Vec<(StatementAddress, CoverageStatus)>,
+}
+
+/// Returns the title of a set.mm heading comment, or `None` if the comment is
+/// not a heading.
+pub fn heading_title(stmt: StatementRef) -> Option<String> {
+    heading(stmt).map(|(_, title)| title)
+}
+
+impl CoverageReport {
+    /// Classifies every `$a` and `$p` statement using the results of the
+    /// scope and verify passes.
+    pub fn new(sset: &SegmentSet, scope: &ScopeResult, verify: &VerifyResult) -> CoverageReport {
+        let diags: HashMap<StatementAddress, Diagnostic> = verify.diagnostics()
+            .into_iter()
+            .collect();
+        let config = JConfig::new(sset);
+        let mut report = CoverageReport::default();
+        report.sections.push(SectionCoverage::default());
+        for sref in sset.segments() {
+            for stmt in sref {
+                let status = match stmt.statement_type() {
+                    StatementType::Comment => {
+                        if let Some(title) = heading_title(stmt) {
+                            report.sections.push(SectionCoverage {
+                                heading: Some(stmt.address()),
+                                title: title,
+                                counts: [0; 6],
+                            });
+                        }
+                        continue;
+                    }
+                    StatementType::Axiom => {
+                        if stmt.math_len() > 0 && config.is_provable(stmt.math_at(0).slice) {
+                            CoverageStatus::Axiom
+                        } else {
+                            CoverageStatus::Syntax
+                        }
+                    }
+                    StatementType::Provable => {
+                        if scope.get(stmt.label()).is_none() {
+                            CoverageStatus::Skipped
+                        } else {
+                            match diags.get(&stmt.address()) {
+                                None => CoverageStatus::Verified,
+                                Some(&Diagnostic::ProofIncomplete) => CoverageStatus::Incomplete,
+                                Some(_) => CoverageStatus::Failed,
+                            }
+                        }
+                    }
+                    _ => continue,
+                };
+                report.sections.last_mut().unwrap().counts[status as usize] += 1;
+                if status != CoverageStatus::Verified {
+                    report.uncovered.push((stmt.address(), status));
+                }
+            }
+        }
+        if report.sections[0].counts == [0; 6] && report.sections.len() > 1 {
+            report.sections.remove(0);
+        }
+        report
+    }
+
+    /// Returns the number of assertions in the whole database with a given
+    /// status.
+    pub fn count(&self, status: CoverageStatus) -> usize {
+        self.sections.iter().map(|section| section.count(status)).sum()
+    }
+
+    /// Returns the number of `$p` statements.
+    pub fn provable_count(&self) -> usize {
+        self.count(CoverageStatus::Verified) + self.count(CoverageStatus::Skipped) +
+        self.count(CoverageStatus::Incomplete) + self.count(CoverageStatus::Failed)
+    }
+
+    /// Returns true if every `$p` statement was verified.
+    pub fn is_complete(&self) -> bool {
+        self.count(CoverageStatus::Verified) == self.provable_count()
+    }
+
+    /// Writes the report as text: one line per section with its counts, then
+    /// the unverified `$p` statements, then a summary line.
+    pub fn write_report<W: Write>(&self, sset: &SegmentSet, out: &mut W) -> io::Result<()> {
+        for name in &["verified", "axiom", "syntax", "skipped", "incomplete", "failed"] {
+            write!(out, "{:>10} ", name)?;
+        }
+        writeln!(out, " section")?;
+        for section in &self.sections {
+            for &status in ALL_STATUSES.iter() {
+                write!(out, "{:>10} ", section.count(status))?;
+            }
+            writeln!(out, " {}", section.title)?;
+        }
+        for &(addr, status) in &self.uncovered {
+            match status {
+                CoverageStatus::Axiom | CoverageStatus::Syntax => {}
+                _ => writeln!(out, "{:?}: {}", status, as_str(sset.statement(addr).label()))?,
+            }
+        }
+        writeln!(out,
+                 "{} of {} provable statements verified",
+                 self.count(CoverageStatus::Verified),
+                 self.provable_count())
+    }
+}
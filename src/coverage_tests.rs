@@ -0,0 +1,33 @@
+use coverage::CoverageStatus;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_coverage() {
+    let text = format!("{}$(
+####
+  Theorems
+####
+$)
+th1 $p |- ( ph -> ( ps -> ph ) ) $= wph wps ax-1 $.
+th2 $p |- ( ph -> ph ) $= ? $.
+th3 $p |- ph $= wph $.
+th4 $p |- ch $= ? $.
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let report = db.coverage();
+    assert_eq!(report.sections.len(), 2);
+    assert_eq!(report.sections[0].title, "");
+    assert_eq!(report.sections[0].count(CoverageStatus::Axiom), 2);
+    assert_eq!(report.sections[0].count(CoverageStatus::Syntax), 1);
+    assert_eq!(report.sections[1].title, "Theorems");
+    assert_eq!(report.sections[1].count(CoverageStatus::Verified), 1);
+    assert_eq!(report.count(CoverageStatus::Incomplete), 1);
+    assert_eq!(report.count(CoverageStatus::Failed), 1);
+    assert_eq!(report.count(CoverageStatus::Skipped), 1);
+    assert_eq!(report.provable_count(), 4);
+    assert!(!report.is_complete());
+    let th4 = db.statement("th4").unwrap().address();
+    assert_eq!(report.uncovered.last(), Some(&(th4, CoverageStatus::Skipped)));
+}
@@ -97,27 +97,162 @@
 //! To improve packing efficiency, jobs are dispatched in descending order of
 //! estimated runtime.  This requires an additional argument when queueing.
 
+use coverage::CoverageReport;
+#[cfg(feature = "html")]
+use definitions;
+#[cfg(feature = "html")]
+use definitions::DefinitionEntry;
+use deprecation::DeprecationConfig;
+use deprecation::DeprecationRegistry;
 use diag;
+use diag::Diagnostic;
 use diag::DiagnosticClass;
+use diag::DiagnosticConfig;
 use diag::Notation;
+use diff::DiffSide;
+use diff::StatementDiff;
+use diff::diff_databases;
+use differential;
+use differential::Disagreement;
+use discouraged::DiscouragedResult;
+use disjoint::DisjointResult;
+use dummy;
+use dummy::OptionalVariable;
+#[cfg(feature = "grammar")]
+use duplicates::DuplicateResult;
+use emit::EmitError;
+#[cfg(feature = "export")]
 use export;
+use extra_info::ExtraInfoResult;
+use extra_info::JConfig;
+#[cfg(feature = "search")]
+use extraction;
+#[cfg(feature = "search")]
+use extraction::DefinitionSuggestion;
+#[cfg(feature = "search")]
+use extraction::ExtractionOptions;
+#[cfg(feature = "grammar")]
+use formula::Formula;
+#[cfg(feature = "grammar")]
+use formula::RewriteStrategy;
+#[cfg(feature = "grammar")]
+use grammar::Coercion;
+#[cfg(feature = "grammar")]
+use grammar::FormulaBuilder;
+#[cfg(feature = "grammar")]
+use grammar::GrammarResult;
+#[cfg(feature = "grammar")]
+use grammar::NextTokens;
+#[cfg(feature = "grammar")]
+use grammar::SyntaxParser;
+#[cfg(feature = "grammar")]
+use grammar::SyntaxTree;
+#[cfg(feature = "grammar")]
+use grammar::global_variables;
+use graph::DependencyGraph;
+use graph::GraphFilter;
+use health::HealthReport;
+use health::PassSummary;
+use history;
+use history::History;
+use history::HistoryResult;
+#[cfg(feature = "html")]
+use html;
+#[cfg(feature = "json")]
+use json;
+use layout::FormatPolicy;
+use line_cache::LineCache;
+use lint::LintResult;
+#[cfg(feature = "grammar")]
+use markup::MarkupConfig;
+#[cfg(feature = "grammar")]
+use markup::MarkupResult;
+use merge;
+use merge::MergeError;
+use merge::PrefixPolicy;
+use metadata::AtomMetadata;
+#[cfg(feature = "export")]
+use mm0;
 use nameck::Nameset;
+#[cfg(feature = "watch")]
+use notify;
+use occurrences::Occurrence;
+use occurrences::OccurrenceIndex;
+use outline::Outline;
+use parser::SegmentId;
+use parser::Span;
+use parser::StatementAddress;
 use parser::StatementRef;
+use parser::StatementType;
+use parser::Token;
+use parser::TokenPtr;
+#[cfg(any(feature = "html", feature = "stats"))]
+use parser::as_str;
+use parser::copy_token;
+use proof;
+use proof::ProofStep;
+use proof_stats::ProofStats;
+#[cfg(feature = "search")]
+use regex;
+#[cfg(feature = "search")]
+use regex::bytes::Regex;
+use repair;
+use repair::RepairReport;
 use scopeck;
 use scopeck::ScopeResult;
+#[cfg(feature = "search")]
+use search;
+#[cfg(feature = "search")]
+use search::SearchMatch;
+#[cfg(feature = "search")]
+use search::SearchOptions;
+#[cfg(feature = "search")]
+use search::TextMatch;
+use segment_set::IncludeOptions;
 use segment_set::SegmentSet;
+use segment_set::StatementEdit;
+use segment_set::StatementPart;
+#[cfg(feature = "json")]
+use serde_json;
+#[cfg(feature = "stats")]
+use stats::Stats;
 use std::cmp::Ordering;
+use std::cmp::Reverse;
 use std::collections::BinaryHeap;
 use std::fmt;
+use std::fs;
 use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::ops::Range;
 use std::panic;
+#[cfg(feature = "html")]
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::Condvar;
 use std::sync::Mutex;
 use std::thread;
+#[cfg(feature = "stats")]
+use std::time::Duration;
 use std::time::Instant;
+use stubs::StubReport;
+#[cfg(feature = "grammar")]
+use subset;
+use transaction::Change;
+use transaction::Transaction;
+use transaction::TransactionError;
+use typesetting::TypesettingData;
+use usage;
+use usage::Usage;
+use usage::UsageIndex;
+use util::HashSet;
 use verify;
+use verify::Goal;
+use verify::VerifyOptions;
 use verify::VerifyResult;
+use view::DatabaseView;
+#[cfg(feature = "watch")]
+use watch;
 
 /// Structure for options that affect database processing, and must be constant
 /// for the lifetime of the database container.
@@ -145,8 +280,47 @@ pub struct DbOptions {
     pub incremental: bool,
     /// Number of jobs to run in parallel at any given time.
     pub jobs: usize,
+    /// Deprecated items and comment tags in addition to those declared in
+    /// `$j` comments.
+    pub deprecation: DeprecationConfig,
+    /// Levels of diagnostic categories, in addition to those set by `$j`
+    /// comments.
+    pub diagnostics: DiagnosticConfig,
+    /// Normalizations of included file names and contents.
+    pub includes: IncludeOptions,
+    /// Bibliography for checking comment markup.
+    #[cfg(feature = "grammar")]
+    pub markup: MarkupConfig,
+    /// Resource limits for verifying each proof.
+    pub verify: VerifyOptions,
+    /// Layout of generated source text, such as rewritten proofs and drafted
+    /// statements.
+    pub format: FormatPolicy,
+    /// True to map source files into memory instead of reading them, when no
+    /// normalization of their contents is needed.  See `parser::Buffer::map`
+    /// for the caveat about files changed while they are loaded.
+    #[cfg(feature = "mmap")]
+    pub mmap: bool,
+    /// True to record the duration of each pass and of the statements it
+    /// processes, for `Database::slowest_proofs` and `Database::pass_times`.
+    #[cfg(feature = "stats")]
+    pub stats: bool,
 }
 
+/// All passes which generate diagnostics, in the order their diagnostics are
+/// reported.
+const ALL_CLASSES: [DiagnosticClass; 11] = [DiagnosticClass::Parse,
+                                           DiagnosticClass::Scope,
+                                           DiagnosticClass::Verify,
+                                           DiagnosticClass::Grammar,
+                                           DiagnosticClass::Duplicate,
+                                           DiagnosticClass::Disjoint,
+                                           DiagnosticClass::Lint,
+                                           DiagnosticClass::Markup,
+                                           DiagnosticClass::History,
+                                           DiagnosticClass::Discouraged,
+                                           DiagnosticClass::Deprecated];
+
 /// Wraps a heap-allocated closure with a difficulty score which can be used for
 /// sorting; this might belong in the standard library as `CompareFirst` or such.
 struct Job(usize, Box<dyn FnMut() + Send>);
@@ -342,6 +516,10 @@ impl<T> Promise<T> {
 /// without affecting the other.
 pub struct Database {
     options: Arc<DbOptions>,
+    /// Durations of passes and statements recorded so far, if
+    /// `DbOptions::stats` is set.
+    #[cfg(feature = "stats")]
+    stats: Option<Arc<Stats>>,
     segments: Option<Arc<SegmentSet>>,
     /// We track the "current" and "previous" for all known passes, so that each
     /// pass can use its most recent results for optimized incremental
@@ -353,12 +531,49 @@ pub struct Database {
     scopes: Option<Arc<ScopeResult>>,
     prev_verify: Option<Arc<VerifyResult>>,
     verify: Option<Arc<VerifyResult>>,
+    typesetting: Option<Arc<TypesettingData>>,
+    discouraged: Option<Arc<DiscouragedResult>>,
+    deprecation: Option<Arc<DeprecationRegistry>>,
+    #[cfg(feature = "grammar")]
+    grammar: Option<Arc<GrammarResult>>,
+    #[cfg(feature = "grammar")]
+    syntax_parser: Option<Arc<SyntaxParser>>,
+    j_config: Option<Arc<JConfig>>,
+    extra_info: Option<Arc<ExtraInfoResult>>,
+    #[cfg(feature = "grammar")]
+    duplicates: Option<Arc<DuplicateResult>>,
+    disjoint: Option<Arc<DisjointResult>>,
+    lint: Option<Arc<LintResult>>,
+    #[cfg(feature = "grammar")]
+    markup: Option<Arc<MarkupResult>>,
+    history: Option<Arc<HistoryResult>>,
+    outline: Option<Arc<Outline>>,
+    proof_stats: Option<Arc<ProofStats>>,
+    usage_index: Option<Arc<UsageIndex>>,
+    occurrence_index: Option<Arc<OccurrenceIndex>>,
+    /// Client data keyed by atom, cleared along with `nameset`.
+    metadata: AtomMetadata,
+    /// Line indexes of source files, cleared along with the segments they
+    /// describe.
+    lines: LineCache,
+}
+
+/// What is needed to time a pass, cloned out of the database so that the pass
+/// can borrow the database mutably.
+struct PassTimer {
+    options: Arc<DbOptions>,
+    #[cfg(feature = "stats")]
+    stats: Option<Arc<Stats>>,
 }
 
-fn time<R, F: FnOnce() -> R>(opts: &DbOptions, name: &str, f: F) -> R {
+fn time<R, F: FnOnce() -> R>(timer: &PassTimer, name: &str, f: F) -> R {
     let now = Instant::now();
     let ret = f();
-    if opts.timing {
+    #[cfg(feature = "stats")]
+    if let Some(ref stats) = timer.stats {
+        stats.record_pass(name, now.elapsed());
+    }
+    if timer.options.timing {
         // no as_msecs :(
         println!("{} {}ms", name, (now.elapsed() * 1000).as_secs());
     }
@@ -367,7 +582,26 @@ fn time<R, F: FnOnce() -> R>(opts: &DbOptions, name: &str, f: F) -> R {
 
 impl Drop for Database {
     fn drop(&mut self) {
-        time(&self.options.clone(), "free", move || {
+        time(&self.timer(), "free", move || {
+            self.typesetting = None;
+            self.discouraged = None;
+            self.deprecation = None;
+            #[cfg(feature = "grammar")]
+            {
+                self.grammar = None;
+                self.syntax_parser = None;
+                self.duplicates = None;
+                self.markup = None;
+            }
+            self.j_config = None;
+            self.extra_info = None;
+            self.disjoint = None;
+            self.lint = None;
+            self.history = None;
+            self.outline = None;
+            self.proof_stats = None;
+            self.usage_index = None;
+            self.occurrence_index = None;
             self.prev_verify = None;
             self.verify = None;
             self.prev_scopes = None;
@@ -380,6 +614,14 @@ impl Drop for Database {
 }
 
 impl Database {
+    fn timer(&self) -> PassTimer {
+        PassTimer {
+            options: self.options.clone(),
+            #[cfg(feature = "stats")]
+            stats: self.stats.clone(),
+        }
+    }
+
     /// Constructs a new database object representing an empty set of segments.
     ///
     /// Use `parse` to load it with data.  Currently this eagerly starts the
@@ -387,12 +629,48 @@ impl Database {
     pub fn new(options: DbOptions) -> Database {
         let options = Arc::new(options);
         let exec = Executor::new(options.jobs);
+        #[cfg_attr(not(feature = "stats"), allow(unused_mut))]
+        let mut segments = SegmentSet::new(options.clone(), &exec);
+        #[cfg(feature = "stats")]
+        let stats = if options.stats {
+            Some(Arc::new(Stats::default()))
+        } else {
+            None
+        };
+        #[cfg(feature = "stats")]
+        {
+            segments.stats = stats.clone();
+        }
         Database {
-            segments: Some(Arc::new(SegmentSet::new(options.clone(), &exec))),
+            segments: Some(Arc::new(segments)),
             options: options,
+            #[cfg(feature = "stats")]
+            stats: stats,
             nameset: None,
             scopes: None,
             verify: None,
+            typesetting: None,
+            discouraged: None,
+            deprecation: None,
+            #[cfg(feature = "grammar")]
+            grammar: None,
+            #[cfg(feature = "grammar")]
+            syntax_parser: None,
+            j_config: None,
+            extra_info: None,
+            #[cfg(feature = "grammar")]
+            duplicates: None,
+            disjoint: None,
+            lint: None,
+            #[cfg(feature = "grammar")]
+            markup: None,
+            history: None,
+            outline: None,
+            proof_stats: None,
+            usage_index: None,
+            occurrence_index: None,
+            metadata: AtomMetadata::default(),
+            lines: LineCache::default(),
             prev_nameset: None,
             prev_scopes: None,
             prev_verify: None,
@@ -429,14 +707,228 @@ impl Database {
     /// current database content and incremental processing will be used as
     /// appropriate.
     pub fn parse(&mut self, start: String, text: Vec<(String, Vec<u8>)>) {
-        time(&self.options.clone(), "parse", || {
+        time(&self.timer(), "parse", || {
             Arc::make_mut(self.segments.as_mut().unwrap()).read(start, text);
-            self.nameset = None;
-            self.scopes = None;
-            self.verify = None;
+            self.invalidate();
+        });
+    }
+
+    /// Loads a checkpoint file written by `save_checkpoint`, so that the next
+    /// `parse` reuses the segments it records instead of parsing their text
+    /// again; see the `checkpoint` module.  Returns the number of source
+    /// slices loaded.
+    pub fn load_checkpoint(&mut self, path: &str) -> io::Result<usize> {
+        let data = fs::read(path)?;
+        Arc::make_mut(self.segments.as_mut().unwrap()).load_checkpoint(&data)
+    }
+
+    /// Writes the parsed segments of the database to a checkpoint file.
+    pub fn save_checkpoint(&mut self, path: &str) -> io::Result<()> {
+        let mut out = File::create(path)?;
+        self.parse_result().write_checkpoint(&mut out)
+    }
+
+    /// Rereads the files of the database which changed on disk since they
+    /// were last read, keeping the in-memory data given to `parse` and any
+    /// edits; see `SegmentSet::reload`.
+    ///
+    /// As with `parse`, all analysis passes are invalidated and will be
+    /// recomputed incrementally when next requested.
+    pub fn reload(&mut self) {
+        time(&self.timer(), "parse", || {
+            Arc::make_mut(self.segments.as_mut().unwrap()).reload();
+            self.invalidate();
         });
     }
 
+    /// Watches source files and reanalyzes the database whenever they change;
+    /// see `watch::watch`.
+    #[cfg(feature = "watch")]
+    pub fn watch<F>(&mut self,
+                    paths: &[String],
+                    classes: Vec<DiagnosticClass>,
+                    callback: F)
+                    -> notify::Result<()>
+        where F: FnMut(&mut Database, Vec<Notation>) -> bool
+    {
+        watch::watch(self, paths, classes, callback)
+    }
+
+    /// Replaces the text of a single statement and reparses; see
+    /// `SegmentSet::replace_statement`.
+    ///
+    /// As with `parse`, all analysis passes are invalidated and will be
+    /// recomputed incrementally when next requested.
+    pub fn replace_statement(&mut self,
+                             addr: StatementAddress,
+                             text: &[u8])
+                             -> Result<(), EmitError> {
+        time(&self.timer(), "parse", || {
+            let result =
+                Arc::make_mut(self.segments.as_mut().unwrap()).replace_statement(addr, text);
+            self.invalidate();
+            result
+        })
+    }
+
+    /// Inserts text before a statement and reparses; see
+    /// `SegmentSet::insert_statement`.
+    pub fn insert_statement(&mut self,
+                            addr: StatementAddress,
+                            text: &[u8])
+                            -> Result<(), EmitError> {
+        time(&self.timer(), "parse", || {
+            let result =
+                Arc::make_mut(self.segments.as_mut().unwrap()).insert_statement(addr, text);
+            self.invalidate();
+            result
+        })
+    }
+
+    /// Deletes a statement and reparses; see `SegmentSet::delete_statement`.
+    pub fn delete_statement(&mut self, addr: StatementAddress) -> Result<(), EmitError> {
+        time(&self.timer(), "parse", || {
+            let result = Arc::make_mut(self.segments.as_mut().unwrap()).delete_statement(addr);
+            self.invalidate();
+            result
+        })
+    }
+
+    /// Applies several statement edits with a single reparse; see
+    /// `SegmentSet::edit_statements`.
+    pub fn edit_statements(&mut self,
+                           edits: &[(StatementAddress, StatementEdit)])
+                           -> Result<(), EmitError> {
+        time(&self.timer(), "parse", || {
+            let result = Arc::make_mut(self.segments.as_mut().unwrap()).edit_statements(edits);
+            self.invalidate();
+            result
+        })
+    }
+
+    /// Renames a label throughout the database: in its defining statement, in
+    /// every proof using it, and in the `~ label` references of comments.
+    ///
+    /// Returns the IDs of the segments which were changed, in the database
+    /// after the rename.  Fails without changing anything if the old label
+    /// does not exist or the new one is already in use.
+    pub fn rename_label(&mut self, old: &str, new: &str) -> Result<Vec<SegmentId>, TransactionError> {
+        self.apply_rename(Change::Rename(old.to_owned(), new.to_owned()))
+    }
+
+    /// Renames a math symbol throughout the database: in its `$c` or `$v`
+    /// declarations, in every math string, in the math text of comments, and
+    /// in the typesetting definitions of `$t` comments.  Tokens are replaced
+    /// whole, so symbols containing the old one are not affected.
+    ///
+    /// Returns the IDs of the segments which were changed, in the database
+    /// after the rename.  Fails without changing anything if the old symbol is
+    /// not declared, or the new one is not a valid symbol or is already used
+    /// by a symbol or a label.
+    pub fn rename_math_symbol(&mut self,
+                              old: &str,
+                              new: &str)
+                              -> Result<Vec<SegmentId>, TransactionError> {
+        self.apply_rename(Change::RenameSymbol(old.to_owned(), new.to_owned()))
+    }
+
+    /// Applies a rename in a transaction and commits it, returning the IDs of
+    /// the changed segments.
+    fn apply_rename(&mut self, change: Change) -> Result<Vec<SegmentId>, TransactionError> {
+        let before = self.parse_result().clone();
+        let files = {
+            let mut txn = Transaction::new(self);
+            txn.apply(&[change])?;
+            txn.commit()
+        };
+        let in_changed_file = |sset: &SegmentSet, id: SegmentId| {
+            files.iter().any(|(name, _)| *name == sset.source_info(id).name)
+        };
+        let segment_text = |sset: &SegmentSet, id: SegmentId| {
+            let mut text = Vec::new();
+            sset.write_segment(id, &mut text).expect("writes to a vector do not fail");
+            text
+        };
+        // the changed files are reparsed entirely, but some of their segments
+        // may have the same text as before
+        let unchanged: HashSet<Vec<u8>> = before.segments()
+            .into_iter()
+            .filter(|sref| in_changed_file(&before, sref.id))
+            .map(|sref| segment_text(&before, sref.id))
+            .collect();
+        let sset = self.parse_result().clone();
+        Ok(sset.segments()
+            .into_iter()
+            .filter(|sref| {
+                in_changed_file(&sset, sref.id) && !unchanged.contains(&segment_text(&sset, sref.id))
+            })
+            .map(|sref| sref.id)
+            .collect())
+    }
+
+    /// Moves statements, in the order given, to just before another
+    /// statement, and returns the name and new text of each changed file.
+    ///
+    /// To move a section, use `Outline::section_statements`.  Fails without
+    /// changing anything if a proof would use a statement defined after it;
+    /// see `Change::Move`.
+    pub fn move_statements(&mut self,
+                           statements: &[StatementAddress],
+                           before: StatementAddress)
+                           -> Result<Vec<(String, Vec<u8>)>, TransactionError> {
+        let mut txn = Transaction::new(self);
+        txn.apply(&[Change::Move(statements.to_vec(), before)])?;
+        Ok(txn.commit())
+    }
+
+    /// Appends the statements of another database to the main file of this
+    /// one, prefixing the imported labels as the policy asks, and returns the
+    /// name and new text of the changed file; see `merge::merge`.
+    pub fn merge(&mut self,
+                 other: &mut Database,
+                 policy: &PrefixPolicy)
+                 -> Result<Vec<(String, Vec<u8>)>, MergeError> {
+        merge::merge(self, other, policy)
+    }
+
+    /// Replaces the parsed data with a copy previously obtained from
+    /// `parse_result`, undoing any edits and reparses made since.
+    ///
+    /// As with `parse`, all analysis passes are invalidated.
+    pub fn restore_parse_result(&mut self, segments: Arc<SegmentSet>) {
+        self.segments = Some(segments);
+        self.invalidate();
+    }
+
+    /// Discards the current results of all analysis passes after a change to
+    /// the segments; the previous results are kept for incremental use.
+    fn invalidate(&mut self) {
+        self.nameset = None;
+        self.scopes = None;
+        self.verify = None;
+        self.typesetting = None;
+        self.discouraged = None;
+        self.deprecation = None;
+        #[cfg(feature = "grammar")]
+        {
+            self.grammar = None;
+            self.syntax_parser = None;
+            self.duplicates = None;
+            self.markup = None;
+        }
+        self.j_config = None;
+        self.extra_info = None;
+        self.disjoint = None;
+        self.lint = None;
+        self.history = None;
+        self.outline = None;
+        self.proof_stats = None;
+        self.usage_index = None;
+        self.occurrence_index = None;
+        self.metadata.clear();
+        self.lines.clear();
+    }
+
     /// Obtains a reference to the current parsed data.
     ///
     /// Unlike the other accessors, this is not lazy (subject to change when the
@@ -445,10 +937,71 @@ impl Database {
         self.segments.as_ref().unwrap()
     }
 
+    /// Resolves a byte offset in a source file to a statement and the part of
+    /// it at that offset; see `SegmentSet::statement_at`.
+    pub fn statement_at(&mut self,
+                        path: &str,
+                        offset: usize)
+                        -> Option<(StatementAddress, StatementPart)> {
+        self.parse_result().statement_at(path, offset)
+    }
+
+    /// Lists the statements overlapping a range of byte offsets in a source
+    /// file; see `SegmentSet::statements_in_range`.
+    pub fn statements_in_range(&mut self, path: &str, range: Range<usize>) -> Vec<StatementAddress> {
+        self.parse_result().statements_in_range(path, range)
+    }
+
+    /// Returns the file name, line and column (both 1-based, the column in
+    /// bytes) of the start of a span of a statement, or of the statement itself
+    /// if the span is null.
+    ///
+    /// The line index of each file is built on first use and kept until the
+    /// database changes.
+    pub fn lookup_position(&mut self, address: StatementAddress, span: Span) -> (String, u32, u32) {
+        let sset = self.parse_result().clone();
+        let stmt = sset.statement(address);
+        let span = if span.is_null() { stmt.span() } else { span };
+        let source = sset.source_info(address.segment_id);
+        let offset = (source.span.start + span.start) as usize;
+        let (line, col) = self.lines.from_offset(&source.text, offset);
+        (source.name.clone(), line, col)
+    }
+
+    /// Finds the statement at a line and column of a source file, returning
+    /// its address and an empty span at that position.  This is the reverse of
+    /// `lookup_position`; returns `None` if the file is not loaded or the
+    /// position is past the end of its line.
+    pub fn lookup_span(&mut self,
+                       path: &str,
+                       line: u32,
+                       col: u32)
+                       -> Option<(StatementAddress, Span)> {
+        let sset = self.parse_result().clone();
+        let text = match sset.segments()
+            .into_iter()
+            .map(|sref| sset.source_info(sref.id))
+            .find(|source| source.name == path) {
+            Some(source) => source.text.clone(),
+            None => return None,
+        };
+        if line == 0 || col == 0 || line > self.lines.from_offset(&text, text.len()).0 {
+            return None;
+        }
+        let start = self.lines.to_offset(&text, line);
+        let offset = start + col as usize - 1;
+        if offset > LineCache::line_end(&text, start) {
+            return None;
+        }
+        let (address, _) = sset.statement_at(path, offset)?;
+        let local = offset - sset.source_info(address.segment_id).span.start as usize;
+        Some((address, Span::new(local, local)))
+    }
+
     /// Calculates and returns the name to definition lookup table.
     pub fn name_result(&mut self) -> &Arc<Nameset> {
         if self.nameset.is_none() {
-            time(&self.options.clone(), "nameck", || {
+            time(&self.timer(), "nameck", || {
                 if self.prev_nameset.is_none() {
                     self.prev_nameset = Some(Arc::new(Nameset::new()));
                 }
@@ -464,6 +1017,16 @@ impl Database {
         self.nameset.as_ref().unwrap()
     }
 
+    /// Returns the data attached to atoms by clients, which is discarded
+    /// whenever the database changes.
+    ///
+    /// Atoms are assigned by `name_result`, so a client would typically look
+    /// up a symbol there and then store its data with
+    /// `db.atom_metadata().insert(atom, data)`.
+    pub fn atom_metadata(&mut self) -> &mut AtomMetadata {
+        &mut self.metadata
+    }
+
     /// Calculates and returns the frames for this database, i.e. the actual
     /// logical system.
     ///
@@ -472,7 +1035,7 @@ impl Database {
     pub fn scope_result(&mut self) -> &Arc<ScopeResult> {
         if self.scopes.is_none() {
             self.name_result();
-            time(&self.options.clone(), "scopeck", || {
+            time(&self.timer(), "scopeck", || {
                 if self.prev_scopes.is_none() {
                     self.prev_scopes = Some(Arc::new(ScopeResult::default()));
                 }
@@ -498,7 +1061,7 @@ impl Database {
         if self.verify.is_none() {
             self.name_result();
             self.scope_result();
-            time(&self.options.clone(), "verify", || {
+            time(&self.timer(), "verify", || {
                 if self.prev_verify.is_none() {
                     self.prev_verify = Some(Arc::new(VerifyResult::default()));
                 }
@@ -516,6 +1079,350 @@ impl Database {
         self.verify.as_ref().unwrap()
     }
 
+    /// Returns the labels of the `count` proofs which took longest to verify,
+    /// slowest first, verifying the database if needed.
+    #[cfg(feature = "stats")]
+    pub fn slowest_proofs(&mut self, count: usize) -> Vec<(String, Duration)> {
+        let stats = match self.stats {
+            Some(ref stats) => stats.clone(),
+            None => return Vec::new(),
+        };
+        self.verify_result();
+        let sset = self.parse_result().clone();
+        stats.slowest(&sset, "verify", count)
+            .into_iter()
+            .map(|(addr, time)| (as_str(sset.statement(addr).label()).to_owned(), time))
+            .collect()
+    }
+
+    /// Returns the duration of each pass run so far, in the order they
+    /// finished.
+    #[cfg(feature = "stats")]
+    pub fn pass_times(&self) -> Vec<(String, Duration)> {
+        match self.stats {
+            Some(ref stats) => stats.pass_times(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Collects and returns the typesetting definitions from `$t` comments.
+    ///
+    /// This is used to render math strings as HTML or LaTeX; it depends only
+    /// on the parse result.
+    pub fn typesetting_result(&mut self) -> &Arc<TypesettingData> {
+        if self.typesetting.is_none() {
+            time(&self.timer(), "typesetting", || {
+                let parse = self.parse_result().clone();
+                self.typesetting = Some(Arc::new(TypesettingData::new(&parse)));
+            });
+        }
+        self.typesetting.as_ref().unwrap()
+    }
+
+    /// Collects the statements marked as discouraged for new usage or proof
+    /// modification, and checks proofs for uses of the former.
+    pub fn discouraged_result(&mut self) -> &Arc<DiscouragedResult> {
+        if self.discouraged.is_none() {
+            time(&self.timer(), "discouraged", || {
+                let parse = self.parse_result().clone();
+                self.discouraged = Some(Arc::new(DiscouragedResult::new(&parse)));
+            });
+        }
+        self.discouraged.as_ref().unwrap()
+    }
+
+    /// Collects the deprecated labels and math symbols, and checks all
+    /// statements for references to them.
+    pub fn deprecation_result(&mut self) -> &Arc<DeprecationRegistry> {
+        if self.deprecation.is_none() {
+            time(&self.timer(), "deprecation", || {
+                let parse = self.parse_result().clone();
+                let config = &self.options.deprecation;
+                self.deprecation = Some(Arc::new(DeprecationRegistry::new(&parse, config)));
+            });
+        }
+        self.deprecation.as_ref().unwrap()
+    }
+
+    /// Checks the grammar formed by the syntax axioms for ambiguities.
+    #[cfg(feature = "grammar")]
+    pub fn grammar_result(&mut self) -> &Arc<GrammarResult> {
+        if self.grammar.is_none() {
+            time(&self.timer(), "grammar", || {
+                let parse = self.parse_result().clone();
+                let name = self.name_result().clone();
+                let scope = self.scope_result().clone();
+                self.grammar = Some(Arc::new(GrammarResult::new(&parse, &name, &scope)));
+            });
+        }
+        self.grammar.as_ref().unwrap()
+    }
+
+    /// Builds a parser for formulas from the syntax axioms.
+    #[cfg(feature = "grammar")]
+    pub fn syntax_parser(&mut self) -> &Arc<SyntaxParser> {
+        if self.syntax_parser.is_none() {
+            time(&self.timer(), "syntax_parser", || {
+                let parse = self.parse_result().clone();
+                let name = self.name_result().clone();
+                let scope = self.scope_result().clone();
+                self.syntax_parser = Some(Arc::new(SyntaxParser::new(&parse, &name, &scope)));
+            });
+        }
+        self.syntax_parser.as_ref().unwrap()
+    }
+
+    /// Reads the syntax, provable and bound typecodes declared by `$j`
+    /// comments; see `extra_info::JConfig`.
+    pub fn j_config(&mut self) -> &Arc<JConfig> {
+        if self.j_config.is_none() {
+            time(&self.timer(), "j_config", || {
+                let parse = self.parse_result().clone();
+                self.j_config = Some(Arc::new(JConfig::new(&parse)));
+            });
+        }
+        self.j_config.as_ref().unwrap()
+    }
+
+    /// Interprets the commands of `$j` comments; see
+    /// `extra_info::ExtraInfoResult`.
+    pub fn extra_info_result(&mut self) -> &Arc<ExtraInfoResult> {
+        if self.extra_info.is_none() {
+            time(&self.timer(), "extra_info", || {
+                let parse = self.parse_result().clone();
+                self.extra_info = Some(Arc::new(ExtraInfoResult::new(&parse)));
+            });
+        }
+        self.extra_info.as_ref().unwrap()
+    }
+
+    /// Lists the syntax axioms which convert formulas of one typecode into
+    /// another; see `SyntaxParser::coercions`.
+    #[cfg(feature = "grammar")]
+    pub fn coercions(&mut self) -> Vec<Coercion> {
+        self.syntax_parser().coercions()
+    }
+
+    /// Returns true if formulas of typecode `from` can be used where `to` is
+    /// expected, possibly through a chain of coercions.
+    #[cfg(feature = "grammar")]
+    pub fn can_coerce(&mut self, from: &str, to: &str) -> bool {
+        self.syntax_parser().can_coerce(from.as_bytes(), to.as_bytes())
+    }
+
+    /// Builds a formula of the given typecode from the tree returned by a
+    /// closure, without going through text; variables are those with a `$f`
+    /// statement anywhere in the database.  For example,
+    /// `db.build_formula("wff", |b| b.app_label(b"wi", vec![b.var(b"ph")?, b.var(b"ps")?]))`
+    /// builds `wff ( ph -> ps )` in set.mm.  Returns `None` if the closure
+    /// does, or if its tree cannot be coerced to the typecode.
+    #[cfg(feature = "grammar")]
+    pub fn build_formula<F>(&mut self, typecode: &str, build: F) -> Option<Formula>
+        where F: FnOnce(&FormulaBuilder) -> Option<SyntaxTree>
+    {
+        let parser = self.syntax_parser().clone();
+        let sset = self.parse_result().clone();
+        let vars = global_variables(&sset);
+        let builder = FormulaBuilder::new(&parser, &sset, &vars);
+        build(&builder).and_then(|tree| builder.build(tree, typecode.as_bytes()))
+    }
+
+    /// Parses a math string entered by a user, with symbols separated by
+    /// whitespace, as a formula of the given typecode.
+    ///
+    /// Variables take the typecode of their `$f` statements, as for
+    /// `grammar::global_variables`.  As for statements, a typecode such as
+    /// `|-` which no syntax axiom produces is parsed as the first syntax
+    /// typecode which works.  Returns `FormulaUnknownSymbol` if a symbol (or
+    /// the typecode) is not declared, `FormulaNotParsed` if the grammar
+    /// does not accept the formula, and `FormulaTooComplex` if it is nested
+    /// deeper than `grammar::MAX_PARSE_DEPTH` or longer than
+    /// `grammar::MAX_PARSE_LEN`.
+    #[cfg(feature = "grammar")]
+    pub fn parse_formula_str(&mut self,
+                             text: &str,
+                             expected_typecode: &str)
+                             -> Result<Formula, Diagnostic> {
+        let name = self.name_result().clone();
+        let mut math: Vec<TokenPtr> = vec![expected_typecode.as_bytes()];
+        math.extend(text.split_whitespace().map(str::as_bytes));
+        if let Some(unknown) = math.iter().find(|sym| name.lookup_symbol(sym).is_none()) {
+            return Err(Diagnostic::FormulaUnknownSymbol(copy_token(unknown)));
+        }
+        let parser = self.syntax_parser().clone();
+        let vars = global_variables(self.parse_result());
+        parser.parse_math_checked(&vars, &math)?;
+        Ok(Formula {
+            typecode: copy_token(math[0]),
+            symbols: math[1..].iter().map(|sym| copy_token(sym)).collect(),
+        })
+    }
+
+    /// Lists the tokens which may follow the beginning of a formula, given as
+    /// its symbols separated by whitespace, according to the grammar of the
+    /// syntax axioms; see `SyntaxParser::next_tokens`.
+    ///
+    /// Variables take the typecode of their `$f` statements, as for
+    /// `parse_formula_str`.  Returns `None` if no formula of the typecode
+    /// begins with these symbols.
+    #[cfg(feature = "grammar")]
+    pub fn next_formula_tokens(&mut self,
+                               text: &str,
+                               expected_typecode: &str)
+                               -> Option<NextTokens> {
+        let parser = self.syntax_parser().clone();
+        let vars = global_variables(self.parse_result());
+        let prefix: Vec<TokenPtr> = text.split_whitespace().map(str::as_bytes).collect();
+        parser.next_tokens(expected_typecode.as_bytes(), &prefix, &vars)
+    }
+
+    /// Expands the symbols defined by the given `df-` axioms into their
+    /// definitions.
+    ///
+    /// A definition is read as a rewrite rule from the first to the second
+    /// argument of its outermost syntax axiom, as in `|- ( ( ph /\ ps ) <-> -.
+    /// ( ph -> -. ps ) )` or `|- A = B`, and the rules are applied with
+    /// `Formula::rewrite` until none matches.  Dummy variables of a
+    /// definition are not renamed, so they may clash with variables of the
+    /// formula.  Labels which are not logical axioms of this shape are
+    /// ignored, and the formula is returned unchanged if it does not parse.
+    #[cfg(feature = "grammar")]
+    pub fn unfold(&mut self, formula: &Formula, definition_labels: &[&str]) -> Formula {
+        let name = self.name_result().clone();
+        let parse = self.parse_result().clone();
+        let parser = self.syntax_parser().clone();
+        let vars = global_variables(&parse);
+        let mut rules = Vec::new();
+        for label in definition_labels {
+            let stmt = match name.lookup_label(label.as_bytes()) {
+                Some(lookup) => parse.statement(lookup.address),
+                None => continue,
+            };
+            if stmt.statement_type() != StatementType::Axiom ||
+               parser.is_syntax_axiom(stmt.address()) {
+                continue;
+            }
+            if let Some((_, SyntaxTree::App(_, args))) = Formula::from_statement(stmt)
+                .parse(&parser, &vars) {
+                if args.len() == 2 {
+                    if let (Some(lhs), Some(rhs)) = (parser.to_formula(&args[0], &vars),
+                                                     parser.to_formula(&args[1], &vars)) {
+                        rules.push((lhs, rhs));
+                    }
+                }
+            }
+        }
+        formula.rewrite(&parser, &vars, &rules, &RewriteStrategy::default())
+            .map_or_else(|| formula.clone(), |rewritten| rewritten.formula)
+    }
+
+    /// Finds assertions which are the same as earlier ones up to renaming of
+    /// variables.
+    #[cfg(feature = "grammar")]
+    pub fn duplicate_result(&mut self) -> &Arc<DuplicateResult> {
+        if self.duplicates.is_none() {
+            time(&self.timer(), "duplicates", || {
+                let parse = self.parse_result().clone();
+                let name = self.name_result().clone();
+                let scope = self.scope_result().clone();
+                let parser = self.syntax_parser().clone();
+                self.duplicates =
+                    Some(Arc::new(DuplicateResult::new(&parse, &name, &scope, &parser)));
+            });
+        }
+        self.duplicates.as_ref().unwrap()
+    }
+
+    /// Finds disjoint variable conditions which proofs do not need.
+    pub fn disjoint_result(&mut self) -> &Arc<DisjointResult> {
+        if self.disjoint.is_none() {
+            time(&self.timer(), "disjoint", || {
+                let parse = self.parse_result().clone();
+                let name = self.name_result().clone();
+                let scope = self.scope_result().clone();
+                self.disjoint = Some(Arc::new(DisjointResult::new(&parse, &name, &scope)));
+            });
+        }
+        self.disjoint.as_ref().unwrap()
+    }
+
+    /// Finds unused hypotheses, variables and theorems.
+    pub fn lint_result(&mut self) -> &Arc<LintResult> {
+        if self.lint.is_none() {
+            time(&self.timer(), "lint", || {
+                let parse = self.parse_result().clone();
+                let scope = self.scope_result().clone();
+                self.lint = Some(Arc::new(LintResult::new(&parse, &scope)));
+            });
+        }
+        self.lint.as_ref().unwrap()
+    }
+
+    /// Checks comment markup and typesetting definitions.
+    #[cfg(feature = "grammar")]
+    pub fn markup_result(&mut self) -> &Arc<MarkupResult> {
+        if self.markup.is_none() {
+            time(&self.timer(), "markup", || {
+                let parse = self.parse_result().clone();
+                let name = self.name_result().clone();
+                let parser = self.syntax_parser().clone();
+                let typesetting = self.typesetting_result().clone();
+                let config = &self.options.markup;
+                self.markup =
+                    Some(Arc::new(MarkupResult::new(&parse, &name, &parser, &typesetting, config)));
+            });
+        }
+        self.markup.as_ref().unwrap()
+    }
+
+    /// Collects the contribution and revision histories of all assertions.
+    pub fn history_result(&mut self) -> &Arc<HistoryResult> {
+        if self.history.is_none() {
+            time(&self.timer(), "history", || {
+                let parse = self.parse_result().clone();
+                self.history = Some(Arc::new(HistoryResult::new(&parse)));
+            });
+        }
+        self.history.as_ref().unwrap()
+    }
+
+    /// Builds the tree of parts, chapters, sections and subsections given by
+    /// the heading comments.
+    pub fn outline(&mut self) -> &Arc<Outline> {
+        if self.outline.is_none() {
+            time(&self.timer(), "outline", || {
+                let parse = self.parse_result().clone();
+                self.outline = Some(Arc::new(Outline::new(&parse)));
+            });
+        }
+        self.outline.as_ref().unwrap()
+    }
+
+    /// Builds the graph of which assertions the proofs use, cut down by a
+    /// filter; see `graph`.
+    pub fn dependency_graph(&mut self, filter: &GraphFilter) -> DependencyGraph {
+        let parse = self.parse_result().clone();
+        let scope = self.scope_result().clone();
+        let outline = self.outline().clone();
+        time(&self.timer(),
+             "dependency_graph",
+             || DependencyGraph::new(&parse, &scope, &outline, filter))
+    }
+
+    /// Computes proof sizes, use counts and axiom dependencies of every
+    /// assertion; see `proof_stats`.
+    pub fn proof_stats(&mut self) -> &Arc<ProofStats> {
+        if self.proof_stats.is_none() {
+            time(&self.timer(), "proof_stats", || {
+                let parse = self.parse_result().clone();
+                let name = self.name_result().clone();
+                let scope = self.scope_result().clone();
+                self.proof_stats = Some(Arc::new(ProofStats::new(&parse, &name, &scope)));
+            });
+        }
+        self.proof_stats.as_ref().unwrap()
+    }
+
     /// Get a statement by label.
     pub fn statement(&mut self, name: &str) -> Option<StatementRef> {
         match self.name_result().lookup_label(name.as_bytes()) {
@@ -524,9 +1431,465 @@ impl Database {
         }
     }
 
+    /// Writes the database as a single `.mm` source with file inclusions
+    /// expanded; see `SegmentSet::write_mm`.
+    ///
+    /// Unmodified text, including comments and whitespace, is reproduced
+    /// exactly.
+    pub fn write_mm<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        self.parse_result().write_mm(out)
+    }
+
+    /// Computes the axioms and theorems that the proof of a statement depends
+    /// on, directly or transitively; see `usage::trace_usage`.
+    ///
+    /// Returns `None` if the label does not exist.
+    pub fn usage(&mut self, label: &str) -> Option<Usage> {
+        let parse = self.parse_result().clone();
+        let scope = self.scope_result().clone();
+        let sref = match self.statement(label) {
+            Some(sref) => sref.address(),
+            None => return None,
+        };
+        Some(time(&self.timer(),
+                  "usage",
+                  || usage::trace_usage(&parse, &scope, parse.statement(sref))))
+    }
+
+    /// Builds the index of which theorems use each statement; see
+    /// `usage::UsageIndex`.
+    pub fn usage_index(&mut self) -> &Arc<UsageIndex> {
+        if self.usage_index.is_none() {
+            time(&self.timer(), "usage_index", || {
+                let parse = self.parse_result().clone();
+                let scope = self.scope_result().clone();
+                self.usage_index = Some(Arc::new(UsageIndex::new(&parse, &scope)));
+            });
+        }
+        self.usage_index.as_ref().unwrap()
+    }
+
+    /// Lists the theorems whose proofs use a statement directly, in database
+    /// order.  The index is built on the first call and kept until the
+    /// database changes.
+    ///
+    /// Returns an empty list if the label does not exist.
+    pub fn usages_of(&mut self, label: &str) -> &[StatementAddress] {
+        let address = match self.statement(label) {
+            Some(stmt) => stmt.address(),
+            None => return &[],
+        };
+        self.usage_index().users_of(address)
+    }
+
+    /// Builds the index of which statements use each math symbol; see
+    /// `occurrences::OccurrenceIndex`.
+    pub fn occurrence_index(&mut self) -> &Arc<OccurrenceIndex> {
+        if self.occurrence_index.is_none() {
+            time(&self.timer(), "occurrence_index", || {
+                let parse = self.parse_result().clone();
+                self.occurrence_index = Some(Arc::new(OccurrenceIndex::new(&parse)));
+            });
+        }
+        self.occurrence_index.as_ref().unwrap()
+    }
+
+    /// Lists every occurrence of a math symbol in the math strings of
+    /// statements, with the span of each token, in database order.  The index
+    /// is built on the first call and kept until the database changes.
+    pub fn occurrences_of(&mut self, symbol: &str) -> Vec<Occurrence> {
+        let parse = self.parse_result().clone();
+        self.occurrence_index().occurrences(&parse, symbol.as_bytes())
+    }
+
+    /// Lists the optional variables of a theorem, which its proof may use as
+    /// dummy variables; see `dummy::optional_variables`.
+    ///
+    /// Returns `None` if the label is not a `$p` statement with a valid frame.
+    pub fn optional_variables(&mut self, label: &str) -> Option<Vec<OptionalVariable>> {
+        let parse = self.parse_result().clone();
+        let name = self.name_result().clone();
+        let scope = self.scope_result().clone();
+        let frame = scope.get(label.as_bytes())?;
+        if frame.stype != StatementType::Provable {
+            return None;
+        }
+        Some(dummy::optional_variables(&parse, &name, frame))
+    }
+
+    /// Suggests dummy variables of a typecode for the proof of a theorem,
+    /// which the proof does not use yet and which are disjoint from the given
+    /// variables; see `dummy::suggest_dummies`.
+    ///
+    /// Returns an empty list if the label is not a `$p` statement with a
+    /// valid frame.
+    pub fn suggest_dummies(&mut self,
+                           label: &str,
+                           typecode: &str,
+                           disjoint_from: &[&str],
+                           used: &[&str])
+                           -> Vec<Token> {
+        let optional = match self.optional_variables(label) {
+            Some(optional) => optional,
+            None => return Vec::new(),
+        };
+        let name = self.name_result().clone();
+        let scope = self.scope_result().clone();
+        let frame = scope.get(label.as_bytes()).unwrap();
+        let disjoint_from: Vec<TokenPtr> = disjoint_from.iter().map(|var| var.as_bytes()).collect();
+        let used: Vec<TokenPtr> = used.iter().map(|var| var.as_bytes()).collect();
+        dummy::suggest_dummies(&name,
+                               frame,
+                               &optional,
+                               typecode.as_bytes(),
+                               &disjoint_from,
+                               &used)
+    }
+
+    /// Expands the proof of a theorem into steps with the formulas they
+    /// prove; see `proof::explain_proof`.
+    ///
+    /// Returns `None` if the label does not exist or is not a `$p` statement.
+    pub fn explain_proof(&mut self, label: &str) -> Option<Result<Vec<ProofStep>, Diagnostic>> {
+        let parse = self.parse_result().clone();
+        let name = self.name_result().clone();
+        let scope = self.scope_result().clone();
+        let addr = match self.statement(label) {
+            Some(sref) if sref.statement_type() == StatementType::Provable => sref.address(),
+            _ => return None,
+        };
+        Some(proof::explain_proof(&parse, &name, &scope, parse.statement(addr)))
+    }
+
+    /// Rewrites the compressed proof of a theorem as compactly as possible,
+    /// laid out by the `format` option; see `proof::rewrap_compressed`.
+    ///
+    /// Returns `None` if the label does not exist or is not a `$p` statement
+    /// with a compressed proof.
+    pub fn rewrap_proof(&mut self, label: &str) -> Option<Result<String, Diagnostic>> {
+        let parse = self.parse_result().clone();
+        let name = self.name_result().clone();
+        let scope = self.scope_result().clone();
+        let addr = match self.statement(label) {
+            Some(sref) if sref.statement_type() == StatementType::Provable => sref.address(),
+            _ => return None,
+        };
+        proof::rewrap_compressed(&parse, &name, &scope, parse.statement(addr))
+    }
+
+    /// Verifies the proof of a single `$p` statement.
+    ///
+    /// Unless the whole database has already been scope checked, this only
+    /// scope checks the segment of the statement and those declaring the labels
+    /// of its proof, so a tool editing one proof need not verify the database
+    /// to check it.  Returns `None` if the label does not exist or is not a
+    /// `$p` statement, and fails with a scope error if the statement has no
+    /// valid frame.
+    pub fn verify_one(&mut self, label: &str) -> Option<Result<(), Diagnostic>> {
+        let parse = self.parse_result().clone();
+        let name = self.name_result().clone();
+        let addr = match self.statement(label) {
+            Some(sref) if sref.statement_type() == StatementType::Provable => sref.address(),
+            _ => return None,
+        };
+        let stmt = parse.statement(addr);
+        let scope = match self.scopes {
+            Some(ref scope) => scope.clone(),
+            None => {
+                let mut ids = vec![addr.segment_id];
+                for index in 0..stmt.proof_len() {
+                    let chunk = stmt.proof_slice_at(index);
+                    let step = chunk.rsplit(|&ch| ch == b':' || ch == b'=').next().unwrap();
+                    if let Some(lookup) = name.lookup_label(step) {
+                        ids.push(lookup.address.segment_id);
+                    }
+                }
+                ids.sort_by_key(|id| id.0);
+                ids.dedup();
+                Arc::new(time(&self.timer(),
+                              "verify_one",
+                              || scopeck::scope_check_segments(&parse, &name, &ids)))
+            }
+        };
+        if scope.get(stmt.label()).is_none() {
+            return Some(Err(scope.diagnostics()
+                .into_iter()
+                .find(|diag| diag.0 == addr)
+                .map_or_else(|| Diagnostic::StepMissing(copy_token(stmt.label())), |diag| diag.1)));
+        }
+        Some(verify::verify_one(&parse, &name, &scope, &mut (), stmt))
+    }
+
+    /// Verifies the proof of a `$p` statement without stopping at the first
+    /// error; see `verify::verify_soft`.
+    ///
+    /// Returns the errors with the index of the step where each occurred, or
+    /// `None` if the label does not exist or is not a `$p` statement with a
+    /// valid frame.
+    pub fn verify_soft(&mut self, label: &str) -> Option<Vec<(usize, Diagnostic)>> {
+        let parse = self.parse_result().clone();
+        let name = self.name_result().clone();
+        let scope = self.scope_result().clone();
+        let stmt = match self.statement(label) {
+            Some(sref) if sref.statement_type() == StatementType::Provable => {
+                parse.statement(sref.address())
+            }
+            _ => return None,
+        };
+        scope.get(stmt.label())?;
+        Some(verify::verify_soft(&parse, &name, &scope, stmt))
+    }
+
+    /// Returns the database as seen from a statement, with only the labels
+    /// and symbols usable there; see `view::DatabaseView`.
+    pub fn view_at(&mut self, address: StatementAddress) -> DatabaseView {
+        let parse = self.parse_result().clone();
+        let name = self.name_result().clone();
+        let scope = self.scope_result().clone();
+        DatabaseView::new(parse, name, scope, address)
+    }
+
+    /// Verifies a proof which may contain `?` steps, returning what each of
+    /// them must prove; see `verify::verify_incomplete`.
+    ///
+    /// Returns `None` if the label does not exist or is not a `$p` statement
+    /// with a valid frame.
+    pub fn verify_incomplete(&mut self, label: &str) -> Option<Result<Vec<Goal>, Diagnostic>> {
+        let parse = self.parse_result().clone();
+        let name = self.name_result().clone();
+        let scope = self.scope_result().clone();
+        let stmt = match self.statement(label) {
+            Some(sref) if sref.statement_type() == StatementType::Provable => {
+                parse.statement(sref.address())
+            }
+            _ => return None,
+        };
+        scope.get(stmt.label())?;
+        Some(verify::verify_incomplete(&parse, &name, &scope, stmt))
+    }
+
+    /// Tries simple repairs of the proof of a theorem which fails to verify;
+    /// see `repair::suggest_repairs`.
+    ///
+    /// Returns `None` if the label does not exist or is not a `$p` statement,
+    /// its proof is correct, or the error is not one which the repairs
+    /// address.
+    pub fn suggest_repairs(&mut self, label: &str) -> Option<RepairReport> {
+        let parse = self.parse_result().clone();
+        let name = self.name_result().clone();
+        let scope = self.scope_result().clone();
+        let addr = match self.statement(label) {
+            Some(sref) if sref.statement_type() == StatementType::Provable => sref.address(),
+            _ => return None,
+        };
+        time(&self.timer(),
+             "repair",
+             || repair::suggest_repairs(&parse, &name, &scope, parse.statement(addr)))
+    }
+
+    /// Builds a database containing only some theorems and what they need to
+    /// verify; see `subset::extract_text`.  The new database has the default
+    /// options, except for the number of jobs and verification limits, and a
+    /// single file named `extract.mm`.
+    ///
+    /// Returns `None` if one of the labels does not exist.
+    #[cfg(feature = "grammar")]
+    pub fn extract(&mut self, labels: &[&str]) -> Option<Database> {
+        let parse = self.parse_result().clone();
+        let name = self.name_result().clone();
+        let scope = self.scope_result().clone();
+        let parser = self.syntax_parser().clone();
+        let mut roots = Vec::new();
+        for label in labels {
+            roots.push(self.statement(label)?.address());
+        }
+        let text = time(&self.timer(), "extract", || {
+            subset::extract_text(&parse, &name, &scope, Some(&parser), &roots)
+        });
+        let mut db = Database::new(DbOptions {
+            jobs: self.options.jobs,
+            verify: self.options.verify,
+            format: self.options.format.clone(),
+            ..DbOptions::default()
+        });
+        db.parse("extract.mm".to_owned(), vec![("extract.mm".to_owned(), text)]);
+        Some(db)
+    }
+
+    /// Reads the contribution and revision history of a statement from its
+    /// description comment.
+    ///
+    /// Returns `None` if the label does not exist.
+    pub fn history(&mut self, label: &str) -> Option<History> {
+        self.statement(label).map(history::statement_history)
+    }
+
+    /// Classifies all assertions by whether and how they were verified.
+    pub fn coverage(&mut self) -> CoverageReport {
+        let parse = self.parse_result().clone();
+        let scope = self.scope_result().clone();
+        let verify = self.verify_result().clone();
+        time(&self.timer(),
+             "coverage",
+             || CoverageReport::new(&parse, &scope, &verify))
+    }
+
+    /// Lists the theorem stubs with the theorems waiting for them.
+    pub fn stub_report(&mut self) -> StubReport {
+        let parse = self.parse_result().clone();
+        let scope = self.scope_result().clone();
+        time(&self.timer(), "stubs", || StubReport::new(&parse, &scope))
+    }
+
+    /// Finds subformulas repeated across sections which may be worth a new
+    /// definition.
+    #[cfg(feature = "search")]
+    pub fn suggest_definitions(&mut self, options: &ExtractionOptions) -> Vec<DefinitionSuggestion> {
+        let parse = self.parse_result().clone();
+        let name = self.name_result().clone();
+        time(&self.timer(),
+             "extraction",
+             || extraction::find_repeated_subformulas(&parse, &name, options))
+    }
+
+    /// Compares the assertions of this database with those of an older
+    /// version of it; see the `diff` module.
+    pub fn diff(&mut self, old: &mut Database) -> Vec<StatementDiff> {
+        let old_parse = old.parse_result().clone();
+        let old_name = old.name_result().clone();
+        let old_scope = old.scope_result().clone();
+        let parse = self.parse_result().clone();
+        let name = self.name_result().clone();
+        let scope = self.scope_result().clone();
+        time(&self.timer(), "diff", || {
+            diff_databases(&DiffSide {
+                               sset: &old_parse,
+                               nset: &old_name,
+                               scope: &old_scope,
+                           },
+                           &DiffSide {
+                               sset: &parse,
+                               nset: &name,
+                               scope: &scope,
+                           })
+        })
+    }
+
+    /// Runs the reference verifier on all proofs and reports where it
+    /// disagrees with the main verifier.
+    pub fn differential_verify(&mut self) -> Vec<Disagreement> {
+        let parse = self.parse_result().clone();
+        let scope = self.scope_result().clone();
+        let verify = self.verify_result().clone();
+        time(&self.timer(),
+             "differential",
+             || differential::compare(&parse, &scope, &verify))
+    }
+
+    /// Finds the `$a` and `$p` statements matching a pattern; see the `search`
+    /// module for the pattern syntax.
+    #[cfg(feature = "search")]
+    pub fn search_formula(&mut self, pattern: &str, options: &SearchOptions) -> Vec<SearchMatch> {
+        let parse = self.parse_result().clone();
+        let name = self.name_result().clone();
+        time(&self.timer(),
+             "search",
+             || search::search_formula(&parse, &name, pattern, options))
+    }
+
+    /// Finds the statements whose labels match a glob with `*` and `?`
+    /// wildcards.
+    #[cfg(feature = "search")]
+    pub fn search_labels(&mut self, glob: &str) -> Vec<TextMatch> {
+        search::search_labels(self.parse_result(), glob)
+    }
+
+    /// Finds every occurrence of a run of math tokens in any statement.
+    #[cfg(feature = "search")]
+    pub fn search_math(&mut self, tokens: &str) -> Vec<TextMatch> {
+        search::search_math(self.parse_result(), tokens)
+    }
+
+    /// Finds every match of a regular expression in the comments.
+    #[cfg(feature = "search")]
+    pub fn search_comments(&mut self, pattern: &str) -> Result<Vec<TextMatch>, regex::Error> {
+        let regex = Regex::new(pattern)?;
+        Ok(search::search_comments(self.parse_result(), &regex))
+    }
+
+    /// Collects all `df-` definitions in the database for a definition index.
+    #[cfg(feature = "html")]
+    pub fn definitions(&mut self) -> Vec<DefinitionEntry> {
+        let parse = self.parse_result().clone();
+        let name = self.name_result().clone();
+        let scope = self.scope_result().clone();
+        let typesetting = self.typesetting_result().clone();
+        time(&self.timer(),
+             "definitions",
+             || definitions::collect_definitions(&parse, &name, &scope, &typesetting))
+    }
+
+    /// Writes an HTML page `LABEL.html` for every `$a` and `$p` statement to
+    /// a directory, typeset with the `althtmldef` definitions if `alt_html`
+    /// is true and the `htmldef` ones otherwise.
+    #[cfg(feature = "html")]
+    pub fn write_html_pages(&mut self, dir: &str, alt_html: bool) -> io::Result<()> {
+        let parse = self.parse_result().clone();
+        let name = self.name_result().clone();
+        let scope = self.scope_result().clone();
+        let typesetting = self.typesetting_result().clone();
+        time(&self.timer(), "html", || {
+            for sref in parse.segments() {
+                for stmt in sref {
+                    match stmt.statement_type() {
+                        StatementType::Axiom | StatementType::Provable => {}
+                        _ => continue,
+                    }
+                    let path = Path::new(dir).join(format!("{}.html", as_str(stmt.label())));
+                    let mut out = File::create(path)?;
+                    html::write_statement_page(&parse,
+                                               &name,
+                                               &scope,
+                                               &typesetting,
+                                               alt_html,
+                                               stmt,
+                                               &mut out)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Writes a Markdown rendering of an `$a` or `$p` statement, with math
+    /// typeset using the `althtmldef` definitions.
+    #[cfg(feature = "html")]
+    pub fn export_markdown<W: Write>(&mut self, label: &str, out: &mut W) -> io::Result<()> {
+        let parse = self.parse_result().clone();
+        let name = self.name_result().clone();
+        let scope = self.scope_result().clone();
+        let typesetting = self.typesetting_result().clone();
+        let stmt = self.statement(label).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("Unknown label {}", label))
+            })?;
+        html::write_statement_markdown(&parse, &name, &scope, &typesetting, stmt, out)
+    }
+
+    /// Writes the statements, frames and parse trees of the database as
+    /// JSON; see the `json` module.
+    #[cfg(feature = "json")]
+    pub fn export_json<W: Write>(&mut self, out: &mut W) -> serde_json::Result<()> {
+        let parse = self.parse_result().clone();
+        let name = self.name_result().clone();
+        let scope = self.scope_result().clone();
+        time(&self.timer(),
+             "json",
+             || json::export_json(&parse, &name, &scope, out))
+    }
+
     /// Export an mmp file for a given statement.
+    #[cfg(feature = "export")]
     pub fn export(&mut self, stmt: String) {
-        time(&self.options.clone(), "export", || {
+        time(&self.timer(), "export", || {
             let parse = self.parse_result().clone();
             let scope = self.scope_result().clone();
             let name = self.name_result().clone();
@@ -542,6 +1905,24 @@ impl Database {
         })
     }
 
+    /// Translates the database to Metamath Zero, writing the specification to
+    /// `base.mm0` and the proofs to `base.mmu`.
+    #[cfg(feature = "export")]
+    pub fn export_mm0(&mut self, base: &str) {
+        time(&self.timer(), "export_mm0", || {
+            let parse = self.parse_result().clone();
+            let scope = self.scope_result().clone();
+            let name = self.name_result().clone();
+            File::create(format!("{}.mm0", base))
+                .and_then(|mm0| File::create(format!("{}.mmu", base)).map(|mmu| (mm0, mmu)))
+                .map_err(export::ExportError::Io)
+                .and_then(|(mut mm0, mut mmu)| {
+                    mm0::export_mm0(&parse, &name, &scope, &mut mm0, &mut mmu)
+                })
+                .unwrap()
+        })
+    }
+
     /// Runs one or more passes and collects all errors they generate.
     ///
     /// Passes are identified by the `types` argument and are not inclusive; if
@@ -552,17 +1933,68 @@ impl Database {
     /// will be a bit slow if there are thousands of errors.
     pub fn diag_notations(&mut self, types: Vec<DiagnosticClass>) -> Vec<Notation> {
         let mut diags = Vec::new();
-        if types.contains(&DiagnosticClass::Parse) {
-            diags.extend(self.parse_result().parse_diagnostics());
+        for &class in ALL_CLASSES.iter() {
+            if types.contains(&class) {
+                diags.extend(self.class_diagnostics(class));
+            }
         }
-        if types.contains(&DiagnosticClass::Scope) {
-            diags.extend(self.scope_result().diagnostics());
+        time(&self.timer(),
+             "diag",
+             || {
+                 let name = self.name_result().clone();
+                 diag::to_annotations(self.parse_result(), &name, diags)
+             })
+    }
+
+    /// Runs a single pass and returns its raw diagnostics.
+    fn class_diagnostics(&mut self, class: DiagnosticClass) -> Vec<(StatementAddress, Diagnostic)> {
+        match class {
+            DiagnosticClass::Parse => self.parse_result().parse_diagnostics(),
+            DiagnosticClass::Scope => self.scope_result().diagnostics(),
+            DiagnosticClass::Verify => self.verify_result().diagnostics(),
+            DiagnosticClass::Discouraged => self.discouraged_result().diagnostics(),
+            DiagnosticClass::Deprecated => self.deprecation_result().diagnostics(),
+            #[cfg(feature = "grammar")]
+            DiagnosticClass::Grammar => self.grammar_result().diagnostics(),
+            #[cfg(feature = "grammar")]
+            DiagnosticClass::Duplicate => self.duplicate_result().diagnostics(),
+            // these passes need the grammar of the syntax axioms
+            #[cfg(not(feature = "grammar"))]
+            DiagnosticClass::Grammar | DiagnosticClass::Duplicate => Vec::new(),
+            DiagnosticClass::Disjoint => self.disjoint_result().diagnostics(),
+            DiagnosticClass::Lint => self.lint_result().diagnostics(),
+            DiagnosticClass::Markup => {
+                #[cfg(feature = "grammar")]
+                let mut diags = self.markup_result().diagnostics();
+                #[cfg(not(feature = "grammar"))]
+                let mut diags = Vec::new();
+                diags.extend(self.extra_info_result().diagnostics());
+                diags
+            }
+            DiagnosticClass::History => self.history_result().diagnostics(),
         }
-        if types.contains(&DiagnosticClass::Verify) {
-            diags.extend(self.verify_result().diagnostics());
+    }
+
+    /// Runs every pass which generates diagnostics and summarizes the result.
+    ///
+    /// Elapsed times are for bringing each pass up to date, so a pass which
+    /// was already current before the call reports almost no time.  At most
+    /// `max_findings` of the most severe notations are kept.
+    pub fn health_check(&mut self, max_findings: usize) -> HealthReport {
+        let mut report = HealthReport::default();
+        let mut notations = Vec::new();
+        for &class in ALL_CLASSES.iter() {
+            let now = Instant::now();
+            let diags = self.class_diagnostics(class);
+            let elapsed = now.elapsed();
+            let name = self.name_result().clone();
+            let notes = diag::to_annotations(self.parse_result(), &name, diags);
+            report.passes.push(PassSummary::new(class, &notes, elapsed));
+            notations.extend(notes);
         }
-        time(&self.options.clone(),
-             "diag",
-             || diag::to_annotations(self.parse_result(), diags))
+        notations.sort_by_key(|note| Reverse(note.level));
+        notations.truncate(max_findings);
+        report.worst = notations;
+        report
     }
 }
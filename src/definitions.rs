@@ -0,0 +1,186 @@
+//! Generation of a definition index for a database.
+//!
+//! Definitions are recognized by the set.mm label convention `df-NAME`.  Since
+//! there is no grammar in this crate, the syntax construct a definition
+//! introduces and its soundness justification are found heuristically.  The
+//! syntax axiom is `wNAME` or `cNAME` if either exists, and otherwise the
+//! closest preceding syntax axiom all of whose constants occur in the
+//! definition (set.mm places the two close together, but labels such as
+//! `wb` for `df-bi` do not follow the naming pattern).  The justification
+//! theorem is `NAMEjust`.  Either is omitted if nothing suitable is found.
+
+use extra_info::JConfig;
+use nameck::Nameset;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::SymbolType;
+use parser::as_str;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use std::io;
+use std::io::Write;
+use typesetting::TypesettingData;
+
+/// Information about a single definition.
+#[derive(Clone,Debug)]
+pub struct DefinitionEntry {
+    /// The address of the `df-` axiom.
+    pub address: StatementAddress,
+    /// The label of the definition.
+    pub label: String,
+    /// The math string of the definition, as ASCII tokens.
+    pub math: String,
+    /// The math string of the definition, typeset using `althtmldef`.
+    pub html: String,
+    /// The label of the syntax axiom for the defined construct, if found.
+    pub syntax: Option<String>,
+    /// Pairs of variables with mandatory disjoint variable conditions.
+    pub disjoint: Vec<(String, String)>,
+    /// The label of the justification theorem, if found.
+    pub justification: Option<String>,
+}
+
+fn find_assertion(nset: &Nameset, sset: &SegmentSet, label: &str, stype: StatementType) -> bool {
+    nset.lookup_label(label.as_bytes())
+        .is_some_and(|l| sset.statement(l.address).statement_type() == stype)
+}
+
+/// Checks whether every constant after the typecode of a syntax axiom occurs
+/// in a definition.
+fn constants_occur(nset: &Nameset, syntax: StatementRef, def: StatementRef) -> bool {
+    let mut any = false;
+    for tok in syntax.math_iter().skip(1) {
+        let is_const = nset.lookup_symbol(tok.slice)
+            .is_some_and(|sym| sym.stype == SymbolType::Constant);
+        if is_const {
+            if !def.math_iter().any(|dtok| dtok.slice == tok.slice) {
+                return false;
+            }
+            any = true;
+        }
+    }
+    any
+}
+
+/// Collects all definitions in the database, in database order.
+pub fn collect_definitions(sset: &SegmentSet,
+                           nset: &Nameset,
+                           scope: &ScopeResult,
+                           typesetting: &TypesettingData)
+                           -> Vec<DefinitionEntry> {
+    let config = JConfig::new(sset);
+    let mut out = Vec::new();
+    let mut syntax_axioms = Vec::new();
+    for sref in sset.segments() {
+        for stmt in sref {
+            if stmt.statement_type() != StatementType::Axiom {
+                continue;
+            }
+            if stmt.math_len() > 0 && !config.is_provable(stmt.math_at(0).slice) {
+                syntax_axioms.push(stmt);
+                continue;
+            }
+            if !stmt.label().starts_with(b"df-") {
+                continue;
+            }
+            let label = as_str(stmt.label()).to_owned();
+            let name = &label[3..];
+            let syntax = ["w", "c"]
+                .iter()
+                .map(|prefix| format!("{}{}", prefix, name))
+                .find(|syn| find_assertion(nset, sset, syn, StatementType::Axiom))
+                .or_else(|| {
+                    syntax_axioms.iter()
+                        .rev()
+                        .find(|&&syn| constants_occur(nset, syn, stmt))
+                        .map(|syn| as_str(syn.label()).to_owned())
+                });
+            let just = format!("{}just", name);
+            let justification = if find_assertion(nset, sset, &just, StatementType::Provable) {
+                Some(just)
+            } else {
+                None
+            };
+            let disjoint = match scope.get(stmt.label()) {
+                Some(frame) => {
+                    frame.mandatory_dv
+                        .iter()
+                        .map(|&(v1, v2)| {
+                            (as_str(nset.atom_name(frame.var_list[v1])).to_owned(),
+                             as_str(nset.atom_name(frame.var_list[v2])).to_owned())
+                        })
+                        .collect()
+                }
+                None => Vec::new(),
+            };
+            out.push(DefinitionEntry {
+                address: stmt.address(),
+                label: label.clone(),
+                math: stmt.math_iter().map(|t| as_str(t.slice)).collect::<Vec<_>>().join(" "),
+                html: typesetting.statement_as_alt_html(stmt),
+                syntax: syntax,
+                disjoint: disjoint,
+                justification: justification,
+            });
+        }
+    }
+    out
+}
+
+/// Escapes the characters of a text which are special in HTML.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Writes a definition index as an HTML table.
+///
+/// Labels link to `LABEL.html`, following the page naming of metamath.exe.
+pub fn write_html<W: Write>(entries: &[DefinitionEntry], out: &mut W) -> io::Result<()> {
+    writeln!(out, "<table class=\"deflist\">")?;
+    writeln!(out,
+             "<tr><th>Definition</th><th>Statement</th><th>Syntax</th><th>Distinct \
+              variables</th><th>Justification</th></tr>")?;
+    for entry in entries {
+        let link = |label: &Option<String>| match *label {
+            Some(ref l) => format!("<a href=\"{0}.html\">{0}</a>", escape_html(l)),
+            None => String::new(),
+        };
+        let dv = entry.disjoint
+            .iter()
+            .map(|(x, y)| format!("{} {}", escape_html(x), escape_html(y)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(out,
+                 "<tr><td><a href=\"{0}.html\">{0}</a></td><td>{1}</td><td>{2}</td><td>{3}</td>\
+                  <td>{4}</td></tr>",
+                 escape_html(&entry.label),
+                 entry.html,
+                 link(&entry.syntax),
+                 dv,
+                 link(&entry.justification))?;
+    }
+    writeln!(out, "</table>")
+}
+
+/// Writes a definition index as a Markdown table, with statements shown as
+/// ASCII math strings.
+pub fn write_markdown<W: Write>(entries: &[DefinitionEntry], out: &mut W) -> io::Result<()> {
+    writeln!(out, "| Definition | Statement | Syntax | Distinct variables | Justification |")?;
+    writeln!(out, "|---|---|---|---|---|")?;
+    for entry in entries {
+        let dv = entry.disjoint
+            .iter()
+            .map(|(x, y)| format!("`{} {}`", x, y))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(out,
+                 "| {} | `{}` | {} | {} | {} |",
+                 entry.label,
+                 entry.math.replace('|', "\\|"),
+                 entry.syntax.as_ref().map_or("", |s| &s[..]),
+                 dv,
+                 entry.justification.as_ref().map_or("", |s| &s[..]))?;
+    }
+    Ok(())
+}
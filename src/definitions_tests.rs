@@ -0,0 +1,37 @@
+use test_fixtures::mkdb;
+
+const DEFINITIONS: &[u8] = b"$c |- ( ) <-> -/\\ wff class A. $.
+$v ph ps x $.
+wph $f wff ph $.
+wps $f wff ps $.
+vx $f class x $.
+wb $a wff ( ph <-> ps ) $.
+wal $a wff A. x ph $.
+wn $a wff -/\\ ph $.
+bijust $p |- ( ph <-> ps ) $= ? $.
+df-bi $a |- ( ph <-> ps ) $.
+${ $d x ph $. df-al $a |- ( A. x ph <-> ph ) $. $}
+df-nand $a |- ( -/\\ ph <-> ph ) $.
+df-x $a |- ph $.
+";
+
+#[test]
+fn test_definitions() {
+    let mut db = mkdb(DEFINITIONS);
+    let defs = db.definitions();
+    let labels: Vec<&str> = defs.iter().map(|d| &d.label[..]).collect();
+    assert_eq!(labels, vec!["df-bi", "df-al", "df-nand", "df-x"]);
+    assert_eq!(defs[0].syntax, Some("wb".to_owned()));
+    assert_eq!(defs[0].justification, Some("bijust".to_owned()));
+    assert_eq!(defs[0].math, "|- ( ph <-> ps )");
+    assert_eq!(defs[1].syntax, Some("wal".to_owned()));
+    assert_eq!(defs[1].disjoint, vec![("x".to_owned(), "ph".to_owned())]);
+    assert_eq!(defs[1].justification, None);
+    assert_eq!(defs[2].syntax, Some("wn".to_owned()));
+    assert_eq!(defs[3].syntax, None);
+
+    let mut md = Vec::new();
+    ::definitions::write_markdown(&defs, &mut md).unwrap();
+    let md = String::from_utf8(md).unwrap();
+    assert!(md.contains("| df-al | `\\|- ( A. x ph <-> ph )` | wal | `x ph` |  |"));
+}
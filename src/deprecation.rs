@@ -0,0 +1,156 @@
+//! Registry of deprecated labels and math symbols.
+//!
+//! Databases evolve by introducing new notations and theorems and phasing out
+//! the old ones; rather than breaking existing content, an item is first
+//! deprecated so that new references to it are flagged.  Items are marked
+//! deprecated from three sources:
+//!
+//! * `$j deprecated 'name';` or `$j deprecated 'name' as 'replacement';`
+//!   commands, where `name` is a statement label or a math symbol;
+//! * `$j deprecation_tag 'phrase';` commands, which deprecate every `$a` or
+//!   `$p` statement whose description comment contains the phrase;
+//! * the same two lists in `DeprecationConfig`, supplied by the caller.
+//!
+//! A warning is generated for every reference to a deprecated label in a
+//! proof, and every use of a deprecated symbol in a math string, except within
+//! statements which are themselves deprecated.  If a replacement is known, it
+//! is carried in the diagnostic.
+
+use diag::Diagnostic;
+use discouraged::comment_contains;
+use extra_info::JDirective;
+use extra_info::parse_directive;
+use extra_info::process_j_commands;
+use parser::StatementAddress;
+use parser::StatementType;
+use parser::Token;
+use parser::copy_token;
+use segment_set::SegmentSet;
+use usage::proof_labels;
+use util::HashMap;
+use util::new_map;
+
+/// Deprecations supplied by the caller rather than the database.
+#[derive(Clone,Debug,Default)]
+pub struct DeprecationConfig {
+    /// Deprecated labels and math symbols, with optional replacements.
+    pub items: Vec<(String, Option<String>)>,
+    /// Phrases which deprecate a statement when they occur in its description
+    /// comment.
+    pub tags: Vec<String>,
+}
+
+/// Analysis pass result for deprecated items.
+#[derive(Default,Clone,Debug)]
+pub struct DeprecationRegistry {
+    items: HashMap<Token, Option<Token>>,
+    tags: Vec<Vec<u8>>,
+    diagnostics: Vec<(StatementAddress, Diagnostic)>,
+}
+
+impl DeprecationRegistry {
+    /// Collects deprecations from the configuration and from `$j` comments,
+    /// and checks all statements for references to deprecated items.
+    pub fn new(sset: &SegmentSet, config: &DeprecationConfig) -> DeprecationRegistry {
+        let mut reg = DeprecationRegistry {
+            items: new_map(),
+            tags: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        for (name, replacement) in &config.items {
+            reg.items.insert(name.as_bytes().to_owned().into_boxed_slice(),
+                             replacement.as_ref()
+                                 .map(|r| r.as_bytes().to_owned().into_boxed_slice()));
+        }
+        for tag in &config.tags {
+            reg.tags.push(tag.as_bytes().to_owned());
+        }
+        for command in process_j_commands(sset) {
+            if let Ok(directive) = parse_directive(&command) {
+                reg.add_directive(directive);
+            }
+        }
+
+        for sref in sset.segments() {
+            for stmt in sref {
+                match stmt.statement_type() {
+                    StatementType::Axiom | StatementType::Provable => {}
+                    _ => continue,
+                }
+                if reg.items.contains_key(stmt.label()) {
+                    continue;
+                }
+                if let Some(comment) = stmt.associated_comment() {
+                    if reg.tags.iter().any(|tag| comment_contains(comment, tag)) {
+                        reg.items.insert(copy_token(stmt.label()), None);
+                    }
+                }
+            }
+        }
+
+        for sref in sset.segments() {
+            for stmt in sref {
+                match stmt.statement_type() {
+                    StatementType::Axiom |
+                    StatementType::Provable |
+                    StatementType::Essential |
+                    StatementType::Floating |
+                    StatementType::Disjoint => {}
+                    _ => continue,
+                }
+                if stmt.statement_type().takes_label() && reg.items.contains_key(stmt.label()) {
+                    continue;
+                }
+                for index in 0..stmt.math_len() {
+                    let symbol = stmt.math_at(index).slice;
+                    if let Some(replacement) = reg.items.get(symbol) {
+                        reg.diagnostics.push((stmt.address(),
+                                              Diagnostic::DeprecatedSymbol(index,
+                                                                           copy_token(symbol),
+                                                                           replacement.clone())));
+                    }
+                }
+                if stmt.statement_type() == StatementType::Provable {
+                    for (index, label) in proof_labels(stmt).0 {
+                        if let Some(replacement) = reg.items.get(label) {
+                            reg.diagnostics.push((stmt.address(),
+                                                  Diagnostic::DeprecatedLabel(index,
+                                                                              copy_token(label),
+                                                                              replacement.clone())));
+                        }
+                    }
+                }
+            }
+        }
+        reg
+    }
+
+    /// Interprets a `deprecated` or `deprecation_tag` command; other commands
+    /// are ignored.
+    fn add_directive(&mut self, directive: JDirective) {
+        match directive {
+            JDirective::Deprecated(name, replacement) => {
+                self.items.insert(name.into_bytes().into_boxed_slice(),
+                                  replacement.map(|r| r.into_bytes().into_boxed_slice()));
+            }
+            JDirective::DeprecationTag(tag) => self.tags.push(tag.into_bytes()),
+            _ => {}
+        }
+    }
+
+    /// Returns true if the label or math symbol is deprecated.
+    pub fn is_deprecated(&self, name: &[u8]) -> bool {
+        self.items.contains_key(name)
+    }
+
+    /// Returns the suggested replacement for a deprecated label or math
+    /// symbol, if one was given.
+    pub fn replacement(&self, name: &[u8]) -> Option<&[u8]> {
+        self.items.get(name).and_then(|r| r.as_ref().map(|r| &r[..]))
+    }
+
+    /// Reports references to deprecated items.
+    pub fn diagnostics(&self) -> Vec<(StatementAddress, Diagnostic)> {
+        self.diagnostics.clone()
+    }
+}
@@ -0,0 +1,36 @@
+use database::Database;
+use database::DbOptions;
+use diag::Diagnostic;
+use test_fixtures::DB;
+
+#[test]
+fn test_deprecated() {
+    let text = format!("{}$( $j deprecated 'ax-old' as 'ax-1';
+  deprecated '~~'; deprecated 'wtil'; $)
+$c ~~ $.
+wtil $a wff ~~ ph $.
+ax-old $a |- ( ph -> ph ) $.
+$( Old axiom.  (Obsolete) $)
+ax-obs $a |- ( ph -> ph ) $.
+th1 $p |- ( ph -> ph ) $= wph ax-old $.
+th2 $p |- ( ~~ ph -> ph ) $= wph wtil ax-obs $.
+",
+                       DB);
+    let mut dbo = DbOptions::default();
+    dbo.deprecation.tags.push("(Obsolete)".to_owned());
+    let mut db = Database::new(dbo);
+    db.parse("test.mm".to_owned(),
+             vec![("test.mm".to_owned(), text.into_bytes())]);
+    let reg = db.deprecation_result().clone();
+    assert!(reg.is_deprecated(b"ax-obs"));
+    assert_eq!(reg.replacement(b"ax-old"), Some(&b"ax-1"[..]));
+    assert_eq!(reg.replacement(b"~~"), None);
+    let tok = |s: &str| s.as_bytes().to_vec().into_boxed_slice();
+    let th1 = db.statement("th1").unwrap().address();
+    let th2 = db.statement("th2").unwrap().address();
+    assert_eq!(reg.diagnostics(),
+               vec![(th1, Diagnostic::DeprecatedLabel(1, tok("ax-old"), Some(tok("ax-1")))),
+                    (th2, Diagnostic::DeprecatedSymbol(2, tok("~~"), None)),
+                    (th2, Diagnostic::DeprecatedLabel(1, tok("wtil"), None)),
+                    (th2, Diagnostic::DeprecatedLabel(2, tok("ax-obs"), None))]);
+}
@@ -4,7 +4,12 @@
 //! interpretation and testing, as well as a mostly-text representation which
 //! can be used for various human-readable outputs.
 
+use extra_info::JDirective;
+use extra_info::parse_directive;
+use extra_info::process_j_commands;
+use nameck::Nameset;
 use parser::as_str;
+use parser::BYTE_ORDER_MARK;
 use parser::Comparer;
 use parser::Span;
 use parser::StatementAddress;
@@ -19,6 +24,8 @@ use std::fmt::Display;
 use std::io;
 use std::mem;
 use std::sync::Arc;
+use util::HashMap;
+use util::new_map;
 
 /// List of passes that generate diagnostics, for use with the
 /// `Database::diag_notations` filter.
@@ -33,6 +40,22 @@ pub enum DiagnosticClass {
     /// Verify errors do not invalidate the interpretation of statements, but
     /// affect only proofs.
     Verify,
+    /// Uses of statements marked `(New usage is discouraged.)`.
+    Discouraged,
+    /// References to deprecated labels and math symbols.
+    Deprecated,
+    /// Ambiguities in the grammar formed by syntax axioms.
+    Grammar,
+    /// Assertions which restate earlier ones.
+    Duplicate,
+    /// Disjoint variable conditions which proofs do not need.
+    Disjoint,
+    /// Unused hypotheses, variables and theorems.
+    Lint,
+    /// Comment markup, typesetting definitions and `$j` commands.
+    Markup,
+    /// Contribution and revision history entries.
+    History,
 }
 
 /// List of all diagnostic codes.  For a description of each, see the source of
@@ -44,42 +67,66 @@ pub enum DiagnosticClass {
 #[derive(Debug,Clone,Eq,PartialEq)]
 #[allow(missing_docs)]
 pub enum Diagnostic {
+    AmbiguousSyntax(StatementAddress, Token),
     BadCharacter(usize, u8),
     BadCommentEnd(Span, Span),
     BadExplicitLabel(Token),
     BadFloating,
     BadLabel(Span),
+    ByteOrderMark,
     ChainBackref(Span),
     CommentMarkerNotStart(Span),
     ConstantNotTopLevel,
+    DeprecatedLabel(TokenIndex, Token, Option<Token>),
+    DeprecatedSymbol(TokenIndex, Token, Option<Token>),
+    DiscouragedUsage(TokenIndex, Token),
     DisjointSingle,
     DjNotVariable(TokenIndex),
     DjRepeatedVariable(TokenIndex, TokenIndex),
+    DuplicateAssertion(StatementAddress),
     DuplicateExplicitLabel(Token),
     DuplicateLabel(StatementAddress),
     EmptyFilename,
     EmptyMathString,
     EssentialAtTopLevel,
     ExprNotConstantPrefix(TokenIndex),
+    FilenameBackslash,
     FilenameDollar,
     FilenameSpaces,
     FloatNotConstant(TokenIndex),
     FloatNotVariable(TokenIndex),
     FloatRedeclared(StatementAddress),
+    FormulaNotParsed(Token),
+    FormulaTooComplex(Token),
+    FormulaUnknownSymbol(Token),
+    IncludeCaseMismatch(Span, String),
+    IncludeNotTopLevel(StatementIndex),
+    IncludeReadError(Span, String),
     IoError(String),
     LocalLabelAmbiguous(Span),
     LocalLabelDuplicate(Span),
+    MalformedDate(Span),
+    MalformedJCommand(Span),
+    MarkupMathNotParsed(Span),
+    MarkupUnclosedMath(Span),
+    MarkupUnknownBibTag(Span),
+    MarkupUnknownLabel(Span),
+    MarkupUnknownSymbol(Span),
     MidStatementCommentMarker(Span),
+    MidStatementInclude(Span),
     MissingLabel,
     MissingProof(Span),
     NestedComment(Span, Span),
     NotActiveSymbol(TokenIndex),
+    ProofBufferTooLarge(usize),
     ProofDvViolation,
     ProofExcessEnd,
     ProofIncomplete,
     ProofInvalidSave,
     ProofMalformedVarint,
     ProofNoSteps,
+    ProofStackTooDeep(usize),
+    ProofTooLarge(usize),
     ProofUnderflow,
     ProofUnterminatedRoster,
     ProofWrongExprEnd,
@@ -94,21 +141,131 @@ pub enum Diagnostic {
     StepOutOfRange,
     StepUsedAfterScope(Token),
     StepUsedBeforeDefinition(Token),
+    SuperfluousDisjoint(Token, Token),
     SymbolDuplicatesLabel(TokenIndex, StatementAddress),
     SymbolRedeclared(TokenIndex, TokenAddress),
+    TypesettingMissing(TokenIndex, &'static str),
     UnclosedBeforeEof,
     UnclosedBeforeInclude(StatementIndex),
     UnclosedComment(Span),
     UnclosedInclude,
     UnclosedMath,
     UnclosedProof,
+    UnknownJCommand(Span),
     UnknownKeyword(Span),
     UnmatchedCloseGroup,
+    UnusedFloating,
+    UnusedHypothesis(StatementAddress),
+    UnusedTheorem,
+    UnusedVariable(TokenIndex),
     VariableMissingFloat(TokenIndex),
     VariableRedeclaredAsConstant(TokenIndex, TokenAddress),
 }
 use self::Diagnostic::*;
 
+impl Diagnostic {
+    /// Returns the stable category code of the diagnostic, the kebab-case form
+    /// of its variant name, which `DiagnosticConfig` and `$j diagnostic`
+    /// commands use to change its level.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            AmbiguousSyntax(..) => "ambiguous-syntax",
+            BadCharacter(..) => "bad-character",
+            BadCommentEnd(..) => "bad-comment-end",
+            BadExplicitLabel(..) => "bad-explicit-label",
+            BadFloating => "bad-floating",
+            BadLabel(..) => "bad-label",
+            ByteOrderMark => "byte-order-mark",
+            ChainBackref(..) => "chain-backref",
+            CommentMarkerNotStart(..) => "comment-marker-not-start",
+            ConstantNotTopLevel => "constant-not-top-level",
+            DeprecatedLabel(..) => "deprecated-label",
+            DeprecatedSymbol(..) => "deprecated-symbol",
+            DiscouragedUsage(..) => "discouraged-usage",
+            DisjointSingle => "disjoint-single",
+            DjNotVariable(..) => "dj-not-variable",
+            DjRepeatedVariable(..) => "dj-repeated-variable",
+            DuplicateAssertion(..) => "duplicate-assertion",
+            DuplicateExplicitLabel(..) => "duplicate-explicit-label",
+            DuplicateLabel(..) => "duplicate-label",
+            EmptyFilename => "empty-filename",
+            EmptyMathString => "empty-math-string",
+            EssentialAtTopLevel => "essential-at-top-level",
+            ExprNotConstantPrefix(..) => "expr-not-constant-prefix",
+            FilenameBackslash => "filename-backslash",
+            FilenameDollar => "filename-dollar",
+            FilenameSpaces => "filename-spaces",
+            FloatNotConstant(..) => "float-not-constant",
+            FloatNotVariable(..) => "float-not-variable",
+            FloatRedeclared(..) => "float-redeclared",
+            FormulaNotParsed(..) => "formula-not-parsed",
+            FormulaTooComplex(..) => "formula-too-complex",
+            FormulaUnknownSymbol(..) => "formula-unknown-symbol",
+            IncludeCaseMismatch(..) => "include-case-mismatch",
+            IncludeNotTopLevel(..) => "include-not-top-level",
+            IncludeReadError(..) => "include-read-error",
+            IoError(..) => "io-error",
+            LocalLabelAmbiguous(..) => "local-label-ambiguous",
+            LocalLabelDuplicate(..) => "local-label-duplicate",
+            MalformedDate(..) => "malformed-date",
+            MalformedJCommand(..) => "malformed-j-command",
+            MarkupMathNotParsed(..) => "markup-math-not-parsed",
+            MarkupUnclosedMath(..) => "markup-unclosed-math",
+            MarkupUnknownBibTag(..) => "markup-unknown-bib-tag",
+            MarkupUnknownLabel(..) => "markup-unknown-label",
+            MarkupUnknownSymbol(..) => "markup-unknown-symbol",
+            MidStatementCommentMarker(..) => "mid-statement-comment-marker",
+            MidStatementInclude(..) => "mid-statement-include",
+            MissingLabel => "missing-label",
+            MissingProof(..) => "missing-proof",
+            NestedComment(..) => "nested-comment",
+            NotActiveSymbol(..) => "not-active-symbol",
+            ProofBufferTooLarge(..) => "proof-buffer-too-large",
+            ProofDvViolation => "proof-dv-violation",
+            ProofExcessEnd => "proof-excess-end",
+            ProofIncomplete => "proof-incomplete",
+            ProofInvalidSave => "proof-invalid-save",
+            ProofMalformedVarint => "proof-malformed-varint",
+            ProofNoSteps => "proof-no-steps",
+            ProofStackTooDeep(..) => "proof-stack-too-deep",
+            ProofTooLarge(..) => "proof-too-large",
+            ProofUnderflow => "proof-underflow",
+            ProofUnterminatedRoster => "proof-unterminated-roster",
+            ProofWrongExprEnd => "proof-wrong-expr-end",
+            ProofWrongTypeEnd => "proof-wrong-type-end",
+            RepeatedLabel(..) => "repeated-label",
+            SpuriousLabel(..) => "spurious-label",
+            SpuriousProof(..) => "spurious-proof",
+            StepEssenWrong => "step-essen-wrong",
+            StepEssenWrongType => "step-essen-wrong-type",
+            StepFloatWrongType => "step-float-wrong-type",
+            StepMissing(..) => "step-missing",
+            StepOutOfRange => "step-out-of-range",
+            StepUsedAfterScope(..) => "step-used-after-scope",
+            StepUsedBeforeDefinition(..) => "step-used-before-definition",
+            SuperfluousDisjoint(..) => "superfluous-disjoint",
+            SymbolDuplicatesLabel(..) => "symbol-duplicates-label",
+            SymbolRedeclared(..) => "symbol-redeclared",
+            TypesettingMissing(..) => "typesetting-missing",
+            UnclosedBeforeEof => "unclosed-before-eof",
+            UnclosedBeforeInclude(..) => "unclosed-before-include",
+            UnclosedComment(..) => "unclosed-comment",
+            UnclosedInclude => "unclosed-include",
+            UnclosedMath => "unclosed-math",
+            UnclosedProof => "unclosed-proof",
+            UnknownJCommand(..) => "unknown-j-command",
+            UnknownKeyword(..) => "unknown-keyword",
+            UnmatchedCloseGroup => "unmatched-close-group",
+            UnusedFloating => "unused-floating",
+            UnusedHypothesis(..) => "unused-hypothesis",
+            UnusedTheorem => "unused-theorem",
+            UnusedVariable(..) => "unused-variable",
+            VariableMissingFloat(..) => "variable-missing-float",
+            VariableRedeclaredAsConstant(..) => "variable-redeclared-as-constant",
+        }
+    }
+}
+
 impl From<io::Error> for Diagnostic {
     fn from(err: io::Error) -> Diagnostic {
         IoError(format!("{}", err))
@@ -116,11 +273,17 @@ impl From<io::Error> for Diagnostic {
 }
 
 /// An indication of the severity of a notation.
-#[derive(Copy,Clone,Debug)]
+///
+/// Levels are ordered by increasing severity.
+#[derive(Copy,Clone,Debug,PartialEq,Eq,PartialOrd,Ord)]
 pub enum Level {
     /// Notes indicate other statements relevant to an error which is primarily
     /// elsewhere.
     Note,
+    /// Informational messages point out matters of style which do not affect
+    /// the validity of the database.  No diagnostic has this level by default;
+    /// it is assigned through `DiagnosticConfig`.
+    Info,
     /// Warnings indicate constructs which are defined by the spec but also
     /// forbidden by the spec, as well as issues with non-spec extensions.
     Warning,
@@ -130,6 +293,59 @@ pub enum Level {
 }
 use self::Level::*;
 
+impl Level {
+    /// Parses a level name as used in `$j diagnostic` commands: `error`,
+    /// `warning`, `info`, or `off` to suppress the diagnostic.
+    pub fn parse(name: &str) -> Option<Option<Level>> {
+        match name {
+            "error" => Some(Some(Error)),
+            "warning" => Some(Some(Warning)),
+            "info" => Some(Some(Info)),
+            "off" => Some(None),
+            _ => None,
+        }
+    }
+}
+
+/// Changes to the levels of diagnostic categories supplied by the caller.
+///
+/// The database can change levels as well, with `$j diagnostic 'code' as
+/// 'level';` commands; the caller's settings take precedence.
+#[derive(Clone,Debug,Default)]
+pub struct DiagnosticConfig {
+    /// Category codes (see `Diagnostic::code`) with their new levels, or
+    /// `None` to suppress them.  Later entries override earlier ones.
+    pub levels: Vec<(String, Option<Level>)>,
+}
+
+impl DiagnosticConfig {
+    /// Reports a category of diagnostics at a different level.  Notes attached
+    /// to the diagnostics keep their level.
+    pub fn set_level(&mut self, code: &str, level: Level) {
+        self.levels.push((code.to_owned(), Some(level)));
+    }
+
+    /// Drops all notations of a category of diagnostics.
+    pub fn suppress(&mut self, code: &str) {
+        self.levels.push((code.to_owned(), None));
+    }
+
+    /// Combines the `$j diagnostic` commands of the database with the
+    /// configuration.
+    fn effective_levels(&self, sset: &SegmentSet) -> HashMap<String, Option<Level>> {
+        let mut levels = new_map();
+        for command in process_j_commands(sset) {
+            if let Ok(JDirective::Diagnostic(code, level)) = parse_directive(&command) {
+                levels.insert(code, level);
+            }
+        }
+        for &(ref code, level) in &self.levels {
+            levels.insert(code.clone(), level);
+        }
+        levels
+    }
+}
+
 /// A notation is a human-readable description of a diagnostic, with a single
 /// structure, named fields, and identifying a single source location.
 pub struct Notation {
@@ -145,25 +361,47 @@ pub struct Notation {
     pub span: Span,
     /// Severity level of the message
     pub level: Level,
+    /// Category code of the diagnostic the notation describes; see
+    /// `Diagnostic::code`.
+    pub code: &'static str,
     /// Values to substitute for the `{placeholders}` in the message.  `String`
     /// could be replaced with a richer enum.
     pub args: Vec<(&'static str, String)>,
 }
 
 /// Converts a collection of raw diagnostics to a notation list before output.
+///
+/// The nameset is used to suggest labels in place of unknown ones.  Levels
+/// are adjusted according to `DbOptions::diagnostics` and the `$j diagnostic`
+/// commands of the database.
 pub fn to_annotations(sset: &SegmentSet,
+                      nset: &Nameset,
                       mut diags: Vec<(StatementAddress, Diagnostic)>)
                       -> Vec<Notation> {
     diags.sort_by(|x, y| sset.order.cmp(&x.0, &y.0));
+    let levels = sset.options.diagnostics.effective_levels(sset);
     let mut out = Vec::new();
     for (saddr, diag) in diags {
-        annotate_diagnostic(&mut out, sset, sset.statement(saddr), &diag);
+        let start = out.len();
+        annotate_diagnostic(&mut out, sset, nset, sset.statement(saddr), &diag);
+        match levels.get(diag.code()) {
+            Some(&None) => out.truncate(start),
+            Some(&Some(level)) => {
+                for note in &mut out[start..] {
+                    if note.level != Note {
+                        note.level = level;
+                    }
+                }
+            }
+            None => {}
+        }
     }
     out
 }
 
 fn annotate_diagnostic(notes: &mut Vec<Notation>,
                        sset: &SegmentSet,
+                       nset: &Nameset,
                        stmt: StatementRef,
                        diag: &Diagnostic) {
     struct AnnInfo<'a> {
@@ -171,6 +409,7 @@ fn annotate_diagnostic(notes: &mut Vec<Notation>,
         sset: &'a SegmentSet,
         stmt: StatementRef<'a>,
         level: Level,
+        code: &'static str,
         s: &'static str,
         args: Vec<(&'static str, String)>,
     }
@@ -184,6 +423,7 @@ fn annotate_diagnostic(notes: &mut Vec<Notation>,
             message: info.s,
             span: span,
             level: info.level,
+            code: info.code,
             args: mem::replace(&mut info.args, Vec::new()),
         })
     }
@@ -196,16 +436,36 @@ fn annotate_diagnostic(notes: &mut Vec<Notation>,
         as_str(v).to_owned()
     }
 
+    fn suggest<'a>(info: &mut AnnInfo<'a>, nset: &Nameset, label: &[u8], span: Span) {
+        if let Some(&other) = nset.suggest_labels(label, 1).first() {
+            info.s = "Did you mean {label}?";
+            info.level = Note;
+            info.args.push(("label", as_str(other).to_owned()));
+            ann(info, span);
+        }
+    }
+
     let mut info = AnnInfo {
         notes: notes,
         sset: sset,
         stmt: stmt,
         level: Error,
+        code: diag.code(),
         s: "",
         args: Vec::new(),
     };
 
     match *diag {
+        AmbiguousSyntax(prevstmt, ref witness) => {
+            info.s = "Syntax axiom has the same production as an earlier one, so {witness} has \
+                      two distinct syntax proofs";
+            info.args.push(("witness", t(witness)));
+            ann(&mut info, stmt.span());
+            info.stmt = sset.statement(prevstmt);
+            info.s = "Earlier syntax axiom with the same production";
+            info.level = Note;
+            ann(&mut info, Span::null());
+        }
         BadCharacter(span, byte) => {
             info.s = "Invalid character (byte value {byte}); Metamath source files are limited to \
                       US-ASCII with controls TAB, CR, LF, FF)";
@@ -233,6 +493,11 @@ fn annotate_diagnostic(notes: &mut Vec<Notation>,
             info.s = "Statement labels may contain only alphanumeric characters and - _ .";
             ann(&mut info, lbl);
         }
+        ByteOrderMark => {
+            info.s = "Source file starts with a UTF-8 byte order mark, which was skipped";
+            info.level = Warning;
+            ann(&mut info, Span::new(0, BYTE_ORDER_MARK.len()));
+        }
         ChainBackref(span) => {
             info.s = "Backreference steps are not permitted to have local labels";
             ann(&mut info, span);
@@ -246,6 +511,32 @@ fn annotate_diagnostic(notes: &mut Vec<Notation>,
             info.s = "$c statements are not allowed in nested groups";
             ann(&mut info, stmt.span());
         }
+        DeprecatedLabel(index, ref label, ref replacement) => {
+            info.s = "{label} is deprecated";
+            info.level = Warning;
+            info.args.push(("label", t(label)));
+            if let Some(ref replacement) = *replacement {
+                info.s = "{label} is deprecated; use {replacement} instead";
+                info.args.push(("replacement", t(replacement)));
+            }
+            ann(&mut info, stmt.proof_span(index));
+        }
+        DeprecatedSymbol(index, ref symbol, ref replacement) => {
+            info.s = "Math symbol {symbol} is deprecated";
+            info.level = Warning;
+            info.args.push(("symbol", t(symbol)));
+            if let Some(ref replacement) = *replacement {
+                info.s = "Math symbol {symbol} is deprecated; use {replacement} instead";
+                info.args.push(("replacement", t(replacement)));
+            }
+            ann(&mut info, stmt.math_span(index));
+        }
+        DiscouragedUsage(index, ref label) => {
+            info.s = "New usage of {label} is discouraged";
+            info.level = Warning;
+            info.args.push(("label", t(label)));
+            ann(&mut info, stmt.proof_span(index));
+        }
         DisjointSingle => {
             info.s = "A $d statement which lists only one variable is meaningless";
             info.level = Warning;
@@ -262,6 +553,16 @@ fn annotate_diagnostic(notes: &mut Vec<Notation>,
             info.level = Note;
             ann(&mut info, stmt.math_span(index2));
         }
+        DuplicateAssertion(prevstmt) => {
+            info.s = "Assertion is the same as {label} up to renaming of variables";
+            info.level = Warning;
+            info.args.push(("label", as_str(sset.statement(prevstmt).label()).to_owned()));
+            ann(&mut info, stmt.span());
+            info.stmt = sset.statement(prevstmt);
+            info.s = "Earlier assertion with the same statement";
+            info.level = Note;
+            ann(&mut info, Span::null());
+        }
         DuplicateExplicitLabel(ref tok) => {
             info.s = "Explicit label {label} is used twice in the same step";
             info.args.push(("label", t(tok)));
@@ -292,6 +593,12 @@ fn annotate_diagnostic(notes: &mut Vec<Notation>,
                      not a variable";
             ann(&mut info, stmt.math_span(index));
         }
+        FilenameBackslash => {
+            info.s = "Filenames included by $[ should use / as the path separator; \\ only \
+                      works on Windows";
+            info.level = Warning;
+            ann(&mut info, stmt.span());
+        }
         FilenameDollar => {
             info.s = "Filenames included by $[ are not allowed to contain the $ character";
             ann(&mut info, stmt.span());
@@ -317,6 +624,40 @@ fn annotate_diagnostic(notes: &mut Vec<Notation>,
             info.level = Note;
             ann(&mut info, Span::null());
         }
+        FormulaNotParsed(ref typecode) => {
+            info.s = "Formula does not parse as {typecode}";
+            info.args.push(("typecode", t(typecode)));
+            ann(&mut info, Span::null());
+        }
+        FormulaTooComplex(ref typecode) => {
+            info.s = "Formula is nested too deeply to parse as {typecode}";
+            info.args.push(("typecode", t(typecode)));
+            ann(&mut info, Span::null());
+        }
+        FormulaUnknownSymbol(ref symbol) => {
+            info.s = "Symbol {symbol} is not declared";
+            info.args.push(("symbol", t(symbol)));
+            ann(&mut info, Span::null());
+        }
+        IncludeCaseMismatch(span, ref earlier) => {
+            info.s = "File differs only in case from {earlier}, which was already included";
+            info.args.push(("earlier", earlier.clone()));
+            info.level = Warning;
+            ann(&mut info, span);
+        }
+        IncludeNotTopLevel(group) => {
+            info.s = "File inclusions are only allowed at the top level, not inside ${ $} groups";
+            ann(&mut info, stmt.span());
+            info.stmt = stmt.segment().statement(group);
+            info.s = "Innermost enclosing group was opened here";
+            info.level = Note;
+            ann(&mut info, Span::null());
+        }
+        IncludeReadError(span, ref err) => {
+            info.s = "Included file could not be read (error: {error})";
+            info.args.push(("error", err.clone()));
+            ann(&mut info, span);
+        }
         IoError(ref err) => {
             info.s = "Source file could not be read (error: {error})";
             info.args.push(("error", err.clone()));
@@ -330,11 +671,62 @@ fn annotate_diagnostic(notes: &mut Vec<Notation>,
             info.s = "Local label duplicates another label in the same proof";
             ann(&mut info, span);
         }
+        MalformedDate(date) => {
+            info.s = "History entry date {date} is not a valid date in DD-Mon-YYYY format";
+            info.level = Warning;
+            info.args.push(("date",
+                            String::from_utf8_lossy(date.as_ref(&stmt.segment().segment.buffer))
+                                .into_owned()));
+            ann(&mut info, date);
+        }
+        MalformedJCommand(command) => {
+            info.s = "$j command {command} does not have the arguments its keyword requires";
+            info.level = Warning;
+            info.args.push(("command",
+                            as_str(command.as_ref(&stmt.segment().segment.buffer)).to_owned()));
+            ann(&mut info, command);
+        }
+        MarkupMathNotParsed(math) => {
+            info.s = "Math text in comment does not parse as a formula";
+            info.level = Warning;
+            ann(&mut info, math);
+        }
+        MarkupUnclosedMath(open) => {
+            info.s = "Math text in comment must be closed with a backquote";
+            info.level = Warning;
+            ann(&mut info, open);
+        }
+        MarkupUnknownBibTag(tag) => {
+            info.s = "Bibliographic reference {tag} is not in the bibliography";
+            info.level = Warning;
+            info.args.push(("tag", as_str(tag.as_ref(&stmt.segment().segment.buffer)).to_owned()));
+            ann(&mut info, tag);
+        }
+        MarkupUnknownLabel(label) => {
+            info.s = "Comment refers to unknown label {label}";
+            info.level = Warning;
+            info.args.push(("label",
+                            as_str(label.as_ref(&stmt.segment().segment.buffer)).to_owned()));
+            ann(&mut info, label);
+            suggest(&mut info, nset, label.as_ref(&stmt.segment().segment.buffer), label);
+        }
+        MarkupUnknownSymbol(symbol) => {
+            info.s = "Math symbol {symbol} in comment is not declared";
+            info.level = Warning;
+            info.args.push(("symbol",
+                            as_str(symbol.as_ref(&stmt.segment().segment.buffer)).to_owned()));
+            ann(&mut info, symbol);
+        }
         MidStatementCommentMarker(marker) => {
             info.s = "Marked comments are only effective between statements, not inside them";
             info.level = Warning;
             ann(&mut info, marker);
         }
+        MidStatementInclude(tok) => {
+            info.s = "File inclusions are only allowed between statements; the statement is \
+                      ended here and the file will be included after it";
+            ann(&mut info, tok);
+        }
         MissingLabel => {
             info.s = "This statement type requires a label";
             ann(&mut info, stmt.span());
@@ -356,6 +748,11 @@ fn annotate_diagnostic(notes: &mut Vec<Notation>,
             info.s = "Token used here must be active in the current scope";
             ann(&mut info, stmt.math_span(index));
         }
+        ProofBufferTooLarge(limit) => {
+            info.s = "Math strings of the proof steps exceed the limit of {limit} bytes";
+            info.args.push(("limit", d(limit)));
+            ann(&mut info, stmt.span());
+        }
         ProofDvViolation => {
             info.s = "Disjoint variable constraint violated";
             ann(&mut info, stmt.span());
@@ -381,6 +778,16 @@ fn annotate_diagnostic(notes: &mut Vec<Notation>,
             info.s = "Proof must have at least one step (use ? if deliberately incomplete)";
             ann(&mut info, stmt.span());
         }
+        ProofStackTooDeep(limit) => {
+            info.s = "Proof stack exceeds the limit of {limit} entries";
+            info.args.push(("limit", d(limit)));
+            ann(&mut info, stmt.span());
+        }
+        ProofTooLarge(limit) => {
+            info.s = "Proof exceeds the limit of {limit} steps";
+            info.args.push(("limit", d(limit)));
+            ann(&mut info, stmt.span());
+        }
         ProofUnderflow => {
             info.s = "Too few statements on stack to satisfy step's mandatory hypotheses";
             ann(&mut info, stmt.span());
@@ -429,6 +836,7 @@ fn annotate_diagnostic(notes: &mut Vec<Notation>,
                       is malformed)";
             info.args.push(("step", t(tok)));
             ann(&mut info, stmt.span());
+            suggest(&mut info, nset, tok, stmt.span());
         }
         StepOutOfRange => {
             info.s = "Step in compressed proof is out of range of defined steps";
@@ -444,6 +852,13 @@ fn annotate_diagnostic(notes: &mut Vec<Notation>,
             info.args.push(("step", t(tok)));
             ann(&mut info, stmt.span());
         }
+        SuperfluousDisjoint(ref var1, ref var2) => {
+            info.s = "Disjoint variable condition on {var1} and {var2} is not needed by the proof";
+            info.level = Warning;
+            info.args.push(("var1", t(var1)));
+            info.args.push(("var2", t(var2)));
+            ann(&mut info, stmt.span());
+        }
         SymbolDuplicatesLabel(index, saddr) => {
             info.s = "Metamath spec forbids symbols which are the same as labels in the same \
                      database";
@@ -463,6 +878,12 @@ fn annotate_diagnostic(notes: &mut Vec<Notation>,
             let sp = info.stmt.math_span(taddr.token_index);
             ann(&mut info, sp);
         }
+        TypesettingMissing(index, command) => {
+            info.s = "Math symbol has no {command} typesetting definition";
+            info.level = Warning;
+            info.args.push(("command", command.to_owned()));
+            ann(&mut info, stmt.math_span(index));
+        }
         UnclosedBeforeEof => {
             info.s = "${ group must be closed with a $} before end of file";
             ann(&mut info, stmt.span());
@@ -491,6 +912,13 @@ fn annotate_diagnostic(notes: &mut Vec<Notation>,
             info.s = "A proof must be closed with $.";
             ann(&mut info, stmt.span());
         }
+        UnknownJCommand(command) => {
+            info.s = "Unknown $j command {command}";
+            info.level = Warning;
+            info.args.push(("command",
+                            as_str(command.as_ref(&stmt.segment().segment.buffer)).to_owned()));
+            ann(&mut info, command);
+        }
         UnknownKeyword(kwspan) => {
             info.s = "Statement-starting keyword must be one of $a $c $d $e $f $p $v";
             ann(&mut info, kwspan);
@@ -499,6 +927,31 @@ fn annotate_diagnostic(notes: &mut Vec<Notation>,
             info.s = "This $} does not match any open ${";
             ann(&mut info, stmt.span());
         }
+        UnusedFloating => {
+            info.s = "Variable type declaration is not used by any assertion or proof";
+            info.level = Warning;
+            ann(&mut info, stmt.span());
+        }
+        UnusedHypothesis(hyp) => {
+            info.s = "Hypothesis {label} is not used by the proof";
+            info.level = Warning;
+            info.args.push(("label", as_str(sset.statement(hyp).label()).to_owned()));
+            ann(&mut info, stmt.span());
+            info.stmt = sset.statement(hyp);
+            info.s = "Hypothesis declared here";
+            info.level = Note;
+            ann(&mut info, Span::null());
+        }
+        UnusedTheorem => {
+            info.s = "Theorem is not used by any other proof";
+            info.level = Warning;
+            ann(&mut info, stmt.span());
+        }
+        UnusedVariable(index) => {
+            info.s = "Variable is not used by any statement";
+            info.level = Warning;
+            ann(&mut info, stmt.math_span(index));
+        }
         VariableMissingFloat(index) => {
             info.s = "Variable token used in statement must have an active $f";
             ann(&mut info, stmt.math_span(index));
@@ -0,0 +1,21 @@
+use database::Database;
+use database::DbOptions;
+use diag::DiagnosticClass;
+use diag::Level;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_diagnostic_levels() {
+    let text = b"${ $c X $. $}\n$( $j diagnostic 'constant-not-top-level' as 'info'; $)\n";
+    let mut db = mkdb(text);
+    let notations = db.diag_notations(vec![DiagnosticClass::Parse]);
+    assert_eq!(notations.len(), 1);
+    assert_eq!(notations[0].code, "constant-not-top-level");
+    assert_eq!(notations[0].level, Level::Info);
+
+    let mut options = DbOptions::default();
+    options.diagnostics.suppress("constant-not-top-level");
+    let mut db = Database::new(options);
+    db.parse("test.mm".to_owned(), vec![("test.mm".to_owned(), text.to_vec())]);
+    assert!(db.diag_notations(vec![DiagnosticClass::Parse]).is_empty());
+}
@@ -0,0 +1,208 @@
+//! Statement-level comparison of two versions of a database.
+//!
+//! Line-based diffs of set.mm are hard to review: a renamed theorem touches
+//! every proof using it, and a reformatted comment looks like a changed
+//! statement.  This module compares the `$a` and `$p` statements of two
+//! databases by label instead, looking only at their logical content: the
+//! math string, the `$e` hypotheses and mandatory disjoint variable
+//! conditions of the frame, and for theorems the proof.
+//!
+//! Labels present in only one version are paired up as renames when their
+//! logical content (apart from the proof) is identical, pairing in database
+//! order if several statements have the same content.  Proofs are compared
+//! after applying the renames, so a theorem whose proof only changed because
+//! a label it uses was renamed is not reported.
+
+use nameck::Nameset;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::as_str;
+use scopeck::Hyp;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use std::fmt;
+use util::HashMap;
+use util::HashSet;
+use util::new_map;
+
+/// A difference between two versions of a database.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum StatementDiff {
+    /// An assertion is only in the new version.
+    Added(String),
+    /// An assertion is only in the old version.
+    Removed(String),
+    /// An assertion was renamed from the first label to the second, without
+    /// other changes to its statement.
+    Renamed(String, String),
+    /// The statement of an assertion changed: its type, math string,
+    /// hypotheses or disjoint variable conditions.
+    Modified(String),
+    /// The proof of a theorem changed, but not its statement.
+    ProofChanged(String),
+}
+
+impl fmt::Display for StatementDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StatementDiff::Added(ref label) => write!(f, "added {}", label),
+            StatementDiff::Removed(ref label) => write!(f, "removed {}", label),
+            StatementDiff::Renamed(ref old, ref new) => write!(f, "renamed {} to {}", old, new),
+            StatementDiff::Modified(ref label) => write!(f, "modified {}", label),
+            StatementDiff::ProofChanged(ref label) => write!(f, "changed proof of {}", label),
+        }
+    }
+}
+
+/// One version of a database, as needed for comparison.
+pub struct DiffSide<'a> {
+    /// The parsed statements.
+    pub sset: &'a SegmentSet,
+    /// The name lookup table, for variable names in disjoint variable
+    /// conditions.
+    pub nset: &'a Nameset,
+    /// The frames of the assertions.
+    pub scope: &'a ScopeResult,
+}
+
+/// The logical content of an assertion's statement, apart from its proof.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+struct Signature {
+    provable: bool,
+    math: Vec<String>,
+    hyps: Vec<Vec<String>>,
+    dv: Vec<(String, String)>,
+}
+
+struct Assertion<'a> {
+    label: String,
+    signature: Signature,
+    stmt: StatementRef<'a>,
+}
+
+fn math_strings(stmt: StatementRef) -> Vec<String> {
+    stmt.math_iter().map(|tok| as_str(tok.slice).to_owned()).collect()
+}
+
+fn assertions<'a>(side: &DiffSide<'a>) -> Vec<Assertion<'a>> {
+    let mut out = Vec::new();
+    for sref in side.sset.segments() {
+        for stmt in sref {
+            match stmt.statement_type() {
+                StatementType::Axiom | StatementType::Provable => {}
+                _ => continue,
+            }
+            let mut hyps = Vec::new();
+            let mut dv = Vec::new();
+            if let Some(frame) = side.scope.get(stmt.label()) {
+                for hyp in frame.hypotheses.iter() {
+                    if let Hyp::Essential(addr, _) = *hyp {
+                        hyps.push(math_strings(side.sset.statement(addr)));
+                    }
+                }
+                let name = |v: usize| as_str(side.nset.atom_name(frame.var_list[v])).to_owned();
+                for &(v1, v2) in frame.mandatory_dv.iter() {
+                    let (n1, n2) = (name(v1), name(v2));
+                    dv.push(if n1 <= n2 { (n1, n2) } else { (n2, n1) });
+                }
+                dv.sort();
+            }
+            out.push(Assertion {
+                label: as_str(stmt.label()).to_owned(),
+                signature: Signature {
+                    provable: stmt.statement_type() == StatementType::Provable,
+                    math: math_strings(stmt),
+                    hyps: hyps,
+                    dv: dv,
+                },
+                stmt: stmt,
+            });
+        }
+    }
+    out
+}
+
+/// Returns the proof tokens of a statement, with the labels renamed; labels
+/// are also renamed after the `:` of a local label or the `=` of a
+/// hypothesis name.
+fn renamed_proof(stmt: StatementRef, renames: &HashMap<&str, &str>) -> Vec<String> {
+    (0..stmt.proof_len())
+        .map(|ix| {
+            let tok = as_str(stmt.proof_slice_at(ix));
+            let start = tok.rfind([':', '=']).map_or(0, |pos| pos + 1);
+            match renames.get(&tok[start..]) {
+                Some(new) => format!("{}{}", &tok[..start], new),
+                None => tok.to_owned(),
+            }
+        })
+        .collect()
+}
+
+fn proof(stmt: StatementRef) -> Vec<String> {
+    (0..stmt.proof_len()).map(|ix| as_str(stmt.proof_slice_at(ix)).to_owned()).collect()
+}
+
+/// Compares the assertions of two versions of a database; see the module
+/// documentation.
+///
+/// Differences are listed in the order of the new version, followed by the
+/// removed assertions in the order of the old version.
+pub fn diff_databases(old: &DiffSide, new: &DiffSide) -> Vec<StatementDiff> {
+    let old_assertions = assertions(old);
+    let new_assertions = assertions(new);
+    let old_by_label: HashMap<&str, &Assertion> =
+        old_assertions.iter().map(|a| (&a.label[..], a)).collect();
+    let new_labels: HashSet<&str> = new_assertions.iter().map(|a| &a.label[..]).collect();
+
+    // candidates for renaming: old assertions whose label is gone, by content
+    let mut vanished: HashMap<&Signature, Vec<&Assertion>> = new_map();
+    for assertion in old_assertions.iter().rev() {
+        if !new_labels.contains(&assertion.label[..]) {
+            vanished.entry(&assertion.signature).or_default().push(assertion);
+        }
+    }
+    let mut renames: HashMap<&str, &str> = new_map();
+    let mut matched: HashMap<&str, &Assertion> = new_map();
+    for assertion in &new_assertions {
+        if old_by_label.contains_key(&assertion.label[..]) {
+            continue;
+        }
+        if let Some(old) = vanished.get_mut(&assertion.signature).and_then(|olds| olds.pop()) {
+            renames.insert(&old.label, &assertion.label);
+            matched.insert(&assertion.label, old);
+        }
+    }
+
+    let mut out = Vec::new();
+    for assertion in &new_assertions {
+        let label = &assertion.label[..];
+        let old = match old_by_label.get(label) {
+            Some(old) => *old,
+            None => {
+                match matched.get(label) {
+                    Some(old) => {
+                        out.push(StatementDiff::Renamed(old.label.clone(), label.to_owned()));
+                        *old
+                    }
+                    None => {
+                        out.push(StatementDiff::Added(label.to_owned()));
+                        continue;
+                    }
+                }
+            }
+        };
+        if old.signature != assertion.signature {
+            out.push(StatementDiff::Modified(label.to_owned()));
+        } else if assertion.signature.provable &&
+                  renamed_proof(old.stmt, &renames) != proof(assertion.stmt) {
+            out.push(StatementDiff::ProofChanged(label.to_owned()));
+        }
+    }
+    for assertion in &old_assertions {
+        let label = &assertion.label[..];
+        if !new_labels.contains(label) && !renames.contains_key(label) {
+            out.push(StatementDiff::Removed(label.to_owned()));
+        }
+    }
+    out
+}
@@ -0,0 +1,28 @@
+use diff::StatementDiff;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_diff() {
+    let old = format!("{}th1 $p |- ( ph -> ( ps -> ph ) ) $= wph wps ax-1 $.
+th2 $p |- ( ps -> ( ph -> ps ) ) $= wps wph ax-1 $.
+th3 $p |- ( ph -> ( ph -> ph ) ) $= wph wph ax-1 $.
+th4 $p |- ( ps -> ( ps -> ps ) ) $= wps wps ax-1 $.
+",
+                      DB);
+    let new = format!("{}th1 $p |- ( ph -> ( ps -> ph ) ) $= wph wps ax-k $.
+th2 $p |- ( ps -> ( ph -> ps ) ) $= wps wph wps wi ax-k $.
+th3new $p |- ( ph -> ( ph -> ph ) ) $= wph wph ax-k $.
+th4 $p |- ( ps -> ( ps -> ph ) ) $= wps wps ax-k $.
+th5 $p |- ( ph -> ( ph -> ph ) ) $= wph wph th3new $.
+",
+                      DB.replace("ax-1", "ax-k"));
+    let mut old_db = mkdb(old.as_bytes());
+    let mut new_db = mkdb(new.as_bytes());
+    assert_eq!(new_db.diff(&mut old_db),
+               vec![StatementDiff::Renamed("ax-1".to_owned(), "ax-k".to_owned()),
+                    StatementDiff::ProofChanged("th2".to_owned()),
+                    StatementDiff::Renamed("th3".to_owned(), "th3new".to_owned()),
+                    StatementDiff::Modified("th4".to_owned()),
+                    StatementDiff::Added("th5".to_owned())]);
+}
@@ -0,0 +1,403 @@
+//! Differential testing of the verifier against a reference implementation.
+//!
+//! The main verifier in `verify` is heavily optimized: it works on compact
+//! byte encodings of expressions prepared by `scopeck`, and verifies segments
+//! in parallel and incrementally.  This module contains a second verifier
+//! written as directly from the Metamath specification as possible, in the
+//! style of `mmverify.py`: it walks the database in order keeping its own
+//! scope stack, represents expressions as token vectors, and shares no code
+//! with `scopeck` or `verify`.  `compare` runs both on every `$p` statement
+//! and reports the statements where they disagree.
+//!
+//! The reference verifier only accepts standard proof formats, so a normal
+//! proof using local labels (`3:label`) or explicit hypothesis names
+//! (`hyp=label`) is reported as unsupported rather than compared.
+
+use diag::Diagnostic;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::as_str;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use util::HashMap;
+use util::HashSet;
+use util::new_map;
+use util::new_set;
+use verify::VerifyResult;
+
+type Expr<'a> = Vec<&'a [u8]>;
+
+/// An active hypothesis in the reference verifier's scope.
+#[derive(Clone)]
+enum RefHyp<'a> {
+    Floating(&'a [u8], Expr<'a>),
+    Essential(&'a [u8], Expr<'a>),
+}
+
+/// A `$a` or `$p` statement with its mandatory hypotheses, as in the spec.
+struct RefAssertion<'a> {
+    hyps: Vec<RefHyp<'a>>,
+    dv: Vec<(&'a [u8], &'a [u8])>,
+    conclusion: Expr<'a>,
+}
+
+#[derive(Default)]
+struct RefFrame<'a> {
+    hyps: Vec<RefHyp<'a>>,
+    dv: Vec<(&'a [u8], &'a [u8])>,
+}
+
+/// The outcome of checking one proof with the reference verifier.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum ReferenceOutcome {
+    /// The proof is correct.
+    Valid,
+    /// The proof contains `?` steps.
+    Incomplete,
+    /// The proof uses an extension of the normal proof format.
+    Unsupported,
+    /// The proof is incorrect, with a description of the first problem.
+    Invalid(String),
+}
+
+/// A `$p` statement on which the two verifiers do not agree.
+#[derive(Clone,Debug)]
+pub struct Disagreement {
+    /// The statement.
+    pub address: StatementAddress,
+    /// The error reported by the main verifier, or `None` if it accepted the
+    /// proof.
+    pub primary: Option<Diagnostic>,
+    /// The outcome of the reference verifier.
+    pub reference: ReferenceOutcome,
+}
+
+struct RefState<'a> {
+    frames: Vec<RefFrame<'a>>,
+    assertions: HashMap<&'a [u8], RefAssertion<'a>>,
+}
+
+impl<'a> RefState<'a> {
+    fn active_hyp(&self, label: &[u8]) -> Option<&RefHyp<'a>> {
+        self.frames
+            .iter()
+            .flat_map(|frame| frame.hyps.iter())
+            .find(|hyp| match **hyp {
+                RefHyp::Floating(l, _) | RefHyp::Essential(l, _) => l == label,
+            })
+    }
+
+    fn has_dv(&self, x: &[u8], y: &[u8]) -> bool {
+        self.frames
+            .iter()
+            .flat_map(|frame| frame.dv.iter())
+            .any(|&(a, b)| (a == x && b == y) || (a == y && b == x))
+    }
+
+    fn make_assertion(&self, conclusion: Expr<'a>) -> RefAssertion<'a> {
+        let mut vars: HashSet<&[u8]> = new_set();
+        let mut essentials = Vec::new();
+        for frame in &self.frames {
+            for hyp in &frame.hyps {
+                if let RefHyp::Essential(_, ref expr) = *hyp {
+                    essentials.extend(expr.iter().cloned());
+                }
+            }
+        }
+        let floats: HashSet<&[u8]> = self.frames
+            .iter()
+            .flat_map(|frame| frame.hyps.iter())
+            .filter_map(|hyp| match *hyp {
+                RefHyp::Floating(_, ref expr) => Some(expr[1]),
+                RefHyp::Essential(..) => None,
+            })
+            .collect();
+        for &tok in conclusion.iter().chain(essentials.iter()) {
+            if floats.contains(tok) {
+                vars.insert(tok);
+            }
+        }
+        let mut hyps = Vec::new();
+        for frame in &self.frames {
+            for hyp in &frame.hyps {
+                match *hyp {
+                    RefHyp::Floating(_, ref expr) if !vars.contains(expr[1]) => {}
+                    _ => hyps.push(hyp.clone()),
+                }
+            }
+        }
+        let dv = self.frames
+            .iter()
+            .flat_map(|frame| frame.dv.iter())
+            .filter(|&&(x, y)| vars.contains(x) && vars.contains(y))
+            .cloned()
+            .collect();
+        RefAssertion {
+            hyps: hyps,
+            dv: dv,
+            conclusion: conclusion,
+        }
+    }
+
+    /// Applies an assertion to the top of the proof stack.
+    fn apply(&self,
+             label: &[u8],
+             assertion: &RefAssertion<'a>,
+             stack: &mut Vec<Expr<'a>>)
+             -> Result<(), String> {
+        if stack.len() < assertion.hyps.len() {
+            return Err(format!("stack underflow applying {}", as_str(label)));
+        }
+        let base = stack.len() - assertion.hyps.len();
+        let mut subst: HashMap<&[u8], Expr<'a>> = new_map();
+        for (hyp, entry) in assertion.hyps.iter().zip(&stack[base..]) {
+            if let RefHyp::Floating(_, ref expr) = *hyp {
+                if entry.is_empty() || entry[0] != expr[0] {
+                    return Err(format!("typecode mismatch for {} in {}",
+                                       as_str(expr[1]),
+                                       as_str(label)));
+                }
+                subst.insert(expr[1], entry[1..].to_vec());
+            }
+        }
+        let apply_subst = |expr: &Expr<'a>| -> Expr<'a> {
+            let mut out = Vec::new();
+            for &tok in expr {
+                match subst.get(tok) {
+                    Some(value) => out.extend(value.iter().cloned()),
+                    None => out.push(tok),
+                }
+            }
+            out
+        };
+        for (hyp, entry) in assertion.hyps.iter().zip(&stack[base..]) {
+            if let RefHyp::Essential(hlabel, ref expr) = *hyp {
+                if apply_subst(expr) != *entry {
+                    return Err(format!("hypothesis {} of {} does not match",
+                                       as_str(hlabel),
+                                       as_str(label)));
+                }
+            }
+        }
+        for &(x, y) in &assertion.dv {
+            for &a in subst[x].iter().filter(|&&a| self.is_var(a)) {
+                for &b in subst[y].iter().filter(|&&b| self.is_var(b)) {
+                    if a == b || !self.has_dv(a, b) {
+                        return Err(format!("disjoint variable violation for {} {} in {}",
+                                           as_str(a),
+                                           as_str(b),
+                                           as_str(label)));
+                    }
+                }
+            }
+        }
+        let result = apply_subst(&assertion.conclusion);
+        stack.truncate(base);
+        stack.push(result);
+        Ok(())
+    }
+
+    fn is_var(&self, tok: &[u8]) -> bool {
+        self.frames.iter().flat_map(|frame| frame.hyps.iter()).any(|hyp| match *hyp {
+            RefHyp::Floating(_, ref expr) => expr[1] == tok,
+            RefHyp::Essential(..) => false,
+        })
+    }
+
+    /// Pushes the result of a single proof step referencing a label.
+    fn step(&self, label: &[u8], stack: &mut Vec<Expr<'a>>) -> Result<(), String> {
+        if let Some(hyp) = self.active_hyp(label) {
+            match *hyp {
+                RefHyp::Floating(_, ref expr) |
+                RefHyp::Essential(_, ref expr) => stack.push(expr.clone()),
+            }
+            return Ok(());
+        }
+        match self.assertions.get(label) {
+            Some(assertion) => self.apply(label, assertion, stack),
+            None => Err(format!("label {} is not defined", as_str(label))),
+        }
+    }
+
+    fn check_proof(&self, stmt: StatementRef<'a>, conclusion: &Expr<'a>) -> ReferenceOutcome {
+        let mut stack = Vec::new();
+        let result = if stmt.proof_len() > 0 && stmt.proof_slice_at(0) == b"(" {
+            self.check_compressed(stmt, &mut stack)
+        } else {
+            self.check_normal(stmt, &mut stack)
+        };
+        match result {
+            Ok(None) => {}
+            Ok(Some(outcome)) => return outcome,
+            Err(msg) => return ReferenceOutcome::Invalid(msg),
+        }
+        if stack.len() != 1 {
+            return ReferenceOutcome::Invalid(format!("{} entries left on the stack", stack.len()));
+        }
+        if stack[0] != *conclusion {
+            return ReferenceOutcome::Invalid("proved expression does not match".to_owned());
+        }
+        ReferenceOutcome::Valid
+    }
+
+    fn check_normal(&self,
+                    stmt: StatementRef<'a>,
+                    stack: &mut Vec<Expr<'a>>)
+                    -> Result<Option<ReferenceOutcome>, String> {
+        for i in 0..stmt.proof_len() {
+            let tok = stmt.proof_slice_at(i);
+            if tok == b"?" {
+                return Ok(Some(ReferenceOutcome::Incomplete));
+            }
+            if tok.contains(&b':') || tok.contains(&b'=') {
+                return Ok(Some(ReferenceOutcome::Unsupported));
+            }
+            self.step(tok, stack)?;
+        }
+        Ok(None)
+    }
+
+    fn check_compressed(&self,
+                        stmt: StatementRef<'a>,
+                        stack: &mut Vec<Expr<'a>>)
+                        -> Result<Option<ReferenceOutcome>, String> {
+        let own = match self.assertions.get(stmt.label()) {
+            Some(own) => own,
+            None => return Err("statement has no frame".to_owned()),
+        };
+        let mut labels: Vec<&[u8]> = own.hyps
+            .iter()
+            .map(|hyp| match *hyp {
+                RefHyp::Floating(l, _) | RefHyp::Essential(l, _) => l,
+            })
+            .collect();
+        let mut i = 1;
+        loop {
+            if i >= stmt.proof_len() {
+                return Err("unterminated label list".to_owned());
+            }
+            let tok = stmt.proof_slice_at(i);
+            i += 1;
+            if tok == b")" {
+                break;
+            }
+            labels.push(tok);
+        }
+        let mut saved: Vec<Expr<'a>> = Vec::new();
+        let mut num = 0usize;
+        while i < stmt.proof_len() {
+            for &ch in stmt.proof_slice_at(i) {
+                if (b'A'..=b'T').contains(&ch) {
+                    num = num * 20 + (ch - b'A') as usize;
+                    if num < labels.len() {
+                        self.step(labels[num], stack)?;
+                    } else if num - labels.len() < saved.len() {
+                        stack.push(saved[num - labels.len()].clone());
+                    } else {
+                        return Err(format!("step number {} out of range", num + 1));
+                    }
+                    num = 0;
+                } else if (b'U'..=b'Y').contains(&ch) {
+                    num = num * 5 + 1 + (ch - b'U') as usize;
+                } else if ch == b'Z' {
+                    match stack.last() {
+                        Some(top) => saved.push(top.clone()),
+                        None => return Err("Z with an empty stack".to_owned()),
+                    }
+                } else if ch == b'?' {
+                    return Ok(Some(ReferenceOutcome::Incomplete));
+                } else {
+                    return Err(format!("bad character {} in proof", ch as char));
+                }
+            }
+            i += 1;
+        }
+        Ok(None)
+    }
+}
+
+/// Checks every `$p` statement in the database with the reference verifier,
+/// in database order.
+pub fn reference_verify(sset: &SegmentSet) -> Vec<(StatementAddress, ReferenceOutcome)> {
+    let mut out = Vec::new();
+    let mut state = RefState {
+        frames: vec![RefFrame::default()],
+        assertions: new_map(),
+    };
+    for sref in sset.segments() {
+        for stmt in sref {
+            let math: Expr = stmt.math_iter().map(|tok| tok.slice).collect();
+            match stmt.statement_type() {
+                StatementType::OpenGroup => state.frames.push(RefFrame::default()),
+                StatementType::CloseGroup if state.frames.len() > 1 => {
+                    state.frames.pop();
+                }
+                StatementType::Floating if math.len() == 2 => {
+                    let frame = state.frames.last_mut().unwrap();
+                    frame.hyps.push(RefHyp::Floating(stmt.label(), math));
+                }
+                StatementType::Essential => {
+                    let frame = state.frames.last_mut().unwrap();
+                    frame.hyps.push(RefHyp::Essential(stmt.label(), math));
+                }
+                StatementType::Disjoint => {
+                    let frame = state.frames.last_mut().unwrap();
+                    for (ix, &x) in math.iter().enumerate() {
+                        for &y in &math[ix + 1..] {
+                            frame.dv.push((x, y));
+                        }
+                    }
+                }
+                StatementType::Axiom => {
+                    let assertion = state.make_assertion(math);
+                    state.assertions.insert(stmt.label(), assertion);
+                }
+                StatementType::Provable => {
+                    let assertion = state.make_assertion(math.clone());
+                    state.assertions.insert(stmt.label(), assertion);
+                    out.push((stmt.address(), state.check_proof(stmt, &math)));
+                }
+                _ => {}
+            }
+        }
+    }
+    out
+}
+
+/// Runs the reference verifier and compares its verdicts with those of the
+/// main verifier, given the results of the scope and verify passes.
+///
+/// Statements which the main verifier skipped because they have no valid
+/// frame, and statements with unsupported proof formats, are not compared.
+/// Both verifiers must agree on whether a proof is valid, and on whether it is
+/// incomplete; the particular error reported for an invalid proof is not
+/// compared.
+pub fn compare(sset: &SegmentSet, scope: &ScopeResult, verify: &VerifyResult) -> Vec<Disagreement> {
+    let diags: HashMap<StatementAddress, Diagnostic> = verify.diagnostics()
+        .into_iter()
+        .collect();
+    let mut out = Vec::new();
+    for (addr, outcome) in reference_verify(sset) {
+        if scope.get(sset.statement(addr).label()).is_none() {
+            continue;
+        }
+        let primary = diags.get(&addr).cloned();
+        let agree = match (&primary, &outcome) {
+            (_, &ReferenceOutcome::Unsupported) => true,
+            (&None, &ReferenceOutcome::Valid) => true,
+            (&Some(Diagnostic::ProofIncomplete), &ReferenceOutcome::Incomplete) => true,
+            (&Some(Diagnostic::ProofIncomplete), _) => false,
+            (&Some(_), &ReferenceOutcome::Invalid(_)) => true,
+            _ => false,
+        };
+        if !agree {
+            out.push(Disagreement {
+                address: addr,
+                primary: primary,
+                reference: outcome,
+            });
+        }
+    }
+    out
+}
@@ -0,0 +1,31 @@
+use differential::ReferenceOutcome;
+use differential::reference_verify;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_differential_verify() {
+    let text = format!("{}th1 $p |- ( ph -> ( ps -> ph ) ) $= wph wps ax-1 $.
+th2 $p |- ( ph -> ( ph -> ph ) ) $= ( ax-1 ) AAB $.
+th3 $p |- ( ph -> ph ) $= wph wps ax-1 $.
+th4 $p |- ph $= ? $.
+${{ th5.1 $e |- ph $. th5 $p |- ( ps -> ph ) $= wph wps wph wi th5.1 wph wps ax-1 ax-mp $. $}}
+${{ $d ph ps $. ax-d $a |- ( ph -> ps ) $. $}}
+th6 $p |- ( ph -> ph ) $= wph wph ax-d $.
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let outcomes: Vec<ReferenceOutcome> = reference_verify(db.parse_result())
+        .into_iter()
+        .map(|(_, outcome)| outcome)
+        .collect();
+    assert_eq!(outcomes[0], ReferenceOutcome::Valid);
+    assert_eq!(outcomes[1], ReferenceOutcome::Valid);
+    assert!(matches!(outcomes[2], ReferenceOutcome::Invalid(_)));
+    assert_eq!(outcomes[3], ReferenceOutcome::Incomplete);
+    assert_eq!(outcomes[4], ReferenceOutcome::Valid);
+    assert_eq!(outcomes[5],
+               ReferenceOutcome::Invalid("disjoint variable violation for ph ph in ax-d"
+                   .to_owned()));
+    assert!(db.differential_verify().is_empty());
+}
@@ -0,0 +1,113 @@
+//! Tracking of discouraged statements.
+//!
+//! set.mm marks statements which should not be used in new proofs with the
+//! parenthetical `(New usage is discouraged.)` in their description comment,
+//! and statements whose proofs should be left alone with `(Proof modification
+//! is discouraged.)`.  This pass collects both markings and, like
+//! metamath.exe's `verify markup`, reports a warning wherever a proof uses a
+//! statement marked for discouraged new usage, unless the proving statement is
+//! itself so marked.
+
+use diag::Diagnostic;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::Token;
+use parser::copy_token;
+use segment_set::SegmentSet;
+use usage::proof_labels;
+use util::HashSet;
+use util::new_set;
+
+const NEW_USAGE: &[u8] = b"(New usage is discouraged.)";
+const PROOF_MODIFICATION: &[u8] = b"(Proof modification is discouraged.)";
+
+/// Checks whether a comment contains a parenthetical phrase, allowing the
+/// phrase to be broken across lines.
+pub fn comment_contains(comment: StatementRef, phrase: &[u8]) -> bool {
+    let mut text = Vec::new();
+    let mut space = false;
+    for &ch in comment.span().as_ref(&comment.segment().segment.buffer) {
+        if ch <= 32 {
+            space = true;
+        } else {
+            if space && !text.is_empty() {
+                text.push(b' ');
+            }
+            space = false;
+            text.push(ch);
+        }
+    }
+    text.windows(phrase.len()).any(|w| w == phrase)
+}
+
+/// Analysis pass result for discouraged statements.
+#[derive(Default,Clone,Debug)]
+pub struct DiscouragedResult {
+    usage_discouraged: HashSet<Token>,
+    modification_discouraged: HashSet<Token>,
+    diagnostics: Vec<(StatementAddress, Diagnostic)>,
+}
+
+impl DiscouragedResult {
+    /// Collects the discouragement markings of all `$a` and `$p` statements,
+    /// and checks all proofs against them.
+    pub fn new(sset: &SegmentSet) -> DiscouragedResult {
+        let mut result = DiscouragedResult {
+            usage_discouraged: new_set(),
+            modification_discouraged: new_set(),
+            diagnostics: Vec::new(),
+        };
+        let mut provables = Vec::new();
+        for sref in sset.segments() {
+            for stmt in sref {
+                match stmt.statement_type() {
+                    StatementType::Axiom | StatementType::Provable => {}
+                    _ => continue,
+                }
+                if stmt.statement_type() == StatementType::Provable {
+                    provables.push(stmt);
+                }
+                if let Some(comment) = stmt.associated_comment() {
+                    if comment_contains(comment, NEW_USAGE) {
+                        result.usage_discouraged.insert(copy_token(stmt.label()));
+                    }
+                    if comment_contains(comment, PROOF_MODIFICATION) {
+                        result.modification_discouraged.insert(copy_token(stmt.label()));
+                    }
+                }
+            }
+        }
+
+        for stmt in provables {
+            if result.usage_discouraged.contains(stmt.label()) {
+                continue;
+            }
+            for (index, label) in proof_labels(stmt).0 {
+                if result.usage_discouraged.contains(label) {
+                    result.diagnostics.push((stmt.address(),
+                                             Diagnostic::DiscouragedUsage(index,
+                                                                          copy_token(label))));
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns true if the statement with the given label is marked
+    /// `(New usage is discouraged.)`.
+    pub fn is_usage_discouraged(&self, label: &[u8]) -> bool {
+        self.usage_discouraged.contains(label)
+    }
+
+    /// Returns true if the statement with the given label is marked
+    /// `(Proof modification is discouraged.)`.
+    pub fn is_modification_discouraged(&self, label: &[u8]) -> bool {
+        self.modification_discouraged.contains(label)
+    }
+
+    /// Reports uses of discouraged statements in proofs.
+    pub fn diagnostics(&self) -> Vec<(StatementAddress, Diagnostic)> {
+        self.diagnostics.clone()
+    }
+}
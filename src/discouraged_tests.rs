@@ -0,0 +1,25 @@
+use diag::Diagnostic;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_discouraged() {
+    let text = format!("{}$( Old axiom.  (New usage is
+    discouraged.) $)
+ax-old $a |- ( ph -> ph ) $.
+th1 $p |- ( ph -> ph ) $= wph ax-old $.
+$( (Proof modification is discouraged.)  (New usage is discouraged.) $)
+th2 $p |- ( ph -> ph ) $= ( ax-old ) AB $.
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let disc = db.discouraged_result().clone();
+    assert!(disc.is_usage_discouraged(b"ax-old"));
+    assert!(!disc.is_modification_discouraged(b"ax-old"));
+    assert!(disc.is_modification_discouraged(b"th2"));
+    let diags = disc.diagnostics();
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].0, db.statement("th1").unwrap().address());
+    assert_eq!(diags[0].1,
+               Diagnostic::DiscouragedUsage(1, b"ax-old".to_vec().into_boxed_slice()));
+}
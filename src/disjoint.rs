@@ -0,0 +1,92 @@
+//! Detection of superfluous disjoint variable conditions.
+//!
+//! Every `$d` condition in scope of a theorem restricts how it can be used, so
+//! conditions which its proof does not need make it needlessly weak.  This
+//! pass verifies each proof again, recording the pairs of variables which the
+//! `$d` conditions of the applied assertions require to be disjoint (see
+//! `verify::dv_usage`), and reports the other conditions of the theorem's
+//! frame, in the manner of metamath.exe's `verify proof / dv audit`.
+//!
+//! Conditions on a variable which neither occurs in the statement or its
+//! hypotheses nor in the proof are not reported, since a `$d` statement
+//! covering a whole block commonly names variables which only some of the
+//! theorems of the block use.  Proofs which do not verify are skipped.
+
+use diag::Diagnostic;
+use nameck::Nameset;
+use parser::SegmentId;
+use parser::StatementAddress;
+use parser::StatementType;
+use parser::copy_token;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use std::sync::Arc;
+use verify::dv_usage;
+
+/// Analysis pass result for superfluous disjoint variable conditions.
+#[derive(Default,Clone,Debug)]
+pub struct DisjointResult {
+    diagnostics: Vec<(StatementAddress, Diagnostic)>,
+}
+
+fn audit_segment(sset: &SegmentSet,
+                 nset: &Nameset,
+                 scope: &ScopeResult,
+                 id: SegmentId)
+                 -> Vec<(StatementAddress, Diagnostic)> {
+    let mut out = Vec::new();
+    for stmt in sset.segment(id) {
+        if stmt.statement_type() != StatementType::Provable {
+            continue;
+        }
+        let frame = match scope.get(stmt.label()) {
+            Some(frame) => frame,
+            None => continue,
+        };
+        let usage = match dv_usage(sset, nset, scope, stmt) {
+            Ok(usage) => usage,
+            Err(_) => continue,
+        };
+        let relevant = |var: usize| var < frame.mandatory_count || usage.variables.has_bit(var);
+        for (var1, others) in frame.optional_dv.iter().enumerate() {
+            for var2 in others {
+                if var1 < var2 && relevant(var1) && relevant(var2) &&
+                   usage.required.binary_search(&(var1, var2)).is_err() {
+                    let name = |var: usize| copy_token(nset.atom_name(frame.var_list[var]));
+                    out.push((stmt.address(),
+                              Diagnostic::SuperfluousDisjoint(name(var1), name(var2))));
+                }
+            }
+        }
+    }
+    out
+}
+
+impl DisjointResult {
+    /// Audits the disjoint variable conditions of every `$p` statement, one
+    /// segment per job.
+    pub fn new(sset: &Arc<SegmentSet>,
+               nset: &Arc<Nameset>,
+               scope: &Arc<ScopeResult>)
+               -> DisjointResult {
+        let mut promises = Vec::new();
+        for sref in sset.segments() {
+            let sset2 = sset.clone();
+            let nset = nset.clone();
+            let scope = scope.clone();
+            let id = sref.id;
+            promises.push(sset.exec.exec(sref.bytes(),
+                                         move || audit_segment(&sset2, &nset, &scope, id)));
+        }
+        let mut result = DisjointResult::default();
+        for promise in promises {
+            result.diagnostics.extend(promise.wait());
+        }
+        result
+    }
+
+    /// Reports conditions which proofs do not need.
+    pub fn diagnostics(&self) -> Vec<(StatementAddress, Diagnostic)> {
+        self.diagnostics.clone()
+    }
+}
@@ -0,0 +1,29 @@
+use diag::Diagnostic;
+use diag::DiagnosticClass;
+use parser::as_str;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_dv_audit() {
+    let mut db = mkdb(b"$c |- setvar = $.\n$v x y z $.\n\
+                        vx $f setvar x $.\nvy $f setvar y $.\nvz $f setvar z $.\n\
+                        ${ $d x y $. ax-d $a |- x = y $. $}\nax-n $a |- x = y $.\n\
+                        ${ $d x y z $.\n\
+                        th1 $p |- x = y $= vx vy ax-d $.\n\
+                        th2 $p |- x = y $= vx vy ax-n $.\n\
+                        th3 $p |- x = z $= vx vz ax-n $. $}\n");
+    assert!(db.diag_notations(vec![DiagnosticClass::Parse,
+                                   DiagnosticClass::Scope,
+                                   DiagnosticClass::Verify])
+        .is_empty());
+    let sset = db.parse_result().clone();
+    let found: Vec<(String, Diagnostic)> = db.disjoint_result()
+        .diagnostics()
+        .into_iter()
+        .map(|(addr, diag)| (as_str(sset.statement(addr).label()).to_owned(), diag))
+        .collect();
+    let var = |name: &[u8]| name.to_vec().into_boxed_slice();
+    assert_eq!(found,
+               vec![("th2".to_owned(), Diagnostic::SuperfluousDisjoint(var(b"x"), var(b"y"))),
+                    ("th3".to_owned(), Diagnostic::SuperfluousDisjoint(var(b"x"), var(b"z")))]);
+}
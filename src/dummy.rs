@@ -0,0 +1,96 @@
+//! Optional variables of theorems, and the choice of dummy variables.
+//!
+//! Besides its mandatory variables, which occur in its hypotheses and
+//! assertion, the proof of a theorem may use any other variable with a `$f`
+//! hypothesis in effect at the theorem, for instance as the bound variable of
+//! a lemma it applies.  These are its optional variables, and a tool building a
+//! proof which needs such a dummy variable has to pick one which the proof does
+//! not use yet, of the right typecode, and disjoint from the variables the
+//! lemma requires; only the theorem's own `$d` statements can provide that.
+//!
+//! Variables are compared by name, since a variable declared by a local `$v`
+//! can be declared again in another group.
+
+use nameck::Nameset;
+use parser::StatementAddress;
+use parser::StatementType;
+use parser::Token;
+use parser::TokenPtr;
+use parser::copy_token;
+use scopeck::Frame;
+use segment_set::SegmentSet;
+use view::in_effect;
+
+/// An optional variable of a theorem.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct OptionalVariable {
+    /// The name of the variable.
+    pub variable: Token,
+    /// The typecode given by its `$f` hypothesis.
+    pub typecode: Token,
+    /// The `$f` hypothesis.
+    pub address: StatementAddress,
+}
+
+/// Returns the variables with a `$f` hypothesis in effect at the statement of
+/// a frame which are not mandatory, in the order of their `$f` hypotheses.
+pub fn optional_variables(sset: &SegmentSet,
+                          nset: &Nameset,
+                          frame: &Frame)
+                          -> Vec<OptionalVariable> {
+    let mandatory: Vec<TokenPtr> =
+        frame.mandatory_variables().iter().map(|&atom| nset.atom_name(atom)).collect();
+    let mut out: Vec<OptionalVariable> = Vec::new();
+    for sref in sset.segments() {
+        for stmt in sref {
+            if stmt.statement_type() != StatementType::Floating || stmt.math_len() != 2 ||
+               !in_effect(sset, stmt, frame.valid.start) {
+                continue;
+            }
+            let variable = stmt.math_at(1).slice;
+            if !mandatory.contains(&variable) &&
+               !out.iter().any(|var| &var.variable[..] == variable) {
+                out.push(OptionalVariable {
+                    variable: copy_token(variable),
+                    typecode: copy_token(stmt.math_at(0).slice),
+                    address: stmt.address(),
+                });
+            }
+        }
+    }
+    out
+}
+
+/// Returns whether the `$d` statements in effect at the statement of a frame
+/// make two variables disjoint.
+pub fn is_disjoint(nset: &Nameset, frame: &Frame, first: TokenPtr, second: TokenPtr) -> bool {
+    let index = |name: TokenPtr| {
+        frame.var_list.iter().position(|&atom| nset.atom_name(atom) == name)
+    };
+    match (index(first), index(second)) {
+        (Some(first), Some(second)) => {
+            first < frame.optional_dv.len() && frame.optional_dv[first].has_bit(second)
+        }
+        _ => false,
+    }
+}
+
+/// Suggests dummy variables for a proof of the statement of a frame: the
+/// optional variables with the given typecode which are not in `used` and are
+/// disjoint from every variable in `disjoint_from`, in the order of
+/// `optional`, which comes from `optional_variables`.
+pub fn suggest_dummies(nset: &Nameset,
+                       frame: &Frame,
+                       optional: &[OptionalVariable],
+                       typecode: TokenPtr,
+                       disjoint_from: &[TokenPtr],
+                       used: &[TokenPtr])
+                       -> Vec<Token> {
+    optional.iter()
+        .filter(|var| &var.typecode[..] == typecode && !used.contains(&&var.variable[..]))
+        .filter(|var| {
+            disjoint_from.iter().all(|&other| is_disjoint(nset, frame, &var.variable, other))
+        })
+        .map(|var| var.variable.clone())
+        .collect()
+}
@@ -0,0 +1,31 @@
+use parser::as_str;
+use parser::copy_token;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_suggest_dummies() {
+    let text = format!("{}$v ch th $.
+wch $f wff ch $.
+wth $f wff th $.
+${{ $d ph ch $. th1 $p |- ( ph -> ( ph -> ph ) ) $= wph wph ax-1 $. $}}
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let optional: Vec<(String, String)> = db.optional_variables("th1")
+        .unwrap()
+        .iter()
+        .map(|var| (as_str(&var.variable).to_owned(), as_str(&var.typecode).to_owned()))
+        .collect();
+    assert_eq!(optional,
+               vec![("ps".to_owned(), "wff".to_owned()),
+                    ("ch".to_owned(), "wff".to_owned()),
+                    ("th".to_owned(), "wff".to_owned())]);
+    assert!(db.optional_variables("ax-1").is_none());
+
+    assert_eq!(db.suggest_dummies("th1", "wff", &[], &["ps"]),
+               vec![copy_token(b"ch"), copy_token(b"th")]);
+    assert_eq!(db.suggest_dummies("th1", "wff", &["ph"], &[]), vec![copy_token(b"ch")]);
+    assert!(db.suggest_dummies("th1", "wff", &["ph"], &["ch"]).is_empty());
+    assert!(db.suggest_dummies("th1", "class", &[], &[]).is_empty());
+}
@@ -0,0 +1,127 @@
+//! Detection of duplicate assertions.
+//!
+//! Large databases accumulate theorems which are restatements of earlier
+//! ones, with different variable names.  This pass parses the hypotheses and
+//! assertion of every logical `$a` and `$p` statement with the grammar of
+//! the syntax axioms (see `grammar`), and reports a statement whose parse
+//! trees are identical to those of an earlier statement up to a consistent
+//! renaming of variables.
+//!
+//! Hypotheses are compared in frame order, so statements differing only in
+//! the order of their `$e` hypotheses are not reported.  Disjoint variable
+//! conditions are not compared: a duplicate with fewer conditions is a
+//! strengthening, which is usually just as redundant.  Statements which do
+//! not parse are skipped.
+
+use diag::Diagnostic;
+use grammar::SyntaxParser;
+use grammar::SyntaxTree;
+use grammar::frame_variables;
+use nameck::Nameset;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::Token;
+use parser::TokenPtr;
+use parser::as_str;
+use scopeck::Hyp;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use util::HashMap;
+use util::new_map;
+
+/// Analysis pass result for duplicate assertions.
+#[derive(Default,Clone,Debug)]
+pub struct DuplicateResult {
+    diagnostics: Vec<(StatementAddress, Diagnostic)>,
+}
+
+/// Writes a parse tree as an s-expression with the variables numbered in
+/// order of first occurrence, so that alpha-equivalent trees give the same
+/// string.
+fn canonical(sset: &SegmentSet,
+             tree: &SyntaxTree,
+             vars: &mut HashMap<Token, usize>,
+             out: &mut String) {
+    match *tree {
+        SyntaxTree::Var(ref name) => {
+            let next = vars.len();
+            let ix = *vars.entry(name.clone()).or_insert(next);
+            out.push_str(&format!(" ${}", ix));
+        }
+        SyntaxTree::App(addr, ref args) => {
+            out.push_str(" (");
+            out.push_str(as_str(sset.statement(addr).label()));
+            for arg in args {
+                canonical(sset, arg, vars, out);
+            }
+            out.push(')');
+        }
+    }
+}
+
+impl DuplicateResult {
+    /// Compares every logical `$a` and `$p` statement with the earlier ones.
+    pub fn new(sset: &SegmentSet,
+               nset: &Nameset,
+               scope: &ScopeResult,
+               parser: &SyntaxParser)
+               -> DuplicateResult {
+        let mut result = DuplicateResult::default();
+        let mut seen: HashMap<String, StatementAddress> = new_map();
+        for sref in sset.segments() {
+            for stmt in sref {
+                match stmt.statement_type() {
+                    StatementType::Axiom | StatementType::Provable => {}
+                    _ => continue,
+                }
+                if parser.is_syntax_axiom(stmt.address()) {
+                    continue;
+                }
+                let frame = match scope.get(stmt.label()) {
+                    Some(frame) => frame,
+                    None => continue,
+                };
+                let frame_vars = frame_variables(nset, frame);
+                let mut vars = new_map();
+                let mut key = String::new();
+                let mut parses = true;
+                let mut add = |formula: StatementRef, key: &mut String| {
+                    let math: Vec<TokenPtr> = formula.math_iter().map(|tok| tok.slice).collect();
+                    match parser.parse_math(&frame_vars, &math) {
+                        Some((_, tree)) => {
+                            key.push_str(as_str(math[0]));
+                            canonical(sset, &tree, &mut vars, key);
+                            key.push(';');
+                        }
+                        None => parses = false,
+                    }
+                };
+                for hyp in frame.hypotheses.iter() {
+                    if let Hyp::Essential(addr, _) = *hyp {
+                        add(sset.statement(addr), &mut key);
+                    }
+                }
+                add(stmt, &mut key);
+                if !parses {
+                    continue;
+                }
+                match seen.get(&key) {
+                    Some(&earlier) => {
+                        result.diagnostics
+                            .push((stmt.address(), Diagnostic::DuplicateAssertion(earlier)));
+                    }
+                    None => {
+                        seen.insert(key, stmt.address());
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Reports statements which duplicate earlier ones.
+    pub fn diagnostics(&self) -> Vec<(StatementAddress, Diagnostic)> {
+        self.diagnostics.clone()
+    }
+}
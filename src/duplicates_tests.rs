@@ -0,0 +1,20 @@
+use database::Database;
+use diag::Diagnostic;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_duplicate_assertions() {
+    let text = format!("{}ax-2 $a |- ( ps -> ( ph -> ps ) ) $.
+${{ th1.1 $e |- ph $. th1 $p |- ( ps -> ph ) $= ? $. $}}
+${{ th2.1 $e |- ps $. th2 $p |- ( ph -> ps ) $= ? $. $}}
+${{ th3.1 $e |- ph $. th3 $p |- ( ph -> ps ) $= ? $. $}}
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let diags = db.duplicate_result().diagnostics();
+    let addr = |db: &mut Database, label| db.statement(label).unwrap().address();
+    assert_eq!(diags,
+               vec![(addr(&mut db, "ax-2"), Diagnostic::DuplicateAssertion(addr(&mut db, "ax-1"))),
+                    (addr(&mut db, "th2"), Diagnostic::DuplicateAssertion(addr(&mut db, "th1")))]);
+}
@@ -0,0 +1,197 @@
+//! Byte-preserving output of source files with explicit edits.
+//!
+//! Automated rewriting tools need to be trusted not to disturb text they did
+//! not mean to change, so that the resulting version control diffs are
+//! minimal.  `emit_file` reconstructs a source file from its parsed segments,
+//! applies a list of edits to explicitly given spans, and then checks that
+//! every region outside the edits is byte-identical to the text originally
+//! loaded before writing anything.
+
+use parser::Span;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::as_str;
+use segment_set::SegmentSet;
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::Write;
+
+/// A replacement of a span of a source file with new text.
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub struct Edit {
+    /// The replaced region, as byte offsets within the source file (not
+    /// within a segment or slice).
+    pub span: Span,
+    /// The text to insert in place of the span.
+    pub text: Vec<u8>,
+}
+
+/// The error type for `emit_file`.
+#[derive(Debug)]
+pub enum EmitError {
+    /// IO error during write
+    Io(io::Error),
+    /// No loaded segment came from a file of the given name
+    UnknownFile(String),
+    /// An edit extends past the end of the file
+    EditOutOfRange(Span),
+    /// Two edits overlap
+    OverlappingEdits(Span, Span),
+    /// No loaded statement has the given address, which is stale or out of
+    /// range
+    UnknownStatement(StatementAddress),
+    /// The reconstructed text of an unedited region did not match the
+    /// original source
+    Mismatch(Span),
+}
+
+impl From<io::Error> for EmitError {
+    fn from(err: io::Error) -> EmitError {
+        EmitError::Io(err)
+    }
+}
+
+impl fmt::Display for EmitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EmitError::Io(ref err) => write!(f, "IO error: {}", err),
+            EmitError::UnknownFile(ref name) => write!(f, "File {} is not loaded", name),
+            EmitError::EditOutOfRange(span) => {
+                write!(f, "Edit {}..{} is out of range", span.start, span.end)
+            }
+            EmitError::OverlappingEdits(a, b) => {
+                write!(f,
+                       "Edits {}..{} and {}..{} overlap",
+                       a.start,
+                       a.end,
+                       b.start,
+                       b.end)
+            }
+            EmitError::UnknownStatement(_) => write!(f, "Statement is not loaded"),
+            EmitError::Mismatch(span) => {
+                write!(f,
+                       "Unedited region {}..{} does not match the original source",
+                       span.start,
+                       span.end)
+            }
+        }
+    }
+}
+
+impl error::Error for EmitError {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            EmitError::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Builds an edit inserting new lines of source before a statement, or
+/// before its description comment if it has one, indented like the
+/// statement and followed by a blank line.
+///
+/// Each line is a comment or a statement, which is wrapped by the `format`
+/// option.
+///
+/// Returns the name of the file to edit along with the edit.
+pub fn insert_before(sset: &SegmentSet, stmt: StatementRef, lines: &[String]) -> (String, Edit) {
+    let anchor = stmt.associated_comment().unwrap_or(stmt);
+    let sinfo = sset.source_info(anchor.segment().id);
+    let start = (sinfo.span.start + anchor.span().start) as usize;
+    let mut offset = start;
+    while offset > 0 && (sinfo.text[offset - 1] == b' ' || sinfo.text[offset - 1] == b'\t') {
+        offset -= 1;
+    }
+    let indent = as_str(&sinfo.text[offset..start]);
+
+    let policy = &sset.options.format;
+    let mut text = String::new();
+    for line in lines {
+        let line = line.trim();
+        let wrapped = if line.starts_with("$(") && line.ends_with("$)") {
+            policy.comment_lines(indent.len(), &line[2..line.len() - 2])
+        } else {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            policy.statement_lines(indent.len(), &words)
+        };
+        for part in wrapped {
+            text.push_str(indent);
+            text.push_str(&part);
+            text.push('\n');
+        }
+    }
+    text.push('\n');
+    (sinfo.name.clone(),
+     Edit {
+        span: Span::new(offset, offset),
+        text: text.into_bytes(),
+    })
+}
+
+/// Writes a source file with a set of edits applied.
+///
+/// The file is reconstructed from the statements of the segments it
+/// generated, and edits are applied in order of position; they must not
+/// overlap, although several zero-length edits at the same offset are
+/// allowed and are applied in the order given.  Before any output is written,
+/// each region between edits is compared with the same region of the text the
+/// file was loaded from, so if `emit_file` succeeds the output differs from
+/// the original only inside the edited spans.
+pub fn emit_file<W: Write>(sset: &SegmentSet,
+                           name: &str,
+                           edits: &[Edit],
+                           out: &mut W)
+                           -> Result<(), EmitError> {
+    let mut original = None;
+    let mut text = Vec::new();
+    for sref in sset.segments() {
+        let sinfo = sset.source_info(sref.id);
+        if sinfo.name == name {
+            if original.is_none() {
+                original = Some(sinfo.text.clone());
+            }
+            sset.write_segment(sref.id, &mut text)?;
+        }
+    }
+    let original = original.ok_or_else(|| EmitError::UnknownFile(name.to_owned()))?;
+
+    let mut order: Vec<&Edit> = edits.iter().collect();
+    order.sort_by_key(|edit| edit.span.start);
+    let mut output = Vec::with_capacity(text.len());
+    let mut pos = 0;
+    let mut prev: Option<Span> = None;
+    for edit in order {
+        let (start, end) = (edit.span.start as usize, edit.span.end as usize);
+        if end > text.len() || start > end {
+            return Err(EmitError::EditOutOfRange(edit.span));
+        }
+        if let Some(pspan) = prev {
+            if start < pspan.end as usize {
+                return Err(EmitError::OverlappingEdits(pspan, edit.span));
+            }
+        }
+        check_region(&text, &original, pos, start)?;
+        output.extend_from_slice(&text[pos..start]);
+        output.extend_from_slice(&edit.text);
+        pos = end;
+        prev = Some(edit.span);
+    }
+    check_region(&text, &original, pos, text.len())?;
+    output.extend_from_slice(&text[pos..]);
+    if text.len() != original.len() {
+        return Err(EmitError::Mismatch(Span::new(text.len().min(original.len()),
+                                                 text.len().max(original.len()))));
+    }
+
+    out.write_all(&output)?;
+    Ok(())
+}
+
+fn check_region(text: &[u8], original: &[u8], start: usize, end: usize) -> Result<(), EmitError> {
+    if end > original.len() || text[start..end] != original[start..end] {
+        return Err(EmitError::Mismatch(Span::new(start, end)));
+    }
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use diag::Diagnostic;
+use emit::Edit;
+use emit::EmitError;
+use emit::emit_file;
+use parser::Span;
+use std::slice;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_emit_file() {
+    let mut db = mkdb(b"$c X $.\n$( old $)\n$c Y $.\n");
+    let edit = Edit {
+        span: Span::new(8, 17),
+        text: b"$( new comment $)".to_vec(),
+    };
+    let mut out = Vec::new();
+    emit_file(db.parse_result(), "test.mm", slice::from_ref(&edit), &mut out).unwrap();
+    assert_eq!(out, b"$c X $.\n$( new comment $)\n$c Y $.\n".to_vec());
+    let overlap = Edit {
+        span: Span::new(16, 20),
+        text: Vec::new(),
+    };
+    match emit_file(db.parse_result(), "test.mm", &[edit, overlap], &mut out) {
+        Err(EmitError::OverlappingEdits(..)) => {}
+        _ => panic!("overlapping edits must be rejected"),
+    }
+}
+
+#[test]
+fn test_emit_byte_order_mark() {
+    let text = b"\xEF\xBB\xBF$c A $.";
+    let mut db = mkdb(text);
+    let diags: Vec<Diagnostic> =
+        db.parse_result().parse_diagnostics().into_iter().map(|(_, diag)| diag).collect();
+    assert_eq!(diags, vec![Diagnostic::ByteOrderMark]);
+    let mut out = Vec::new();
+    db.parse_result().write_file("test.mm", &mut out).unwrap();
+    assert_eq!(out, text.to_vec());
+    let mut out = Vec::new();
+    emit_file(db.parse_result(), "test.mm", &[], &mut out).unwrap();
+    assert_eq!(out, text.to_vec());
+}
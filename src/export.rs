@@ -1,4 +1,10 @@
-//! Export support for mmj2 proof files.
+//! Export support for mmj2 proof files and proof terms.
+//!
+//! `export_mmp` writes a proof as an mmj2 proof worksheet.
+//! `export_proof_term` writes it as a term in the syntax of Lean 4, in which
+//! every step is the label of the applied assertion followed by its
+//! hypotheses, as a starting point for translating proofs to type-theoretic
+//! systems.  Formulas are not translated, since that needs a grammar.
 
 use diag::Diagnostic;
 use nameck::Nameset;
@@ -164,6 +170,7 @@ pub fn export_mmp<W: Write>(sset: &SegmentSet,
         line.push_str(&String::from_utf8_lossy(&arr.exprs[cur]));
         writeln!(out, "{}", line)?;
     }
+    let (indent, line_width) = sset.options.format.proof_layout(4);
     writeln!(out,
                   "\n$={}",
                   ProofTreePrinter {
@@ -174,10 +181,84 @@ pub fn export_mmp<W: Write>(sset: &SegmentSet,
                       style: ProofStyle::Compressed,
                       arr: &arr,
                       initial_chr: 2,
-                      indent: 6,
-                      line_width: 79,
+                      indent: indent,
+                      line_width: line_width,
                   })?;
 
     writeln!(out, "\n$)")?;
     Ok(())
 }
+
+/// Returns a label as a Lean identifier, quoting it with `«»` if it contains
+/// characters other than letters, digits and `_` or starts with a digit.
+fn lean_ident(label: &[u8]) -> String {
+    let plain = label.iter().all(|&ch| ch.is_ascii_alphanumeric() || ch == b'_') &&
+                label.first().is_some_and(|ch| !ch.is_ascii_digit());
+    if plain {
+        as_str(label).to_owned()
+    } else {
+        format!("«{}»", as_str(label))
+    }
+}
+
+/// Export the proof of a statement as a Lean 4 term.
+///
+/// Each step applies the label of an assertion to the steps proving its
+/// hypotheses, in database order; `$f` hypotheses become their variables and
+/// `$e` hypotheses their labels.  Steps used more than once are bound with
+/// `let` to `sN`, where `N` is the index of the step in the
+/// `ProofTreeArray`.
+pub fn export_proof_term<W: Write>(sset: &SegmentSet,
+                                   nset: &Nameset,
+                                   scope: &ScopeResult,
+                                   stmt: StatementRef,
+                                   out: &mut W)
+                                   -> Result<(), ExportError> {
+    let arr = ProofTreeArray::new(sset, nset, scope, stmt)?;
+    let parents = arr.count_parents();
+    let mut reachable = vec![false; arr.trees.len()];
+    let mut stack = vec![arr.qed];
+    while let Some(ix) = stack.pop() {
+        if !reachable[ix] {
+            reachable[ix] = true;
+            stack.extend(&arr.trees[ix].children);
+        }
+    }
+
+    fn term(arr: &ProofTreeArray,
+            sset: &SegmentSet,
+            names: &[Option<String>],
+            ix: usize)
+            -> String {
+        if let Some(ref name) = names[ix] {
+            return name.clone();
+        }
+        let step = sset.statement(arr.trees[ix].address);
+        if step.statement_type() == StatementType::Floating {
+            return lean_ident(step.math_at(1).slice);
+        }
+        let mut out = lean_ident(step.label());
+        for &child in &arr.trees[ix].children {
+            let arg = term(arr, sset, names, child);
+            if arg.contains(' ') {
+                out.push_str(&format!(" ({})", arg));
+            } else {
+                out.push(' ');
+                out.push_str(&arg);
+            }
+        }
+        out
+    }
+
+    writeln!(out, "-- Proof of {}", as_str(stmt.label()))?;
+    let mut names = vec![None; arr.trees.len()];
+    for ix in 0..arr.trees.len() {
+        let shared = parents[ix] > 1 && !arr.trees[ix].children.is_empty();
+        if reachable[ix] && ix != arr.qed && shared {
+            writeln!(out, "let s{} := {}", ix, term(&arr, sset, &names, ix))?;
+            names[ix] = Some(format!("s{}", ix));
+        }
+    }
+    writeln!(out, "{}", term(&arr, sset, &names, arr.qed))?;
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use export::export_proof_term;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_proof_term() {
+    let text = format!("{}${{
+  th1.1 $e |- ph $.
+  th1 $p |- ( ps -> ph ) $= wph wps wph wi th1.1 wph wps ax-1 ax-mp $.
+$}}
+th2 $p |- ( ( ph -> ph ) -> ( ( ph -> ph ) -> ( ph -> ph ) ) ) $=
+  ( wi ax-1 ) AABZDC $.
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let sset = db.parse_result().clone();
+    let nset = db.name_result().clone();
+    let scope = db.scope_result().clone();
+    let term = |label: &str| {
+        let mut out = Vec::new();
+        let stmt = sset.statement(nset.lookup_label(label.as_bytes()).unwrap().address);
+        export_proof_term(&sset, &nset, &scope, stmt, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(term("th1"),
+               "-- Proof of th1\n«ax-mp» ph (wi ps ph) «th1.1» («ax-1» ph ps)\n");
+    assert_eq!(term("th2"), "-- Proof of th2\nlet s1 := wi ph ph\n«ax-1» s1 s1\n");
+}
@@ -0,0 +1,409 @@
+//! Extraction of commands from `$j` additional information comments.
+//!
+//! `$j` comments use the same command language as `$t` comments (see
+//! `typesetting`), but their vocabulary is open-ended and defined by the tools
+//! which consume them.  `process_j_commands` splits them into commands, and
+//! `parse_directive` interprets the keywords of set.mm and of this program
+//! as a `JDirective`; `ExtraInfoResult` collects them for the whole database,
+//! with a diagnostic for each command it does not know or cannot read.
+//!
+//! The commands which describe the logic itself are collected in `JConfig`:
+//! `syntax 'wff';` declares a syntax typecode, `syntax '|-' as 'wff';` a
+//! provable typecode whose formulas are parsed as `wff`, and `bound
+//! 'setvar';` a typecode whose variables are only ever substituted by
+//! variables.  A database may declare several provable typecodes, for
+//! instance one per logic it formalizes.
+
+use diag::Diagnostic;
+use diag::Level;
+use parser::Span;
+use parser::StatementAddress;
+use parser::StatementType;
+use parser::Token;
+use segment_set::SegmentSet;
+use typesetting::Lexeme;
+use typesetting::comment_command_spans;
+use typesetting::concat;
+
+/// A single command from a `$j` comment.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct JCommand {
+    /// The address of the comment statement containing the command.
+    pub address: StatementAddress,
+    /// The span of the command in the segment buffer, from its keyword to
+    /// its last argument.
+    pub span: Span,
+    /// The lexemes of the command, starting with its keyword and excluding
+    /// the terminating `;`.
+    pub lexemes: Vec<Lexeme>,
+}
+
+impl JCommand {
+    /// Returns the keyword of the command, if it starts with a bare word.
+    pub fn keyword(&self) -> Option<&[u8]> {
+        match self.lexemes.first() {
+            Some(Lexeme::Word(word)) => Some(word),
+            _ => None,
+        }
+    }
+
+    /// Returns the span of the keyword of the command, or of the whole
+    /// command if it does not start with a bare word.
+    pub fn keyword_span(&self) -> Span {
+        match self.keyword() {
+            Some(word) => {
+                let start = self.span.start as usize;
+                Span::new(start, start + word.len())
+            }
+            None => self.span,
+        }
+    }
+}
+
+/// A `$j` command with a known keyword, with its arguments.  Labels and
+/// typecodes are given as quoted strings; the arguments which name a
+/// diagnostic code, a lint exemption or a deprecated item may be
+/// concatenated from several strings with `+`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum JDirective {
+    /// `syntax 'wff';` declares a syntax typecode, and `syntax '|-' as
+    /// 'wff';` a provable typecode with the syntax typecode of its formulas.
+    Syntax(Token, Option<Token>),
+    /// `bound 'setvar';` declares a typecode whose variables are only ever
+    /// substituted by variables.
+    Bound(Token),
+    /// `unambiguous 'klr 5';` names the method by which the grammar has been
+    /// shown to be unambiguous.
+    Unambiguous(Token),
+    /// `primitive 'wn' 'wi';` lists syntax axioms which have no definition.
+    Primitive(Vec<Token>),
+    /// `justification 'bijust' for 'df-bi';` gives the theorem justifying a
+    /// definition.
+    Justification {
+        /// The justification theorem.
+        theorem: Token,
+        /// The definition it justifies.
+        definition: Token,
+    },
+    /// `definition 'dfbi1' for 'wb';` gives the theorem to use as the
+    /// definition of a syntax axiom, in place of its definitional axiom.
+    Definition {
+        /// The theorem stating the definition.
+        theorem: Token,
+        /// The syntax axiom it defines.
+        syntax: Token,
+    },
+    /// `equality 'wb' from 'biid' 'bicomi' 'bitri';` declares an equality
+    /// relation with its reflexivity, symmetry and transitivity theorems.
+    Equality {
+        /// The syntax axiom of the relation.
+        syntax: Token,
+        /// The reflexivity theorem.
+        reflexivity: Token,
+        /// The symmetry theorem.
+        symmetry: Token,
+        /// The transitivity theorem.
+        transitivity: Token,
+    },
+    /// `condequality 'wceq' from 'dedhb';` gives the theorem turning an
+    /// equality into a conditional one.
+    CondEquality {
+        /// The syntax axiom of the equality.
+        syntax: Token,
+        /// The theorem.
+        theorem: Token,
+    },
+    /// `congruence 'notbii' 'imbi12i';` lists congruence theorems of
+    /// equalities for syntax axioms.
+    Congruence(Vec<Token>),
+    /// `condcongruence 'ifbid';` lists congruence theorems of conditional
+    /// equalities.
+    CondCongruence(Vec<Token>),
+    /// `notfree 'wnf' from 'nfv';` declares a not-free predicate, with the
+    /// theorem for expressions in which the variable does not occur.
+    NotFree {
+        /// The syntax axiom of the predicate.
+        syntax: Token,
+        /// The theorem.
+        theorem: Token,
+    },
+    /// `free_var 'nfa1' 'nfe1';` lists theorems stating that a variable is
+    /// not free in an expression which binds it.
+    FreeVar(Vec<Token>),
+    /// `type_conversions 'cv';` lists the syntax axioms which convert between
+    /// typecodes; a command without arguments allows all of them.
+    TypeConversions(Vec<Token>),
+    /// `garden_path ...;` gives a hint to parsers for an ambiguous prefix;
+    /// its lexemes are kept as they are.
+    GardenPath(Vec<Lexeme>),
+    /// `usage 'ax-10' avoids 'ax-11';` records the axioms a statement does not
+    /// use.
+    Usage {
+        /// The statement.
+        label: Token,
+        /// The axioms it avoids.
+        avoids: Vec<Token>,
+    },
+    /// `diagnostic 'code' as 'level';` changes the level of a category of
+    /// diagnostics, or suppresses it for the level `off`; see
+    /// `diag::DiagnosticConfig`.
+    Diagnostic(String, Option<Level>),
+    /// `lint_ignore 'name';` exempts a label or math symbol from the checks
+    /// of `lint`.
+    LintIgnore(String),
+    /// `deprecated 'name';` or `deprecated 'name' as 'replacement';` marks a
+    /// label or math symbol as deprecated; see `deprecation`.
+    Deprecated(String, Option<String>),
+    /// `deprecation_tag 'text';` marks the assertions whose comment contains
+    /// the text as deprecated.
+    DeprecationTag(String),
+}
+
+/// Returns the tokens of a list of quoted strings, or `None` if another
+/// lexeme is in the list.
+fn strings(args: &[Lexeme]) -> Option<Vec<Token>> {
+    args.iter()
+        .map(|lex| match lex {
+            Lexeme::Str(string) => Some(string.clone().into_boxed_slice()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the token of a single quoted string.
+fn string(args: &[Lexeme]) -> Option<Token> {
+    match strings(args) {
+        Some(mut list) if list.len() == 1 => list.pop(),
+        _ => None,
+    }
+}
+
+/// Splits arguments at the first bare word `word`.
+fn split_at_word<'a>(args: &'a [Lexeme], word: &[u8]) -> Option<(&'a [Lexeme], &'a [Lexeme])> {
+    args.iter()
+        .position(|lex| match lex {
+            Lexeme::Word(other) => &other[..] == word,
+            _ => false,
+        })
+        .map(|pos| (&args[..pos], &args[pos + 1..]))
+}
+
+/// Interprets a `$j` command.  Commands with an unknown keyword give an
+/// `UnknownJCommand` diagnostic, and those with unexpected arguments a
+/// `MalformedJCommand`.
+pub fn parse_directive(command: &JCommand) -> Result<JDirective, Diagnostic> {
+    let keyword = match command.keyword() {
+        Some(keyword) => keyword,
+        None => return Err(Diagnostic::MalformedJCommand(command.span)),
+    };
+    let args = &command.lexemes[1..];
+    let directive = match keyword {
+        b"syntax" => {
+            match split_at_word(args, b"as") {
+                Some((name, syntax)) => {
+                    string(name).and_then(|name| {
+                        string(syntax).map(|syntax| JDirective::Syntax(name, Some(syntax)))
+                    })
+                }
+                None => string(args).map(|name| JDirective::Syntax(name, None)),
+            }
+        }
+        b"bound" => string(args).map(JDirective::Bound),
+        b"unambiguous" => string(args).map(JDirective::Unambiguous),
+        b"primitive" => strings(args).map(JDirective::Primitive),
+        b"justification" | b"definition" => {
+            split_at_word(args, b"for").and_then(|(theorem, target)| {
+                let (theorem, target) = (string(theorem)?, string(target)?);
+                Some(if keyword == b"justification" {
+                    JDirective::Justification {
+                        theorem: theorem,
+                        definition: target,
+                    }
+                } else {
+                    JDirective::Definition {
+                        theorem: theorem,
+                        syntax: target,
+                    }
+                })
+            })
+        }
+        b"equality" => {
+            split_at_word(args, b"from").and_then(|(syntax, theorems)| {
+                match strings(theorems) {
+                    Some(ref theorems) if theorems.len() == 3 => {
+                        Some(JDirective::Equality {
+                            syntax: string(syntax)?,
+                            reflexivity: theorems[0].clone(),
+                            symmetry: theorems[1].clone(),
+                            transitivity: theorems[2].clone(),
+                        })
+                    }
+                    _ => None,
+                }
+            })
+        }
+        b"condequality" | b"notfree" => {
+            split_at_word(args, b"from").and_then(|(syntax, theorem)| {
+                let (syntax, theorem) = (string(syntax)?, string(theorem)?);
+                Some(if keyword == b"condequality" {
+                    JDirective::CondEquality {
+                        syntax: syntax,
+                        theorem: theorem,
+                    }
+                } else {
+                    JDirective::NotFree {
+                        syntax: syntax,
+                        theorem: theorem,
+                    }
+                })
+            })
+        }
+        b"congruence" => strings(args).map(JDirective::Congruence),
+        b"condcongruence" => strings(args).map(JDirective::CondCongruence),
+        b"free_var" => strings(args).map(JDirective::FreeVar),
+        b"type_conversions" => strings(args).map(JDirective::TypeConversions),
+        b"garden_path" => Some(JDirective::GardenPath(args.to_vec())),
+        b"usage" => {
+            split_at_word(args, b"avoids").and_then(|(label, avoids)| {
+                Some(JDirective::Usage {
+                    label: string(label)?,
+                    avoids: strings(avoids)?,
+                })
+            })
+        }
+        b"diagnostic" => {
+            split_at_word(args, b"as").and_then(|(code, level)| {
+                let level = Level::parse(&concat(level)?)?;
+                Some(JDirective::Diagnostic(concat(code)?, level))
+            })
+        }
+        b"lint_ignore" => concat(args).map(JDirective::LintIgnore),
+        b"deprecated" => {
+            match split_at_word(args, b"as") {
+                Some((name, replacement)) => {
+                    concat(name).and_then(|name| {
+                        concat(replacement)
+                            .map(|replacement| JDirective::Deprecated(name, Some(replacement)))
+                    })
+                }
+                None => concat(args).map(|name| JDirective::Deprecated(name, None)),
+            }
+        }
+        b"deprecation_tag" => concat(args).map(JDirective::DeprecationTag),
+        _ => return Err(Diagnostic::UnknownJCommand(command.keyword_span())),
+    };
+    directive.ok_or(Diagnostic::MalformedJCommand(command.span))
+}
+
+/// Analysis pass result for the `$j` commands of a database.
+#[derive(Clone,Debug,Default)]
+pub struct ExtraInfoResult {
+    directives: Vec<(StatementAddress, JDirective)>,
+    diagnostics: Vec<(StatementAddress, Diagnostic)>,
+}
+
+impl ExtraInfoResult {
+    /// Interprets every `$j` command of the database.
+    pub fn new(sset: &SegmentSet) -> ExtraInfoResult {
+        let mut result = ExtraInfoResult::default();
+        for command in process_j_commands(sset) {
+            match parse_directive(&command) {
+                Ok(directive) => result.directives.push((command.address, directive)),
+                Err(diag) => result.diagnostics.push((command.address, diag)),
+            }
+        }
+        result
+    }
+
+    /// Returns the commands which could be interpreted, in database order,
+    /// each with the address of its comment.
+    pub fn directives(&self) -> &[(StatementAddress, JDirective)] {
+        &self.directives
+    }
+
+    /// Reports the commands which are unknown or malformed.
+    pub fn diagnostics(&self) -> Vec<(StatementAddress, Diagnostic)> {
+        self.diagnostics.clone()
+    }
+}
+
+/// The provable typecode assumed when a database declares none, as in
+/// databases written before `$j` comments existed.
+pub const DEFAULT_PROVABLE: &[u8] = b"|-";
+
+/// The typecodes declared by the `syntax` and `bound` commands of `$j`
+/// comments; see the module documentation.
+#[derive(Clone,Debug,Default,PartialEq,Eq)]
+pub struct JConfig {
+    /// The syntax typecodes, in order of declaration.
+    pub syntax: Vec<Token>,
+    /// The provable typecodes, in order of declaration, each with the syntax
+    /// typecode its formulas are parsed as.
+    pub provable: Vec<(Token, Token)>,
+    /// The typecodes whose variables can only be substituted by variables,
+    /// in order of declaration.
+    pub bound: Vec<Token>,
+}
+
+impl JConfig {
+    /// Reads the `syntax` and `bound` commands of every `$j` comment in the
+    /// database.  Malformed commands are ignored.
+    pub fn new(sset: &SegmentSet) -> JConfig {
+        let mut config = JConfig::default();
+        for command in process_j_commands(sset) {
+            match parse_directive(&command) {
+                Ok(JDirective::Syntax(name, None)) => config.syntax.push(name),
+                Ok(JDirective::Syntax(name, Some(syntax))) => config.provable.push((name, syntax)),
+                Ok(JDirective::Bound(name)) => config.bound.push(name),
+                _ => {}
+            }
+        }
+        config
+    }
+
+    /// Returns the provable typecodes, or `|-` if none is declared.
+    pub fn provable_typecodes(&self) -> Vec<&[u8]> {
+        if self.provable.is_empty() {
+            vec![DEFAULT_PROVABLE]
+        } else {
+            self.provable.iter().map(|(provable, _)| &provable[..]).collect()
+        }
+    }
+
+    /// Returns true if a typecode is provable: it is declared so, or it is
+    /// `|-` and no provable typecode is declared.
+    pub fn is_provable(&self, typecode: &[u8]) -> bool {
+        self.provable_typecodes().contains(&typecode)
+    }
+
+    /// Returns the syntax typecode the formulas of a provable typecode are
+    /// parsed as, if one is declared.
+    pub fn syntax_typecode(&self, provable: &[u8]) -> Option<&[u8]> {
+        self.provable.iter().find(|(tc, _)| &tc[..] == provable).map(|(_, syntax)| &syntax[..])
+    }
+
+    /// Returns true if a typecode is declared by a `bound` command.
+    pub fn is_bound(&self, typecode: &[u8]) -> bool {
+        self.bound.iter().any(|tc| &tc[..] == typecode)
+    }
+}
+
+/// Collects the commands of every `$j` comment in the database, in database
+/// order.
+pub fn process_j_commands(sset: &SegmentSet) -> Vec<JCommand> {
+    let mut out = Vec::new();
+    for segment in sset.segments() {
+        for stmt in segment {
+            if stmt.statement_type() == StatementType::AdditionalInfoComment {
+                for (span, lexemes) in comment_command_spans(stmt, b"$j") {
+                    out.push(JCommand {
+                        address: stmt.address(),
+                        span: span,
+                        lexemes: lexemes,
+                    });
+                }
+            }
+        }
+    }
+    out
+}
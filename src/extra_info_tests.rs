@@ -0,0 +1,82 @@
+use diag::Diagnostic;
+#[cfg(feature = "grammar")]
+use extra_info::JConfig;
+use extra_info::JDirective;
+use parser::as_str;
+use parser::copy_token;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+#[cfg(feature = "grammar")]
+fn test_j_config() {
+    let logic = "$c |= $.\nax-m $a |= ( ph -> ph ) $.\n";
+    let text = format!("{}{}", DB, logic);
+    let mut db = mkdb(text.as_bytes());
+    assert_eq!(**db.j_config(), JConfig::default());
+    assert_eq!(db.j_config().provable_typecodes(), vec![b"|-"]);
+    let ax_m = db.statement("ax-m").unwrap().address();
+    assert!(db.syntax_parser().is_syntax_axiom(ax_m));
+
+    let text = format!("$( $j syntax 'wff'; syntax '|-' as 'wff'; syntax '|=' as 'wff';\n\
+                        bound 'setvar'; syntax 'bad' as; $)\n{}{}",
+                       DB,
+                       logic);
+    let mut db = mkdb(text.as_bytes());
+    let config = db.j_config().clone();
+    assert_eq!(config.syntax, vec![copy_token(b"wff")]);
+    assert_eq!(config.provable_typecodes(), vec![b"|-", b"|="]);
+    assert_eq!(config.syntax_typecode(b"|="), Some(&b"wff"[..]));
+    assert!(config.is_provable(b"|=") && !config.is_provable(b"wff"));
+    assert!(config.is_bound(b"setvar"));
+    let ax_m = db.statement("ax-m").unwrap().address();
+    assert!(!db.syntax_parser().is_syntax_axiom(ax_m));
+    assert!(db.parse_formula_str("( ph -> ps )", "|=").is_ok());
+    let next = db.next_formula_tokens("( ph", "|=").unwrap();
+    assert_eq!(next.constants, vec![copy_token(b"->")]);
+    let formula = db.build_formula("|=", |b| b.app_label(b"wi", vec![b.var(b"ph")?, b.var(b"ps")?]))
+        .unwrap();
+    assert_eq!(format!("{}", formula), "|= ( ph -> ps )");
+}
+
+#[test]
+fn test_j_directives() {
+    let text = format!("$( $j syntax '|-' as 'wff'; primitive 'wi';\n\
+                        equality 'wb' from 'biid' 'bicomi' 'bitri';\n\
+                        justification 'bijust' for 'df-bi'; lint_ignore 'ax-' + '1';\n\
+                        frobnicate 'x'; congruence 'imbi12i' as; $)\n{}",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let result = db.extra_info_result().clone();
+    let directives: Vec<&JDirective> = result.directives().iter().map(|(_, d)| d).collect();
+    assert_eq!(directives,
+               vec![&JDirective::Syntax(copy_token(b"|-"), Some(copy_token(b"wff"))),
+                    &JDirective::Primitive(vec![copy_token(b"wi")]),
+                    &JDirective::Equality {
+                        syntax: copy_token(b"wb"),
+                        reflexivity: copy_token(b"biid"),
+                        symmetry: copy_token(b"bicomi"),
+                        transitivity: copy_token(b"bitri"),
+                    },
+                    &JDirective::Justification {
+                        theorem: copy_token(b"bijust"),
+                        definition: copy_token(b"df-bi"),
+                    },
+                    &JDirective::LintIgnore("ax-1".to_owned())]);
+    let sset = db.parse_result().clone();
+    let spans: Vec<(&str, String)> = result.diagnostics()
+        .iter()
+        .map(|&(addr, ref diag)| {
+            let buffer = &sset.statement(addr).segment().segment.buffer;
+            match *diag {
+                Diagnostic::UnknownJCommand(span) => ("unknown", as_str(span.as_ref(buffer))),
+                Diagnostic::MalformedJCommand(span) => ("malformed", as_str(span.as_ref(buffer))),
+                _ => ("other", ""),
+            }
+        })
+        .map(|(kind, text)| (kind, text.to_owned()))
+        .collect();
+    assert_eq!(spans,
+               vec![("unknown", "frobnicate".to_owned()),
+                    ("malformed", "congruence 'imbi12i' as".to_owned())]);
+}
@@ -0,0 +1,196 @@
+//! Suggestion of new definitions for subformulas repeated across a section.
+//!
+//! When the same large subformula appears in many statements of a section,
+//! introducing a definition for it usually shortens the statements and their
+//! proofs.  Without a grammar the subformulas considered are the balanced
+//! parenthesized token sequences `( ... )` in the math strings of statements
+//! with a provable typecode such as `|-`, which covers the great majority of
+//! set.mm notation.  A repeated subformula is only reported if it is not
+//! contained in a larger one found in the same statements.
+//!
+//! `draft_definition` turns a suggestion into a draft syntax axiom and `df-`
+//! statement, as an `emit::Edit` inserting it before the first statement which
+//! uses the subformula.
+
+use coverage::heading_title;
+use emit;
+use emit::Edit;
+use extra_info::JConfig;
+use nameck::Nameset;
+use parser::Comparer;
+use parser::StatementAddress;
+use parser::StatementType;
+use parser::SymbolType;
+use parser::Token;
+use parser::as_str;
+use parser::copy_token;
+use segment_set::SegmentSet;
+use util::HashMap;
+use util::new_map;
+
+/// Thresholds for reporting a repeated subformula.
+#[derive(Copy,Clone,Debug)]
+pub struct ExtractionOptions {
+    /// Minimum number of tokens in the subformula, including the outer
+    /// parentheses.
+    pub min_length: usize,
+    /// Minimum number of distinct statements of a section which must contain
+    /// the subformula.
+    pub min_statements: usize,
+}
+
+impl Default for ExtractionOptions {
+    fn default() -> ExtractionOptions {
+        ExtractionOptions {
+            min_length: 8,
+            min_statements: 3,
+        }
+    }
+}
+
+/// A subformula repeated within one section.
+#[derive(Clone,Debug)]
+pub struct DefinitionSuggestion {
+    /// The heading comment of the section, or `None` for the statements before
+    /// the first heading.
+    pub section: Option<StatementAddress>,
+    /// The title of the section.
+    pub section_title: String,
+    /// The tokens of the subformula.
+    pub tokens: Vec<Token>,
+    /// The variables occurring in the subformula, in order of first
+    /// occurrence.
+    pub variables: Vec<Token>,
+    /// The statements containing the subformula, in database order.
+    pub statements: Vec<StatementAddress>,
+}
+
+fn contains_run(haystack: &[Token], needle: &[Token]) -> bool {
+    haystack.len() > needle.len() && haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+/// Adds the suggestions for one section to `out`, best first: ordered by the
+/// number of tokens which a definition would save.
+fn flush_section(out: &mut Vec<DefinitionSuggestion>,
+                 sset: &SegmentSet,
+                 nset: &Nameset,
+                 options: &ExtractionOptions,
+                 section: Option<StatementAddress>,
+                 title: &str,
+                 seen: &mut HashMap<Vec<Token>, Vec<StatementAddress>>) {
+    let mut found: Vec<(Vec<Token>, Vec<StatementAddress>)> = seen.drain()
+        .filter(|(_, stmts)| stmts.len() >= options.min_statements)
+        .collect();
+    let maximal: Vec<bool> = found.iter()
+        .map(|(toks, stmts)| {
+            !found.iter()
+                .any(|(other, ostmts)| ostmts == stmts && contains_run(other, toks))
+        })
+        .collect();
+    let mut keep = maximal.into_iter();
+    found.retain(|_| keep.next().unwrap());
+    found.sort_by(|a, b| {
+        (b.0.len() * b.1.len())
+            .cmp(&(a.0.len() * a.1.len()))
+            .then_with(|| sset.order.cmp(&a.1[0], &b.1[0]))
+            .then_with(|| a.0.len().cmp(&b.0.len()))
+    });
+    for (tokens, statements) in found {
+        let mut variables: Vec<Token> = Vec::new();
+        for tok in &tokens {
+            let is_var = nset.lookup_symbol(tok)
+                .is_some_and(|sym| sym.stype == SymbolType::Variable);
+            if is_var && !variables.contains(tok) {
+                variables.push(tok.clone());
+            }
+        }
+        out.push(DefinitionSuggestion {
+            section: section,
+            section_title: title.to_owned(),
+            tokens: tokens,
+            variables: variables,
+            statements: statements,
+        });
+    }
+}
+
+/// Finds subformulas repeated in many statements of the same section.
+pub fn find_repeated_subformulas(sset: &SegmentSet,
+                                 nset: &Nameset,
+                                 options: &ExtractionOptions)
+                                 -> Vec<DefinitionSuggestion> {
+    let config = JConfig::new(sset);
+    let mut out = Vec::new();
+    let mut section = None;
+    let mut title = String::new();
+    let mut seen: HashMap<Vec<Token>, Vec<StatementAddress>> = new_map();
+    for sref in sset.segments() {
+        for stmt in sref {
+            match stmt.statement_type() {
+                StatementType::Comment => {
+                    if let Some(heading) = heading_title(stmt) {
+                        flush_section(&mut out, sset, nset, options, section, &title, &mut seen);
+                        section = Some(stmt.address());
+                        title = heading;
+                    }
+                    continue;
+                }
+                StatementType::Axiom | StatementType::Provable | StatementType::Essential => {}
+                _ => continue,
+            }
+            if stmt.math_len() == 0 || !config.is_provable(stmt.math_at(0).slice) {
+                continue;
+            }
+            let math: Vec<&[u8]> = stmt.math_iter().map(|tok| tok.slice).collect();
+            let mut opens = Vec::new();
+            for (ix, &tok) in math.iter().enumerate() {
+                if tok == b"(" {
+                    opens.push(ix);
+                } else if tok == b")" {
+                    if let Some(start) = opens.pop() {
+                        if ix + 1 - start >= options.min_length {
+                            let run = math[start..ix + 1].iter().map(|&t| copy_token(t)).collect();
+                            let stmts = seen.entry(run).or_default();
+                            if stmts.last() != Some(&stmt.address()) {
+                                stmts.push(stmt.address());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    flush_section(&mut out, sset, nset, options, section, &title, &mut seen);
+    out
+}
+
+/// Drafts a definition for a suggested subformula.
+///
+/// The new notation is the constant `name`, followed by the variables of the
+/// subformula in parentheses if there are any.  The draft declares the
+/// constant, a syntax axiom `cNAME` with the given typecode, and `df-NAME`
+/// equating the notation with the subformula, using `<->` for the `wff`
+/// typecode and `=` otherwise as in set.mm.  Returns the name of the file to
+/// edit and an edit inserting the draft before the first statement which uses
+/// the subformula, or before its description comment.
+pub fn draft_definition(sset: &SegmentSet,
+                        suggestion: &DefinitionSuggestion,
+                        name: &str,
+                        typecode: &str)
+                        -> (String, Edit) {
+    let notation = if suggestion.variables.is_empty() {
+        name.to_owned()
+    } else {
+        let vars: Vec<&str> = suggestion.variables.iter().map(|v| as_str(v)).collect();
+        format!("( {} {} )", name, vars.join(" "))
+    };
+    let body: Vec<&str> = suggestion.tokens.iter().map(|t| as_str(t)).collect();
+    let equality = if typecode == "wff" { "<->" } else { "=" };
+
+    let lines = [format!("$( Draft definition for a subformula repeated in {} statements. $)",
+                         suggestion.statements.len()),
+                 format!("$c {} $.", name),
+                 format!("c{} $a {} {} $.", name, typecode, notation),
+                 format!("df-{} $a |- {} {} {} $.", name, notation, equality, body.join(" "))];
+    emit::insert_before(sset, sset.statement(suggestion.statements[0]), &lines)
+}
@@ -0,0 +1,52 @@
+use emit::emit_file;
+use extraction::ExtractionOptions;
+use extraction::draft_definition;
+use parser::as_str;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_suggest_definitions() {
+    let text = format!("{}$(
+=-=-=-
+  Section
+=-=-=-
+$)
+a1 $a |- ( ( ph -> ps ) -> ( ps -> ph ) ) $.
+a2 $a |- ( ph -> ( ( ph -> ps ) -> ( ps -> ph ) ) ) $.
+  $( Comment. $)
+  a3 $a |- ( ( ( ph -> ps ) -> ( ps -> ph ) ) -> ps ) $.
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let mut options = ExtractionOptions {
+        min_statements: 4,
+        ..ExtractionOptions::default()
+    };
+    assert!(db.suggest_definitions(&options).is_empty());
+    options.min_statements = 3;
+    let found = db.suggest_definitions(&options);
+    assert_eq!(found.len(), 1);
+    let body: Vec<&str> = found[0].tokens.iter().map(|t| as_str(t)).collect();
+    assert_eq!(body.join(" "), "( ( ph -> ps ) -> ( ps -> ph ) )");
+    assert_eq!(found[0].section_title, "Section");
+    assert_eq!(found[0].statements.len(), 3);
+
+    let sset = db.parse_result().clone();
+    let mut late = found[0].clone();
+    late.statements.remove(0);
+    late.statements.remove(0);
+    let (file, edit) = draft_definition(&sset, &late, "foo", "wff");
+    assert_eq!(file, "test.mm");
+    let mut out = Vec::new();
+    emit_file(&sset, "test.mm", &[edit], &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("
+  $( Draft definition for a subformula repeated in 1 statements. $)
+  $c foo $.
+  cfoo $a wff ( foo ph ps ) $.
+  df-foo $a |- ( foo ph ps ) <-> ( ( ph -> ps ) -> ( ps -> ph ) ) $.
+
+  $( Comment. $)
+"));
+}
@@ -0,0 +1,285 @@
+//! Formulas as sequences of symbols.
+//!
+//! The verifier works on math strings packed into byte buffers, with the
+//! last byte of each symbol marked by its high bit (see `verify`), and
+//! `ProofTreeArray::exprs` holds them as space-separated text without the
+//! typecode.  `Formula` is the unpacked form for use outside of the
+//! verifier: a typecode and a list of symbols, which can be printed, typeset
+//! or parsed into a `SyntaxTree`.
+
+#[cfg(feature = "grammar")]
+use grammar::SyntaxParser;
+#[cfg(feature = "grammar")]
+use grammar::SyntaxTree;
+#[cfg(feature = "grammar")]
+use grammar::SyntaxVisitor;
+use parser::StatementRef;
+use parser::Token;
+use parser::TokenPtr;
+use parser::as_str;
+use parser::copy_token;
+use std::fmt;
+use typesetting::TypesettingData;
+use typesetting::TypesettingMode;
+#[cfg(feature = "grammar")]
+use util::HashMap;
+
+/// A math string with its typecode, such as `|- ( ph -> ph )`.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct Formula {
+    /// The typecode, such as `|-` or `wff`.
+    pub typecode: Token,
+    /// The symbols following the typecode.
+    pub symbols: Vec<Token>,
+}
+
+impl Formula {
+    /// Returns the math string of a statement, which must have a typecode.
+    pub fn from_statement(stmt: StatementRef) -> Formula {
+        let mut math = stmt.math_iter().map(|tok| copy_token(tok.slice));
+        Formula {
+            typecode: math.next().expect("statement has a typecode"),
+            symbols: math.collect(),
+        }
+    }
+
+    /// Builds a formula from a typecode and symbols separated by spaces, as
+    /// in `ProofTreeArray::exprs`.
+    pub fn from_expr(typecode: TokenPtr, expr: &[u8]) -> Formula {
+        Formula {
+            typecode: copy_token(typecode),
+            symbols: expr.split(|&ch| ch == b' ')
+                .filter(|tok| !tok.is_empty())
+                .map(copy_token)
+                .collect(),
+        }
+    }
+
+    /// Returns the tokens of the formula, starting with the typecode.
+    pub fn to_tokens(&self) -> Vec<Token> {
+        let mut out = Vec::with_capacity(self.symbols.len() + 1);
+        out.push(self.typecode.clone());
+        out.extend(self.symbols.iter().cloned());
+        out
+    }
+
+    /// Renders the formula, including its typecode.  In ASCII mode this is
+    /// the same as `to_string`: the tokens separated by single spaces, which
+    /// can be written back into a statement.
+    pub fn to_string_with(&self, mode: &TypesettingMode) -> String {
+        mode.render(self.to_tokens().iter().map(|tok| &tok[..]))
+    }
+
+    /// Renders the formula, including its typecode, as HTML using the
+    /// `htmldef` strings.
+    pub fn to_html(&self, data: &TypesettingData) -> String {
+        self.to_string_with(&TypesettingMode::Html(data))
+    }
+
+    /// Renders the formula, including its typecode, as HTML using the
+    /// `althtmldef` strings.
+    pub fn to_alt_html(&self, data: &TypesettingData) -> String {
+        self.to_string_with(&TypesettingMode::AltHtml(data))
+    }
+
+    /// Renders the formula, including its typecode, as LaTeX using the
+    /// `latexdef` strings.
+    pub fn to_latex(&self, data: &TypesettingData) -> String {
+        self.to_string_with(&TypesettingMode::Latex(data))
+    }
+}
+
+#[cfg(feature = "grammar")]
+impl Formula {
+    /// Parses the formula; see `SyntaxParser::parse_math`.
+    pub fn parse(&self,
+                 parser: &SyntaxParser,
+                 vars: &HashMap<Token, Token>)
+                 -> Option<(Token, SyntaxTree)> {
+        let mut math: Vec<TokenPtr> = vec![&self.typecode];
+        math.extend(self.symbols.iter().map(|sym| &sym[..]));
+        parser.parse_math(vars, &math)
+    }
+
+    /// Returns every subformula in depth-first order, starting with the
+    /// formula itself, along with its path in the parse tree as for
+    /// `SyntaxTree::get_by_path`.  Subformulas get the typecode of their syntax
+    /// axiom, or of their variable.  Returns `None` if the formula does not
+    /// parse.
+    pub fn sub_formulas(&self,
+                        parser: &SyntaxParser,
+                        vars: &HashMap<Token, Token>)
+                        -> Option<Vec<(Vec<usize>, Formula)>> {
+        let (_, tree) = self.parse(parser, vars)?;
+        let mut subs = SubFormulas {
+            parser: parser,
+            vars: vars,
+            out: Vec::new(),
+        };
+        tree.visit(&mut subs);
+        if let Some(&mut (ref path, ref mut root)) = subs.out.first_mut() {
+            if path.is_empty() {
+                root.typecode = self.typecode.clone();
+            }
+        }
+        Some(subs.out)
+    }
+
+    /// Rewrites the formula with rules `lhs => rhs` until none applies.
+    ///
+    /// Each step finds a subformula which the left-hand side of a rule
+    /// matches, trying the rules in order at each subformula, and replaces it
+    /// with the right-hand side under the substitution found.  The variables
+    /// of the rules are those in `vars`; the left-hand side is matched as a
+    /// pattern, without checking that the typecodes of the variables agree
+    /// with the subformulas they match.  Returns `None` if the formula or a
+    /// rule does not parse.
+    pub fn rewrite(&self,
+                   parser: &SyntaxParser,
+                   vars: &HashMap<Token, Token>,
+                   rules: &[(Formula, Formula)],
+                   strategy: &RewriteStrategy)
+                   -> Option<Rewritten> {
+        let (_, mut tree) = self.parse(parser, vars)?;
+        let mut trees = Vec::new();
+        for (lhs, rhs) in rules {
+            trees.push((lhs.parse(parser, vars)?.1, rhs.parse(parser, vars)?.1));
+        }
+        let mut steps = Vec::new();
+        while steps.len() < strategy.max_steps {
+            match find_redex(&tree, &trees, strategy.innermost, &mut Vec::new()) {
+                Some((rule, path, new)) => {
+                    replace_at(&mut tree, &path, new);
+                    steps.push(RewriteStep {
+                        rule: rule,
+                        path: path,
+                    });
+                }
+                None => break,
+            }
+        }
+        let mut formula = parser.to_formula(&tree, vars)?;
+        formula.typecode = self.typecode.clone();
+        Some(Rewritten {
+            formula: formula,
+            steps: steps,
+            normal: find_redex(&tree, &trees, false, &mut Vec::new()).is_none(),
+        })
+    }
+}
+
+#[cfg(feature = "grammar")]
+/// How `Formula::rewrite` chooses where to apply rules.
+#[derive(Copy,Clone,Debug)]
+pub struct RewriteStrategy {
+    /// Rewrite the innermost (and then leftmost) matching subformula first,
+    /// rather than the outermost.
+    pub innermost: bool,
+    /// The maximum number of rewrites, since rules such as commutativity can
+    /// be applied forever.
+    pub max_steps: usize,
+}
+
+#[cfg(feature = "grammar")]
+impl Default for RewriteStrategy {
+    fn default() -> RewriteStrategy {
+        RewriteStrategy {
+            innermost: false,
+            max_steps: 1000,
+        }
+    }
+}
+
+#[cfg(feature = "grammar")]
+/// An application of a rule by `Formula::rewrite`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct RewriteStep {
+    /// The index of the rule.
+    pub rule: usize,
+    /// The path of the rewritten subformula, as for `SyntaxTree::get_by_path`.
+    pub path: Vec<usize>,
+}
+
+#[cfg(feature = "grammar")]
+/// The result of `Formula::rewrite`.
+#[derive(Clone,Debug)]
+pub struct Rewritten {
+    /// The formula after the last rewrite.
+    pub formula: Formula,
+    /// The rewrites, in the order they were applied.
+    pub steps: Vec<RewriteStep>,
+    /// True if rewriting stopped because no rule applied, false if it was
+    /// cut off by `RewriteStrategy::max_steps`.
+    pub normal: bool,
+}
+
+#[cfg(feature = "grammar")]
+/// Finds the first subtree which the left-hand side of some rule matches,
+/// returning the rule, the path and the instance of its right-hand side.
+fn find_redex(tree: &SyntaxTree,
+              rules: &[(SyntaxTree, SyntaxTree)],
+              innermost: bool,
+              path: &mut Vec<usize>)
+              -> Option<(usize, Vec<usize>, SyntaxTree)> {
+    let here = |path: &Vec<usize>| {
+        rules.iter().enumerate().find_map(|(ix, (lhs, rhs))| {
+            lhs.match_onto(tree).map(|subst| (ix, path.clone(), rhs.substitute(&subst)))
+        })
+    };
+    if !innermost {
+        if let Some(found) = here(path) {
+            return Some(found);
+        }
+    }
+    if let SyntaxTree::App(_, ref args) = *tree {
+        for (ix, arg) in args.iter().enumerate() {
+            path.push(ix);
+            let found = find_redex(arg, rules, innermost, path);
+            path.pop();
+            if found.is_some() {
+                return found;
+            }
+        }
+    }
+    if innermost { here(path) } else { None }
+}
+
+#[cfg(feature = "grammar")]
+/// Replaces the subtree at a path.
+fn replace_at(tree: &mut SyntaxTree, path: &[usize], new: SyntaxTree) {
+    match (path.split_first(), tree) {
+        (None, tree) => *tree = new,
+        (Some((&ix, rest)), &mut SyntaxTree::App(_, ref mut args)) => {
+            replace_at(&mut args[ix], rest, new)
+        }
+        (Some(_), &mut SyntaxTree::Var(_)) => panic!("path leads into a variable"),
+    }
+}
+
+#[cfg(feature = "grammar")]
+/// Collects the subformulas of a tree with their paths.
+struct SubFormulas<'a> {
+    parser: &'a SyntaxParser,
+    vars: &'a HashMap<Token, Token>,
+    out: Vec<(Vec<usize>, Formula)>,
+}
+
+#[cfg(feature = "grammar")]
+impl<'a> SyntaxVisitor for SubFormulas<'a> {
+    fn pre(&mut self, path: &[usize], tree: &SyntaxTree) -> bool {
+        if let Some(formula) = self.parser.to_formula(tree, self.vars) {
+            self.out.push((path.to_vec(), formula));
+        }
+        true
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(as_str(&self.typecode))?;
+        for sym in &self.symbols {
+            write!(f, " {}", as_str(sym))?;
+        }
+        Ok(())
+    }
+}
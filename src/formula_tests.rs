@@ -0,0 +1,75 @@
+use formula::RewriteStep;
+use formula::RewriteStrategy;
+use grammar::SyntaxTree;
+use grammar::global_variables;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_sub_formulas() {
+    let mut db = mkdb(DB.as_bytes());
+    let formula = db.parse_formula_str("( ph -> ( ps -> ph ) )", "|-").unwrap();
+    let parser = db.syntax_parser().clone();
+    let vars = global_variables(db.parse_result());
+    let subs: Vec<(Vec<usize>, String)> = formula.sub_formulas(&parser, &vars)
+        .unwrap()
+        .into_iter()
+        .map(|(path, sub)| (path, sub.to_string()))
+        .collect();
+    assert_eq!(subs,
+               vec![(vec![], "|- ( ph -> ( ps -> ph ) )".to_owned()),
+                    (vec![0], "wff ph".to_owned()),
+                    (vec![1], "wff ( ps -> ph )".to_owned()),
+                    (vec![1, 0], "wff ps".to_owned()),
+                    (vec![1, 1], "wff ph".to_owned())]);
+    let (_, tree) = formula.parse(&parser, &vars).unwrap();
+    assert_eq!(tree.get_by_path(&[1, 0]),
+               Some(&SyntaxTree::Var(b"ps".to_vec().into_boxed_slice())));
+    assert_eq!(tree.get_by_path(&[0, 0]), None);
+}
+
+#[test]
+fn test_rewrite() {
+    let mut db = mkdb(DB.as_bytes());
+    let parser = db.syntax_parser().clone();
+    let vars = global_variables(db.parse_result());
+    let formula = db.parse_formula_str("( ( ph -> ps ) -> ( ps -> ph ) )", "|-").unwrap();
+    // swap the sides of every implication
+    let rules = vec![(db.parse_formula_str("( ph -> ps )", "wff").unwrap(),
+                      db.parse_formula_str("( ps -> ph )", "wff").unwrap())];
+    let mut strategy = RewriteStrategy {
+        max_steps: 1,
+        ..RewriteStrategy::default()
+    };
+    let once = formula.rewrite(&parser, &vars, &rules, &strategy).unwrap();
+    assert_eq!(once.formula.to_string(), "|- ( ( ps -> ph ) -> ( ph -> ps ) )");
+    assert_eq!(once.steps, vec![RewriteStep { rule: 0, path: vec![] }]);
+    assert!(!once.normal);
+    strategy.innermost = true;
+    let inner = formula.rewrite(&parser, &vars, &rules, &strategy).unwrap();
+    assert_eq!(inner.formula.to_string(), "|- ( ( ps -> ph ) -> ( ps -> ph ) )");
+    assert_eq!(inner.steps, vec![RewriteStep { rule: 0, path: vec![0] }]);
+    // a rule which removes implications terminates
+    let rules = vec![(db.parse_formula_str("( ph -> ps )", "wff").unwrap(),
+                      db.parse_formula_str("ps", "wff").unwrap())];
+    let done = formula.rewrite(&parser, &vars, &rules, &RewriteStrategy::default()).unwrap();
+    assert_eq!(done.formula.to_string(), "|- ph");
+    assert_eq!(done.steps.len(), 2);
+    assert!(done.normal);
+}
+
+#[test]
+fn test_unfold() {
+    let text = DB.replace("$c |- wff ( -> ) $.", "$c |- wff ( -> ) -. /\\ <-> $.") +
+               "wn $a wff -. ph $.
+wa $a wff ( ph /\\ ps ) $.
+wb $a wff ( ph <-> ps ) $.
+df-an $a |- ( ( ph /\\ ps ) <-> -. ( ph -> -. ps ) ) $.
+";
+    let mut db = mkdb(text.as_bytes());
+    let formula = db.parse_formula_str("( ( ph /\\ ps ) -> ( ps /\\ ph ) )", "|-").unwrap();
+    assert_eq!(db.unfold(&formula, &["df-an"]).to_string(),
+               "|- ( -. ( ph -> -. ps ) -> -. ( ps -> -. ph ) )");
+    // syntax axioms and unknown labels are not definitions
+    assert_eq!(db.unfold(&formula, &["wa", "df-or"]), formula);
+}
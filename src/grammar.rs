@@ -0,0 +1,938 @@
+//! Analysis of the grammar implied by syntax axioms.
+//!
+//! A syntax axiom is an `$a` statement whose typecode is not provable, which
+//! is `|-` unless the `$j` comments declare others (see `extra_info::JConfig`);
+//! read as a production, `wi $a wff ( ph -> ps ) $.` says that a `wff` may be formed
+//! from `(`, a `wff`, `->`, a `wff` and `)`.  A database is expected to make
+//! this grammar unambiguous, so that every expression has exactly one syntax
+//! proof.
+//!
+//! `SyntaxParser` parses formulas with this grammar into `SyntaxTree`s.  It
+//! finds every parse, memoizing partial results, which is simple and fast
+//! enough for the grammars of actual databases but not for arbitrary ones.
+//!
+//! Deciding ambiguity in general is much harder than parsing.  The grammar
+//! pass detects the most direct case, two syntax axioms with the same
+//! production up to renaming of variables; any string matching the
+//! production then has two distinct syntax proofs, and the later axiom's math
+//! string serves as the witness.
+//!
+//! Syntax axioms whose production is a single variable, such as `cv $a class
+//! x $.` in set.mm, are coercions: they convert a formula of one typecode into
+//! another.  `SyntaxParser::coercions` lists them, and `FormulaBuilder`
+//! inserts them where a subtree of the wrong typecode is used.
+//!
+//! For structured formula entry, `SyntaxParser::next_tokens` reads the
+//! beginning of a formula with an Earley recognizer and lists the tokens the
+//! grammar allows next, like the expected-token set of an LR parser.
+
+use diag::Diagnostic;
+use extra_info::JConfig;
+use formula::Formula;
+use nameck::Atom;
+use nameck::Nameset;
+use parser::StatementAddress;
+use parser::StatementType;
+use parser::Token;
+use parser::TokenPtr;
+use parser::as_str;
+use parser::copy_token;
+use scopeck::Frame;
+use scopeck::Hyp;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+#[cfg(feature = "stats")]
+use std::time::Instant;
+use util::HashMap;
+use util::HashSet;
+use util::new_map;
+use util::new_set;
+
+/// A symbol on the right-hand side of a production.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+enum ProdSymbol {
+    /// A constant which must appear literally.
+    Const(Atom),
+    /// A variable, identified only by its typecode.
+    Var(Atom),
+}
+
+/// Analysis pass result for the syntax axiom grammar.
+#[derive(Default,Clone,Debug)]
+pub struct GrammarResult {
+    diagnostics: Vec<(StatementAddress, Diagnostic)>,
+}
+
+impl GrammarResult {
+    /// Checks every syntax axiom in the database for a duplicate production.
+    pub fn new(sset: &SegmentSet, nset: &Nameset, scope: &ScopeResult) -> GrammarResult {
+        let mut result = GrammarResult { diagnostics: Vec::new() };
+        let config = JConfig::new(sset);
+        let mut productions: HashMap<Vec<ProdSymbol>, StatementAddress> = new_map();
+        for sref in sset.segments() {
+            #[cfg(feature = "stats")]
+            let mut times = Vec::new();
+            for stmt in sref {
+                if stmt.statement_type() != StatementType::Axiom || stmt.math_len() == 0 ||
+                   config.is_provable(stmt.math_at(0).slice) {
+                    continue;
+                }
+                #[cfg(feature = "stats")]
+                let start = Instant::now();
+                let frame = match scope.get(stmt.label()) {
+                    Some(frame) => frame,
+                    None => continue,
+                };
+                let mut var_types = new_map();
+                for hyp in frame.hypotheses.iter() {
+                    if let Hyp::Floating(_, index, typecode) = *hyp {
+                        var_types.insert(frame.var_list[index], typecode);
+                    }
+                }
+                let mut production = Vec::new();
+                let mut valid = true;
+                for tok in stmt.math_iter() {
+                    match nset.lookup_symbol(tok.slice) {
+                        Some(sym) => {
+                            production.push(match var_types.get(&sym.atom) {
+                                Some(&typecode) => ProdSymbol::Var(typecode),
+                                None => ProdSymbol::Const(sym.atom),
+                            })
+                        }
+                        None => valid = false,
+                    }
+                }
+                if !valid {
+                    continue;
+                }
+                if let Some(&prev) = productions.get(&production) {
+                    let witness = stmt.math_iter()
+                        .map(|tok| tok.slice)
+                        .collect::<Vec<_>>()
+                        .join(&b' ');
+                    result.diagnostics.push((stmt.address(),
+                                             Diagnostic::AmbiguousSyntax(prev,
+                                                                         copy_token(&witness))));
+                } else {
+                    productions.insert(production, stmt.address());
+                }
+                #[cfg(feature = "stats")]
+                times.push((stmt.index(), start.elapsed()));
+            }
+            #[cfg(feature = "stats")]
+            if let Some(ref stats) = sset.stats {
+                stats.record_segment("grammar", sref.id, times);
+            }
+        }
+        result
+    }
+
+    /// Reports syntax axioms which duplicate the production of an earlier one.
+    pub fn diagnostics(&self) -> Vec<(StatementAddress, Diagnostic)> {
+        self.diagnostics.clone()
+    }
+}
+
+/// A parse tree of a formula, corresponding to its syntax proof.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum SyntaxTree {
+    /// A variable.
+    Var(Token),
+    /// A syntax axiom applied to the trees substituted for its variables, in
+    /// the order of its `$f` hypotheses.
+    App(StatementAddress, Vec<SyntaxTree>),
+}
+
+impl SyntaxTree {
+    /// Writes the tree as an s-expression of labels and variable names, such
+    /// as `(wi ph (wi ps ph))`.
+    pub fn to_sexpr(&self, sset: &SegmentSet) -> String {
+        match *self {
+            SyntaxTree::Var(ref name) => as_str(name).to_owned(),
+            SyntaxTree::App(addr, ref args) => {
+                let mut out = format!("({}", as_str(sset.statement(addr).label()));
+                for arg in args {
+                    out.push(' ');
+                    out.push_str(&arg.to_sexpr(sset));
+                }
+                out + ")"
+            }
+        }
+    }
+
+    /// Matches the tree, as a pattern whose variables may be substituted,
+    /// against another tree.  Returns the substitution making the two equal,
+    /// mapping each variable of the pattern to a subtree of the target, or
+    /// `None` if there is none.  Variables of the target are treated as
+    /// constants.
+    pub fn match_onto(&self, target: &SyntaxTree) -> Option<HashMap<Token, SyntaxTree>> {
+        let mut subst = new_map();
+        if self.match_with(target, &mut subst) {
+            Some(subst)
+        } else {
+            None
+        }
+    }
+
+    /// Like `match_onto`, but extends a substitution for some of the
+    /// variables of the pattern, for matching several patterns which share
+    /// variables.  Returns false if there is no extension making the trees
+    /// equal, in which case `subst` may have been partially extended.
+    pub fn match_with(&self, target: &SyntaxTree, subst: &mut HashMap<Token, SyntaxTree>) -> bool {
+        match (self, target) {
+            (SyntaxTree::Var(var), _) => {
+                if let Some(prev) = subst.get(var) {
+                    return prev == target;
+                }
+                subst.insert(var.clone(), target.clone());
+                true
+            }
+            (SyntaxTree::App(a1, args1), SyntaxTree::App(a2, args2)) => {
+                a1 == a2 && args1.len() == args2.len() &&
+                args1.iter().zip(args2).all(|(x, y)| x.match_with(y, subst))
+            }
+            _ => false,
+        }
+    }
+
+    /// Replaces the variables of the tree which a substitution maps, as
+    /// returned by `match_onto`.
+    pub fn substitute(&self, subst: &HashMap<Token, SyntaxTree>) -> SyntaxTree {
+        match *self {
+            SyntaxTree::Var(ref var) => subst.get(var).cloned().unwrap_or_else(|| self.clone()),
+            SyntaxTree::App(addr, ref args) => {
+                SyntaxTree::App(addr, args.iter().map(|arg| arg.substitute(subst)).collect())
+            }
+        }
+    }
+
+    /// Returns the subtree reached by following a path of argument indices
+    /// from the root, as passed to `SyntaxVisitor`.
+    pub fn get_by_path(&self, path: &[usize]) -> Option<&SyntaxTree> {
+        match (path.split_first(), self) {
+            (None, _) => Some(self),
+            (Some((&ix, rest)), SyntaxTree::App(_, args)) => args.get(ix)?.get_by_path(rest),
+            (Some(_), SyntaxTree::Var(_)) => None,
+        }
+    }
+
+    /// Walks the tree depth-first, calling the hooks of a visitor on each
+    /// node.
+    pub fn visit<V: SyntaxVisitor>(&self, visitor: &mut V) {
+        self.visit_at(&mut Vec::new(), visitor);
+    }
+
+    fn visit_at<V: SyntaxVisitor>(&self, path: &mut Vec<usize>, visitor: &mut V) {
+        if visitor.pre(path, self) {
+            if let SyntaxTree::App(_, ref args) = *self {
+                for (ix, arg) in args.iter().enumerate() {
+                    path.push(ix);
+                    arg.visit_at(path, visitor);
+                    path.pop();
+                }
+            }
+        }
+        visitor.post(path, self);
+    }
+}
+
+/// Hooks called by `SyntaxTree::visit` on each node of a tree.  A node is
+/// identified by its path: the indices of the arguments leading to it from
+/// the root, which is the empty path.
+pub trait SyntaxVisitor {
+    /// Called before the subtrees of a node; returns false to skip them.
+    fn pre(&mut self, _path: &[usize], _tree: &SyntaxTree) -> bool {
+        true
+    }
+
+    /// Called after the subtrees of a node, or after `pre` if they were
+    /// skipped.
+    fn post(&mut self, _path: &[usize], _tree: &SyntaxTree) {}
+}
+
+/// A symbol on the right-hand side of a production for parsing.
+enum ParseSymbol {
+    Const(Token),
+    /// A variable, with its typecode and the index of its `$f` hypothesis
+    /// among those of the syntax axiom.
+    Var(Token, usize),
+}
+
+struct Production {
+    address: StatementAddress,
+    typecode: Token,
+    arity: usize,
+    rhs: Vec<ParseSymbol>,
+}
+
+/// The tokens which may follow the beginning of a formula; see
+/// `SyntaxParser::next_tokens`.
+#[derive(Clone,Debug,Default,PartialEq,Eq)]
+pub struct NextTokens {
+    /// The constants which may come next, in byte order.
+    pub constants: Vec<Token>,
+    /// The variables which may come next, in byte order.
+    pub variables: Vec<Token>,
+    /// True if the tokens read so far already form a complete formula.
+    pub complete: bool,
+}
+
+/// An item of the Earley recognizer: a production, the number of symbols of
+/// its right-hand side read so far, and the position where it started.  The
+/// production `None` stands for the whole formula.
+type EarleyItem = (Option<usize>, usize, usize);
+
+/// A syntax axiom converting formulas of one typecode into another.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Coercion {
+    /// The syntax axiom.
+    pub address: StatementAddress,
+    /// The typecode of its only variable.
+    pub from: Token,
+    /// The typecode it produces.
+    pub to: Token,
+}
+
+/// A parser for formulas, using the syntax axioms of a database as a
+/// context-free grammar.
+#[derive(Default)]
+pub struct SyntaxParser {
+    prods: Vec<Production>,
+    by_type: HashMap<Token, Vec<usize>>,
+    typecodes: Vec<Token>,
+    config: JConfig,
+}
+
+/// The deepest nesting of subformulas the parser accepts; a formula nested
+/// deeper is reported as too complex rather than overflowing the stack.
+pub const MAX_PARSE_DEPTH: usize = 256;
+
+/// The most symbols, after the typecode, of a formula the parser accepts;
+/// the parses it remembers grow with the length of the formula.
+pub const MAX_PARSE_LEN: usize = 10000;
+
+/// The parses of a typecode starting at one position, each with the position
+/// after its end.
+type Parses = Vec<(SyntaxTree, usize)>;
+
+/// The state of parsing one formula.
+struct ParseState<'a> {
+    parser: &'a SyntaxParser,
+    tokens: &'a [TokenPtr<'a>],
+    vars: &'a HashMap<Token, Token>,
+    /// All parses of a typecode starting at a position, with their ends;
+    /// `None` while the parse is in progress, to cut off cycles.
+    memo: HashMap<(Token, usize), Option<Parses>>,
+    /// The number of subformulas being parsed, each inside the previous one.
+    depth: usize,
+    /// Set when a parse was cut off at `MAX_PARSE_DEPTH`.
+    too_deep: bool,
+}
+
+impl<'a> ParseState<'a> {
+    fn parse_at(&mut self, typecode: &[u8], pos: usize) -> Parses {
+        let key = (copy_token(typecode), pos);
+        if let Some(done) = self.memo.get(&key) {
+            return done.clone().unwrap_or_default();
+        }
+        if self.depth == MAX_PARSE_DEPTH {
+            self.too_deep = true;
+            return Vec::new();
+        }
+        self.depth += 1;
+        self.memo.insert(key.clone(), None);
+        let mut out = Vec::new();
+        if let Some(&tok) = self.tokens.get(pos) {
+            if self.vars.get(tok).is_some_and(|tc| &tc[..] == typecode) {
+                out.push((SyntaxTree::Var(copy_token(tok)), pos + 1));
+            }
+        }
+        let parser = self.parser;
+        for &ix in parser.by_type.get(typecode).map_or(&[][..], |v| &v[..]) {
+            let mut args = vec![None; parser.prods[ix].arity];
+            self.match_rhs(ix, 0, pos, &mut args, &mut out);
+        }
+        self.depth -= 1;
+        self.memo.insert(key, Some(out.clone()));
+        out
+    }
+
+    fn match_rhs(&mut self,
+                 ix: usize,
+                 sym: usize,
+                 pos: usize,
+                 args: &mut Vec<Option<SyntaxTree>>,
+                 out: &mut Parses) {
+        let parser = self.parser;
+        match parser.prods[ix].rhs.get(sym) {
+            None => {
+                if args.iter().all(|arg| arg.is_some()) {
+                    let args = args.iter().map(|arg| arg.clone().unwrap()).collect();
+                    out.push((SyntaxTree::App(parser.prods[ix].address, args), pos));
+                }
+            }
+            Some(ParseSymbol::Const(tok)) => {
+                if self.tokens.get(pos).is_some_and(|&t| t == &tok[..]) {
+                    self.match_rhs(ix, sym + 1, pos + 1, args, out);
+                }
+            }
+            Some(ParseSymbol::Var(typecode, slot)) => {
+                for (tree, end) in self.parse_at(typecode, pos) {
+                    // a variable used twice must match the same subformula
+                    let saved = args[*slot].clone();
+                    if saved.as_ref().is_none_or(|prev| *prev == tree) {
+                        args[*slot] = Some(tree);
+                        self.match_rhs(ix, sym + 1, end, args, out);
+                    }
+                    args[*slot] = saved;
+                }
+            }
+        }
+    }
+}
+
+impl SyntaxParser {
+    /// Collects the syntax axioms of a database: the `$a` statements whose
+    /// typecode is not provable.
+    pub fn new(sset: &SegmentSet, nset: &Nameset, scope: &ScopeResult) -> SyntaxParser {
+        let config = JConfig::new(sset);
+        let mut parser = SyntaxParser {
+            prods: Vec::new(),
+            by_type: new_map(),
+            typecodes: config.syntax.clone(),
+            config: config,
+        };
+        for sref in sset.segments() {
+            for stmt in sref {
+                let stype = stmt.statement_type();
+                if stype != StatementType::Floating && stype != StatementType::Axiom ||
+                   stmt.math_len() == 0 || parser.config.is_provable(stmt.math_at(0).slice) {
+                    continue;
+                }
+                let typecode = copy_token(stmt.math_at(0).slice);
+                if !parser.typecodes.contains(&typecode) {
+                    parser.typecodes.push(typecode.clone());
+                }
+                let frame = match scope.get(stmt.label()) {
+                    Some(frame) if stype == StatementType::Axiom => frame,
+                    _ => continue,
+                };
+                let mut slots = new_map();
+                let mut arity = 0;
+                for hyp in frame.hypotheses.iter() {
+                    if let Hyp::Floating(_, var, typecode) = *hyp {
+                        slots.insert(nset.atom_name(frame.var_list[var]), (typecode, arity));
+                        arity += 1;
+                    }
+                }
+                let rhs = stmt.math_iter()
+                    .skip(1)
+                    .map(|tok| match slots.get(tok.slice) {
+                        Some(&(typecode, slot)) => {
+                            ParseSymbol::Var(copy_token(nset.atom_name(typecode)), slot)
+                        }
+                        None => ParseSymbol::Const(copy_token(tok.slice)),
+                    })
+                    .collect();
+                parser.by_type
+                    .entry(typecode.clone())
+                    .or_default()
+                    .push(parser.prods.len());
+                parser.prods.push(Production {
+                    address: stmt.address(),
+                    typecode: typecode,
+                    arity: arity,
+                    rhs: rhs,
+                });
+            }
+        }
+        parser
+    }
+
+    /// Returns the typecodes declared by `$j syntax` commands, then those of
+    /// other variables and syntax axioms, in order of first appearance.
+    pub fn typecodes(&self) -> &[Token] {
+        &self.typecodes
+    }
+
+    /// Returns the `$j` configuration the parser was built with.
+    pub fn config(&self) -> &JConfig {
+        &self.config
+    }
+
+    /// Returns true if a typecode is provable, rather than a syntax typecode.
+    pub fn is_provable(&self, typecode: &[u8]) -> bool {
+        self.config.is_provable(typecode)
+    }
+
+    /// Returns the syntax typecodes a formula of the given typecode may be
+    /// parsed as: the typecode itself if it is a syntax typecode, the one a
+    /// provable typecode is declared with, or else every typecode which
+    /// syntax axioms produce.
+    fn parse_typecodes<'a>(&'a self, typecode: &'a [u8]) -> Vec<&'a [u8]> {
+        if self.typecodes.iter().any(|tc| &tc[..] == typecode) {
+            return vec![typecode];
+        }
+        if let Some(syntax) = self.config.syntax_typecode(typecode) {
+            return vec![syntax];
+        }
+        self.typecodes
+            .iter()
+            .filter(|tc| self.has_syntax_axioms(tc))
+            .map(|tc| &tc[..])
+            .collect()
+    }
+
+    /// Returns true if some syntax axiom produces formulas of a typecode;
+    /// the variables of other typecodes, such as `setvar` in set.mm, can
+    /// only be substituted by variables.
+    pub fn has_syntax_axioms(&self, typecode: &[u8]) -> bool {
+        self.by_type.contains_key(typecode)
+    }
+
+    /// Returns true if a statement is a syntax axiom.
+    pub fn is_syntax_axiom(&self, address: StatementAddress) -> bool {
+        self.prods.iter().any(|prod| prod.address == address)
+    }
+
+    /// Returns the formula a tree is the parse of, with the typecode of its
+    /// syntax axiom, or for a variable the typecode in `vars`.  Returns `None`
+    /// if the tree uses a statement which is not a syntax axiom or a variable
+    /// which is not in `vars`.
+    pub fn to_formula(&self, tree: &SyntaxTree, vars: &HashMap<Token, Token>) -> Option<Formula> {
+        let typecode = self.typecode_of(tree, vars)?;
+        let mut symbols = Vec::new();
+        self.flatten(tree, &mut symbols)?;
+        Some(Formula {
+            typecode: typecode,
+            symbols: symbols,
+        })
+    }
+
+    /// Returns the typecode of the formula a tree is the parse of, as for
+    /// `to_formula`.
+    pub fn typecode_of(&self, tree: &SyntaxTree, vars: &HashMap<Token, Token>) -> Option<Token> {
+        match *tree {
+            SyntaxTree::Var(ref name) => vars.get(name).cloned(),
+            SyntaxTree::App(addr, _) => self.production(addr).map(|prod| prod.typecode.clone()),
+        }
+    }
+
+    /// Lists the coercions, in database order.
+    pub fn coercions(&self) -> Vec<Coercion> {
+        self.prods
+            .iter()
+            .filter_map(|prod| match prod.rhs[..] {
+                [ParseSymbol::Var(ref from, _)] => {
+                    Some(Coercion {
+                        address: prod.address,
+                        from: from.clone(),
+                        to: prod.typecode.clone(),
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the shortest chain of coercions converting formulas of one
+    /// typecode into another, in the order they apply; the chain is empty if
+    /// the typecodes are the same.  Returns `None` if there is no such chain.
+    pub fn coercion_path(&self, from: &[u8], to: &[u8]) -> Option<Vec<StatementAddress>> {
+        let coercions = self.coercions();
+        // breadth-first search, remembering the coercion reaching each typecode
+        let mut reached: HashMap<&[u8], Option<&Coercion>> = new_map();
+        reached.insert(from, None);
+        let mut queue = vec![from];
+        let mut next = 0;
+        while next < queue.len() && !reached.contains_key(to) {
+            let typecode = queue[next];
+            next += 1;
+            for coercion in coercions.iter().filter(|c| &c.from[..] == typecode) {
+                if !reached.contains_key(&coercion.to[..]) {
+                    reached.insert(&coercion.to, Some(coercion));
+                    queue.push(&coercion.to);
+                }
+            }
+        }
+        let mut path = Vec::new();
+        let mut typecode = to;
+        while let Some(coercion) = *reached.get(typecode)? {
+            path.push(coercion.address);
+            typecode = &coercion.from;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Returns true if formulas of one typecode can be converted into another
+    /// by coercions, including when the typecodes are the same.
+    pub fn can_coerce(&self, from: &[u8], to: &[u8]) -> bool {
+        self.coercion_path(from, to).is_some()
+    }
+
+    fn production(&self, address: StatementAddress) -> Option<&Production> {
+        self.prods.iter().find(|prod| prod.address == address)
+    }
+
+    fn flatten(&self, tree: &SyntaxTree, out: &mut Vec<Token>) -> Option<()> {
+        match *tree {
+            SyntaxTree::Var(ref name) => out.push(name.clone()),
+            SyntaxTree::App(addr, ref args) => {
+                for sym in &self.production(addr)?.rhs {
+                    match *sym {
+                        ParseSymbol::Const(ref tok) => out.push(tok.clone()),
+                        ParseSymbol::Var(_, slot) => self.flatten(args.get(slot)?, out)?,
+                    }
+                }
+            }
+        }
+        Some(())
+    }
+
+    fn symbol_at<'a>(&'a self,
+                     root: &'a ParseSymbol,
+                     prod: Option<usize>,
+                     dot: usize)
+                     -> Option<&'a ParseSymbol> {
+        match prod {
+            Some(ix) => self.prods[ix].rhs.get(dot),
+            None if dot == 0 => Some(root),
+            None => None,
+        }
+    }
+
+    /// Adds the items predicted and completed from those of the last set,
+    /// until there are no more.
+    fn earley_close(&self,
+                    root: &ParseSymbol,
+                    sets: &mut [Vec<EarleyItem>],
+                    seen: &mut HashSet<EarleyItem>) {
+        let pos = sets.len() - 1;
+        let mut next = 0;
+        while next < sets[pos].len() {
+            let (prod, dot, origin) = sets[pos][next];
+            next += 1;
+            let mut added = Vec::new();
+            match self.symbol_at(root, prod, dot) {
+                Some(ParseSymbol::Var(typecode, _)) => {
+                    for &ix in self.by_type.get(typecode).map_or(&[][..], |v| &v[..]) {
+                        added.push((Some(ix), 0, pos));
+                    }
+                    // a production with an empty right-hand side may already
+                    // have been completed here
+                    let empty = sets[pos].iter().any(|&(p, d, o)| match p {
+                        Some(ix) if o == pos => {
+                            d == self.prods[ix].rhs.len() && self.prods[ix].typecode == *typecode
+                        }
+                        _ => false,
+                    });
+                    if empty {
+                        added.push((prod, dot + 1, origin));
+                    }
+                }
+                Some(ParseSymbol::Const(_)) => {}
+                None => {
+                    if let Some(ix) = prod {
+                        let typecode = &self.prods[ix].typecode;
+                        for &(p, d, o) in &sets[origin] {
+                            match self.symbol_at(root, p, d) {
+                                Some(ParseSymbol::Var(tc, _)) if tc == typecode => {
+                                    added.push((p, d + 1, o))
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+            }
+            for item in added {
+                if seen.insert(item) {
+                    sets[pos].push(item);
+                }
+            }
+        }
+    }
+
+    /// Reads the beginning of a formula of a syntax typecode, returning the
+    /// expected constants and variable typecodes and whether it is complete,
+    /// or `None` if no formula starts this way.
+    fn expected(&self,
+                typecode: &[u8],
+                prefix: &[TokenPtr],
+                vars: &HashMap<Token, Token>)
+                -> Option<(Vec<Token>, Vec<Token>, bool)> {
+        let root = ParseSymbol::Var(copy_token(typecode), 0);
+        let mut sets: Vec<Vec<EarleyItem>> = vec![vec![(None, 0, 0)]];
+        let mut seen = new_set();
+        seen.insert((None, 0, 0));
+        self.earley_close(&root, &mut sets, &mut seen);
+        for &tok in prefix {
+            let mut scanned = Vec::new();
+            for &(prod, dot, origin) in sets.last().unwrap() {
+                let matched = match self.symbol_at(&root, prod, dot) {
+                    Some(ParseSymbol::Const(c)) => &c[..] == tok,
+                    Some(ParseSymbol::Var(tc, _)) => vars.get(tok) == Some(tc),
+                    None => false,
+                };
+                if matched {
+                    scanned.push((prod, dot + 1, origin));
+                }
+            }
+            if scanned.is_empty() {
+                return None;
+            }
+            seen = scanned.iter().cloned().collect();
+            sets.push(scanned);
+            self.earley_close(&root, &mut sets, &mut seen);
+        }
+        let mut constants = Vec::new();
+        let mut typecodes = Vec::new();
+        for &(prod, dot, _) in sets.last().unwrap() {
+            match self.symbol_at(&root, prod, dot) {
+                Some(ParseSymbol::Const(c)) => constants.push(c.clone()),
+                Some(ParseSymbol::Var(tc, _)) => typecodes.push(tc.clone()),
+                None => {}
+            }
+        }
+        Some((constants, typecodes, seen.contains(&(None, 1, 0))))
+    }
+
+    /// Lists the tokens which may follow the beginning of a formula of the
+    /// given typecode, a sequence of tokens without typecode.  `vars` gives
+    /// the typecode of each variable in scope, and the variables which may
+    /// come next are taken from it.
+    ///
+    /// As for `parse_math`, a provable typecode such as `|-` stands for the
+    /// syntax typecode it is declared with, or for any typecode which syntax
+    /// axioms produce.  Returns `None` if no formula begins with the tokens.
+    pub fn next_tokens(&self,
+                       typecode: &[u8],
+                       prefix: &[TokenPtr],
+                       vars: &HashMap<Token, Token>)
+                       -> Option<NextTokens> {
+        let mut out: Option<NextTokens> = None;
+        let mut var_types = Vec::new();
+        for tc in self.parse_typecodes(typecode) {
+            if let Some((constants, types, complete)) = self.expected(tc, prefix, vars) {
+                let next = out.get_or_insert_with(NextTokens::default);
+                next.constants.extend(constants);
+                next.complete |= complete;
+                var_types.extend(types);
+            }
+        }
+        if let Some(ref mut next) = out {
+            next.constants.sort();
+            next.constants.dedup();
+            next.variables = vars.iter()
+                .filter(|&(_, tc)| var_types.contains(tc))
+                .map(|(var, _)| var.clone())
+                .collect();
+            next.variables.sort();
+        }
+        out
+    }
+
+    /// Parses a sequence of tokens, without typecode, as a formula of the
+    /// given typecode.  `vars` gives the typecode of each variable in scope.
+    /// Returns `None` if the formula does not parse, is nested deeper than
+    /// `MAX_PARSE_DEPTH` or is longer than `MAX_PARSE_LEN`.
+    pub fn parse(&self,
+                 typecode: &[u8],
+                 tokens: &[TokenPtr],
+                 vars: &HashMap<Token, Token>)
+                 -> Option<SyntaxTree> {
+        self.parse_checked(typecode, tokens, vars).ok()
+    }
+
+    /// Parses a sequence of tokens as for `parse`, returning
+    /// `FormulaNotParsed` if the grammar does not accept it, and
+    /// `FormulaTooComplex` if it is nested deeper than `MAX_PARSE_DEPTH` or
+    /// longer than `MAX_PARSE_LEN`.
+    pub fn parse_checked(&self,
+                         typecode: &[u8],
+                         tokens: &[TokenPtr],
+                         vars: &HashMap<Token, Token>)
+                         -> Result<SyntaxTree, Diagnostic> {
+        if tokens.len() > MAX_PARSE_LEN {
+            return Err(Diagnostic::FormulaTooComplex(copy_token(typecode)));
+        }
+        let mut state = ParseState {
+            parser: self,
+            tokens: tokens,
+            vars: vars,
+            memo: new_map(),
+            depth: 0,
+            too_deep: false,
+        };
+        let tree = state.parse_at(typecode, 0)
+            .into_iter()
+            .find(|&(_, end)| end == tokens.len())
+            .map(|(tree, _)| tree);
+        match tree {
+            Some(tree) => Ok(tree),
+            None if state.too_deep => Err(Diagnostic::FormulaTooComplex(copy_token(typecode))),
+            None => Err(Diagnostic::FormulaNotParsed(copy_token(typecode))),
+        }
+    }
+
+    /// Parses a math string, starting with its typecode.  `vars` gives the
+    /// typecode of each variable in scope, as returned by `frame_variables`
+    /// or `global_variables`.
+    ///
+    /// A math string whose typecode is not a syntax typecode, such as `|-`,
+    /// is parsed as the syntax typecode declared for it by `$j syntax ... as`,
+    /// or else as the first typecode produced by syntax axioms which works.
+    /// Returns the typecode the string was parsed as along with the tree.
+    pub fn parse_math(&self,
+                      vars: &HashMap<Token, Token>,
+                      math: &[TokenPtr])
+                      -> Option<(Token, SyntaxTree)> {
+        self.parse_math_checked(vars, math).ok()
+    }
+
+    /// Parses a math string as for `parse_math`, returning
+    /// `FormulaTooComplex` if it is nested deeper than `MAX_PARSE_DEPTH` or
+    /// longer than `MAX_PARSE_LEN`, and `FormulaNotParsed` if it does not
+    /// parse otherwise, with the typecode of the string.
+    pub fn parse_math_checked(&self,
+                              vars: &HashMap<Token, Token>,
+                              math: &[TokenPtr])
+                              -> Result<(Token, SyntaxTree), Diagnostic> {
+        let (typecode, tokens) = match math.split_first() {
+            Some((typecode, tokens)) => (*typecode, tokens),
+            None => return Err(Diagnostic::FormulaNotParsed(Token::default())),
+        };
+        let mut too_deep = false;
+        for tc in self.parse_typecodes(typecode) {
+            match self.parse_checked(tc, tokens, vars) {
+                Ok(tree) => return Ok((copy_token(tc), tree)),
+                Err(Diagnostic::FormulaTooComplex(_)) => too_deep = true,
+                Err(_) => {}
+            }
+        }
+        if too_deep {
+            Err(Diagnostic::FormulaTooComplex(copy_token(typecode)))
+        } else {
+            Err(Diagnostic::FormulaNotParsed(copy_token(typecode)))
+        }
+    }
+}
+
+/// Builds syntax trees from syntax axioms and variables, inserting coercions
+/// where an argument has a different typecode than its slot.
+///
+/// Every method checks its input and returns `None` rather than building an
+/// ill-formed tree, so the trees it returns always convert to formulas.
+pub struct FormulaBuilder<'a> {
+    parser: &'a SyntaxParser,
+    sset: &'a SegmentSet,
+    vars: &'a HashMap<Token, Token>,
+}
+
+impl<'a> FormulaBuilder<'a> {
+    /// Creates a builder; `vars` gives the typecode of each variable, as
+    /// returned by `frame_variables` or `global_variables`.
+    pub fn new(parser: &'a SyntaxParser,
+               sset: &'a SegmentSet,
+               vars: &'a HashMap<Token, Token>)
+               -> FormulaBuilder<'a> {
+        FormulaBuilder {
+            parser: parser,
+            sset: sset,
+            vars: vars,
+        }
+    }
+
+    /// Returns the tree of a variable, or `None` if it has no typecode.
+    pub fn var(&self, name: &[u8]) -> Option<SyntaxTree> {
+        if self.vars.contains_key(name) {
+            Some(SyntaxTree::Var(copy_token(name)))
+        } else {
+            None
+        }
+    }
+
+    /// Applies a syntax axiom to trees for its variables, in the order of its
+    /// `$f` hypotheses.  Returns `None` if the statement is not a syntax
+    /// axiom, the number of arguments is wrong, or an argument cannot be
+    /// coerced to the typecode of its variable.
+    pub fn app(&self, address: StatementAddress, args: Vec<SyntaxTree>) -> Option<SyntaxTree> {
+        let prod = self.parser.production(address)?;
+        if args.len() != prod.arity {
+            return None;
+        }
+        let mut slots = vec![None; prod.arity];
+        for sym in &prod.rhs {
+            if let ParseSymbol::Var(ref typecode, slot) = *sym {
+                slots[slot] = Some(typecode);
+            }
+        }
+        let args = args.into_iter()
+            .zip(slots)
+            .map(|(arg, typecode)| self.coerce(arg, typecode?))
+            .collect::<Option<Vec<_>>>()?;
+        Some(SyntaxTree::App(address, args))
+    }
+
+    /// Applies a syntax axiom given by its label; see `app`.
+    pub fn app_label(&self, label: &[u8], args: Vec<SyntaxTree>) -> Option<SyntaxTree> {
+        let prod = self.parser
+            .prods
+            .iter()
+            .find(|prod| self.sset.statement(prod.address).label() == label)?;
+        self.app(prod.address, args)
+    }
+
+    /// Wraps a tree in the coercions converting it to a typecode, if needed.
+    pub fn coerce(&self, tree: SyntaxTree, typecode: &[u8]) -> Option<SyntaxTree> {
+        let from = self.parser.typecode_of(&tree, self.vars)?;
+        let path = self.parser.coercion_path(&from, typecode)?;
+        Some(path.into_iter().fold(tree, |tree, address| SyntaxTree::App(address, vec![tree])))
+    }
+
+    /// Returns the formula of a tree coerced to a typecode.  As in
+    /// `SyntaxParser::parse_math`, a typecode which is not a syntax typecode,
+    /// such as `|-`, accepts a tree of the syntax typecode declared for it,
+    /// or of any typecode if there is none, and replaces it.
+    pub fn build(&self, tree: SyntaxTree, typecode: &[u8]) -> Option<Formula> {
+        if !self.parser.typecodes.iter().any(|tc| &tc[..] == typecode) {
+            let tree = match self.parser.config.syntax_typecode(typecode) {
+                Some(syntax) => self.coerce(tree, syntax)?,
+                None => tree,
+            };
+            let mut formula = self.parser.to_formula(&tree, self.vars)?;
+            formula.typecode = copy_token(typecode);
+            return Some(formula);
+        }
+        let tree = self.coerce(tree, typecode)?;
+        self.parser.to_formula(&tree, self.vars)
+    }
+}
+
+/// Returns the typecode of each variable with a `$f` hypothesis in a frame.
+pub fn frame_variables(nset: &Nameset, frame: &Frame) -> HashMap<Token, Token> {
+    let mut vars = new_map();
+    for hyp in frame.hypotheses.iter() {
+        if let Hyp::Floating(_, var, typecode) = *hyp {
+            vars.insert(copy_token(nset.atom_name(frame.var_list[var])),
+                        copy_token(nset.atom_name(typecode)));
+        }
+    }
+    vars
+}
+
+/// Returns the typecode of every variable with a `$f` statement anywhere in
+/// the database, for parsing formulas outside of any frame; a variable with
+/// several `$f` statements gets the typecode of the last one.
+pub fn global_variables(sset: &SegmentSet) -> HashMap<Token, Token> {
+    let mut vars = new_map();
+    for sref in sset.segments() {
+        for stmt in sref {
+            if stmt.statement_type() == StatementType::Floating && stmt.math_len() == 2 {
+                vars.insert(copy_token(stmt.math_at(1).slice), copy_token(stmt.math_at(0).slice));
+            }
+        }
+    }
+    vars
+}
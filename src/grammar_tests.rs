@@ -0,0 +1,122 @@
+use diag::Diagnostic;
+use grammar::FormulaBuilder;
+use grammar::MAX_PARSE_DEPTH;
+use grammar::MAX_PARSE_LEN;
+use grammar::SyntaxTree;
+use grammar::global_variables;
+use parser::Token;
+use parser::as_str;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_ambiguous_syntax() {
+    let text = format!("{}wi2 $a wff ( ps -> ph ) $.\nwn $a wff ( ph ) $.\n", DB);
+    let mut db = mkdb(text.as_bytes());
+    let diags = db.grammar_result().diagnostics();
+    let wi = db.statement("wi").unwrap().address();
+    let wi2 = db.statement("wi2").unwrap().address();
+    let witness = b"wff ( ps -> ph )".to_vec().into_boxed_slice();
+    assert_eq!(diags, vec![(wi2, Diagnostic::AmbiguousSyntax(wi, witness))]);
+}
+
+#[test]
+fn test_parse_formula_str() {
+    let mut db = mkdb(DB.as_bytes());
+    let tok = |name: &[u8]| name.to_vec().into_boxed_slice();
+    let formula = db.parse_formula_str(" ( ph  -> ps )", "|-").unwrap();
+    assert_eq!(formula.typecode, tok(b"|-"));
+    assert_eq!(formula.to_string(), "|- ( ph -> ps )");
+    assert!(db.parse_formula_str("ph", "wff").is_ok());
+    assert_eq!(db.parse_formula_str("( ph -> ch )", "wff"),
+               Err(Diagnostic::FormulaUnknownSymbol(tok(b"ch"))));
+    assert_eq!(db.parse_formula_str("( ph -> ps", "wff"),
+               Err(Diagnostic::FormulaNotParsed(tok(b"wff"))));
+    assert_eq!(db.parse_formula_str("ph", "class"),
+               Err(Diagnostic::FormulaUnknownSymbol(tok(b"class"))));
+}
+
+#[test]
+fn test_parse_formula_too_complex() {
+    let mut db = mkdb(DB.as_bytes());
+    let tok = |name: &[u8]| name.to_vec().into_boxed_slice();
+    let nested = |depth: usize| format!("{}ph{}", "( ph -> ".repeat(depth), " )".repeat(depth));
+    assert!(db.parse_formula_str(&nested(MAX_PARSE_DEPTH - 1), "|-").is_ok());
+    assert_eq!(db.parse_formula_str(&nested(20000), "|-"),
+               Err(Diagnostic::FormulaTooComplex(tok(b"|-"))));
+    assert_eq!(db.parse_formula_str(&"ph ".repeat(MAX_PARSE_LEN), "wff"),
+               Err(Diagnostic::FormulaNotParsed(tok(b"wff"))));
+    assert_eq!(db.parse_formula_str(&"ph ".repeat(MAX_PARSE_LEN + 1), "wff"),
+               Err(Diagnostic::FormulaTooComplex(tok(b"wff"))));
+}
+
+#[test]
+fn test_coercions() {
+    let mut db = mkdb(b"$c |- wff class setvar = $.
+$v x y A B $.
+vx $f setvar x $.
+vy $f setvar y $.
+cA $f class A $.
+cB $f class B $.
+cv $a class x $.
+wceq $a wff A = B $.
+");
+    let cv = db.statement("cv").unwrap().address();
+    let wceq = db.statement("wceq").unwrap().address();
+    let coercions = db.coercions();
+    assert_eq!(coercions.len(), 1);
+    assert_eq!((coercions[0].address, &coercions[0].from[..], &coercions[0].to[..]),
+               (cv, &b"setvar"[..], &b"class"[..]));
+    assert!(db.can_coerce("setvar", "class"));
+    assert!(db.can_coerce("wff", "wff"));
+    assert!(!db.can_coerce("class", "setvar"));
+
+    let parser = db.syntax_parser().clone();
+    let vars = global_variables(db.parse_result());
+    let sset = db.parse_result().clone();
+    let builder = FormulaBuilder::new(&parser, &sset, &vars);
+    let (x, a) = (builder.var(b"x").unwrap(), builder.var(b"A").unwrap());
+    let tree = builder.app(wceq, vec![x.clone(), a.clone()]).unwrap();
+    assert_eq!(tree, SyntaxTree::App(wceq, vec![SyntaxTree::App(cv, vec![x.clone()]), a.clone()]));
+    assert_eq!(builder.build(tree, b"wff").unwrap().to_string(), "wff x = A");
+    assert_eq!(builder.app(cv, vec![a.clone()]), None);
+    assert_eq!(builder.app(wceq, vec![x]), None);
+    assert_eq!(builder.build(a, b"setvar"), None);
+}
+
+#[test]
+fn test_build_formula() {
+    let mut db = mkdb(DB.as_bytes());
+    let formula = db.build_formula("|-", |b| {
+        let ph = b.var(b"ph")?;
+        let inner = b.app_label(b"wi", vec![b.var(b"ps")?, ph.clone()])?;
+        b.app_label(b"wi", vec![ph, inner])
+    });
+    assert_eq!(formula.unwrap().to_string(), "|- ( ph -> ( ps -> ph ) )");
+    assert_eq!(db.build_formula("wff", |b| b.app_label(b"ax-1", vec![])), None);
+    assert_eq!(db.build_formula("wff", |b| b.app_label(b"wi", vec![b.var(b"ph")?])), None);
+    assert_eq!(db.build_formula("wff", |b| b.var(b"ch")), None);
+}
+
+#[test]
+fn test_next_formula_tokens() {
+    let text = format!("{}$c -. $.\nwn $a wff -. ph $.\n", DB);
+    let mut db = mkdb(text.as_bytes());
+    let tokens = |list: &[Token]| -> Vec<String> {
+        list.iter().map(|tok| as_str(tok).to_owned()).collect()
+    };
+    let next = db.next_formula_tokens("", "wff").unwrap();
+    assert_eq!(tokens(&next.constants), vec!["(", "-."]);
+    assert_eq!(tokens(&next.variables), vec!["ph", "ps"]);
+    assert!(!next.complete);
+    let next = db.next_formula_tokens("( -. ph", "wff").unwrap();
+    assert_eq!(tokens(&next.constants), vec!["->"]);
+    assert!(next.variables.is_empty());
+    let next = db.next_formula_tokens("( ph -> -. ps )", "wff").unwrap();
+    assert!(next.constants.is_empty() && next.variables.is_empty());
+    assert!(next.complete);
+    let next = db.next_formula_tokens("-. ph", "|-").unwrap();
+    assert!(next.constants.is_empty() && next.complete);
+    assert_eq!(db.next_formula_tokens("( ph )", "wff"), None);
+    assert_eq!(db.next_formula_tokens("->", "|-"), None);
+}
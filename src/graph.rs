@@ -0,0 +1,250 @@
+//! Export of the dependency graph between assertions.
+//!
+//! The nodes of the graph are the `$a` and `$p` statements, and there is an
+//! edge from each theorem to every assertion its proof uses directly, as read
+//! by `usage::proof_labels`.  For set.mm the full graph has tens of thousands
+//! of nodes, so it can be cut down to the statements under one heading of the
+//! outline, or to those reachable from some root theorems within a given
+//! number of edges, or both.
+//!
+//! The graph is written as GraphML, Graphviz DOT, or GEXF, which between them
+//! are read by most visualization tools.  Each node carries the label of its
+//! statement and whether it is an axiom or a theorem.  Metamath labels only
+//! contain letters, digits, `-`, `_` and `.`, so they need no escaping in
+//! either format.
+
+use outline::Outline;
+use parser::StatementAddress;
+use parser::StatementType;
+use parser::as_str;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use std::io;
+use std::io::Write;
+use usage::proof_labels;
+use util::HashMap;
+use util::HashSet;
+use util::new_map;
+
+/// A file format for the dependency graph.
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum GraphFormat {
+    /// GraphML, an XML format.
+    GraphML,
+    /// The Graphviz DOT language.
+    Dot,
+    /// GEXF, the XML format of Gephi.
+    Gexf,
+}
+
+impl GraphFormat {
+    /// Chooses the format from the extension of a file name: `.graphml`,
+    /// `.dot` or `.gv`, or `.gexf`.
+    pub fn from_path(path: &str) -> Option<GraphFormat> {
+        let ext = match path.rfind('.') {
+            Some(pos) => path[pos + 1..].to_lowercase(),
+            None => return None,
+        };
+        match &ext[..] {
+            "graphml" => Some(GraphFormat::GraphML),
+            "dot" | "gv" => Some(GraphFormat::Dot),
+            "gexf" => Some(GraphFormat::Gexf),
+            _ => None,
+        }
+    }
+}
+
+/// Restricts the statements included in the dependency graph.
+#[derive(Clone,Debug,Default)]
+pub struct GraphFilter {
+    /// Keep only the statements under a heading, given as an index into
+    /// `Outline::nodes`.
+    pub section: Option<usize>,
+    /// If not empty, keep only these statements and the assertions they
+    /// depend on.
+    pub roots: Vec<StatementAddress>,
+    /// With roots, keep only the assertions reached from them by at most
+    /// this many edges.
+    pub depth: Option<usize>,
+}
+
+/// The dependency graph of a database, or of part of it.
+#[derive(Clone,Debug,Default)]
+pub struct DependencyGraph {
+    /// The statements, in database order.
+    pub nodes: Vec<StatementAddress>,
+    /// The edges as indices into `nodes`, from a theorem to an assertion its
+    /// proof uses.
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl DependencyGraph {
+    /// Builds the dependency graph, keeping the statements which pass the
+    /// filter and the edges between them.
+    pub fn new(sset: &SegmentSet,
+               scope: &ScopeResult,
+               outline: &Outline,
+               filter: &GraphFilter)
+               -> DependencyGraph {
+        let mut nodes = Vec::new();
+        let mut index: HashMap<StatementAddress, usize> = new_map();
+        let mut uses: Vec<Vec<usize>> = Vec::new();
+        for sref in sset.segments() {
+            for stmt in sref {
+                if stmt.statement_type() != StatementType::Axiom &&
+                   stmt.statement_type() != StatementType::Provable {
+                    continue;
+                }
+                let mut used = Vec::new();
+                for (_, label) in proof_labels(stmt).0 {
+                    match scope.get(label).and_then(|frame| index.get(&frame.valid.start)) {
+                        Some(&ix) if !used.contains(&ix) => used.push(ix),
+                        _ => {}
+                    }
+                }
+                index.insert(stmt.address(), nodes.len());
+                nodes.push(stmt.address());
+                uses.push(used);
+            }
+        }
+
+        let mut keep = vec![true; nodes.len()];
+        if let Some(section) = filter.section {
+            let inside: HashSet<StatementAddress> =
+                outline.section_statements(sset, section).into_iter().collect();
+            for (ix, addr) in nodes.iter().enumerate() {
+                keep[ix] = inside.contains(addr);
+            }
+        }
+        if !filter.roots.is_empty() {
+            let mut reached = vec![false; nodes.len()];
+            let mut frontier: Vec<usize> =
+                filter.roots.iter().filter_map(|addr| index.get(addr).cloned()).collect();
+            for &ix in &frontier {
+                reached[ix] = true;
+            }
+            let mut distance = 0;
+            while !frontier.is_empty() && filter.depth != Some(distance) {
+                let mut next = Vec::new();
+                for ix in frontier {
+                    for &used in &uses[ix] {
+                        if !reached[used] {
+                            reached[used] = true;
+                            next.push(used);
+                        }
+                    }
+                }
+                frontier = next;
+                distance += 1;
+            }
+            for ix in 0..nodes.len() {
+                keep[ix] = keep[ix] && reached[ix];
+            }
+        }
+
+        let mut graph = DependencyGraph::default();
+        let mut renumber = vec![0; nodes.len()];
+        for ix in 0..nodes.len() {
+            if keep[ix] {
+                renumber[ix] = graph.nodes.len();
+                graph.nodes.push(nodes[ix]);
+            }
+        }
+        for ix in 0..nodes.len() {
+            if keep[ix] {
+                for &used in &uses[ix] {
+                    if keep[used] {
+                        graph.edges.push((renumber[ix], renumber[used]));
+                    }
+                }
+            }
+        }
+        graph
+    }
+
+    /// Writes the graph in the given format.
+    pub fn write<W: Write>(&self,
+                           sset: &SegmentSet,
+                           format: GraphFormat,
+                           out: &mut W)
+                           -> io::Result<()> {
+        let nodes: Vec<(&str, &str)> = self.nodes
+            .iter()
+            .map(|&addr| {
+                let stmt = sset.statement(addr);
+                let kind = if stmt.statement_type() == StatementType::Axiom {
+                    "axiom"
+                } else {
+                    "theorem"
+                };
+                (as_str(stmt.label()), kind)
+            })
+            .collect();
+        match format {
+            GraphFormat::GraphML => {
+                writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+                writeln!(out, "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">")?;
+                writeln!(out,
+                         "  <key id=\"label\" for=\"node\" attr.name=\"label\" \
+                          attr.type=\"string\"/>")?;
+                writeln!(out,
+                         "  <key id=\"type\" for=\"node\" attr.name=\"type\" \
+                          attr.type=\"string\"/>")?;
+                writeln!(out, "  <graph id=\"dependencies\" edgedefault=\"directed\">")?;
+                for (ix, &(label, kind)) in nodes.iter().enumerate() {
+                    writeln!(out,
+                             "    <node id=\"n{}\"><data key=\"label\">{}</data>\
+                              <data key=\"type\">{}</data></node>",
+                             ix,
+                             label,
+                             kind)?;
+                }
+                for &(from, to) in &self.edges {
+                    writeln!(out, "    <edge source=\"n{}\" target=\"n{}\"/>", from, to)?;
+                }
+                writeln!(out, "  </graph>")?;
+                writeln!(out, "</graphml>")
+            }
+            GraphFormat::Dot => {
+                writeln!(out, "digraph dependencies {{")?;
+                for &(label, kind) in &nodes {
+                    let shape = if kind == "axiom" { "box" } else { "ellipse" };
+                    writeln!(out, "  \"{}\" [shape={}];", label, shape)?;
+                }
+                for &(from, to) in &self.edges {
+                    writeln!(out, "  \"{}\" -> \"{}\";", nodes[from].0, nodes[to].0)?;
+                }
+                writeln!(out, "}}")
+            }
+            GraphFormat::Gexf => {
+                writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+                writeln!(out, "<gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">")?;
+                writeln!(out, "  <graph defaultedgetype=\"directed\">")?;
+                writeln!(out, "    <attributes class=\"node\">")?;
+                writeln!(out, "      <attribute id=\"type\" title=\"type\" type=\"string\"/>")?;
+                writeln!(out, "    </attributes>")?;
+                writeln!(out, "    <nodes>")?;
+                for (ix, &(label, kind)) in nodes.iter().enumerate() {
+                    writeln!(out,
+                             "      <node id=\"n{}\" label=\"{}\"><attvalues>\
+                              <attvalue for=\"type\" value=\"{}\"/></attvalues></node>",
+                             ix,
+                             label,
+                             kind)?;
+                }
+                writeln!(out, "    </nodes>")?;
+                writeln!(out, "    <edges>")?;
+                for (ix, &(from, to)) in self.edges.iter().enumerate() {
+                    writeln!(out,
+                             "      <edge id=\"e{}\" source=\"n{}\" target=\"n{}\"/>",
+                             ix,
+                             from,
+                             to)?;
+                }
+                writeln!(out, "    </edges>")?;
+                writeln!(out, "  </graph>")?;
+                writeln!(out, "</gexf>")
+            }
+        }
+    }
+}
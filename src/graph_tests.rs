@@ -0,0 +1,47 @@
+use graph::DependencyGraph;
+use graph::GraphFilter;
+use graph::GraphFormat;
+use parser::as_str;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_dependency_graph() {
+    let text = format!("{}$(
+=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=
+        Theorems
+=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=
+$)
+th1 $p |- ( ph -> ( ps -> ph ) ) $= wph wps ax-1 $.
+${{ h1 $e |- ph $. th2 $p |- ( ps -> ph ) $= wph wps wph wi h1 wph wps th1 ax-mp $. $}}\n",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let sset = db.parse_result().clone();
+    let labels = |graph: &DependencyGraph| {
+        graph.nodes.iter().map(|&addr| as_str(sset.statement(addr).label()).to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    let graph = db.dependency_graph(&GraphFilter::default());
+    assert_eq!(labels(&graph), vec!["wi", "ax-mp", "ax-1", "th1", "th2"]);
+    assert_eq!(graph.edges, vec![(3, 2), (4, 0), (4, 3), (4, 1)]);
+
+    let root = db.statement("th2").unwrap().address();
+    let graph = db.dependency_graph(&GraphFilter {
+        section: None,
+        roots: vec![root],
+        depth: Some(1),
+    });
+    assert_eq!(labels(&graph), vec!["wi", "ax-mp", "th1", "th2"]);
+    assert_eq!(graph.edges, vec![(3, 0), (3, 2), (3, 1)]);
+
+    let graph = db.dependency_graph(&GraphFilter { section: Some(1), ..GraphFilter::default() });
+    assert_eq!(labels(&graph), vec!["th1", "th2"]);
+    let mut dot = Vec::new();
+    graph.write(&sset, GraphFormat::Dot, &mut dot).unwrap();
+    assert_eq!(as_str(&dot),
+               "digraph dependencies {\n  \"th1\" [shape=ellipse];\n  \"th2\" [shape=ellipse];\n  \
+                \"th2\" -> \"th1\";\n}\n");
+    assert_eq!(GraphFormat::from_path("deps.GraphML"), Some(GraphFormat::GraphML));
+    assert_eq!(GraphFormat::from_path("deps.txt"), None);
+}
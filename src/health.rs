@@ -0,0 +1,68 @@
+//! One-call summary of all correctness checks, for status displays.
+//!
+//! `Database::health_check` runs every pass which generates diagnostics and
+//! collects a `HealthReport` with error and warning counts and the time taken
+//! for each pass, plus the most severe individual findings.
+
+use diag::DiagnosticClass;
+use diag::Level;
+use diag::Notation;
+use std::time::Duration;
+
+/// Summary of the diagnostics generated by one pass.
+#[derive(Clone,Debug)]
+pub struct PassSummary {
+    /// The pass.
+    pub class: DiagnosticClass,
+    /// Number of error-level notations.
+    pub errors: usize,
+    /// Number of warning-level notations.
+    pub warnings: usize,
+    /// Time taken to bring the pass up to date.
+    pub elapsed: Duration,
+}
+
+impl PassSummary {
+    /// Counts the errors and warnings among the notations generated by a
+    /// pass.
+    pub fn new(class: DiagnosticClass, notations: &[Notation], elapsed: Duration) -> PassSummary {
+        PassSummary {
+            class: class,
+            errors: notations.iter().filter(|n| n.level == Level::Error).count(),
+            warnings: notations.iter().filter(|n| n.level == Level::Warning).count(),
+            elapsed: elapsed,
+        }
+    }
+}
+
+/// Aggregated result of all correctness passes.
+#[derive(Default)]
+pub struct HealthReport {
+    /// Per-pass summaries, in the order the passes were run.
+    pub passes: Vec<PassSummary>,
+    /// The most severe notations found, errors first and otherwise in
+    /// database order.
+    pub worst: Vec<Notation>,
+}
+
+impl HealthReport {
+    /// Total number of errors over all passes.
+    pub fn errors(&self) -> usize {
+        self.passes.iter().map(|pass| pass.errors).sum()
+    }
+
+    /// Total number of warnings over all passes.
+    pub fn warnings(&self) -> usize {
+        self.passes.iter().map(|pass| pass.warnings).sum()
+    }
+
+    /// Total time taken by all passes.
+    pub fn elapsed(&self) -> Duration {
+        self.passes.iter().fold(Duration::new(0, 0), |acc, pass| acc + pass.elapsed)
+    }
+
+    /// Returns true if no pass reported an error.
+    pub fn is_healthy(&self) -> bool {
+        self.errors() == 0
+    }
+}
@@ -0,0 +1,22 @@
+use diag::DiagnosticClass;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_health_check() {
+    let text = format!("{}th1 $p |- ( ph -> ph ) $= wph wps ax-1 $.
+wi2 $a wff ( ps -> ph ) $.
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let report = db.health_check(1);
+    assert_eq!(report.passes.len(), 11);
+    assert_eq!(report.passes[2].class, DiagnosticClass::Verify);
+    assert_eq!(report.passes[2].errors, 1);
+    assert_eq!(report.passes[3].class, DiagnosticClass::Grammar);
+    assert_eq!(report.passes[3].errors, 1);
+    assert_eq!(report.errors(), 2);
+    assert!(!report.is_healthy());
+    assert_eq!(report.worst.len(), 1);
+    assert!(mkdb(DB.as_bytes()).health_check(10).is_healthy());
+}
@@ -0,0 +1,294 @@
+//! Contribution and revision history of statements.
+//!
+//! set.mm records who wrote a statement and who later changed it with
+//! parentheticals in its description comment, such as `(Contributed by NM,
+//! 5-Aug-1993.)`, `(Revised by Mario Carneiro, 24-Jun-2014.)` and `(Proof
+//! shortened by Wolf Lammen, 20-Dec-2012.)`.  This module parses them into a
+//! typed history, and builds the edit appending a new entry so that tools
+//! which modify a proof can record the attribution in the standard form.
+//!
+//! The `HistoryResult` pass collects the histories of all assertions for
+//! queries by contributor or date, and flags entries whose date is malformed.
+
+use comment_parser::CommentItem;
+use comment_parser::comment_items;
+use diag::Diagnostic;
+use emit::Edit;
+use parser::Span;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use segment_set::SegmentSet;
+use std::fmt;
+use util::HashMap;
+use util::new_map;
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug",
+                            "Sep", "Oct", "Nov", "Dec"];
+
+/// A date in the `5-Aug-1993` format used by set.mm.
+#[derive(Copy,Clone,Debug,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub struct Date {
+    /// The year.
+    pub year: u32,
+    /// The month, from 1 to 12.
+    pub month: u32,
+    /// The day of the month.
+    pub day: u32,
+}
+
+impl Date {
+    /// Parses a date such as `5-Aug-1993`, returning `None` if it is not in
+    /// that format or the day does not exist.
+    pub fn parse(text: &str) -> Option<Date> {
+        let parts: Vec<&str> = text.split('-').collect();
+        if parts.len() != 3 {
+            return None;
+        }
+        let month = match MONTHS.iter().position(|&m| m == parts[1]) {
+            Some(ix) => ix as u32 + 1,
+            None => return None,
+        };
+        if parts[2].len() != 4 {
+            return None;
+        }
+        match (parts[0].parse(), parts[2].parse()) {
+            (Ok(day), Ok(year)) if day >= 1 && day <= days_in_month(year, month) => {
+                Some(Date {
+                    year: year,
+                    month: month,
+                    day: day,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) &&
+             (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}-{}", self.day, MONTHS[self.month as usize - 1], self.year)
+    }
+}
+
+/// The kind of a history entry.
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum RevisionKind {
+    /// `(Contributed by ...)`: the original author.
+    Contributed,
+    /// `(Revised by ...)`: a change to the statement or its proof.
+    Revised,
+    /// `(Proof shortened by ...)`: a shorter proof.
+    ProofShortened,
+}
+
+impl RevisionKind {
+    fn phrase(self) -> &'static str {
+        match self {
+            RevisionKind::Contributed => "Contributed by ",
+            RevisionKind::Revised => "Revised by ",
+            RevisionKind::ProofShortened => "Proof shortened by ",
+        }
+    }
+}
+
+/// One entry of a statement's history.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Revision {
+    /// What was done.
+    pub kind: RevisionKind,
+    /// Who did it, usually initials or a full name.
+    pub author: String,
+    /// When it was done.
+    pub date: Date,
+}
+
+impl fmt::Display for Revision {
+    /// Formats the entry as the parenthetical used in comments.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}{}, {}.)", self.kind.phrase(), self.author, self.date)
+    }
+}
+
+/// The history of a statement, as recorded in its description comment.
+#[derive(Clone,Debug,Default,PartialEq,Eq)]
+pub struct History {
+    /// The first `Contributed by` entry.
+    pub contributor: Option<Revision>,
+    /// All other entries, in the order they appear in the comment.
+    pub revisions: Vec<Revision>,
+}
+
+/// Parses the text between the parentheses of a history entry.
+fn parse_entry(text: &str) -> Option<Revision> {
+    let kinds = [RevisionKind::Contributed, RevisionKind::Revised, RevisionKind::ProofShortened];
+    for &kind in &kinds {
+        if !text.starts_with(kind.phrase()) || !text.ends_with('.') {
+            continue;
+        }
+        let body = &text[kind.phrase().len()..text.len() - 1];
+        return body.rfind(", ").and_then(|comma| {
+            Date::parse(&body[comma + 2..]).map(|date| {
+                Revision {
+                    kind: kind,
+                    author: body[..comma].to_owned(),
+                    date: date,
+                }
+            })
+        });
+    }
+    None
+}
+
+/// Finds the history entries of a comment, with the offset just past each
+/// entry relative to the start of the comment.  Entries may be broken across
+/// lines.
+fn scan_comment(comment: StatementRef) -> Vec<(Revision, usize)> {
+    let text = comment.span().as_ref(&comment.segment().segment.buffer);
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while let Some(open) = text[pos..].iter().position(|&ch| ch == b'(') {
+        let open = pos + open;
+        let close = match text[open..].iter().position(|&ch| ch == b')') {
+            Some(close) => open + close,
+            None => break,
+        };
+        let inner = String::from_utf8_lossy(&text[open + 1..close]);
+        let inner: Vec<&str> = inner.split_whitespace().collect();
+        match parse_entry(&inner.join(" ")) {
+            Some(revision) => {
+                out.push((revision, close + 1));
+                pos = close + 1;
+            }
+            None => pos = open + 1,
+        }
+    }
+    out
+}
+
+/// Reads the history of a statement from its description comment.
+///
+/// A statement without a comment has an empty history.
+pub fn statement_history(stmt: StatementRef) -> History {
+    let mut history = History::default();
+    if let Some(comment) = stmt.associated_comment() {
+        for (revision, _) in scan_comment(comment) {
+            if revision.kind == RevisionKind::Contributed && history.contributor.is_none() {
+                history.contributor = Some(revision);
+            } else {
+                history.revisions.push(revision);
+            }
+        }
+    }
+    history
+}
+
+/// Builds an edit appending an entry to the history of a statement.
+///
+/// The entry is inserted after the last existing entry, or at the end of the
+/// comment if there is none.  Returns the name of the file to edit and the
+/// edit, or `None` if the statement has no description comment.
+pub fn append_revision(sset: &SegmentSet,
+                       stmt: StatementRef,
+                       revision: &Revision)
+                       -> Option<(String, Edit)> {
+    stmt.associated_comment().map(|comment| {
+        let sinfo = sset.source_info(comment.segment().id);
+        let span = comment.span();
+        let start = (sinfo.span.start + span.start) as usize;
+        let (offset, text) = match scan_comment(comment).last() {
+            Some(&(_, end)) => (start + end, format!(" {}", revision)),
+            // before the closing `$)`
+            None => (start + (span.end - span.start) as usize - 2, format!("{} ", revision)),
+        };
+        (sinfo.name.clone(),
+         Edit {
+            span: Span::new(offset, offset),
+            text: text.into_bytes(),
+        })
+    })
+}
+
+/// Analysis pass result collecting the histories of all `$a` and `$p`
+/// statements.
+#[derive(Debug)]
+pub struct HistoryResult {
+    histories: Vec<(StatementAddress, History)>,
+    index: HashMap<StatementAddress, usize>,
+    diagnostics: Vec<(StatementAddress, Diagnostic)>,
+}
+
+impl HistoryResult {
+    /// Reads the history of every assertion, as `statement_history` does, and
+    /// reports entries whose date is not a valid `DD-Mon-YYYY` date on the
+    /// comment containing them.
+    pub fn new(sset: &SegmentSet) -> HistoryResult {
+        let mut result = HistoryResult {
+            histories: Vec::new(),
+            index: new_map(),
+            diagnostics: Vec::new(),
+        };
+        for sref in sset.segments() {
+            for stmt in sref {
+                match stmt.statement_type() {
+                    StatementType::Axiom | StatementType::Provable => {}
+                    _ => continue,
+                }
+                if let Some(comment) = stmt.associated_comment() {
+                    for item in comment_items(comment) {
+                        if let CommentItem::Contribution(contribution) = item {
+                            if contribution.date.is_none() {
+                                let diag = Diagnostic::MalformedDate(contribution.date_span);
+                                result.diagnostics.push((comment.address(), diag));
+                            }
+                        }
+                    }
+                }
+                result.index.insert(stmt.address(), result.histories.len());
+                result.histories.push((stmt.address(), statement_history(stmt)));
+            }
+        }
+        result
+    }
+
+    /// Returns the history of an assertion, or `None` if the address is not
+    /// that of an assertion.
+    pub fn history(&self, address: StatementAddress) -> Option<&History> {
+        self.index.get(&address).map(|&ix| &self.histories[ix].1)
+    }
+
+    /// Lists the assertions with a history entry by an author, in database
+    /// order.
+    pub fn by_contributor(&self, author: &str) -> Vec<StatementAddress> {
+        self.matching(|revision| revision.author == author)
+    }
+
+    /// Lists the assertions with a history entry dated between `from` and `to`
+    /// inclusive, in database order.
+    pub fn in_date_range(&self, from: Date, to: Date) -> Vec<StatementAddress> {
+        self.matching(|revision| revision.date >= from && revision.date <= to)
+    }
+
+    fn matching<F: Fn(&Revision) -> bool>(&self, pred: F) -> Vec<StatementAddress> {
+        self.histories
+            .iter()
+            .filter(|entry| entry.1.contributor.iter().chain(&entry.1.revisions).any(&pred))
+            .map(|entry| entry.0)
+            .collect()
+    }
+
+    /// Reports history entries with malformed dates.
+    pub fn diagnostics(&self) -> Vec<(StatementAddress, Diagnostic)> {
+        self.diagnostics.clone()
+    }
+}
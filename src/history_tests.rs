@@ -0,0 +1,78 @@
+use diag::DiagnosticClass;
+use emit::emit_file;
+use history::Date;
+use history::History;
+use history::Revision;
+use history::RevisionKind;
+use history::append_revision;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_history() {
+    let text = format!("{}$( Identity.  (Contributed by NM,
+     5-Aug-1993.)  (Proof shortened by Wolf Lammen, 20-Dec-2012.)
+     (Revised by Someone, 1-Feb-2003) $)
+th1 $p |- ( ph -> ph ) $= ? $.
+$( No history. $)
+th2 $p |- ( ph -> ph ) $= ? $.
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let history = db.history("th1").unwrap();
+    let contributor = history.contributor.unwrap();
+    assert_eq!(contributor.kind, RevisionKind::Contributed);
+    assert_eq!(contributor.author, "NM");
+    assert_eq!(contributor.date.to_string(), "5-Aug-1993");
+    assert_eq!(history.revisions.len(), 1);
+    assert_eq!(history.revisions[0].to_string(),
+               "(Proof shortened by Wolf Lammen, 20-Dec-2012.)");
+    assert!(history.revisions[0].date > contributor.date);
+    assert_eq!(db.history("th2"), Some(History::default()));
+    assert_eq!(db.history("ax-1"), Some(History::default()));
+
+    let revision = Revision {
+        kind: RevisionKind::ProofShortened,
+        author: "AB".to_owned(),
+        date: Date::parse("3-Mar-2020").unwrap(),
+    };
+    let th1 = db.statement("th1").unwrap().address();
+    let th2 = db.statement("th2").unwrap().address();
+    let ax1 = db.statement("ax-1").unwrap().address();
+    let sset = db.parse_result().clone();
+    let (file, edit1) = append_revision(&sset, sset.statement(th1), &revision).unwrap();
+    assert_eq!(file, "test.mm");
+    let (_, edit2) = append_revision(&sset, sset.statement(th2), &revision).unwrap();
+    assert_eq!(append_revision(&sset, sset.statement(ax1), &revision), None);
+    let mut out = Vec::new();
+    emit_file(&sset, "test.mm", &[edit1, edit2], &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("20-Dec-2012.) (Proof shortened by AB, 3-Mar-2020.)
+     (Revised by Someone, 1-Feb-2003) $)"));
+    assert!(out.contains("$( No history. (Proof shortened by AB, 3-Mar-2020.) $)"));
+}
+
+#[test]
+fn test_history_result() {
+    let text = format!("{}$( (Contributed by NM, 5-Aug-1993.) (Revised by AB, 12-Jan-2004.) $)
+th1 $p |- ( ph -> ph ) $= ? $.
+$( (Contributed by AB, 29-Feb-2001.) (Revised by AB, 29-Feb-2004.) $)
+th2 $p |- ( ph -> ph ) $= ? $.
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let th1 = db.statement("th1").unwrap().address();
+    let th2 = db.statement("th2").unwrap().address();
+    let result = db.history_result().clone();
+    assert_eq!(result.history(th2).unwrap().revisions.len(), 1);
+    assert_eq!(result.by_contributor("AB"), vec![th1, th2]);
+    assert_eq!(result.by_contributor("NM"), vec![th1]);
+    let (from, to) = (Date::parse("1-Jan-2004").unwrap(), Date::parse("31-Dec-2004").unwrap());
+    assert_eq!(result.in_date_range(from, to), vec![th1, th2]);
+    let to = Date::parse("1-Feb-2004").unwrap();
+    assert_eq!(result.in_date_range(from, to), vec![th1]);
+
+    let notations = db.diag_notations(vec![DiagnosticClass::History]);
+    assert_eq!(notations.len(), 1);
+    assert_eq!(notations[0].args, vec![("date", "29-Feb-2001".to_owned())]);
+}
@@ -0,0 +1,327 @@
+//! Generation of per-statement HTML pages in the style of metamath.exe, and
+//! of their Markdown equivalent.
+//!
+//! `write_statement_page` produces the equivalent of metamath.exe's `show
+//! statement /html` output, as written for every statement by `write
+//! theorem_list`: the description, the hypotheses and assertion, the
+//! distinct variable groups, and for theorems the proof table.  The proof
+//! table shows only the logical (`|-`) steps, numbered in proof order; each
+//! row lists the steps it uses, the label applied, and the formula, indented
+//! by the distance of the step from the final one.  Math is typeset with the
+//! `htmldef` or `althtmldef` definitions, and the page title and style sheet
+//! are taken from the `htmltitle` and `htmlcss` settings.
+//!
+//! `write_statement_markdown` renders the same content as GitHub-flavored
+//! Markdown, for documentation generators and review comments.
+//!
+//! Pages link to each other as `LABEL.html`, the naming used by metamath.exe.
+
+use definitions::escape_html;
+use nameck::Nameset;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::as_str;
+use proof::ProofTreeArray;
+use scopeck::Hyp;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use std::io;
+use std::io::Write;
+use typesetting::TypesettingData;
+
+/// Renders math tokens with the selected typesetting definitions.
+fn render<'b, I>(typesetting: &TypesettingData, alt_html: bool, tokens: I) -> String
+    where I: IntoIterator<Item = &'b [u8]>
+{
+    if alt_html {
+        typesetting.render_alt_html(tokens)
+    } else {
+        typesetting.render_html(tokens)
+    }
+}
+
+fn link(label: &[u8]) -> String {
+    format!("<a href=\"{0}.html\">{0}</a>", escape_html(as_str(label)))
+}
+
+/// A logical step of a proof, as shown in a proof table.
+struct ProofRow {
+    /// The 1-based number of the step.
+    number: usize,
+    /// The numbers of the logical steps it uses.
+    hyps: Vec<String>,
+    /// The statement applied at this step.
+    step: StatementAddress,
+    /// The distance of the step from the final step.
+    indent: u16,
+    /// The math tokens of the formula, including the typecode.
+    math: Vec<Vec<u8>>,
+}
+
+/// Collects the logical steps of a proof in proof order, or `None` if the
+/// proof cannot be reconstructed.
+fn proof_rows(sset: &SegmentSet,
+              nset: &Nameset,
+              scope: &ScopeResult,
+              stmt: StatementRef)
+              -> Option<Vec<ProofRow>> {
+    let arr = match ProofTreeArray::new(sset, nset, scope, stmt) {
+        Ok(arr) => arr,
+        Err(_) => return None,
+    };
+
+    // 0 for syntax steps and a 1-based step number for logical steps, as in
+    // export::export_mmp
+    let mut numbers = Vec::with_capacity(arr.trees.len());
+    let mut next = 0;
+    for tree in &arr.trees {
+        if sset.statement(tree.address).math_at(0).slice == b"|-" {
+            next += 1;
+            numbers.push(next);
+        } else {
+            numbers.push(0);
+        }
+    }
+
+    let indent = arr.indent();
+    let mut rows = Vec::new();
+    for (ix, tree) in arr.trees.iter().enumerate() {
+        if numbers[ix] == 0 {
+            continue;
+        }
+        let mut math = vec![sset.statement(tree.address).math_at(0).slice.to_vec()];
+        math.extend(arr.exprs[ix]
+            .split(|&ch| ch == b' ')
+            .filter(|tok| !tok.is_empty())
+            .map(|tok| tok.to_vec()));
+        rows.push(ProofRow {
+            number: numbers[ix],
+            hyps: tree.children
+                .iter()
+                .filter(|&&child| numbers[child] != 0)
+                .map(|&child| numbers[child].to_string())
+                .collect(),
+            step: tree.address,
+            indent: indent[ix],
+            math: math,
+        });
+    }
+    Some(rows)
+}
+
+/// Returns the `$e` hypotheses of a statement.
+fn essential_hyps<'a>(sset: &'a SegmentSet,
+                      scope: &ScopeResult,
+                      stmt: StatementRef)
+                      -> Vec<StatementRef<'a>> {
+    scope.get(stmt.label()).map_or(Vec::new(), |frame| {
+        frame.hypotheses
+            .iter()
+            .filter_map(|hyp| match *hyp {
+                Hyp::Essential(addr, _) => Some(sset.statement(addr)),
+                Hyp::Floating(..) => None,
+            })
+            .collect()
+    })
+}
+
+/// Returns the mandatory disjoint variable pairs of a statement, each
+/// rendered as two comma-separated variables.
+fn distinct_groups(nset: &Nameset,
+                   scope: &ScopeResult,
+                   typesetting: &TypesettingData,
+                   alt_html: bool,
+                   stmt: StatementRef)
+                   -> Vec<String> {
+    scope.get(stmt.label()).map_or(Vec::new(), |frame| {
+        frame.mandatory_dv
+            .iter()
+            .map(|&(v1, v2)| {
+                let var = |v: usize| {
+                    render(typesetting, alt_html, Some(nset.atom_name(frame.var_list[v])))
+                };
+                format!("{},{}", var(v1), var(v2))
+            })
+            .collect()
+    })
+}
+
+/// Returns the description comment of a statement, without the comment
+/// delimiters.
+fn description(stmt: StatementRef) -> Option<String> {
+    stmt.associated_comment().map(|comment| {
+        let span = comment.span();
+        let text = span.as_ref(&comment.segment().segment.buffer);
+        String::from_utf8_lossy(&text[2..text.len() - 2]).trim().to_owned()
+    })
+}
+
+fn statement_kind(stmt: StatementRef) -> &'static str {
+    match stmt.statement_type() {
+        StatementType::Provable => "Theorem",
+        _ if stmt.label().starts_with(b"df-") => "Definition",
+        _ if stmt.math_len() > 0 && stmt.math_at(0).slice != b"|-" => "Syntax definition",
+        _ => "Axiom",
+    }
+}
+
+/// Writes the HTML page for an `$a` or `$p` statement; see the module
+/// documentation.
+pub fn write_statement_page<W: Write>(sset: &SegmentSet,
+                                      nset: &Nameset,
+                                      scope: &ScopeResult,
+                                      typesetting: &TypesettingData,
+                                      alt_html: bool,
+                                      stmt: StatementRef,
+                                      out: &mut W)
+                                      -> io::Result<()> {
+    let label = escape_html(as_str(stmt.label()));
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(out,
+             "<title>{} - {}</title>",
+             label,
+             typesetting.setting(b"htmltitle").unwrap_or("Metamath"))?;
+    if let Some(css) = typesetting.setting(b"htmlcss") {
+        writeln!(out, "{}", css)?;
+    }
+    writeln!(out, "</head>\n<body>")?;
+    writeln!(out, "<h1>{} {}</h1>", statement_kind(stmt), label)?;
+    if let Some(text) = description(stmt) {
+        writeln!(out, "<p class=\"description\">{}</p>", escape_html(&text))?;
+    }
+
+    let hyps = essential_hyps(sset, scope, stmt);
+    if !hyps.is_empty() {
+        writeln!(out, "<table class=\"hypotheses\">")?;
+        writeln!(out, "<tr><th>Ref</th><th>Expression</th></tr>")?;
+        for hyp in hyps {
+            writeln!(out,
+                     "<tr><td>{}</td><td>{}</td></tr>",
+                     escape_html(as_str(hyp.label())),
+                     render(typesetting, alt_html, hyp.math_iter().map(|t| t.slice)))?;
+        }
+        writeln!(out, "</table>")?;
+    }
+    writeln!(out, "<table class=\"assertion\">")?;
+    writeln!(out, "<tr><th>Ref</th><th>Expression</th></tr>")?;
+    writeln!(out,
+             "<tr><td>{}</td><td>{}</td></tr>",
+             label,
+             render(typesetting, alt_html, stmt.math_iter().map(|t| t.slice)))?;
+    writeln!(out, "</table>")?;
+
+    let groups = distinct_groups(nset, scope, typesetting, alt_html, stmt);
+    if !groups.is_empty() {
+        writeln!(out,
+                 "<p class=\"distinct\">Distinct variable groups: {}</p>",
+                 groups.join(" &nbsp; "))?;
+    }
+
+    if stmt.statement_type() == StatementType::Provable {
+        match proof_rows(sset, nset, scope, stmt) {
+            None => writeln!(out, "<p class=\"proof\">The proof is incomplete or invalid.</p>")?,
+            Some(rows) => {
+                writeln!(out, "<table class=\"proof\">")?;
+                writeln!(out, "<caption>Proof of theorem {}</caption>", label)?;
+                writeln!(out,
+                         "<tr><th>Step</th><th>Hyp</th><th>Ref</th><th>Expression</th></tr>")?;
+                for row in rows {
+                    let step = sset.statement(row.step);
+                    let reference = if step.statement_type() == StatementType::Essential {
+                        escape_html(as_str(step.label()))
+                    } else {
+                        link(step.label())
+                    };
+                    writeln!(out,
+                             "<tr><td>{}</td><td>{}</td><td>{}</td><td><span \
+                              class=\"i\">{}{}</span> {}</td></tr>",
+                             row.number,
+                             row.hyps.join(", "),
+                             reference,
+                             ". ".repeat(row.indent as usize),
+                             row.indent + 1,
+                             render(typesetting, alt_html, row.math.iter().map(|t| &t[..])))?;
+                }
+                writeln!(out, "</table>")?;
+            }
+        }
+    }
+    writeln!(out, "</body>\n</html>")
+}
+
+/// Escapes the characters of rendered math which would end a Markdown
+/// table cell.
+fn escape_cell(text: &str) -> String {
+    text.trim().replace('|', "\\|")
+}
+
+/// Writes a Markdown rendering of an `$a` or `$p` statement, in the
+/// GitHub-flavored dialect: a heading, the description, a table of the
+/// hypotheses, the assertion, the distinct variable groups and for theorems
+/// the proof table.  Math is typeset with the `althtmldef` definitions, as
+/// inline HTML; references link to the pages written by
+/// `write_statement_page`.
+pub fn write_statement_markdown<W: Write>(sset: &SegmentSet,
+                                          nset: &Nameset,
+                                          scope: &ScopeResult,
+                                          typesetting: &TypesettingData,
+                                          stmt: StatementRef,
+                                          out: &mut W)
+                                          -> io::Result<()> {
+    let label = as_str(stmt.label());
+    let math = |tokens: &mut dyn Iterator<Item = &[u8]>| {
+        escape_cell(&typesetting.render_alt_html(tokens))
+    };
+    writeln!(out, "## {} {}\n", statement_kind(stmt), label)?;
+    if let Some(text) = description(stmt) {
+        let lines: Vec<&str> = text.lines().map(|line| line.trim()).collect();
+        writeln!(out, "{}\n", escape_html(&lines.join("\n")))?;
+    }
+
+    let hyps = essential_hyps(sset, scope, stmt);
+    if !hyps.is_empty() {
+        writeln!(out, "| Hypothesis | Expression |\n|---|---|")?;
+        for hyp in hyps {
+            writeln!(out,
+                     "| {} | {} |",
+                     as_str(hyp.label()),
+                     math(&mut hyp.math_iter().map(|t| t.slice)))?;
+        }
+        writeln!(out)?;
+    }
+    writeln!(out, "| Assertion | Expression |\n|---|---|")?;
+    writeln!(out, "| {} | {} |\n", label, math(&mut stmt.math_iter().map(|t| t.slice)))?;
+
+    let groups = distinct_groups(nset, scope, typesetting, true, stmt);
+    if !groups.is_empty() {
+        let groups: Vec<String> = groups.iter().map(|group| group.trim().to_owned()).collect();
+        writeln!(out, "Distinct variable groups: {}\n", groups.join("; "))?;
+    }
+
+    if stmt.statement_type() == StatementType::Provable {
+        match proof_rows(sset, nset, scope, stmt) {
+            None => writeln!(out, "The proof is incomplete or invalid.")?,
+            Some(rows) => {
+                writeln!(out, "| Step | Hyp | Ref | Expression |\n|---|---|---|---|")?;
+                for row in rows {
+                    let step = sset.statement(row.step);
+                    let reference = if step.statement_type() == StatementType::Essential {
+                        as_str(step.label()).to_owned()
+                    } else {
+                        format!("[{0}]({0}.html)", as_str(step.label()))
+                    };
+                    writeln!(out,
+                             "| {} | {} | {} | {}{} |",
+                             row.number,
+                             row.hyps.join(", "),
+                             reference,
+                             ". ".repeat(row.indent as usize),
+                             math(&mut row.math.iter().map(|t| &t[..])))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use test_fixtures::mkdb;
+
+const PAGES: &[u8] = b"$c |- ( ) -> wff $.
+$v ph ps $.
+$( $t htmldef \"->\" as \" &rarr; \"; althtmldef \"->\" as \" &#8594; \";
+   althtmldef \"|-\" as \"&#8866; \"; htmltitle \"Test\"; $)
+wph $f wff ph $.
+wps $f wff ps $.
+wi $a wff ( ph -> ps ) $.
+${ min $e |- ph $. maj $e |- ( ph -> ps ) $. ax-mp $a |- ps $. $}
+${
+  th.1 $e |- ph $.
+  th.2 $e |- ( ph -> ps ) $.
+  $( Modus ponens & more. $)
+  th $p |- ps $= wph wps th.1 th.2 ax-mp $.
+$}
+";
+
+#[test]
+fn test_statement_page() {
+    let mut db = mkdb(PAGES);
+    let sset = db.parse_result().clone();
+    let nset = db.name_result().clone();
+    let scope = db.scope_result().clone();
+    let ts = db.typesetting_result().clone();
+    let stmt = db.statement("th").unwrap();
+    let mut out = Vec::new();
+    ::html::write_statement_page(&sset, &nset, &scope, &ts, false, stmt, &mut out).unwrap();
+    let page = String::from_utf8(out).unwrap();
+    assert!(page.contains("<title>th - Test</title>"));
+    assert!(page.contains("<h1>Theorem th</h1>"));
+    assert!(page.contains("<p class=\"description\">Modus ponens &amp; more.</p>"));
+    assert!(page.contains("<tr><td>th.2</td><td>|- ( ph  &rarr; ps ) </td></tr>"));
+    assert!(page.contains("<tr><td>2</td><td></td><td>th.2</td><td><span class=\"i\">. 2</span> \
+                           |- ( ph  &rarr; ps ) </td></tr>"));
+    assert!(page.contains("<tr><td>3</td><td>1, 2</td><td><a href=\"ax-mp.html\">ax-mp</a></td>\
+                           <td><span class=\"i\">1</span> |- ps </td></tr>"));
+}
+
+#[test]
+fn test_statement_markdown() {
+    let mut db = mkdb(PAGES);
+    let mut out = Vec::new();
+    db.export_markdown("th", &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "## Theorem th
+
+Modus ponens &amp; more.
+
+| Hypothesis | Expression |
+|---|---|
+| th.1 | &#8866; ph |
+| th.2 | &#8866; ( ph  &#8594; ps ) |
+
+| Assertion | Expression |
+|---|---|
+| th | &#8866; ps |
+
+| Step | Hyp | Ref | Expression |
+|---|---|---|---|
+| 1 |  | th.1 | . &#8866; ph |
+| 2 |  | th.2 | . &#8866; ( ph  &#8594; ps ) |
+| 3 | 1, 2 | [ax-mp](ax-mp.html) | &#8866; ps |
+");
+    assert!(db.export_markdown("nonexistent", &mut Vec::new()).is_err());
+}
@@ -0,0 +1,179 @@
+//! Export of the structure of a database for consumption by other tools.
+//!
+//! `DatabaseJson::new` collects every math-bearing statement with its math
+//! string, and for assertions their frame (hypotheses and mandatory disjoint
+//! variable pairs) and the parse trees of their formulas.  Parse trees are
+//! given as s-expressions of syntax axiom labels, as written by
+//! `SyntaxTree::to_sexpr`, and are omitted for formulas which do not parse.
+//!
+//! The types implement `serde::Serialize`, so they can be written in any
+//! serde format; `export_json` writes JSON.
+
+use grammar::SyntaxParser;
+use grammar::frame_variables;
+use nameck::Nameset;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::TokenPtr;
+use parser::as_str;
+use scopeck::Frame;
+use scopeck::Hyp;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use serde_json;
+use std::io::Write;
+
+/// A hypothesis of a frame.
+#[derive(Clone,Debug,Serialize)]
+pub struct HypothesisJson {
+    /// The label of the hypothesis.
+    pub label: String,
+    /// `$f` or `$e`.
+    pub kind: &'static str,
+    /// The math string, including the typecode.
+    pub math: Vec<String>,
+    /// The parse tree, if the formula parses.
+    pub parse: Option<String>,
+}
+
+/// The frame of an assertion.
+#[derive(Clone,Debug,Serialize)]
+pub struct FrameJson {
+    /// The mandatory hypotheses, in the order they are applied.
+    pub hypotheses: Vec<HypothesisJson>,
+    /// The pairs of variables which must be substituted with disjoint
+    /// expressions.
+    pub disjoint: Vec<(String, String)>,
+}
+
+/// A statement of the database.
+#[derive(Clone,Debug,Serialize)]
+pub struct StatementJson {
+    /// The label, for statements which have one.
+    pub label: Option<String>,
+    /// The keyword of the statement, such as `$a`.
+    pub kind: &'static str,
+    /// The math string, including the typecode for labeled statements.
+    pub math: Vec<String>,
+    /// The frame, for `$a` and `$p` statements.
+    pub frame: Option<FrameJson>,
+    /// The parse tree of the math string of a `$a` or `$p` statement, if it
+    /// parses.
+    pub parse: Option<String>,
+}
+
+/// The statements of a database, in database order.
+#[derive(Clone,Debug,Default,Serialize)]
+pub struct DatabaseJson {
+    /// The statements.
+    pub statements: Vec<StatementJson>,
+}
+
+fn math_strings(stmt: StatementRef) -> Vec<String> {
+    stmt.math_iter().map(|tok| as_str(tok.slice).to_owned()).collect()
+}
+
+fn parse(parser: &SyntaxParser,
+         sset: &SegmentSet,
+         nset: &Nameset,
+         frame: &Frame,
+         stmt: StatementRef)
+         -> Option<String> {
+    let math: Vec<TokenPtr> = stmt.math_iter().map(|tok| tok.slice).collect();
+    parser.parse_math(&frame_variables(nset, frame), &math).map(|(_, tree)| tree.to_sexpr(sset))
+}
+
+impl StatementJson {
+    /// Describes a statement, or returns `None` for statements without a
+    /// math string such as comments.  `parser` is used for the parse trees.
+    pub fn new(parser: &SyntaxParser,
+               sset: &SegmentSet,
+               nset: &Nameset,
+               scope: &ScopeResult,
+               stmt: StatementRef)
+               -> Option<StatementJson> {
+        let kind = match stmt.statement_type() {
+            StatementType::Constant => "$c",
+            StatementType::Variable => "$v",
+            StatementType::Disjoint => "$d",
+            StatementType::Floating => "$f",
+            StatementType::Essential => "$e",
+            StatementType::Axiom => "$a",
+            StatementType::Provable => "$p",
+            _ => return None,
+        };
+        let label = if stmt.statement_type().takes_label() {
+            Some(as_str(stmt.label()).to_owned())
+        } else {
+            None
+        };
+        let (frame, tree) = match kind {
+            "$a" | "$p" => {
+                match scope.get(stmt.label()) {
+                    Some(frame) => {
+                        (Some(frame_json(parser, sset, nset, frame)),
+                         parse(parser, sset, nset, frame, stmt))
+                    }
+                    None => (None, None),
+                }
+            }
+            _ => (None, None),
+        };
+        Some(StatementJson {
+            label: label,
+            kind: kind,
+            math: math_strings(stmt),
+            frame: frame,
+            parse: tree,
+        })
+    }
+}
+
+impl DatabaseJson {
+    /// Collects the statements of a database; see the module documentation.
+    pub fn new(sset: &SegmentSet, nset: &Nameset, scope: &ScopeResult) -> DatabaseJson {
+        let parser = SyntaxParser::new(sset, nset, scope);
+        let mut db = DatabaseJson::default();
+        for sref in sset.segments() {
+            for stmt in sref {
+                db.statements.extend(StatementJson::new(&parser, sset, nset, scope, stmt));
+            }
+        }
+        db
+    }
+}
+
+fn frame_json(parser: &SyntaxParser,
+              sset: &SegmentSet,
+              nset: &Nameset,
+              frame: &Frame)
+              -> FrameJson {
+    let var_name = |v: usize| as_str(nset.atom_name(frame.var_list[v])).to_owned();
+    FrameJson {
+        hypotheses: frame.hypotheses
+            .iter()
+            .map(|hyp| {
+                let stmt = sset.statement(hyp.address());
+                HypothesisJson {
+                    label: as_str(stmt.label()).to_owned(),
+                    kind: match *hyp {
+                        Hyp::Floating(..) => "$f",
+                        Hyp::Essential(..) => "$e",
+                    },
+                    math: math_strings(stmt),
+                    parse: parse(parser, sset, nset, frame, stmt),
+                }
+            })
+            .collect(),
+        disjoint: frame.mandatory_dv.iter().map(|&(v1, v2)| (var_name(v1), var_name(v2))).collect(),
+    }
+}
+
+/// Writes the structure of a database as JSON.
+pub fn export_json<W: Write>(sset: &SegmentSet,
+                             nset: &Nameset,
+                             scope: &ScopeResult,
+                             out: &mut W)
+                             -> serde_json::Result<()> {
+    serde_json::to_writer(out, &DatabaseJson::new(sset, nset, scope))
+}
@@ -0,0 +1,25 @@
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_json_export() {
+    let text = format!("{}${{ $d ph ps $. th1.1 $e |- ph $.
+  th1 $p |- ( ps -> ph ) $= wph wps wph wi th1.1 wph wps ax-1 ax-mp $. $}}
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let mut out = Vec::new();
+    db.export_json(&mut out).unwrap();
+    let json = String::from_utf8(out).unwrap();
+    assert!(json.starts_with("{\"statements\":[{\"label\":null,\"kind\":\"$c\",\
+                              \"math\":[\"|-\",\"wff\",\"(\",\"->\",\")\"],\"frame\":null,\
+                              \"parse\":null},"));
+    assert!(json.contains("{\"label\":\"ax-1\",\"kind\":\"$a\",\
+                           \"math\":[\"|-\",\"(\",\"ph\",\"->\",\"(\",\"ps\",\"->\",\"ph\",\")\",\")\"],\
+                           \"frame\":{\"hypotheses\":[\
+                           {\"label\":\"wph\",\"kind\":\"$f\",\"math\":[\"wff\",\"ph\"],\"parse\":\"ph\"},\
+                           {\"label\":\"wps\",\"kind\":\"$f\",\"math\":[\"wff\",\"ps\"],\"parse\":\"ps\"}],\
+                           \"disjoint\":[]},\"parse\":\"(wi ph (wi ps ph))\"}"));
+    assert!(json.contains("{\"label\":\"th1.1\",\"kind\":\"$e\",\"math\":[\"|-\",\"ph\"],\
+                           \"parse\":\"ph\"}],\"disjoint\":[[\"ph\",\"ps\"]]},\"parse\":\"(wi ps ph)\"}"));
+}
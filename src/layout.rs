@@ -0,0 +1,122 @@
+//! Layout of generated `.mm` source text.
+//!
+//! Text which is copied from the loaded files is always written back as it
+//! was; this module decides how new text is laid out, such as a rewritten
+//! proof, a drafted statement or a cut-down declaration.  The defaults follow
+//! the conventions of set.mm, as produced by metamath.exe: lines of at most 79
+//! characters, statements indented by 2 for each enclosing `${ $}` group plus
+//! 2, math strings continued 4 columns further in, and proofs 2 columns
+//! further in than their statement.
+//!
+//! The policy in effect is `DbOptions::format`.
+
+use parser::StatementRef;
+use segment_set::SegmentSet;
+use std::cmp;
+
+/// How to lay out generated source text; see the module documentation.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct FormatPolicy {
+    /// The maximum length of a line, unless a single word is longer.
+    pub line_width: usize,
+    /// The indentation added for each enclosing `${ $}` group; top-level
+    /// statements are indented by this much too.
+    pub block_indent: usize,
+    /// The indentation of the continuation lines of the math string of a
+    /// statement, relative to the statement.
+    pub math_indent: usize,
+    /// The indentation of the lines of a proof, relative to its statement;
+    /// proofs are indented by at least 6 columns as in set.mm.
+    pub proof_indent: usize,
+    /// The number of spaces between the words of a statement.
+    pub token_spacing: usize,
+    /// True to wrap the text of comments at the line width; otherwise a
+    /// comment is written on one line.
+    pub wrap_comments: bool,
+}
+
+impl Default for FormatPolicy {
+    fn default() -> FormatPolicy {
+        FormatPolicy {
+            line_width: 79,
+            block_indent: 2,
+            math_indent: 4,
+            proof_indent: 2,
+            token_spacing: 1,
+            wrap_comments: true,
+        }
+    }
+}
+
+/// Returns the number of spaces and tabs before a statement on its line.
+pub fn statement_indent(sset: &SegmentSet, stmt: StatementRef) -> usize {
+    let sinfo = sset.source_info(stmt.segment().id);
+    let start = (sinfo.span.start + stmt.span().start) as usize;
+    sinfo.text[..start].iter().rev().take_while(|&&ch| ch == b' ' || ch == b'\t').count()
+}
+
+/// Fills lines with words, the first line starting at column `first` and the
+/// others at `next`, and returns them without their indentation.
+fn fill(words: &[&str], separator: &str, first: usize, next: usize, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut column = first;
+    for word in words {
+        if !line.is_empty() && column + line.len() + separator.len() + word.len() > width {
+            lines.push(line);
+            line = String::new();
+            column = next;
+        }
+        if !line.is_empty() {
+            line.push_str(separator);
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    lines
+}
+
+impl FormatPolicy {
+    /// Returns the indentation of a statement inside `depth` groups.
+    pub fn indent(&self, depth: usize) -> usize {
+        self.block_indent * (depth + 1)
+    }
+
+    /// Lays out a statement, given as its words including the keyword and
+    /// the final `$.`, starting at column `indent`.  The first line is
+    /// returned without indentation and the others with the math indentation.
+    pub fn statement_lines(&self, indent: usize, words: &[&str]) -> Vec<String> {
+        let separator = " ".repeat(cmp::max(self.token_spacing, 1));
+        let next = indent + self.math_indent;
+        let mut lines = fill(words, &separator, indent, next, self.line_width);
+        for line in lines.iter_mut().skip(1) {
+            line.insert_str(0, &" ".repeat(self.math_indent));
+        }
+        lines
+    }
+
+    /// Lays out a comment with the given text, starting at column `indent`.
+    /// The first line is returned without indentation and the others aligned
+    /// with the text of the first.
+    pub fn comment_lines(&self, indent: usize, text: &str) -> Vec<String> {
+        let mut words = vec!["$("];
+        words.extend(text.split_whitespace());
+        words.push("$)");
+        if !self.wrap_comments {
+            return vec![words.join(" ")];
+        }
+        let mut lines = fill(&words, " ", indent, indent + 3, self.line_width);
+        for line in lines.iter_mut().skip(1) {
+            line.insert_str(0, "   ");
+        }
+        lines
+    }
+
+    /// Returns the indentation and line width for a proof of a statement
+    /// indented by `indent`, as given to `ProofTreePrinter`.
+    pub fn proof_layout(&self, indent: usize) -> (u16, u16) {
+        let proof = (indent + self.proof_indent).clamp(6, 1000);
+        let width = cmp::min(cmp::max(self.line_width, proof + 10), u16::MAX as usize);
+        (proof as u16, width as u16)
+    }
+}
@@ -0,0 +1,25 @@
+use layout::FormatPolicy;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_format_policy() {
+    let mut policy = FormatPolicy { line_width: 20, ..FormatPolicy::default() };
+    let words = ["th1", "$p", "|-", "(", "ph", "->", "(", "ps", "->", "ph", ")", ")", "$="];
+    assert_eq!(policy.statement_lines(0, &words),
+               vec!["th1 $p |- ( ph -> (", "    ps -> ph ) ) $="]);
+    assert_eq!(policy.comment_lines(0, " Planned theorem th1. "),
+               vec!["$( Planned theorem", "   th1. $)"]);
+    policy.wrap_comments = false;
+    assert_eq!(policy.comment_lines(0, "Planned theorem th1."),
+               vec!["$( Planned theorem th1. $)"]);
+    assert_eq!(policy.indent(1), 4);
+    assert_eq!(policy.proof_layout(0), (6, 20));
+    assert_eq!(policy.proof_layout(6), (8, 20));
+
+    let text = format!("{}${{\n      th1 $p |- ( ph -> ( ph -> ph ) ) $= ( ax-1 ) AAB $.\n$}}\n",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let proof = db.rewrap_proof("th1").unwrap().unwrap();
+    assert!(proof.starts_with("      ( ax-1 ) AAB $."), "{:?}", proof);
+}
@@ -88,6 +88,12 @@ impl LineCache {
         self.map.entry((buf.as_ptr() as usize, buf.len())).or_insert_with(|| make_index(buf))
     }
 
+    /// Forgets the indexes of all buffers, which must be done before any of
+    /// them is freed if the cache is to be used again.
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+
     /// Map a line to a buffer index.  Panics if out of range.
     pub fn to_offset(&mut self, buf: &[u8], line: u32) -> usize {
         line_to_offset(buf, self.get_index(buf), line - 1)
@@ -0,0 +1,15 @@
+use parser::Span;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_lookup_position() {
+    let mut db = mkdb(b"$c X $.\n  $c Y $.\n");
+    let (addr, _) = db.statement_at("test.mm", 12).unwrap();
+    assert_eq!(db.lookup_position(addr, Span::null()), ("test.mm".to_owned(), 2, 3));
+    let (found, span) = db.lookup_span("test.mm", 2, 6).unwrap();
+    assert_eq!(found, addr);
+    assert_eq!(db.lookup_position(found, span), ("test.mm".to_owned(), 2, 6));
+    assert_eq!(db.lookup_span("test.mm", 1, 9), None);
+    assert_eq!(db.lookup_span("test.mm", 4, 1), None);
+    assert_eq!(db.lookup_span("other.mm", 1, 1), None);
+}
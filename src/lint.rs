@@ -0,0 +1,168 @@
+//! Detection of unused statements and hypotheses.
+//!
+//! This pass reports declarations which contribute nothing to the database:
+//!
+//! * `$e` hypotheses of a theorem which its proof never uses, which make the
+//!   theorem needlessly weak;
+//! * `$f` statements which are neither hypotheses of any assertion nor used
+//!   in any proof, and variables which no `$e`, `$a`, `$p` or `$d` statement
+//!   mentions;
+//! * theorems which no other proof uses.
+//!
+//! Proofs are read without running the verifier; compressed proofs are
+//! decoded with `proof::decode_compressed`, since they refer to hypotheses by
+//! position rather than by label.  Hypotheses of incomplete or malformed
+//! proofs are not checked.
+//!
+//! Findings are suppressed with `$j lint_ignore 'name';`, where `name` is the
+//! label of the statement the finding is reported on, the label of the `$e`
+//! or `$f` hypothesis concerned, or the variable concerned.
+
+use diag::Diagnostic;
+use extra_info::JDirective;
+use extra_info::parse_directive;
+use extra_info::process_j_commands;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::Token;
+use proof::decode_compressed;
+use scopeck::Hyp;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use usage::proof_labels;
+use util::HashSet;
+use util::new_set;
+
+/// Analysis pass result for unused statements and hypotheses.
+#[derive(Default,Clone,Debug)]
+pub struct LintResult {
+    diagnostics: Vec<(StatementAddress, Diagnostic)>,
+}
+
+/// Returns the hypotheses used by the proof of a `$p` statement, or `None` if
+/// the proof is incomplete or cannot be decoded.
+fn used_hypotheses(sset: &SegmentSet,
+                   scope: &ScopeResult,
+                   stmt: StatementRef)
+                   -> Option<HashSet<StatementAddress>> {
+    let mut used = new_set();
+    match decode_compressed(sset, scope, stmt) {
+        Some(Ok(arr)) => {
+            for tree in &arr.trees {
+                used.insert(tree.address);
+            }
+        }
+        Some(Err(_)) => return None,
+        None => {
+            let frame = scope.get(stmt.label())?;
+            let (labels, incomplete) = proof_labels(stmt);
+            if incomplete {
+                return None;
+            }
+            for hyp in frame.hypotheses.iter() {
+                let label = sset.statement(hyp.address()).label();
+                if labels.iter().any(|&(_, used)| used == label) {
+                    used.insert(hyp.address());
+                }
+            }
+        }
+    }
+    Some(used)
+}
+
+impl LintResult {
+    /// Checks the whole database for unused statements and hypotheses.
+    pub fn new(sset: &SegmentSet, scope: &ScopeResult) -> LintResult {
+        let mut result = LintResult::default();
+        let mut ignored: HashSet<Token> = new_set();
+        for command in process_j_commands(sset) {
+            if let Ok(JDirective::LintIgnore(name)) = parse_directive(&command) {
+                ignored.insert(name.into_bytes().into_boxed_slice());
+            }
+        }
+
+        // labels referenced by proofs, hypotheses of frames, and symbols
+        // used in math strings
+        let mut referenced: HashSet<&[u8]> = new_set();
+        let mut frame_hyps: HashSet<StatementAddress> = new_set();
+        let mut symbols: HashSet<&[u8]> = new_set();
+        for sref in sset.segments() {
+            for stmt in sref {
+                match stmt.statement_type() {
+                    StatementType::Axiom | StatementType::Provable => {
+                        if let Some(frame) = scope.get(stmt.label()) {
+                            frame_hyps.extend(frame.hypotheses.iter().map(Hyp::address));
+                        }
+                        if stmt.statement_type() == StatementType::Provable {
+                            for (_, label) in proof_labels(stmt).0 {
+                                if label != stmt.label() {
+                                    referenced.insert(label);
+                                }
+                            }
+                        }
+                    }
+                    StatementType::Essential | StatementType::Disjoint => {}
+                    _ => continue,
+                }
+                symbols.extend(stmt.math_iter().map(|tok| tok.slice));
+            }
+        }
+
+        for sref in sset.segments() {
+            for stmt in sref {
+                let label = stmt.label();
+                match stmt.statement_type() {
+                    StatementType::Provable => {
+                        if ignored.contains(label) {
+                            continue;
+                        }
+                        if !referenced.contains(label) {
+                            result.diagnostics.push((stmt.address(), Diagnostic::UnusedTheorem));
+                        }
+                        let frame = match scope.get(label) {
+                            Some(frame) => frame,
+                            None => continue,
+                        };
+                        let used = match used_hypotheses(sset, scope, stmt) {
+                            Some(used) => used,
+                            None => continue,
+                        };
+                        for hyp in frame.hypotheses.iter() {
+                            if let Hyp::Essential(addr, _) = *hyp {
+                                if !used.contains(&addr) &&
+                                   !ignored.contains(sset.statement(addr).label()) {
+                                    result.diagnostics
+                                        .push((stmt.address(), Diagnostic::UnusedHypothesis(addr)));
+                                }
+                            }
+                        }
+                    }
+                    StatementType::Floating if stmt.math_len() == 2 => {
+                        let var = stmt.math_at(1).slice;
+                        if !frame_hyps.contains(&stmt.address()) && !referenced.contains(label) &&
+                           !ignored.contains(label) && !ignored.contains(var) {
+                            result.diagnostics.push((stmt.address(), Diagnostic::UnusedFloating));
+                        }
+                    }
+                    StatementType::Variable => {
+                        for index in 0..stmt.math_len() {
+                            let var = stmt.math_at(index).slice;
+                            if !symbols.contains(var) && !ignored.contains(var) {
+                                result.diagnostics
+                                    .push((stmt.address(), Diagnostic::UnusedVariable(index)));
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        result
+    }
+
+    /// Reports unused statements and hypotheses.
+    pub fn diagnostics(&self) -> Vec<(StatementAddress, Diagnostic)> {
+        self.diagnostics.clone()
+    }
+}
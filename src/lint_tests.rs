@@ -0,0 +1,34 @@
+use diag::Diagnostic;
+use diag::DiagnosticClass;
+use parser::as_str;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_lint() {
+    let mut db = mkdb(b"$c |- wff $.\n$v ph ps ch $.\n\
+                        wph $f wff ph $.\nwps $f wff ps $.\nwch $f wff ch $.\n$v ta $.\n\
+                        ${ h1 $e |- ph $. h2 $e |- ps $. th1 $p |- ph $= h1 $. $}\n\
+                        ${ h3 $e |- ph $. h4 $e |- ps $. th2 $p |- ph $= ( th1 ) ABCDE $. $}\n\
+                        ${ h5 $e |- ph $. h6 $e |- ps $. th3 $p |- ph $= ( ) C $. $}\n\
+                        $( $j lint_ignore 'th2'; $)\n");
+    assert!(db.diag_notations(vec![DiagnosticClass::Parse,
+                                   DiagnosticClass::Scope,
+                                   DiagnosticClass::Verify])
+        .is_empty());
+    let sset = db.parse_result().clone();
+    let nset = db.name_result().clone();
+    let hyp = |label: &[u8]| nset.lookup_label(label).unwrap().address;
+    let (h2, h6) = (hyp(b"h2"), hyp(b"h6"));
+    let found: Vec<(String, Diagnostic)> = db.lint_result()
+        .diagnostics()
+        .into_iter()
+        .map(|(addr, diag)| (as_str(sset.statement(addr).label()).to_owned(), diag))
+        .collect();
+    assert_eq!(found,
+               vec![("".to_owned(), Diagnostic::UnusedVariable(2)),
+                    ("wch".to_owned(), Diagnostic::UnusedFloating),
+                    ("".to_owned(), Diagnostic::UnusedVariable(0)),
+                    ("th1".to_owned(), Diagnostic::UnusedHypothesis(h2)),
+                    ("th3".to_owned(), Diagnostic::UnusedTheorem),
+                    ("th3".to_owned(), Diagnostic::UnusedHypothesis(h6))]);
+}
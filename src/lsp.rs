@@ -0,0 +1,589 @@
+//! A Language Server Protocol server for Metamath source files.
+//!
+//! The server is started on a database and speaks LSP over standard input and
+//! output, so editors can use the crate as their Metamath engine.  It offers:
+//!
+//! * diagnostics from the parse, scope, verify and grammar passes, published
+//!   for every open document after each change;
+//! * go-to-definition for labels (statement labels and labels used in
+//!   proofs) and math symbols (to their `$c` or `$v` declaration);
+//! * hover, showing the statement a label or symbol refers to and its
+//!   description comment;
+//! * document symbols, listing the `$a` and `$p` statements of a file within
+//!   the tree of its headings (see `outline`).
+//!
+//! Documents are synchronized in full: the text of each open document
+//! overrides the file on disk, and the database is reparsed incrementally
+//! after every change.  Documents are matched to the files of the database
+//! by path; documents which are not part of the database are ignored.
+//!
+//! Columns are counted in bytes rather than UTF-16 code units, which agree
+//! for the ASCII text of Metamath databases.
+
+use database::Database;
+use diag::DiagnosticClass;
+use diag::Level;
+use diag::Notation;
+use line_cache::LineCache;
+use outline::Outline;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::TokenIndex;
+use parser::as_str;
+use segment_set::SegmentSet;
+use segment_set::StatementPart;
+use serde_json::Value;
+use serde_json;
+use std::env;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+use util::HashMap;
+use util::new_map;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// LSP `SymbolKind` values.
+const SYMBOL_NAMESPACE: u32 = 3;
+const SYMBOL_FUNCTION: u32 = 12;
+const SYMBOL_CONSTANT: u32 = 14;
+
+/// The passes whose diagnostics are published.
+const CLASSES: [DiagnosticClass; 4] = [DiagnosticClass::Parse,
+                                       DiagnosticClass::Scope,
+                                       DiagnosticClass::Verify,
+                                       DiagnosticClass::Grammar];
+
+/// Converts a `file:` URI to a path, decoding percent escapes.
+fn uri_path(uri: &str) -> String {
+    let path = uri.trim_start_matches("file://").as_bytes();
+    let mut out = Vec::with_capacity(path.len());
+    let mut ix = 0;
+    while ix < path.len() {
+        let escaped = if path[ix] == b'%' && ix + 2 < path.len() {
+            u8::from_str_radix(&String::from_utf8_lossy(&path[ix + 1..ix + 3]), 16).ok()
+        } else {
+            None
+        };
+        match escaped {
+            Some(ch) => {
+                out.push(ch);
+                ix += 3;
+            }
+            None => {
+                out.push(path[ix]);
+                ix += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Converts a file name of the database to a `file:` URI.
+fn name_uri(name: &str) -> String {
+    let path = fs::canonicalize(name)
+        .or_else(|_| env::current_dir().map(|dir| dir.join(name)))
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| name.to_owned());
+    let mut uri = "file://".to_owned();
+    for ch in path.chars() {
+        match ch {
+            ' ' => uri.push_str("%20"),
+            '%' => uri.push_str("%25"),
+            '#' => uri.push_str("%23"),
+            '?' => uri.push_str("%3F"),
+            _ => uri.push(ch),
+        }
+    }
+    uri
+}
+
+/// Returns whether a document path names a file of the database.
+fn same_file(path: &str, name: &str) -> bool {
+    if path == name {
+        return true;
+    }
+    match (fs::canonicalize(path), fs::canonicalize(name)) {
+        (Ok(p1), Ok(p2)) => p1 == p2,
+        _ => Path::new(path).ends_with(name) && Path::new(name).is_relative(),
+    }
+}
+
+/// Finds the label a proof token refers to: the token itself, without the
+/// local label (`3:label`) or hypothesis name (`hyp=label`) of a normal
+/// proof.  Compressed proofs only refer to labels in their label list.
+fn proof_label(stmt: StatementRef<'_>, ix: TokenIndex) -> Option<&[u8]> {
+    if stmt.proof_slice_at(0) == b"(" {
+        let end = (0..stmt.proof_len()).position(|i| stmt.proof_slice_at(i) == b")");
+        if ix == 0 || end.is_some_and(|end| ix >= end as TokenIndex) {
+            return None;
+        }
+    }
+    let tok = stmt.proof_slice_at(ix);
+    let start = tok.iter().rposition(|&ch| ch == b':' || ch == b'=').map_or(0, |pos| pos + 1);
+    Some(&tok[start..])
+}
+
+/// Returns the text of a statement without its proof, e.g. `ax-1 $a |- ...
+/// $.`.
+fn statement_text(stmt: StatementRef) -> String {
+    let keyword = match stmt.statement_type() {
+        StatementType::Constant => "$c",
+        StatementType::Variable => "$v",
+        StatementType::Floating => "$f",
+        StatementType::Essential => "$e",
+        StatementType::Axiom => "$a",
+        StatementType::Provable => "$p",
+        StatementType::Disjoint => "$d",
+        _ => "",
+    };
+    let mut text = String::new();
+    if stmt.statement_type().takes_label() {
+        text.push_str(as_str(stmt.label()));
+        text.push(' ');
+    }
+    text.push_str(keyword);
+    for tok in stmt.math_iter() {
+        text.push(' ');
+        text.push_str(as_str(tok.slice));
+    }
+    text.push_str(if stmt.statement_type() == StatementType::Provable {
+        " $= ... $."
+    } else {
+        " $."
+    });
+    text
+}
+
+/// Returns the description comment of a statement, without the comment
+/// delimiters.
+fn description(stmt: StatementRef) -> Option<String> {
+    stmt.associated_comment().map(|comment| {
+        let span = comment.span();
+        let text = span.as_ref(&comment.segment().segment.buffer);
+        String::from_utf8_lossy(&text[2..text.len() - 2]).trim().to_owned()
+    })
+}
+
+/// Substitutes the arguments of a notation into its message.
+fn notation_message(notation: &Notation) -> String {
+    let mut message = notation.message.to_owned();
+    for &(name, ref value) in &notation.args {
+        message = message.replace(&format!("{{{}}}", name), value);
+    }
+    message
+}
+
+fn error(code: i64, message: &str) -> Result<Value, (i64, String)> {
+    Err((code, message.to_owned()))
+}
+
+/// The state of a language server; see the module documentation.
+pub struct LanguageServer<'a> {
+    db: &'a mut Database,
+    start: String,
+    /// The text of the open documents, by database file name.
+    documents: HashMap<String, Vec<u8>>,
+    /// The URIs of the open documents, by database file name.
+    uris: HashMap<String, String>,
+    lines: LineCache,
+}
+
+impl<'a> LanguageServer<'a> {
+    /// Creates a server for a database which has been loaded from `start`.
+    pub fn new(db: &'a mut Database, start: String) -> LanguageServer<'a> {
+        LanguageServer {
+            db: db,
+            start: start,
+            documents: new_map(),
+            uris: new_map(),
+            lines: LineCache::default(),
+        }
+    }
+
+    /// Finds the database file name of a document.
+    fn document_name(&mut self, uri: &str) -> Option<String> {
+        let path = uri_path(uri);
+        self.db
+            .parse_result()
+            .file_order()
+            .into_iter()
+            .find(|name| same_file(&path, name))
+            .map(|name| name.to_owned())
+    }
+
+    fn uri(&self, name: &str) -> String {
+        self.uris.get(name).cloned().unwrap_or_else(|| name_uri(name))
+    }
+
+    fn file_text<'b>(sset: &'b SegmentSet, name: &str) -> Option<&'b [u8]> {
+        sset.segments()
+            .into_iter()
+            .map(|sref| sset.source_info(sref.id))
+            .find(|sinfo| sinfo.name == name)
+            .map(|sinfo| &sinfo.text[..])
+    }
+
+    /// Converts a byte offset in a file to an LSP position.
+    fn position(&mut self, text: &[u8], offset: usize) -> Value {
+        let (line, col) = self.lines.from_offset(text, offset);
+        json!({"line": line - 1, "character": col - 1})
+    }
+
+    /// Converts an LSP position to a byte offset in a file.
+    fn offset(&mut self, text: &[u8], position: &Value) -> Option<usize> {
+        let line = position.get("line").and_then(|v| v.as_u64())? as u32;
+        let character = position.get("character").and_then(|v| v.as_u64())? as usize;
+        if line + 1 > self.lines.from_offset(text, text.len()).0 {
+            return None;
+        }
+        let start = self.lines.to_offset(text, line + 1);
+        Some((start + character).min(LineCache::line_end(text, start)))
+    }
+
+    fn range(&mut self, text: &[u8], start: usize, end: usize) -> Value {
+        json!({"start": self.position(text, start), "end": self.position(text, end)})
+    }
+
+    /// Returns the file name and text offsets of part of a statement.
+    fn statement_range(&mut self,
+                       sset: &SegmentSet,
+                       addr: StatementAddress,
+                       label_only: bool)
+                       -> (String, Value) {
+        let stmt = sset.statement(addr);
+        let sinfo = sset.source_info(addr.segment_id).clone();
+        let base = sinfo.span.start as usize;
+        let span = stmt.span();
+        let end = if label_only {
+            span.start as usize + stmt.label().len()
+        } else {
+            span.end as usize
+        };
+        let range = self.range(&sinfo.text, base + span.start as usize, base + end);
+        (sinfo.name.clone(), range)
+    }
+
+    /// Resolves the label or math symbol at a document position to the
+    /// statement defining it.
+    fn target(&mut self, params: &Value) -> Option<StatementAddress> {
+        let name = self.document_name(params["textDocument"]["uri"].as_str()?)?;
+        let sset = self.db.parse_result().clone();
+        let offset = self.offset(Self::file_text(&sset, &name)?, params.get("position")?)?;
+        let (addr, part) = sset.statement_at(&name, offset)?;
+        let stmt = sset.statement(addr);
+        let nset = self.db.name_result().clone();
+        match part {
+            StatementPart::Label => Some(addr),
+            StatementPart::Math(ix) => {
+                nset.lookup_symbol(stmt.math_at(ix).slice).map(|sym| sym.address.statement)
+            }
+            StatementPart::Proof(ix) => {
+                proof_label(stmt, ix)
+                    .and_then(|label| nset.lookup_label(label))
+                    .map(|label| label.address)
+            }
+            StatementPart::Other => None,
+        }
+    }
+
+    fn definition(&mut self, params: &Value) -> Result<Value, (i64, String)> {
+        Ok(match self.target(params) {
+            Some(addr) => {
+                let sset = self.db.parse_result().clone();
+                let (name, range) = self.statement_range(&sset, addr, false);
+                json!({"uri": self.uri(&name), "range": range})
+            }
+            None => Value::Null,
+        })
+    }
+
+    fn hover(&mut self, params: &Value) -> Result<Value, (i64, String)> {
+        Ok(match self.target(params) {
+            Some(addr) => {
+                let sset = self.db.parse_result().clone();
+                let stmt = sset.statement(addr);
+                let mut text = format!("```metamath\n{}\n```", statement_text(stmt));
+                if let Some(comment) = description(stmt) {
+                    text.push_str("\n\n");
+                    text.push_str(&comment);
+                }
+                json!({"contents": {"kind": "markdown", "value": text}})
+            }
+            None => Value::Null,
+        })
+    }
+
+    fn document_symbols(&mut self, params: &Value) -> Result<Value, (i64, String)> {
+        let uri = match params["textDocument"]["uri"].as_str() {
+            Some(uri) => uri,
+            None => return error(INVALID_PARAMS, "Missing textDocument"),
+        };
+        let name = match self.document_name(uri) {
+            Some(name) => name,
+            None => return Ok(Value::Array(Vec::new())),
+        };
+        let sset = self.db.parse_result().clone();
+        let outline = self.db.outline().clone();
+        Ok(Value::Array(self.outline_symbols(&sset, &outline, outline.root(), &name)))
+    }
+
+    /// Lists the symbols of a file within an outline node: its `$a` and `$p`
+    /// statements, and the headings of its children, with their own symbols
+    /// nested.  The symbols of children whose heading is in another file are
+    /// listed directly.
+    fn outline_symbols(&mut self,
+                       sset: &SegmentSet,
+                       outline: &Outline,
+                       ix: usize,
+                       name: &str)
+                       -> Vec<Value> {
+        let mut symbols = Vec::new();
+        for &addr in &outline.node(ix).statements {
+            if sset.source_info(addr.segment_id).name != name {
+                continue;
+            }
+            let stmt = sset.statement(addr);
+            let (kind, detail) = match stmt.statement_type() {
+                StatementType::Axiom => (SYMBOL_CONSTANT, "$a"),
+                StatementType::Provable => (SYMBOL_FUNCTION, "$p"),
+                _ => continue,
+            };
+            let (_, range) = self.statement_range(sset, addr, false);
+            let (_, selection) = self.statement_range(sset, addr, true);
+            symbols.push(json!({
+                "name": as_str(stmt.label()),
+                "detail": detail,
+                "kind": kind,
+                "range": range,
+                "selectionRange": selection,
+            }));
+        }
+        for &child in outline.children(ix) {
+            let node = outline.node(child);
+            let heading = node.heading.unwrap();
+            let children = self.outline_symbols(sset, outline, child, name);
+            if sset.source_info(heading.segment_id).name != name {
+                symbols.extend(children);
+                continue;
+            }
+            let (_, selection) = self.statement_range(sset, heading, false);
+            let mut range = selection.clone();
+            for symbol in &children {
+                if let Some(end) = symbol["range"].get("end") {
+                    let later = (end["line"].as_u64(), end["character"].as_u64()) >
+                                (range["end"]["line"].as_u64(), range["end"]["character"].as_u64());
+                    if later {
+                        range["end"] = end.clone();
+                    }
+                }
+            }
+            symbols.push(json!({
+                "name": node.title,
+                "detail": format!("{:?}", node.level),
+                "kind": SYMBOL_NAMESPACE,
+                "range": range,
+                "selectionRange": selection,
+                "children": children,
+            }));
+        }
+        symbols
+    }
+
+    /// Reparses the database, with the text of the open documents in place
+    /// of their files.
+    fn reparse(&mut self) {
+        let overrides = self.documents
+            .iter()
+            .map(|(name, text)| (name.clone(), text.clone()))
+            .collect();
+        self.db.parse(self.start.clone(), overrides);
+    }
+
+    /// Reparses the database with the open documents and returns the
+    /// diagnostics notifications for them.
+    fn publish_diagnostics(&mut self) -> Vec<Value> {
+        self.reparse();
+        let mut by_file: HashMap<String, Vec<Value>> = new_map();
+        for name in self.documents.keys() {
+            by_file.insert(name.clone(), Vec::new());
+        }
+        for notation in self.db.diag_notations(CLASSES.to_vec()) {
+            if !by_file.contains_key(&notation.source.name) {
+                continue;
+            }
+            let base = notation.source.span.start as usize;
+            let range = self.range(&notation.source.text,
+                                   base + notation.span.start as usize,
+                                   base + notation.span.end as usize);
+            let severity = match notation.level {
+                Level::Error => 1,
+                Level::Warning => 2,
+                Level::Info | Level::Note => 3,
+            };
+            by_file.get_mut(&notation.source.name).unwrap().push(json!({
+                "range": range,
+                "severity": severity,
+                "code": notation.code,
+                "source": "metamath-knife",
+                "message": notation_message(&notation),
+            }));
+        }
+        let mut files: Vec<(String, Vec<Value>)> = by_file.into_iter().collect();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        files.into_iter()
+            .map(|(name, diagnostics)| {
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/publishDiagnostics",
+                    "params": {"uri": self.uri(&name), "diagnostics": diagnostics},
+                })
+            })
+            .collect()
+    }
+
+    /// Records the new text of a document, returning whether it belongs to
+    /// the database.
+    fn update_document(&mut self, params: &Value, text: Option<&str>) -> bool {
+        let uri = match params["textDocument"]["uri"].as_str() {
+            Some(uri) => uri.to_owned(),
+            None => return false,
+        };
+        let name = match self.document_name(&uri) {
+            Some(name) => name,
+            None => return false,
+        };
+        match text {
+            Some(text) => {
+                self.documents.insert(name.clone(), text.as_bytes().to_vec());
+                self.uris.insert(name, uri);
+            }
+            None => {
+                self.documents.remove(&name);
+                self.uris.remove(&name);
+            }
+        }
+        true
+    }
+
+    /// Handles one message, returning the messages to send in reply: the
+    /// response to a request, and any notifications.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message.get("method").and_then(|method| method.as_str()).unwrap_or("");
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let result = match method {
+            "initialize" => {
+                Ok(json!({
+                    "capabilities": {
+                        "textDocumentSync": 1,
+                        "definitionProvider": true,
+                        "hoverProvider": true,
+                        "documentSymbolProvider": true,
+                    },
+                    "serverInfo": {"name": "metamath-knife"},
+                }))
+            }
+            "shutdown" => Ok(Value::Null),
+            "textDocument/definition" => self.definition(&params),
+            "textDocument/hover" => self.hover(&params),
+            "textDocument/documentSymbol" => self.document_symbols(&params),
+            "textDocument/didOpen" => {
+                if self.update_document(&params, params["textDocument"]["text"].as_str()) {
+                    return self.publish_diagnostics();
+                }
+                return Vec::new();
+            }
+            "textDocument/didChange" => {
+                // full synchronization: the last change holds the whole text
+                let text = params.get("contentChanges")
+                    .and_then(|changes| changes.as_array())
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text"))
+                    .and_then(|text| text.as_str());
+                if text.is_some() && self.update_document(&params, text) {
+                    return self.publish_diagnostics();
+                }
+                return Vec::new();
+            }
+            "textDocument/didClose" => {
+                // the file on disk applies again once the document is closed
+                if self.update_document(&params, None) {
+                    let uri = params["textDocument"]["uri"].clone();
+                    self.reparse();
+                    return vec![json!({
+                        "jsonrpc": "2.0",
+                        "method": "textDocument/publishDiagnostics",
+                        "params": {"uri": uri, "diagnostics": []},
+                    })];
+                }
+                return Vec::new();
+            }
+            _ => error(METHOD_NOT_FOUND, &format!("Unknown method {}", method)),
+        };
+        match message.get("id") {
+            None => Vec::new(),
+            Some(id) => {
+                vec![match result {
+                         Ok(value) => json!({"jsonrpc": "2.0", "id": id, "result": value}),
+                         Err((code, message)) => {
+                             let error = json!({"code": code, "message": message});
+                             json!({"jsonrpc": "2.0", "id": id, "error": error})
+                         }
+                     }]
+            }
+        }
+    }
+}
+
+/// Reads one message with its `Content-Length` header, or `None` at the end
+/// of the input.
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let invalid = |err| io::Error::new(io::ErrorKind::InvalidData, err);
+    let length = length.ok_or_else(|| invalid("Missing Content-Length".to_owned()))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(|err| invalid(err.to_string()))
+}
+
+fn write_message<W: Write>(output: &mut W, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// Runs a language server for a database loaded from `start`, until the
+/// client sends `exit` or closes the input.
+pub fn serve<R: BufRead, W: Write>(db: &mut Database,
+                                   start: String,
+                                   mut input: R,
+                                   output: &mut W)
+                                   -> io::Result<()> {
+    let mut server = LanguageServer::new(db, start);
+    while let Some(message) = read_message(&mut input)? {
+        if message.get("method").and_then(|method| method.as_str()) == Some("exit") {
+            break;
+        }
+        for reply in server.handle(&message) {
+            write_message(output, &reply)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use lsp::LanguageServer;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_language_server() {
+    let text = format!("{}${{ th1.1 $e |- ph $.
+th1 $p |- ( ps -> ph ) $= wph wps wph wi th1.1 wph wps ax-1 ax-mp $. $}}
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let mut server = LanguageServer::new(&mut db, "test.mm".to_owned());
+    let uri = "file:///work/test.mm";
+    let doc = json!({ "uri": uri });
+
+    let opened = server.handle(&json!({"jsonrpc": "2.0", "method": "textDocument/didOpen",
+        "params": {"textDocument": {"uri": uri, "text": text}}}));
+    assert_eq!(opened.len(), 1);
+    assert_eq!(opened[0]["params"]["diagnostics"], json!([]));
+
+    let definition = server.handle(&json!({"jsonrpc": "2.0", "id": 1, "method": "textDocument/definition",
+        "params": {"textDocument": doc, "position": {"line": 8, "character": 56}}}));
+    assert_eq!(definition[0]["result"],
+               json!({"uri": uri, "range": {"start": {"line": 6, "character": 0},
+                                            "end": {"line": 6, "character": 36}}}));
+
+    let hover = server.handle(&json!({"jsonrpc": "2.0", "id": 2, "method": "textDocument/hover",
+        "params": {"textDocument": doc, "position": {"line": 8, "character": 18}}}));
+    assert_eq!(hover[0]["result"]["contents"]["value"], json!("```metamath\n$v ph ps $.\n```"));
+
+    let symbols = server.handle(&json!({"jsonrpc": "2.0", "id": 3, "method": "textDocument/documentSymbol",
+        "params": {"textDocument": doc}}));
+    let names: Vec<&str> = symbols[0]["result"].as_array().unwrap().iter()
+        .map(|symbol| symbol["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["wi", "ax-mp", "ax-1", "th1"]);
+
+    let changed = server.handle(&json!({"jsonrpc": "2.0", "method": "textDocument/didChange",
+        "params": {"textDocument": doc, "contentChanges": [{"text": text.replace("ax-1 ax-mp", "ax-mp")}]}}));
+    let diagnostics = changed[0]["params"]["diagnostics"].as_array().unwrap();
+    assert!(!diagnostics.is_empty());
+    assert_eq!(diagnostics[0]["severity"], json!(1));
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], json!(8));
+}
@@ -7,36 +7,233 @@
 extern crate clap;
 extern crate filetime;
 extern crate fnv;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+#[cfg(feature = "watch")]
+extern crate notify;
+#[cfg(any(feature = "export", feature = "search"))]
 extern crate regex;
+#[cfg(feature = "json")]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "json")]
+#[cfg_attr(any(feature = "lsp", feature = "server"), macro_use)]
+extern crate serde_json;
 
 #[cfg(feature = "sysalloc")]
 extern crate alloc_system;
 
 pub mod bit_set;
+pub mod checkpoint;
+pub mod comment_parser;
+#[cfg(feature = "grammar")]
+pub mod congruence;
+pub mod coverage;
 pub mod database;
+#[cfg(feature = "html")]
+pub mod definitions;
+pub mod deprecation;
 pub mod diag;
+pub mod diff;
+pub mod differential;
+pub mod discouraged;
+pub mod disjoint;
+pub mod dummy;
+#[cfg(feature = "grammar")]
+pub mod duplicates;
+pub mod emit;
+#[cfg(feature = "export")]
 pub mod export;
+pub mod extra_info;
+#[cfg(feature = "search")]
+pub mod extraction;
+pub mod formula;
+#[cfg(feature = "grammar")]
+pub mod grammar;
+pub mod graph;
+pub mod health;
+pub mod history;
+#[cfg(feature = "html")]
+pub mod html;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod layout;
 pub mod line_cache;
+pub mod lint;
+#[cfg(feature = "lsp")]
+pub mod lsp;
+#[cfg(feature = "grammar")]
+pub mod markup;
+pub mod merge;
+pub mod metadata;
+#[cfg(feature = "grammar")]
+pub mod minimize;
+#[cfg(feature = "export")]
+pub mod mm0;
+#[cfg(feature = "export")]
+pub mod mmp;
 pub mod nameck;
+pub mod occurrences;
+pub mod outline;
 pub mod parser;
 pub mod proof;
+pub mod proof_stats;
+#[cfg(feature = "grammar")]
+pub mod prover;
+pub mod repair;
 pub mod scopeck;
+#[cfg(feature = "search")]
+pub mod search;
 pub mod segment_set;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "stats")]
+pub mod stats;
+pub mod stubs;
+#[cfg(feature = "grammar")]
+pub mod subset;
+pub mod transaction;
+pub mod typesetting;
+pub mod usage;
 pub mod util;
 pub mod verify;
+pub mod view;
+#[cfg(feature = "watch")]
+pub mod watch;
 
 #[cfg(test)]
 mod util_tests;
 #[cfg(test)]
 mod parser_tests;
+#[cfg(test)]
+mod checkpoint_tests;
+#[cfg(test)]
+mod comment_parser_tests;
+#[cfg(all(test, feature = "grammar"))]
+mod congruence_tests;
+#[cfg(test)]
+mod coverage_tests;
+#[cfg(all(test, feature = "html"))]
+mod definitions_tests;
+#[cfg(test)]
+mod deprecation_tests;
+#[cfg(test)]
+mod diag_tests;
+#[cfg(test)]
+mod diff_tests;
+#[cfg(test)]
+mod differential_tests;
+#[cfg(test)]
+mod discouraged_tests;
+#[cfg(test)]
+mod disjoint_tests;
+#[cfg(test)]
+mod dummy_tests;
+#[cfg(all(test, feature = "grammar"))]
+mod duplicates_tests;
+#[cfg(test)]
+mod emit_tests;
+#[cfg(all(test, feature = "export"))]
+mod export_tests;
+#[cfg(test)]
+mod extra_info_tests;
+#[cfg(all(test, feature = "search"))]
+mod extraction_tests;
+#[cfg(all(test, feature = "grammar"))]
+mod formula_tests;
+#[cfg(all(test, feature = "grammar"))]
+mod grammar_tests;
+#[cfg(test)]
+mod graph_tests;
+#[cfg(all(test, feature = "grammar"))]
+mod health_tests;
+#[cfg(test)]
+mod history_tests;
+#[cfg(all(test, feature = "html"))]
+mod html_tests;
+#[cfg(all(test, feature = "json"))]
+mod json_tests;
+#[cfg(test)]
+mod layout_tests;
+#[cfg(test)]
+mod line_cache_tests;
+#[cfg(test)]
+mod lint_tests;
+#[cfg(all(test, feature = "lsp"))]
+mod lsp_tests;
+#[cfg(all(test, feature = "grammar"))]
+mod markup_tests;
+#[cfg(test)]
+mod merge_tests;
+#[cfg(test)]
+mod metadata_tests;
+#[cfg(all(test, feature = "grammar"))]
+mod minimize_tests;
+#[cfg(all(test, feature = "export"))]
+mod mm0_tests;
+#[cfg(all(test, feature = "export"))]
+mod mmp_tests;
+#[cfg(test)]
+mod nameck_tests;
+#[cfg(test)]
+mod occurrences_tests;
+#[cfg(test)]
+mod outline_tests;
+#[cfg(test)]
+mod proof_tests;
+#[cfg(test)]
+mod proof_stats_tests;
+#[cfg(all(test, feature = "grammar"))]
+mod prover_tests;
+#[cfg(test)]
+mod repair_tests;
+#[cfg(test)]
+mod scopeck_tests;
+#[cfg(all(test, feature = "search"))]
+mod search_tests;
+#[cfg(test)]
+mod segment_set_tests;
+#[cfg(all(test, feature = "server"))]
+mod server_tests;
+#[cfg(all(test, feature = "stats"))]
+mod stats_tests;
+#[cfg(test)]
+mod stubs_tests;
+#[cfg(all(test, feature = "grammar"))]
+mod subset_tests;
+#[cfg(test)]
+mod test_fixtures;
+#[cfg(test)]
+mod transaction_tests;
+#[cfg(test)]
+mod typesetting_tests;
+#[cfg(test)]
+mod usage_tests;
+#[cfg(test)]
+mod verify_tests;
+#[cfg(test)]
+mod view_tests;
+#[cfg(all(test, feature = "watch"))]
+mod watch_tests;
 
 use clap::Arg;
 use clap::App;
 use database::Database;
 use database::DbOptions;
 use diag::DiagnosticClass;
+use diag::Level;
 use diag::Notation;
+use graph::GraphFilter;
+use graph::GraphFormat;
 use line_cache::LineCache;
+#[cfg(feature = "grammar")]
+use markup::bibliography_tags;
+#[cfg(feature = "grammar")]
+use minimize::MinimizeOptions;
+use segment_set::IncludeOptions;
+use std::fs::File;
+#[cfg(feature = "grammar")]
+use std::fs;
 use std::io;
 use std::mem;
 use std::str::FromStr;
@@ -45,16 +242,63 @@ fn positive_integer(val: String) -> Result<(), String> {
     u32::from_str(&val).map(|_| ()).map_err(|e| format!("{}", e))
 }
 
+fn diagnostic_level(val: String) -> Result<(), String> {
+    match val.rfind('=') {
+        Some(pos) if Level::parse(&val[pos + 1..]).is_some() => Ok(()),
+        _ => Err("expected CODE=LEVEL with LEVEL one of error, warning, info, off".to_owned()),
+    }
+}
+
+fn graph_file(val: String) -> Result<(), String> {
+    match GraphFormat::from_path(&val) {
+        Some(_) => Ok(()),
+        None => Err("expected a file name ending in .graphml, .dot, .gv or .gexf".to_owned()),
+    }
+}
+
 fn main() {
-    let matches = App::new("smetamath-knife")
+    let app = App::new("smetamath-knife")
         .version(crate_version!())
         .about("A Metamath database verifier and processing tool")
         .arg(Arg::with_name("DATABASE").help("Database file to load").required_unless("TEXT"))
         .arg(Arg::with_name("split")
             .help("Process files > 1 MiB in multiple segments")
             .long("split"))
+        .arg(Arg::with_name("normalize")
+            .help("Accept Windows-style include paths, file name case differences, BOM and CRLF")
+            .long("normalize"))
         .arg(Arg::with_name("timing").help("Print milliseconds after each stage").long("timing"))
         .arg(Arg::with_name("verify").help("Check proof validity").long("verify").short("v"))
+        .arg(Arg::with_name("discouraged")
+            .help("Warn about uses of statements marked as discouraged")
+            .long("discouraged"))
+        .arg(Arg::with_name("deprecated")
+            .help("Warn about references to deprecated labels and math symbols")
+            .long("deprecated"))
+        .arg(Arg::with_name("dv-audit")
+            .help("Warn about disjoint variable conditions which proofs do not need")
+            .long("dv-audit"))
+        .arg(Arg::with_name("lint")
+            .help("Warn about unused hypotheses, variables and theorems")
+            .long("lint"))
+        .arg(Arg::with_name("markup")
+            .help("Check comment markup, typesetting definitions and $j commands")
+            .long("markup"))
+        .arg(Arg::with_name("history")
+            .help("Check the dates of contribution and revision history entries")
+            .long("history"))
+        .arg(Arg::with_name("diagnostic")
+            .help("Change the level of a category of diagnostics, or suppress it with `off`")
+            .long("diagnostic")
+            .value_name("CODE=LEVEL")
+            .multiple(true)
+            .number_of_values(1)
+            .validator(diagnostic_level))
+        .arg(Arg::with_name("checkpoint")
+            .help("Reuse parsed segments from a checkpoint file, and update it after parsing")
+            .long("checkpoint")
+            .value_name("FILE")
+            .takes_value(true))
         .arg(Arg::with_name("trace-recalc")
             .help("Print segments as they are recalculated")
             .long("trace-recalc"))
@@ -68,26 +312,184 @@ fn main() {
             .short("j")
             .takes_value(true)
             .validator(positive_integer))
-        .arg(Arg::with_name("export")
-            .help("Output a proof file")
-            .long("export")
-            .short("e")
-            .multiple(true)
+        .arg(Arg::with_name("coverage")
+            .help("Report statements not covered by proof verification")
+            .long("coverage"))
+        .arg(Arg::with_name("stubs")
+            .help("List unproved theorem stubs by the number of theorems they block")
+            .long("stubs"))
+        .arg(Arg::with_name("differential")
+            .help("Cross-check the verifier against a simple reference verifier")
+            .long("differential"))
+        .arg(Arg::with_name("diff")
+            .help("List the assertions added, removed, renamed or changed since an older version")
+            .long("diff")
+            .value_name("OLD_DATABASE")
             .takes_value(true))
+        .arg(Arg::with_name("rewrap")
+            .help("Rewrite the compressed proof of a theorem as compactly as possible")
+            .long("rewrap")
+            .value_name("LABEL")
+            .takes_value(true))
+        .arg(Arg::with_name("line-width")
+            .help("Wrap generated source text, such as rewritten proofs, at WIDTH columns \
+                   (default 79)")
+            .long("line-width")
+            .value_name("WIDTH")
+            .takes_value(true)
+            .validator(positive_integer))
+        .arg(Arg::with_name("repair")
+            .help("Suggest simple repairs of a proof which fails to verify")
+            .long("repair")
+            .value_name("LABEL")
+            .takes_value(true))
+        .arg(Arg::with_name("graph")
+            .help("Write the dependency graph of the assertions as GraphML, DOT or GEXF")
+            .long("graph")
+            .value_name("FILE")
+            .takes_value(true)
+            .validator(graph_file))
+        .arg(Arg::with_name("graph-section")
+            .help("Only include the assertions under a heading in the dependency graph")
+            .long("graph-section")
+            .value_name("TITLE")
+            .takes_value(true)
+            .requires("graph"))
+        .arg(Arg::with_name("graph-root")
+            .help("Only include the dependencies of a theorem in the dependency graph")
+            .long("graph-root")
+            .value_name("LABEL")
+            .multiple(true)
+            .takes_value(true)
+            .requires("graph"))
+        .arg(Arg::with_name("graph-depth")
+            .help("Only include dependencies up to DEPTH steps from the graph roots")
+            .long("graph-depth")
+            .value_name("DEPTH")
+            .takes_value(true)
+            .validator(positive_integer)
+            .requires("graph-root"))
         .arg(Arg::with_name("TEXT")
             .long("text")
             .help("Provide raw database content on the command line")
             .value_names(&["NAME", "TEXT"])
-            .multiple(true))
-        .get_matches();
+            .multiple(true));
+    #[cfg(feature = "export")]
+    let app = app.arg(Arg::with_name("export")
+        .help("Output a proof file")
+        .long("export")
+        .short("e")
+        .multiple(true)
+        .takes_value(true));
+    #[cfg(feature = "export")]
+    let app = app.arg(Arg::with_name("export-mm0")
+        .help("Translate the database to Metamath Zero, writing BASE.mm0 and BASE.mmu")
+        .long("export-mm0")
+        .value_name("BASE")
+        .takes_value(true));
+    #[cfg(feature = "grammar")]
+    let app = app.arg(Arg::with_name("grammar")
+            .help("Check syntax axioms for ambiguous productions")
+            .long("grammar"))
+        .arg(Arg::with_name("duplicates")
+            .help("Warn about assertions which restate earlier ones with renamed variables")
+            .long("duplicates"))
+        .arg(Arg::with_name("bibliography")
+            .help("Check bibliographic references in comments against the anchors of an HTML \
+                   bibliography")
+            .long("bibliography")
+            .value_name("FILE")
+            .takes_value(true))
+        .arg(Arg::with_name("minimize")
+            .help("Shorten the proof of a theorem using earlier theorems")
+            .long("minimize")
+            .value_name("LABEL")
+            .takes_value(true));
+    #[cfg(feature = "json")]
+    let app = app.arg(Arg::with_name("json")
+        .help("Write the statements, frames and parse trees of the database as JSON")
+        .long("json")
+        .value_name("FILE")
+        .takes_value(true));
+    #[cfg(feature = "html")]
+    let app = app.arg(Arg::with_name("definitions")
+        .help("Write a definition index (Markdown if the file name ends in .md, else HTML)")
+        .long("definitions")
+        .takes_value(true));
+    #[cfg(feature = "html")]
+    let app = app.arg(Arg::with_name("html")
+            .help("Write an HTML page for every axiom and theorem to a directory")
+            .long("html")
+            .value_name("DIR")
+            .takes_value(true))
+        .arg(Arg::with_name("alt-html")
+            .help("Typeset HTML pages with the althtmldef definitions")
+            .long("alt-html"));
+    #[cfg(feature = "lsp")]
+    let app = app.arg(Arg::with_name("lsp")
+        .help("Run a Language Server Protocol server on standard input and output")
+        .long("lsp"));
+    #[cfg(feature = "mmap")]
+    let app = app.arg(Arg::with_name("mmap")
+        .help("Map database files into memory instead of reading them")
+        .long("mmap"));
+    #[cfg(feature = "server")]
+    let app = app.arg(Arg::with_name("server")
+        .help("Answer JSON-RPC requests on standard input, one per line")
+        .long("server"));
+    #[cfg(feature = "stats")]
+    let app = app.arg(Arg::with_name("stats")
+        .help("Print the time spent in each pass and the COUNT slowest proofs")
+        .long("stats")
+        .value_name("COUNT")
+        .takes_value(true)
+        .validator(positive_integer));
+    #[cfg(feature = "watch")]
+    let app = app.arg(Arg::with_name("watch")
+        .help("Reanalyze the database whenever one of its files changes")
+        .long("watch"));
+    let matches = app.get_matches();
 
     let mut options = DbOptions::default();
     options.autosplit = matches.is_present("split");
     options.timing = matches.is_present("timing");
     options.trace_recalc = matches.is_present("trace-recalc");
+    if matches.is_present("normalize") {
+        options.includes = IncludeOptions::all();
+    }
     options.incremental = matches.is_present("repeat");
+    #[cfg(feature = "mmap")]
+    {
+        options.mmap = matches.is_present("mmap");
+    }
+    #[cfg(feature = "stats")]
+    {
+        options.stats = matches.is_present("stats");
+    }
+    #[cfg(feature = "watch")]
+    {
+        options.incremental |= matches.is_present("watch");
+    }
     options.jobs = usize::from_str(matches.value_of("jobs").unwrap_or("1"))
         .expect("validator should check this");
+    if let Some(width) = matches.value_of("line-width") {
+        options.format.line_width = usize::from_str(width).expect("validator should check this");
+    }
+    #[cfg(feature = "grammar")]
+    if let Some(file) = matches.value_of("bibliography") {
+        match fs::read(file) {
+            Ok(html) => options.markup.bibliography = Some(bibliography_tags(&html)),
+            Err(err) => eprintln!("Could not read {}: {}", file, err),
+        }
+    }
+
+    if let Some(settings) = matches.values_of("diagnostic") {
+        for setting in settings {
+            let pos = setting.rfind('=').expect("validator should check this");
+            let level = Level::parse(&setting[pos + 1..]).expect("validator should check this");
+            options.diagnostics.levels.push((setting[..pos].to_owned(), level));
+        }
+    }
 
     let mut db = Database::new(options);
 
@@ -101,8 +503,22 @@ fn main() {
         .map(|x| x.to_owned())
         .unwrap_or_else(|| data[0].0.clone());
 
+    let checkpoint = matches.value_of("checkpoint");
+    if let Some(file) = checkpoint {
+        if let Err(err) = db.load_checkpoint(file) {
+            if err.kind() != io::ErrorKind::NotFound {
+                eprintln!("Could not load checkpoint {}: {}", file, err);
+            }
+        }
+    }
+
     loop {
         db.parse(start.clone(), data.clone());
+        if let Some(file) = checkpoint {
+            if let Err(err) = db.save_checkpoint(file) {
+                eprintln!("Could not save checkpoint {}: {}", file, err);
+            }
+        }
 
         let mut types = vec![
             DiagnosticClass::Parse,
@@ -113,17 +529,233 @@ fn main() {
             types.push(DiagnosticClass::Verify);
         }
 
+        if matches.is_present("discouraged") {
+            types.push(DiagnosticClass::Discouraged);
+        }
+
+        if matches.is_present("deprecated") {
+            types.push(DiagnosticClass::Deprecated);
+        }
+
+        #[cfg(feature = "grammar")]
+        if matches.is_present("grammar") {
+            types.push(DiagnosticClass::Grammar);
+        }
+
+        #[cfg(feature = "grammar")]
+        if matches.is_present("duplicates") {
+            types.push(DiagnosticClass::Duplicate);
+        }
+
+        if matches.is_present("dv-audit") {
+            types.push(DiagnosticClass::Disjoint);
+        }
+
+        if matches.is_present("lint") {
+            types.push(DiagnosticClass::Lint);
+        }
+
+        if matches.is_present("markup") {
+            types.push(DiagnosticClass::Markup);
+        }
+
+        if matches.is_present("history") {
+            types.push(DiagnosticClass::History);
+        }
+
         let mut lc = LineCache::default();
-        for notation in db.diag_notations(types) {
+        for notation in db.diag_notations(types.clone()) {
             print_annotation(&mut lc, notation);
         }
 
+        #[cfg(feature = "export")]
         if let Some(exps) = matches.values_of_lossy("export") {
             for file in exps {
                 db.export(file);
             }
         }
 
+        #[cfg(feature = "export")]
+        if let Some(base) = matches.value_of("export-mm0") {
+            db.export_mm0(base);
+        }
+
+        if matches.is_present("coverage") {
+            let report = db.coverage();
+            let sset = db.parse_result().clone();
+            report.write_report(&sset, &mut io::stdout()).unwrap();
+        }
+
+        if matches.is_present("stubs") {
+            let sset = db.parse_result().clone();
+            for stub in db.stub_report().stubs {
+                println!("{}: unblocks {}, {} dependents",
+                         parser::as_str(sset.statement(stub.address).label()),
+                         stub.unblocks,
+                         stub.dependents.len());
+            }
+        }
+
+        if matches.is_present("differential") {
+            let sset = db.parse_result().clone();
+            for dis in db.differential_verify() {
+                println!("{}: verifier {:?}, reference {:?}",
+                         parser::as_str(sset.statement(dis.address).label()),
+                         dis.primary,
+                         dis.reference);
+            }
+        }
+
+        if let Some(file) = matches.value_of("diff") {
+            let options = DbOptions {
+                jobs: usize::from_str(matches.value_of("jobs").unwrap_or("1"))
+                    .expect("validator should check this"),
+                ..DbOptions::default()
+            };
+            let mut old = Database::new(options);
+            old.parse(file.to_owned(), Vec::new());
+            for change in db.diff(&mut old) {
+                println!("{}", change);
+            }
+        }
+
+        #[cfg(feature = "grammar")]
+        if let Some(label) = matches.value_of("minimize") {
+            match minimize::minimize(&mut db, label, &MinimizeOptions::default()) {
+                Ok(result) => {
+                    for used in &result.used {
+                        println!("Used {}", used);
+                    }
+                    println!("Length {} -> {}", result.original_length, result.length);
+                    println!("$= {} $.", result.proof);
+                }
+                Err(err) => eprintln!("{}", err),
+            }
+        }
+
+        if let Some(label) = matches.value_of("rewrap") {
+            match db.rewrap_proof(label) {
+                Some(Ok(proof)) => println!("$= {}", proof.trim_start()),
+                Some(Err(diag)) => eprintln!("{}: {:?}", label, diag),
+                None => eprintln!("No compressed proof for {}", label),
+            }
+        }
+
+        if let Some(label) = matches.value_of("repair") {
+            match db.suggest_repairs(label) {
+                Some(report) => {
+                    println!("Step {}: {:?}", report.step + 1, report.diagnostic);
+                    for repair in &report.repairs {
+                        println!("{}: $= {} $.", repair.kind, repair.proof);
+                    }
+                }
+                None => eprintln!("No repairs for {}", label),
+            }
+        }
+
+        if let Some(file) = matches.value_of("graph") {
+            let mut filter = GraphFilter::default();
+            if let Some(title) = matches.value_of("graph-section") {
+                filter.section = db.outline().nodes.iter().position(|node| node.title == title);
+                if filter.section.is_none() {
+                    eprintln!("No heading {}", title);
+                }
+            }
+            for label in matches.values_of("graph-root").into_iter().flatten() {
+                match db.statement(label) {
+                    Some(stmt) => filter.roots.push(stmt.address()),
+                    None => eprintln!("No statement {}", label),
+                }
+            }
+            filter.depth = matches.value_of("graph-depth")
+                .map(|depth| usize::from_str(depth).expect("validator should check this"));
+            let format = GraphFormat::from_path(file).expect("validator should check this");
+            let graph = db.dependency_graph(&filter);
+            let sset = db.parse_result().clone();
+            let result = File::create(file)
+                .and_then(|mut out| graph.write(&sset, format, &mut out));
+            if let Err(err) = result {
+                println!("Error writing {}: {}", file, err);
+            }
+        }
+
+        #[cfg(feature = "stats")]
+        if let Some(count) = matches.value_of("stats") {
+            let count = usize::from_str(count).expect("validator should check this");
+            for (label, time) in db.slowest_proofs(count) {
+                println!("{} {}us", label, time.as_micros());
+            }
+            for (pass, time) in db.pass_times() {
+                println!("{} {}ms", pass, time.as_millis());
+            }
+        }
+
+        #[cfg(feature = "json")]
+        if let Some(file) = matches.value_of("json") {
+            let result = File::create(file)
+                .map_err(|err| err.to_string())
+                .and_then(|mut out| db.export_json(&mut out).map_err(|err| err.to_string()));
+            if let Err(err) = result {
+                println!("Error writing {}: {}", file, err);
+            }
+        }
+
+        #[cfg(feature = "html")]
+        if let Some(file) = matches.value_of("definitions") {
+            let entries = db.definitions();
+            let result = File::create(file).and_then(|mut out| if file.ends_with(".md") {
+                definitions::write_markdown(&entries, &mut out)
+            } else {
+                definitions::write_html(&entries, &mut out)
+            });
+            if let Err(err) = result {
+                println!("Error writing {}: {}", file, err);
+            }
+        }
+
+        #[cfg(feature = "html")]
+        if let Some(dir) = matches.value_of("html") {
+            if let Err(err) = db.write_html_pages(dir, matches.is_present("alt-html")) {
+                println!("Error writing {}: {}", dir, err);
+            }
+        }
+
+        #[cfg(feature = "lsp")]
+        if matches.is_present("lsp") {
+            let stdin = io::stdin();
+            let stdout = io::stdout();
+            if let Err(err) = lsp::serve(&mut db, start.clone(), stdin.lock(), &mut stdout.lock()) {
+                eprintln!("Language server error: {}", err);
+            }
+            break;
+        }
+
+        #[cfg(feature = "server")]
+        if matches.is_present("server") {
+            let stdin = io::stdin();
+            let stdout = io::stdout();
+            if let Err(err) = server::serve(&mut db, stdin.lock(), &mut stdout.lock()) {
+                eprintln!("Server error: {}", err);
+            }
+            break;
+        }
+
+        #[cfg(feature = "watch")]
+        if matches.is_present("watch") {
+            let result = db.watch(&[], types, |_, notations| {
+                let mut lc = LineCache::default();
+                for notation in notations {
+                    print_annotation(&mut lc, notation);
+                }
+                println!("Reanalyzed");
+                true
+            });
+            if let Err(err) = result {
+                eprintln!("Watch error: {}", err);
+            }
+            break;
+        }
+
         if matches.is_present("repeat") {
             let mut input = String::new();
             if io::stdin().read_line(&mut input).unwrap() == 0 {
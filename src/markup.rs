@@ -0,0 +1,223 @@
+//! Validation of comment markup and typesetting definitions.
+//!
+//! This is the equivalent of metamath.exe's `verify markup`.  It checks that:
+//!
+//! * the label of each `~ label` reference in a comment exists;
+//! * the symbols of math text in comments are declared, and math text of more
+//!   than one symbol parses with the grammar formed by the syntax axioms, as a
+//!   formula of some typecode or with its first symbol as typecode;
+//! * math text is closed before the end of the comment;
+//! * every `$c` and `$v` symbol has a `htmldef` and a `latexdef` once the
+//!   database has `$t` comments, and an `althtmldef` once any symbol has one;
+//! * if a bibliography is configured, each `[tag]` reference names one of its
+//!   entries.
+//!
+//! Only ordinary comments are checked, not `$t` and `$j` comments.  The
+//! bibliography is given as a list of tags; `bibliography_tags` extracts them
+//! from the anchors of an HTML bibliography such as set.mm's `mmbiblio.html`.
+
+use comment_parser::CommentItem;
+use comment_parser::comment_items;
+use diag::Diagnostic;
+use grammar::SyntaxParser;
+use grammar::global_variables;
+use nameck::Nameset;
+use parser::SegmentId;
+use parser::Span;
+use parser::StatementAddress;
+use parser::StatementType;
+use parser::Token;
+use parser::TokenPtr;
+use segment_set::SegmentSet;
+use std::sync::Arc;
+use typesetting::TypesettingData;
+use util::HashMap;
+use util::HashSet;
+
+/// Markup checks which depend on files outside of the database.
+#[derive(Clone,Debug,Default)]
+pub struct MarkupConfig {
+    /// Tags of the bibliography entries; bibliographic references are not
+    /// checked if this is `None`.
+    pub bibliography: Option<Vec<String>>,
+}
+
+/// Analysis pass result for comment markup and typesetting definitions.
+#[derive(Default,Clone,Debug)]
+pub struct MarkupResult {
+    diagnostics: Vec<(StatementAddress, Diagnostic)>,
+}
+
+/// Returns the tags of an HTML bibliography, which are the names of its
+/// `<a name="tag">` anchors.
+pub fn bibliography_tags(html: &[u8]) -> Vec<String> {
+    let lower = html.to_ascii_lowercase();
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while let Some(found) = lower[pos..].windows(7).position(|w| w == b"<a name") {
+        pos += found + 7;
+        let rest = &html[pos..];
+        let start = match rest.iter().position(|&ch| ch != b' ' && ch != b'=') {
+            Some(start) if rest[start] == b'"' => start + 1,
+            _ => continue,
+        };
+        if let Some(len) = rest[start..].iter().position(|&ch| ch == b'"') {
+            out.push(String::from_utf8_lossy(&rest[start..start + len]).into_owned());
+        }
+    }
+    out
+}
+
+/// Returns true if math text parses as a formula.
+fn parses(parser: &SyntaxParser, vars: &HashMap<Token, Token>, math: &[TokenPtr]) -> bool {
+    parser.parse_math(vars, math).is_some() ||
+    parser.typecodes().iter().any(|tc| parser.parse(tc, math, vars).is_some())
+}
+
+fn check_segment(sset: &SegmentSet,
+                 nset: &Nameset,
+                 parser: &SyntaxParser,
+                 vars: &HashMap<Token, Token>,
+                 bibliography: Option<&HashSet<Vec<u8>>>,
+                 id: SegmentId)
+                 -> Vec<(StatementAddress, Diagnostic)> {
+    let mut out = Vec::new();
+    let grammar = parser.typecodes().iter().any(|tc| parser.has_syntax_axioms(tc));
+    let seg = sset.segment(id);
+    let buf = &seg.segment.buffer;
+    for stmt in seg {
+        if stmt.statement_type() != StatementType::Comment {
+            continue;
+        }
+        let mut math: Option<(Span, Vec<Span>)> = None;
+        for item in comment_items(stmt) {
+            match item {
+                CommentItem::Label(span) => {
+                    if nset.lookup_label(span.as_ref(buf)).is_none() {
+                        out.push((stmt.address(), Diagnostic::MarkupUnknownLabel(span)));
+                    }
+                }
+                CommentItem::BibTag(span) => {
+                    if let Some(tags) = bibliography {
+                        if !tags.contains(span.as_ref(buf)) {
+                            out.push((stmt.address(), Diagnostic::MarkupUnknownBibTag(span)));
+                        }
+                    }
+                }
+                CommentItem::StartMath(span) => math = Some((span, Vec::new())),
+                CommentItem::MathToken(span) => {
+                    if let Some((_, ref mut tokens)) = math {
+                        tokens.push(span);
+                    }
+                }
+                CommentItem::EndMath(_) => {
+                    let (open, tokens) = math.take().expect("math text was opened");
+                    let mut declared = true;
+                    for &span in &tokens {
+                        if nset.lookup_symbol(span.as_ref(buf)).is_none() {
+                            declared = false;
+                            out.push((stmt.address(), Diagnostic::MarkupUnknownSymbol(span)));
+                        }
+                    }
+                    if declared && grammar && tokens.len() > 1 {
+                        let symbols: Vec<TokenPtr> =
+                            tokens.iter().map(|span| span.as_ref(buf)).collect();
+                        if !parses(parser, vars, &symbols) {
+                            let end = tokens.last().expect("math text has symbols").end;
+                            out.push((stmt.address(),
+                                      Diagnostic::MarkupMathNotParsed(Span::new(open.start as usize,
+                                                                                end as usize))));
+                        }
+                    }
+                }
+                CommentItem::Text(_) |
+                CommentItem::Url(_) |
+                CommentItem::ListItem(..) |
+                CommentItem::Contribution(_) => {}
+            }
+        }
+        if let Some((open, _)) = math {
+            out.push((stmt.address(), Diagnostic::MarkupUnclosedMath(open)));
+        }
+    }
+    out
+}
+
+/// Checks that every math symbol has the typesetting definitions the database
+/// uses.
+fn check_typesetting(sset: &SegmentSet,
+                     typesetting: &TypesettingData)
+                     -> Vec<(StatementAddress, Diagnostic)> {
+    let mut out = Vec::new();
+    let kinds = [("htmldef", true), ("latexdef", true), ("althtmldef", typesetting.has_alt_html())];
+    for sref in sset.segments() {
+        for stmt in sref {
+            match stmt.statement_type() {
+                StatementType::Constant | StatementType::Variable => {}
+                _ => continue,
+            }
+            for index in 0..stmt.math_len() {
+                let symbol = stmt.math_at(index).slice;
+                for &(command, required) in &kinds {
+                    let def = match command {
+                        "htmldef" => typesetting.html_def(symbol),
+                        "latexdef" => typesetting.latex_def(symbol),
+                        _ => typesetting.alt_html_def(symbol),
+                    };
+                    if required && def.is_none() {
+                        out.push((stmt.address(), Diagnostic::TypesettingMissing(index, command)));
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+impl MarkupResult {
+    /// Checks the markup of every comment, one segment per job, and the
+    /// typesetting definitions of every math symbol.
+    pub fn new(sset: &Arc<SegmentSet>,
+               nset: &Arc<Nameset>,
+               parser: &Arc<SyntaxParser>,
+               typesetting: &TypesettingData,
+               config: &MarkupConfig)
+               -> MarkupResult {
+        let bibliography: Option<Arc<HashSet<Vec<u8>>>> = config.bibliography
+            .as_ref()
+            .map(|tags| Arc::new(tags.iter().map(|tag| tag.as_bytes().to_vec()).collect()));
+        let vars = Arc::new(global_variables(sset));
+        let mut promises = Vec::new();
+        for sref in sset.segments() {
+            let sset2 = sset.clone();
+            let nset = nset.clone();
+            let parser = parser.clone();
+            let vars = vars.clone();
+            let bibliography = bibliography.clone();
+            let id = sref.id;
+            promises.push(sset.exec.exec(sref.bytes(), move || {
+                let bibliography = bibliography.as_deref();
+                check_segment(&sset2, &nset, &parser, &vars, bibliography, id)
+            }));
+        }
+        let mut result = MarkupResult::default();
+        for promise in promises {
+            result.diagnostics.extend(promise.wait());
+        }
+        let mut typeset = false;
+        for sref in sset.segments() {
+            for stmt in sref {
+                typeset |= stmt.statement_type() == StatementType::TypesettingComment;
+            }
+        }
+        if typeset {
+            result.diagnostics.extend(check_typesetting(sset, typesetting));
+        }
+        result
+    }
+
+    /// Reports markup errors and missing typesetting definitions.
+    pub fn diagnostics(&self) -> Vec<(StatementAddress, Diagnostic)> {
+        self.diagnostics.clone()
+    }
+}
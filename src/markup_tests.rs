@@ -0,0 +1,46 @@
+use database::Database;
+use database::DbOptions;
+use diag::Diagnostic;
+use markup::bibliography_tags;
+use parser::Span;
+
+const MARKUP: &str = "$c |- ( ) -> wff $.
+$v ph ps $.
+$( $t
+  htmldef \"|-\" as \"|-\"; htmldef \"(\" as \"(\"; htmldef \")\" as \")\";
+  htmldef \"->\" as \"-&gt;\"; htmldef \"wff\" as \"wff\";
+  htmldef \"ph\" as \"ph\"; htmldef \"ps\" as \"ps\";
+  latexdef \"|-\" as \"\\vdash\"; latexdef \"(\" as \"(\"; latexdef \")\" as \")\";
+  latexdef \"->\" as \"\\to\"; latexdef \"ph\" as \"\\varphi\"; latexdef \"ps\" as \"\\psi\";
+$)
+wph $f wff ph $.
+wps $f wff ps $.
+wi $a wff ( ph -> ps ) $.
+$( See ~ wi and ~ nothere , ` ( ph -> ps ) ` and ` ph -> ` , ` ph foo ` ``
+   ~~ [[x] ~ https://example.org [Good] [Bad], ` ps $)
+";
+
+#[test]
+fn test_markup() {
+    let mut options = DbOptions::default();
+    options.markup.bibliography =
+        Some(bibliography_tags(b"<p><A NAME=\"Good\"></A>Good book</p><a name=\"Other\">"));
+    assert_eq!(options.markup.bibliography,
+               Some(vec!["Good".to_owned(), "Other".to_owned()]));
+    let mut db = Database::new(options);
+    db.parse("test.mm".to_owned(),
+             vec![("test.mm".to_owned(), MARKUP.as_bytes().to_owned())]);
+    let span = |text: &str, len: usize| {
+        let start = MARKUP.find(text).unwrap();
+        Span::new(start, start + len)
+    };
+    let found: Vec<Diagnostic> =
+        db.markup_result().diagnostics().into_iter().map(|(_, diag)| diag).collect();
+    assert_eq!(found,
+               vec![Diagnostic::MarkupUnknownLabel(span("nothere", 7)),
+                    Diagnostic::MarkupMathNotParsed(span("` ph -> `", 7)),
+                    Diagnostic::MarkupUnknownSymbol(span("foo", 3)),
+                    Diagnostic::MarkupUnknownBibTag(span("Bad", 3)),
+                    Diagnostic::MarkupUnclosedMath(span("` ps $)", 1)),
+                    Diagnostic::TypesettingMissing(4, "latexdef")]);
+}
@@ -0,0 +1,166 @@
+//! Combining two databases.
+//!
+//! `merge` imports the statements of another database at the end of the main
+//! file of this one, as a local development file would be appended to
+//! set.mm.  Unlike a textual `$[ $]` inclusion, it first checks that the two
+//! databases can live together: a label may only be defined once, and a
+//! math symbol declared at top level in this database cannot be declared
+//! again.  Colliding labels can be prefixed in the imported database, along
+//! with the proofs and `~ label` comment references which use them; math
+//! symbols are never renamed, since that would change the meaning of the
+//! formulas.
+//!
+//! The imported source is written with its file inclusions expanded, and the
+//! other database is left unchanged.
+
+use database::Database;
+use parser::StatementType;
+use parser::as_str;
+use segment_set::SegmentSet;
+use segment_set::StatementEdit;
+use std::error;
+use std::fmt;
+use transaction::Change;
+use transaction::Transaction;
+use transaction::TransactionError;
+use util::HashSet;
+use util::new_set;
+
+/// How `merge` treats the labels of the imported database.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum PrefixPolicy {
+    /// Keep the labels, failing if one is already used.
+    Reject,
+    /// Prefix the labels which are already used.
+    Colliding(String),
+    /// Prefix every label.
+    All(String),
+}
+
+/// The error type for `merge`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum MergeError {
+    /// Labels of the imported database, after prefixing, which are already
+    /// used.
+    LabelCollision(Vec<String>),
+    /// Math symbols declared by the imported database which are declared at
+    /// top level in this one.
+    SymbolCollision(Vec<String>),
+    /// The imported labels could not be prefixed, because a new label is
+    /// also used by the imported database.
+    Rename(TransactionError),
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MergeError::LabelCollision(ref labels) => {
+                write!(f, "Labels already in use: {}", labels.join(" "))
+            }
+            MergeError::SymbolCollision(ref symbols) => {
+                write!(f, "Math symbols already declared: {}", symbols.join(" "))
+            }
+            MergeError::Rename(ref err) => write!(f, "Cannot prefix labels: {}", err),
+        }
+    }
+}
+
+impl error::Error for MergeError {}
+
+/// Returns the labels of a database, in order.
+fn labels(sset: &SegmentSet) -> Vec<String> {
+    let mut out = Vec::new();
+    for sref in sset.segments() {
+        for stmt in sref {
+            if stmt.statement_type().takes_label() {
+                out.push(as_str(stmt.label()).to_owned());
+            }
+        }
+    }
+    out
+}
+
+/// Returns the math symbols declared by `$c` and `$v` statements, at top
+/// level only if `top_level` is set.
+fn declared_symbols(sset: &SegmentSet, top_level: bool) -> Vec<String> {
+    let mut out = Vec::new();
+    for sref in sset.segments() {
+        for stmt in sref {
+            let declares = stmt.statement_type() == StatementType::Constant ||
+                           stmt.statement_type() == StatementType::Variable;
+            if declares && !(top_level && stmt.in_group()) {
+                out.extend(stmt.math_iter().map(|tok| as_str(&tok).to_owned()));
+            }
+        }
+    }
+    out
+}
+
+/// Appends the statements of `other` to the main file of `db`; see the module
+/// documentation.
+///
+/// Returns the name and new text of the changed file.  Fails without changing
+/// either database if labels or math symbols collide.
+pub fn merge(db: &mut Database,
+             other: &mut Database,
+             policy: &PrefixPolicy)
+             -> Result<Vec<(String, Vec<u8>)>, MergeError> {
+    let sset = db.parse_result().clone();
+    let other_sset = other.parse_result().clone();
+
+    let top_symbols: HashSet<String> = declared_symbols(&sset, true).into_iter().collect();
+    let mut seen = new_set();
+    let symbols: Vec<String> = declared_symbols(&other_sset, false)
+        .into_iter()
+        .filter(|symbol| top_symbols.contains(symbol) && seen.insert(symbol.clone()))
+        .collect();
+    if !symbols.is_empty() {
+        return Err(MergeError::SymbolCollision(symbols));
+    }
+
+    let used: HashSet<String> = labels(&sset).into_iter().collect();
+    let mut renames = Vec::new();
+    let mut collisions = Vec::new();
+    for label in labels(&other_sset) {
+        let new = match *policy {
+            PrefixPolicy::All(ref prefix) => format!("{}{}", prefix, label),
+            PrefixPolicy::Colliding(ref prefix) if used.contains(&label) => {
+                format!("{}{}", prefix, label)
+            }
+            _ => label.clone(),
+        };
+        if used.contains(&new) {
+            collisions.push(new);
+        } else if new != label {
+            renames.push((label, new));
+        }
+    }
+    if !collisions.is_empty() {
+        return Err(MergeError::LabelCollision(collisions));
+    }
+
+    let mut text = b"\n".to_vec();
+    {
+        let mut txn = Transaction::new(other);
+        if !renames.is_empty() {
+            txn.apply(&[Change::RenameAll(renames)]).map_err(MergeError::Rename)?;
+        }
+        txn.database()
+            .write_mm(&mut text)
+            .expect("writes to a vector do not fail");
+        // dropping the transaction restores the original labels
+    }
+    if !text.ends_with(b"\n") {
+        text.push(b'\n');
+    }
+
+    let eof = sset.segments()
+        .pop()
+        .and_then(|sref| sref.into_iter().last())
+        .expect("a parsed database ends with an end of file statement")
+        .address();
+    let mut txn = Transaction::new(db);
+    txn.apply(&[Change::Edit(eof, StatementEdit::InsertBefore(text))])
+        .expect("inserting before the end of the main file");
+    Ok(txn.commit())
+}
@@ -0,0 +1,34 @@
+use diag::DiagnosticClass;
+use merge::MergeError;
+use merge::PrefixPolicy;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_merge() {
+    let text = b"$c |- wff ( -> ) $.\n$v ph ps $.\nwph $f wff ph $.\nwps $f wff ps $.\n\
+                 wi $a wff ( ph -> ps ) $.\nax-1 $a |- ( ph -> ( ps -> ph ) ) $.\n";
+    let local = b"ax-1 $a |- ( ph -> ( ps -> ph ) ) $.\n$( Uses ~ ax-1 . $)\n\
+                  th1 $p |- ( ph -> ( ph -> ph ) ) $= ( ax-1 ) AAB $.\n";
+    let mut db = mkdb(text);
+    let mut other = mkdb(local);
+
+    assert_eq!(db.merge(&mut other, &PrefixPolicy::Reject),
+               Err(MergeError::LabelCollision(vec!["ax-1".to_owned()])));
+    assert_eq!(db.merge(&mut mkdb(b"$c wff ph $.\n"), &PrefixPolicy::All("my.".to_owned())),
+               Err(MergeError::SymbolCollision(vec!["wff".to_owned(), "ph".to_owned()])));
+    assert!(db.statement("th1").is_none());
+
+    let files = db.merge(&mut other, &PrefixPolicy::Colliding("my.".to_owned())).unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].0, "test.mm");
+    let out = String::from_utf8(files[0].1.clone()).unwrap();
+    assert!(out.contains("my.ax-1 $a |- ( ph -> ( ps -> ph ) ) $.\n$( Uses ~ my.ax-1 . $)\n\
+                          th1 $p |- ( ph -> ( ph -> ph ) ) $= ( my.ax-1 ) AAB $.\n"));
+    assert!(db.statement("my.ax-1").is_some());
+    assert!(db.diag_notations(vec![DiagnosticClass::Parse,
+                                   DiagnosticClass::Scope,
+                                   DiagnosticClass::Verify])
+        .is_empty());
+    assert!(other.statement("ax-1").is_some());
+    assert!(other.statement("my.ax-1").is_none());
+}
@@ -0,0 +1,61 @@
+//! Per-atom data attached by clients of the database.
+//!
+//! Tools built on the database often need to cache something for each math
+//! symbol or label, such as a rendering or a precedence.  Rather than keeping
+//! their own tables keyed by names, they can store the data in the
+//! `AtomMetadata` of the database, keyed by `Atom`, with one table per type of
+//! data.  The tables are cleared whenever the database changes, along with the
+//! nameck result which assigns the atoms, since the data usually depends on
+//! the declarations.  Labels only have atoms if the database is `incremental`.
+
+use nameck::Atom;
+use std::any::Any;
+use std::any::TypeId;
+use util::HashMap;
+use util::new_map;
+
+/// Tables of data keyed by atom, one for each type of data.
+#[derive(Default)]
+pub struct AtomMetadata {
+    tables: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl AtomMetadata {
+    /// Returns the table for a type of data, if any data of the type has been
+    /// stored.
+    pub fn table<T: Any + Send + Sync>(&self) -> Option<&HashMap<Atom, T>> {
+        self.tables
+            .get(&TypeId::of::<T>())
+            .map(|table| table.downcast_ref().expect("tables are keyed by type"))
+    }
+
+    /// Returns the table for a type of data, creating it if needed.
+    pub fn table_mut<T: Any + Send + Sync>(&mut self) -> &mut HashMap<Atom, T> {
+        self.tables
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(new_map::<Atom, T>()))
+            .downcast_mut()
+            .expect("tables are keyed by type")
+    }
+
+    /// Returns the data of a type stored for an atom.
+    pub fn get<T: Any + Send + Sync>(&self, atom: Atom) -> Option<&T> {
+        self.table().and_then(|table| table.get(&atom))
+    }
+
+    /// Stores data for an atom, returning the data of the same type it
+    /// replaces.
+    pub fn insert<T: Any + Send + Sync>(&mut self, atom: Atom, value: T) -> Option<T> {
+        self.table_mut().insert(atom, value)
+    }
+
+    /// Removes and returns the data of a type stored for an atom.
+    pub fn remove<T: Any + Send + Sync>(&mut self, atom: Atom) -> Option<T> {
+        self.table_mut().remove(&atom)
+    }
+
+    /// Removes the data of every type.
+    pub fn clear(&mut self) {
+        self.tables.clear();
+    }
+}
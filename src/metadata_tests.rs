@@ -0,0 +1,20 @@
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_atom_metadata() {
+    let mut db = mkdb(DB.as_bytes());
+    let ph = db.name_result().lookup_symbol(b"ph").unwrap().atom;
+    let ps = db.name_result().lookup_symbol(b"ps").unwrap().atom;
+    assert_eq!(db.atom_metadata().insert(ph, "phi".to_owned()), None);
+    db.atom_metadata().insert(ph, 1usize);
+    db.atom_metadata().insert(ps, 2usize);
+    assert_eq!(db.atom_metadata().get::<String>(ph).map(|s| &s[..]), Some("phi"));
+    assert_eq!(db.atom_metadata().get::<usize>(ph), Some(&1));
+    assert_eq!(db.atom_metadata().get::<String>(ps), None);
+    assert_eq!(db.atom_metadata().table::<usize>().unwrap().len(), 2);
+    assert_eq!(db.atom_metadata().remove::<usize>(ps), Some(2));
+    // any change to the database discards the data
+    db.parse("test.mm".to_owned(), vec![("test.mm".to_owned(), DB.as_bytes().to_vec())]);
+    assert!(db.atom_metadata().get::<usize>(ph).is_none());
+}
@@ -0,0 +1,511 @@
+//! Shortening proofs with existing theorems.
+//!
+//! This is the `MINIMIZE_WITH` command of metamath.exe.  The proof of a
+//! theorem is verified into a `ProofTreeArray`, and the formula of each
+//! logical step is matched against the assertions of the earlier theorems.
+//! Where one of them applies, with its hypotheses proved by steps below the
+//! matched one and its disjoint variable conditions satisfied in the frame of
+//! the theorem, the subproof can be replaced by a reference to it.
+//! Formulas are compared as parse trees (see `grammar`), so this needs the
+//! syntax axioms of the database to parse them.
+//!
+//! The search is greedy: each round makes the single replacement giving the
+//! shortest compressed proof, until no replacement makes the proof shorter.
+//! The length of a proof is the number of characters of its compressed form,
+//! not counting whitespace, as set.mm is formatted.  The result is checked
+//! by the verifier before it is returned.
+
+use database::Database;
+use diag::Diagnostic;
+use discouraged::DiscouragedResult;
+use grammar::SyntaxParser;
+use grammar::SyntaxTree;
+use grammar::frame_variables;
+use grammar::global_variables;
+use layout::statement_indent;
+use nameck::Nameset;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::Token;
+use parser::TokenPtr;
+use parser::as_str;
+use proof::ProofStyle;
+use proof::ProofTreeArray;
+use proof::ProofTreePrinter;
+use scopeck::Frame;
+use scopeck::Hyp;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use std::fmt;
+use transaction::Change;
+use transaction::Transaction;
+use util::HashMap;
+use util::new_map;
+
+/// The theorems which may be used by `minimize`.
+#[derive(Copy,Clone,Debug,Default)]
+pub struct MinimizeOptions {
+    /// Use axioms as well as theorems; metamath.exe's `/ALLOW_NEW_AXIOMS`.
+    pub allow_axioms: bool,
+    /// Use theorems marked "(New usage is discouraged.)".
+    pub allow_discouraged: bool,
+}
+
+/// A minimized proof.
+#[derive(Clone,Debug)]
+pub struct Minimized {
+    /// The compressed proof, as the text between `$=` and `$.`.  This is the
+    /// original proof recompressed if no shorter one was found.
+    pub proof: String,
+    /// The length of the minimized proof.
+    pub length: usize,
+    /// The length of the original proof, recompressed.
+    pub original_length: usize,
+    /// The labels of the theorems introduced, in the order they were.
+    pub used: Vec<String>,
+}
+
+/// The error type for `minimize`.
+#[derive(Clone,Debug)]
+pub enum MinimizeError {
+    /// The label does not exist.
+    UnknownLabel(String),
+    /// The label is not a `$p` statement.
+    NotProvable(String),
+    /// The original proof does not verify.
+    InvalidProof(Diagnostic),
+    /// The minimized proof does not verify, which should not happen.
+    Unverified(Vec<Diagnostic>),
+}
+
+impl fmt::Display for MinimizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MinimizeError::UnknownLabel(ref label) => write!(f, "Unknown label {}", label),
+            MinimizeError::NotProvable(ref label) => write!(f, "{} is not a theorem", label),
+            MinimizeError::InvalidProof(ref diag) => write!(f, "The proof is invalid: {:?}", diag),
+            MinimizeError::Unverified(ref diags) => {
+                write!(f, "The minimized proof does not verify: {:?}", diags)
+            }
+        }
+    }
+}
+
+/// An earlier theorem which may replace subproofs.
+struct Candidate<'a> {
+    address: StatementAddress,
+    frame: &'a Frame,
+    assertion: SyntaxTree,
+    /// The `$e` hypotheses, in frame order.
+    hyps: Vec<SyntaxTree>,
+}
+
+/// A way to prove a step with a candidate.
+struct Replacement<'a, 'b: 'a> {
+    candidate: &'a Candidate<'b>,
+    subst: HashMap<Token, SyntaxTree>,
+    /// The steps proving the `$e` hypotheses, in frame order.
+    hyps: Vec<usize>,
+}
+
+struct Minimizer<'a> {
+    sset: &'a SegmentSet,
+    nset: &'a Nameset,
+    scope: &'a ScopeResult,
+    parser: &'a SyntaxParser,
+    stmt: StatementRef<'a>,
+    frame: &'a Frame,
+    /// The typecode of each variable, for parsing the steps.
+    vars: HashMap<Token, Token>,
+    /// The `$f` hypothesis proving each variable in syntax proofs.
+    floats: HashMap<Token, StatementAddress>,
+}
+
+/// Returns the steps a step depends on, not including itself, in order.
+fn descendants(arr: &ProofTreeArray, ix: usize) -> Vec<usize> {
+    let mut seen = vec![false; arr.trees.len()];
+    let mut stack = arr.trees[ix].children.clone();
+    let mut out = Vec::new();
+    while let Some(j) = stack.pop() {
+        if !seen[j] {
+            seen[j] = true;
+            out.push(j);
+            stack.extend_from_slice(&arr.trees[j].children);
+        }
+    }
+    out.sort();
+    out
+}
+
+fn variables(tree: &SyntaxTree, out: &mut Vec<Token>) {
+    match *tree {
+        SyntaxTree::Var(ref name) => out.push(name.clone()),
+        SyntaxTree::App(_, ref args) => {
+            for arg in args {
+                variables(arg, out);
+            }
+        }
+    }
+}
+
+/// Matches the `$e` hypotheses of a candidate against steps, extending the
+/// substitution, and backtracking over the possible choices of steps.
+fn match_hyps(hyps: &[SyntaxTree],
+              below: &[usize],
+              formulas: &[Option<SyntaxTree>],
+              subst: &mut HashMap<Token, SyntaxTree>,
+              out: &mut Vec<usize>)
+              -> bool {
+    let (hyp, rest) = match hyps.split_first() {
+        Some(split) => split,
+        None => return true,
+    };
+    for &j in below {
+        if let Some(ref formula) = formulas[j] {
+            let mut extended = subst.clone();
+            if hyp.match_with(formula, &mut extended) {
+                out.push(j);
+                if match_hyps(rest, below, formulas, &mut extended, out) {
+                    *subst = extended;
+                    return true;
+                }
+                out.pop();
+            }
+        }
+    }
+    false
+}
+
+impl<'a> Minimizer<'a> {
+    fn parse(&self, vars: &HashMap<Token, Token>, stmt: StatementRef) -> Option<SyntaxTree> {
+        let math: Vec<TokenPtr> = stmt.math_iter().map(|tok| tok.slice).collect();
+        self.parser.parse_math(vars, &math).map(|(_, tree)| tree)
+    }
+
+    /// Collects the usable theorems, by the syntax axiom at the root of their
+    /// assertion.
+    fn candidates(&self,
+                  options: &MinimizeOptions,
+                  discouraged: &DiscouragedResult)
+                  -> HashMap<StatementAddress, Vec<Candidate<'a>>> {
+        let mut out = new_map();
+        for sref in self.sset.segments() {
+            for stmt in sref {
+                if stmt.address() == self.stmt.address() {
+                    return out;
+                }
+                match stmt.statement_type() {
+                    StatementType::Provable => {}
+                    StatementType::Axiom if options.allow_axioms => {}
+                    _ => continue,
+                }
+                if stmt.math_len() == 0 || !self.parser.is_provable(stmt.math_at(0).slice) ||
+                   !options.allow_discouraged && discouraged.is_usage_discouraged(stmt.label()) {
+                    continue;
+                }
+                let frame = match self.scope.get(stmt.label()) {
+                    Some(frame) => frame,
+                    None => continue,
+                };
+                let vars = frame_variables(self.nset, frame);
+                let assertion = match self.parse(&vars, stmt) {
+                    Some(tree) => tree,
+                    None => continue,
+                };
+                let root = match assertion {
+                    SyntaxTree::App(root, _) => root,
+                    // an assertion which is a bare variable would match anything
+                    SyntaxTree::Var(_) => continue,
+                };
+                let hyps: Option<Vec<SyntaxTree>> = frame.hypotheses
+                    .iter()
+                    .filter_map(|hyp| match *hyp {
+                        Hyp::Essential(addr, _) => Some(self.parse(&vars, self.sset.statement(addr))),
+                        Hyp::Floating(..) => None,
+                    })
+                    .collect();
+                if let Some(hyps) = hyps {
+                    out.entry(root).or_insert_with(Vec::new).push(Candidate {
+                        address: stmt.address(),
+                        frame: frame,
+                        assertion: assertion,
+                        hyps: hyps,
+                    });
+                }
+            }
+        }
+        out
+    }
+
+    /// Parses the formula of each step with a provable typecode.
+    fn formulas(&self, arr: &ProofTreeArray) -> Vec<Option<SyntaxTree>> {
+        arr.trees
+            .iter()
+            .zip(&arr.exprs)
+            .map(|(tree, expr)| {
+                let typecode = self.sset.statement(tree.address).math_at(0).slice;
+                if !self.parser.is_provable(typecode) {
+                    return None;
+                }
+                let mut math = vec![typecode];
+                math.extend(expr.split(|&ch| ch == b' ').filter(|tok| !tok.is_empty()));
+                self.parser.parse_math(&self.vars, &math).map(|(_, tree)| tree)
+            })
+            .collect()
+    }
+
+    /// Checks that the disjoint variable conditions of a candidate hold for
+    /// a substitution in the frame of the theorem being minimized.
+    fn disjoint(&self, candidate: &Candidate, subst: &HashMap<Token, SyntaxTree>) -> bool {
+        let index: HashMap<TokenPtr, usize> = self.frame
+            .var_list
+            .iter()
+            .enumerate()
+            .map(|(ix, &atom)| (self.nset.atom_name(atom), ix))
+            .collect();
+        let frame = candidate.frame;
+        let substituted = |var: usize| {
+            let mut out = Vec::new();
+            if let Some(tree) = subst.get(self.nset.atom_name(frame.var_list[var])) {
+                variables(tree, &mut out);
+            }
+            out
+        };
+        frame.mandatory_dv.iter().all(|&(v1, v2)| {
+            let vars2 = substituted(v2);
+            substituted(v1).iter().all(|x| {
+                vars2.iter().all(|y| {
+                    match (index.get(&x[..]), index.get(&y[..])) {
+                        (Some(&i), Some(&j)) => i != j && self.frame.optional_dv[i].has_bit(j),
+                        _ => false,
+                    }
+                })
+            })
+        })
+    }
+
+    /// Finds how a candidate proves a step, if it does.
+    fn replacement<'b>(&self,
+                       candidate: &'b Candidate<'a>,
+                       formulas: &[Option<SyntaxTree>],
+                       ix: usize,
+                       below: &[usize])
+                       -> Option<Replacement<'b, 'a>> {
+        let mut subst = match formulas[ix] {
+            Some(ref formula) => candidate.assertion.match_onto(formula)?,
+            None => return None,
+        };
+        let mut hyps = Vec::new();
+        if !match_hyps(&candidate.hyps, below, formulas, &mut subst, &mut hyps) {
+            return None;
+        }
+        let frame = candidate.frame;
+        let bound = frame.hypotheses.iter().all(|hyp| match *hyp {
+            Hyp::Floating(_, var, _) => {
+                subst.contains_key(self.nset.atom_name(frame.var_list[var]))
+            }
+            Hyp::Essential(..) => true,
+        });
+        if !bound || !self.disjoint(candidate, &subst) {
+            return None;
+        }
+        Some(Replacement {
+            candidate: candidate,
+            subst: subst,
+            hyps: hyps,
+        })
+    }
+
+    /// Adds the syntax proof of a formula, returning its step and its math
+    /// string in the format of `ProofTreeArray::exprs`.
+    fn syntax_step(&self, arr: &mut ProofTreeArray, tree: &SyntaxTree) -> Option<(usize, Vec<u8>)> {
+        arr.add_syntax_proof(self.sset, self.nset, self.scope, &self.floats, tree)
+    }
+
+    /// Copies a step and the steps it depends on into a new proof, replacing
+    /// the step `target` with the application of a candidate.
+    fn copy(&self,
+            arr: &ProofTreeArray,
+            ix: usize,
+            target: usize,
+            replacement: &Replacement,
+            out: &mut ProofTreeArray,
+            map: &mut Vec<Option<usize>>)
+            -> Option<usize> {
+        if let Some(new) = map[ix] {
+            return Some(new);
+        }
+        let mut children = Vec::new();
+        let address = if ix == target {
+            let frame = replacement.candidate.frame;
+            let mut hyps = replacement.hyps.iter();
+            for hyp in frame.hypotheses.iter() {
+                children.push(match *hyp {
+                    Hyp::Floating(_, var, _) => {
+                        let name = self.nset.atom_name(frame.var_list[var]);
+                        self.syntax_step(out, replacement.subst.get(name)?)?.0
+                    }
+                    Hyp::Essential(..) => {
+                        self.copy(arr, *hyps.next()?, target, replacement, out, map)?
+                    }
+                });
+            }
+            replacement.candidate.address
+        } else {
+            for &child in &arr.trees[ix].children {
+                children.push(self.copy(arr, child, target, replacement, out, map)?);
+            }
+            arr.trees[ix].address
+        };
+        let new = out.add_step(address, children, arr.exprs[ix].clone());
+        map[ix] = Some(new);
+        Some(new)
+    }
+
+    fn rebuild(&self,
+               arr: &ProofTreeArray,
+               target: usize,
+               replacement: &Replacement)
+               -> Option<ProofTreeArray> {
+        let mut out = ProofTreeArray::default();
+        let mut map = vec![None; arr.trees.len()];
+        let qed = self.copy(arr, arr.qed, target, replacement, &mut out, &mut map)?;
+        out.set_qed(qed);
+        Some(out)
+    }
+
+    /// Prints a proof in compressed form, returning the text between `$=`
+    /// and `$.` and its length.
+    fn print(&self, arr: &ProofTreeArray) -> (String, usize) {
+        let (indent, line_width) =
+            self.sset.options.format.proof_layout(statement_indent(self.sset, self.stmt));
+        let text = format!("{}",
+                           ProofTreePrinter {
+                               sset: self.sset,
+                               nset: self.nset,
+                               scope: self.scope,
+                               thm_label: self.stmt.label(),
+                               style: ProofStyle::Compressed,
+                               arr: arr,
+                               initial_chr: 2,
+                               indent: indent,
+                               line_width: line_width,
+                           });
+        let proof = text.trim().trim_end_matches("$.").trim().to_owned();
+        let length = proof.chars().filter(|ch| !ch.is_whitespace()).count();
+        (proof, length)
+    }
+}
+
+/// Shortens the proof of a theorem by using earlier theorems; see the module
+/// documentation.
+///
+/// The database is not changed: the caller can write the new proof with a
+/// `Transaction`.
+pub fn minimize(db: &mut Database,
+                label: &str,
+                options: &MinimizeOptions)
+                -> Result<Minimized, MinimizeError> {
+    let sset = db.parse_result().clone();
+    let nset = db.name_result().clone();
+    let scope = db.scope_result().clone();
+    let parser = db.syntax_parser().clone();
+    let discouraged = db.discouraged_result().clone();
+    let stmt = match db.statement(label) {
+        Some(stmt) => sset.statement(stmt.address()),
+        None => return Err(MinimizeError::UnknownLabel(label.to_owned())),
+    };
+    let frame = match scope.get(stmt.label()) {
+        Some(frame) if stmt.statement_type() == StatementType::Provable => frame,
+        _ => return Err(MinimizeError::NotProvable(label.to_owned())),
+    };
+    let mut arr = ProofTreeArray::new(&sset, &nset, &scope, stmt)
+        .map_err(MinimizeError::InvalidProof)?;
+
+    let mut vars = global_variables(&sset);
+    vars.extend(frame_variables(&nset, frame));
+    let mut floats = new_map();
+    for tree in &arr.trees {
+        let hyp = sset.statement(tree.address);
+        if hyp.statement_type() == StatementType::Floating {
+            floats.insert(hyp.math_at(1).slice.into(), tree.address);
+        }
+    }
+    for hyp in frame.hypotheses.iter() {
+        if let Hyp::Floating(addr, var, _) = *hyp {
+            floats.insert(nset.atom_name(frame.var_list[var]).into(), addr);
+        }
+    }
+    let minimizer = Minimizer {
+        sset: &sset,
+        nset: &nset,
+        scope: &scope,
+        parser: &parser,
+        stmt: stmt,
+        frame: frame,
+        vars: vars,
+        floats: floats,
+    };
+    let candidates = minimizer.candidates(options, &discouraged);
+
+    let (mut proof, original_length) = minimizer.print(&arr);
+    let mut length = original_length;
+    let mut used = Vec::new();
+    loop {
+        let formulas = minimizer.formulas(&arr);
+        let mut best = None;
+        for ix in 0..arr.trees.len() {
+            let root = match formulas[ix] {
+                Some(SyntaxTree::App(root, _)) => root,
+                _ => continue,
+            };
+            let below = descendants(&arr, ix);
+            for candidate in candidates.get(&root).map_or(&[][..], |cands| &cands[..]) {
+                if candidate.address == arr.trees[ix].address {
+                    continue;
+                }
+                let new = match minimizer.replacement(candidate, &formulas, ix, &below)
+                    .and_then(|replacement| minimizer.rebuild(&arr, ix, &replacement)) {
+                    Some(new) => new,
+                    None => continue,
+                };
+                let (text, len) = minimizer.print(&new);
+                let shortest = best.as_ref().map_or(length, |&(_, _, len, _)| len);
+                if len < shortest {
+                    best = Some((new, text, len, candidate.address));
+                }
+            }
+        }
+        match best {
+            Some((new, text, len, addr)) => {
+                arr = new;
+                proof = text;
+                length = len;
+                used.push(as_str(sset.statement(addr).label()).to_owned());
+            }
+            None => break,
+        }
+    }
+
+    if !used.is_empty() {
+        let mut txn = Transaction::new(db);
+        txn.apply(&[Change::ReplaceProof(stmt.address(), proof.clone())])
+            .expect("replacing a proof of a $p statement");
+        let db = txn.database();
+        let addr = db.statement(label).expect("label still exists").address();
+        let mut diags = db.scope_result().diagnostics();
+        diags.extend(db.verify_result().diagnostics());
+        diags.retain(|&(daddr, _)| daddr == addr);
+        if !diags.is_empty() {
+            return Err(MinimizeError::Unverified(diags.into_iter().map(|(_, diag)| diag).collect()));
+        }
+        // dropping the transaction restores the original proof
+    }
+    Ok(Minimized {
+        proof: proof,
+        length: length,
+        original_length: original_length,
+        used: used,
+    })
+}
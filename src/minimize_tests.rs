@@ -0,0 +1,31 @@
+use diag::DiagnosticClass;
+use minimize::MinimizeOptions;
+use minimize::minimize;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_minimize() {
+    let text = format!("{}${{ a1i.1 $e |- ph $.
+a1i $p |- ( ps -> ph ) $= wph wps wph wi a1i.1 wph wps ax-1 ax-mp $. $}}
+${{ th.1 $e |- ps $.
+th $p |- ( ph -> ps ) $= wps wph wps wi th.1 wps wph ax-1 ax-mp $. $}}
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let result = minimize(&mut db, "th", &MinimizeOptions::default()).unwrap();
+    assert_eq!(result.used, vec!["a1i".to_owned()]);
+    assert!(result.length < result.original_length);
+    assert_eq!(result.proof.split_whitespace().collect::<Vec<_>>(),
+               vec!["(", "a1i", ")", "BACD"]);
+
+    // the minimized proof must verify in place of the original
+    let text = text.replace("wps wph wps wi th.1 wps wph ax-1 ax-mp", &result.proof);
+    let mut db = mkdb(text.as_bytes());
+    assert!(db.diag_notations(vec![DiagnosticClass::Parse,
+                                   DiagnosticClass::Scope,
+                                   DiagnosticClass::Verify])
+        .is_empty());
+    assert!(minimize(&mut db, "th", &MinimizeOptions::default()).unwrap().used.is_empty());
+    assert!(minimize(&mut db, "wi", &MinimizeOptions::default()).is_err());
+}
@@ -0,0 +1,394 @@
+//! Export to Metamath Zero specification (`.mm0`) and proof (`.mmu`) files.
+//!
+//! The translation follows the usual correspondence between the two
+//! languages.  Every typecode of a `$f` statement or syntax axiom becomes a
+//! sort, and the sorts which no syntax axiom produces (`setvar` in set.mm)
+//! are `pure` sorts of bound variables.  Syntax axioms become `term`s, and
+//! the other `$a` and `$p` statements become `axiom`s and `theorem`s.  A
+//! variable of a bound sort is a bound binder `{x: setvar}`; any other
+//! variable may depend on each bound variable of the same frame except those
+//! it is required to be disjoint from by `$d`.  Bound binders are listed
+//! first, in database order, followed by the others.
+//!
+//! Formulas are parsed with `grammar::SyntaxParser` and written in prefix
+//! form such as `wi ph (wi ps ph)`; no notations are declared.  The sorts
+//! which formulas of provable statements parse as are declared `provable`; the
+//! provable typecodes are those declared by `$j` comments, or `|-`.  Statements
+//! which cannot be translated, such as those whose formulas do not parse or
+//! whose proofs are incomplete, are left out with an explanatory comment in
+//! the `.mm0` file.
+//!
+//! Labels and variables are turned into identifiers by replacing `-` and `.`
+//! with `_`, and other characters with `_` and their hexadecimal code.
+//! Proofs are written as trees, without sharing repeated subproofs.
+
+use export::ExportError;
+use grammar::SyntaxParser;
+use grammar::SyntaxTree;
+use grammar::frame_variables;
+use nameck::Atom;
+use nameck::Nameset;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::Token;
+use parser::TokenPtr;
+use proof::ProofTreeArray;
+use scopeck::Frame;
+use scopeck::Hyp;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use std::io::Write;
+
+/// Returns a label or variable name as a Metamath Zero identifier.
+fn mm0_ident(name: &[u8]) -> String {
+    let mut out = String::new();
+    if name.first().is_none_or(|ch| ch.is_ascii_digit()) {
+        out.push('_');
+    }
+    for &ch in name {
+        match ch {
+            b'-' | b'.' => out.push('_'),
+            _ if ch.is_ascii_alphanumeric() || ch == b'_' => out.push(ch as char),
+            _ => out.push_str(&format!("_{:02x}", ch)),
+        }
+    }
+    out
+}
+
+/// A variable of a frame, as a binder.
+struct Binder {
+    name: String,
+    sort: String,
+    /// The bound variables this one may depend on, or `None` if it is bound
+    /// itself.
+    deps: Option<Vec<String>>,
+}
+
+/// A statement translated for output.
+enum Decl {
+    Term(String, Vec<Binder>, String),
+    Axiom(String, Vec<Binder>, Vec<(String, SyntaxTree)>, SyntaxTree),
+    Theorem(String,
+            Vec<Binder>,
+            Vec<(String, SyntaxTree)>,
+            SyntaxTree,
+            Vec<(String, String)>,
+            String),
+    Skipped(String, &'static str),
+}
+
+/// Everything known about the database during the translation.
+struct Translator<'a> {
+    sset: &'a SegmentSet,
+    nset: &'a Nameset,
+    scope: &'a ScopeResult,
+    parser: SyntaxParser,
+    provable_sorts: Vec<Token>,
+}
+
+impl<'a> Translator<'a> {
+    fn var_name(&self, frame: &Frame, index: usize) -> Vec<u8> {
+        self.nset.atom_name(frame.var_list[index]).to_vec()
+    }
+
+    /// Returns true if the variables of a typecode are bound variables.
+    fn is_bound(&self, typecode: Atom) -> bool {
+        !self.parser.has_syntax_axioms(self.nset.atom_name(typecode))
+    }
+
+    /// Writes a formula in prefix form, with the arguments of each term in
+    /// binder order.  In `.mmu` form every application is parenthesized; in
+    /// `.mm0` form only arguments which have arguments themselves are.
+    fn write_expr(&self, tree: &SyntaxTree, mmu: bool, nested: bool, out: &mut String) {
+        match *tree {
+            SyntaxTree::Var(ref name) => out.push_str(&mm0_ident(name)),
+            SyntaxTree::App(addr, ref args) => {
+                let paren = mmu || nested && !args.is_empty();
+                if paren {
+                    out.push('(');
+                }
+                let label = self.sset.statement(addr).label();
+                out.push_str(&mm0_ident(label));
+                let frame = self.scope.get(label).expect("syntax axioms have frames");
+                let floats: Vec<usize> = (0..frame.hypotheses.len())
+                    .filter(|&h| match frame.hypotheses[h] {
+                        Hyp::Floating(..) => true,
+                        Hyp::Essential(..) => false,
+                    })
+                    .collect();
+                for h in self.binder_order(frame) {
+                    let arg = floats.iter().position(|&f| f == h).expect("binders are floating");
+                    out.push(' ');
+                    self.write_expr(&args[arg], mmu, true, out);
+                }
+                if paren {
+                    out.push(')');
+                }
+            }
+        }
+    }
+
+    fn mm0_string(&self, tree: &SyntaxTree) -> String {
+        let mut out = String::new();
+        self.write_expr(tree, false, false, &mut out);
+        out
+    }
+
+    fn mmu_string(&self, tree: &SyntaxTree) -> String {
+        let mut out = String::new();
+        self.write_expr(tree, true, false, &mut out);
+        out
+    }
+
+    /// Returns the indices of the `$f` hypotheses of a frame in binder order,
+    /// bound variables first.
+    fn binder_order(&self, frame: &Frame) -> Vec<usize> {
+        let floats: Vec<(usize, bool)> = frame.hypotheses
+            .iter()
+            .enumerate()
+            .filter_map(|(ix, hyp)| match *hyp {
+                Hyp::Floating(_, _, typecode) => Some((ix, self.is_bound(typecode))),
+                Hyp::Essential(..) => None,
+            })
+            .collect();
+        let bound = floats.iter().filter(|&&(_, b)| b).map(|&(ix, _)| ix);
+        let regular = floats.iter().filter(|&&(_, b)| !b).map(|&(ix, _)| ix);
+        bound.chain(regular).collect()
+    }
+
+    fn binders(&self, frame: &Frame) -> Vec<Binder> {
+        let order = self.binder_order(frame);
+        let mut bound = Vec::new();
+        let mut out = Vec::new();
+        for ix in order {
+            if let Hyp::Floating(_, var, typecode) = frame.hypotheses[ix] {
+                let sort = self.nset.atom_name(typecode);
+                let deps = if self.is_bound(typecode) {
+                    bound.push(var);
+                    None
+                } else {
+                    Some(bound.iter()
+                        .filter(|&&b| {
+                            !frame.mandatory_dv
+                                .iter()
+                                .any(|&(x, y)| (x, y) == (b, var) || (y, x) == (b, var))
+                        })
+                        .map(|&b| mm0_ident(&self.var_name(frame, b)))
+                        .collect())
+                };
+                out.push(Binder {
+                    name: mm0_ident(&self.var_name(frame, var)),
+                    sort: mm0_ident(sort),
+                    deps: deps,
+                });
+            }
+        }
+        out
+    }
+
+    /// Parses the math string of a provable statement, recording the sort it
+    /// parsed as as provable.
+    fn parse_provable(&mut self, frame: &Frame, stmt: StatementRef) -> Option<SyntaxTree> {
+        let math: Vec<TokenPtr> = stmt.math_iter().map(|tok| tok.slice).collect();
+        self.parser.parse_math(&frame_variables(self.nset, frame), &math).map(|(sort, tree)| {
+            if !self.provable_sorts.contains(&sort) {
+                self.provable_sorts.push(sort);
+            }
+            tree
+        })
+    }
+
+    /// Writes a step of a proof, with the arguments of each assertion in
+    /// binder order followed by its hypotheses.
+    fn write_step(&self,
+                  arr: &ProofTreeArray,
+                  ix: usize,
+                  dummies: &mut Vec<(String, String)>,
+                  mandatory: &[String],
+                  out: &mut String) {
+        let tree = &arr.trees[ix];
+        let step = self.sset.statement(tree.address);
+        match step.statement_type() {
+            StatementType::Floating => {
+                let name = mm0_ident(step.math_at(1).slice);
+                if !mandatory.contains(&name) && !dummies.iter().any(|d| d.0 == name) {
+                    dummies.push((name.clone(), mm0_ident(step.math_at(0).slice)));
+                }
+                out.push_str(&name);
+            }
+            StatementType::Essential => out.push_str(&mm0_ident(step.label())),
+            _ => {
+                let frame = self.scope.get(step.label()).expect("proof steps have frames");
+                let mut order = self.binder_order(frame);
+                order.extend((0..frame.hypotheses.len()).filter(|&h| match frame.hypotheses[h] {
+                    Hyp::Essential(..) => true,
+                    Hyp::Floating(..) => false,
+                }));
+                out.push('(');
+                out.push_str(&mm0_ident(step.label()));
+                for h in order {
+                    out.push(' ');
+                    self.write_step(arr, tree.children[h], dummies, mandatory, out);
+                }
+                out.push(')');
+            }
+        }
+    }
+
+    fn translate(&mut self, stmt: StatementRef) -> Decl {
+        let label = mm0_ident(stmt.label());
+        let frame = match self.scope.get(stmt.label()) {
+            Some(frame) => frame,
+            None => return Decl::Skipped(label, "no valid frame"),
+        };
+        let binders = self.binders(frame);
+        if self.parser.is_syntax_axiom(stmt.address()) {
+            let sort = mm0_ident(stmt.math_at(0).slice);
+            return Decl::Term(label, binders, sort);
+        }
+        if stmt.math_len() == 0 || !self.parser.is_provable(stmt.math_at(0).slice) {
+            return Decl::Skipped(label, "syntax theorems are not supported");
+        }
+
+        let mut hyps = Vec::new();
+        for hyp in frame.hypotheses.iter() {
+            if let Hyp::Essential(addr, _) = *hyp {
+                let hstmt = self.sset.statement(addr);
+                match self.parse_provable(frame, hstmt) {
+                    Some(expr) => hyps.push((mm0_ident(hstmt.label()), expr)),
+                    None => return Decl::Skipped(label, "a hypothesis does not parse"),
+                }
+            }
+        }
+        let concl = match self.parse_provable(frame, stmt) {
+            Some(expr) => expr,
+            None => return Decl::Skipped(label, "the assertion does not parse"),
+        };
+
+        if stmt.statement_type() == StatementType::Axiom {
+            return Decl::Axiom(label, binders, hyps, concl);
+        }
+        let arr = match ProofTreeArray::new(self.sset, self.nset, self.scope, stmt) {
+            Ok(arr) => arr,
+            Err(_) => return Decl::Skipped(label, "the proof is incomplete or invalid"),
+        };
+        let mandatory: Vec<String> = binders.iter().map(|b| b.name.clone()).collect();
+        let mut dummies = Vec::new();
+        let mut proof = String::new();
+        self.write_step(&arr, arr.qed, &mut dummies, &mandatory, &mut proof);
+        Decl::Theorem(label, binders, hyps, concl, dummies, proof)
+    }
+}
+
+fn mm0_binders(binders: &[Binder]) -> String {
+    binders.iter()
+        .map(|b| match b.deps {
+            None => format!(" {{{}: {}}}", b.name, b.sort),
+            Some(ref deps) => {
+                let mut out = format!(" ({}: {}", b.name, b.sort);
+                for dep in deps {
+                    out.push(' ');
+                    out.push_str(dep);
+                }
+                out + ")"
+            }
+        })
+        .collect()
+}
+
+fn mmu_binders(binders: &[Binder]) -> String {
+    let items: Vec<String> = binders.iter()
+        .map(|b| match b.deps {
+            None => format!("({} {})", b.name, b.sort),
+            Some(ref deps) => format!("({} {} ({}))", b.name, b.sort, deps.join(" ")),
+        })
+        .collect();
+    format!("({})", items.join(" "))
+}
+
+/// Exports a database as a Metamath Zero specification, written to `mm0`,
+/// and its proofs, written to `mmu`.
+///
+/// See the module documentation for the translation.
+pub fn export_mm0<W: Write, V: Write>(sset: &SegmentSet,
+                                      nset: &Nameset,
+                                      scope: &ScopeResult,
+                                      mm0: &mut W,
+                                      mmu: &mut V)
+                                      -> Result<(), ExportError> {
+    let mut tr = Translator {
+        sset: sset,
+        nset: nset,
+        scope: scope,
+        parser: SyntaxParser::new(sset, nset, scope),
+        provable_sorts: Vec::new(),
+    };
+
+    let mut decls = Vec::new();
+    for sref in sset.segments() {
+        for stmt in sref {
+            match stmt.statement_type() {
+                StatementType::Axiom | StatementType::Provable => decls.push(tr.translate(stmt)),
+                _ => {}
+            }
+        }
+    }
+
+    for sort in tr.parser.typecodes() {
+        let name = mm0_ident(sort);
+        if !tr.parser.has_syntax_axioms(sort) {
+            writeln!(mm0, "pure sort {};", name)?;
+            writeln!(mmu, "(sort {} pure)", name)?;
+        } else if tr.provable_sorts.contains(sort) {
+            writeln!(mm0, "provable sort {};", name)?;
+            writeln!(mmu, "(sort {} provable)", name)?;
+        } else {
+            writeln!(mm0, "sort {};", name)?;
+            writeln!(mmu, "(sort {})", name)?;
+        }
+    }
+    for decl in decls {
+        match decl {
+            Decl::Term(label, binders, sort) => {
+                writeln!(mm0, "term {}{}: {};", label, mm0_binders(&binders), sort)?;
+                writeln!(mmu, "(term {} {} ({} ()))", label, mmu_binders(&binders), sort)?;
+            }
+            Decl::Axiom(label, binders, hyps, concl) => {
+                write!(mm0, "axiom {}{}", label, mm0_binders(&binders))?;
+                for (name, expr) in &hyps {
+                    write!(mm0, " ({}: $ {} $)", name, tr.mm0_string(expr))?;
+                }
+                writeln!(mm0, ": $ {} $;", tr.mm0_string(&concl))?;
+                let hyps: Vec<String> = hyps.iter().map(|h| tr.mmu_string(&h.1)).collect();
+                writeln!(mmu,
+                         "(axiom {} {} ({}) {})",
+                         label,
+                         mmu_binders(&binders),
+                         hyps.join(" "),
+                         tr.mmu_string(&concl))?;
+            }
+            Decl::Theorem(label, binders, hyps, concl, dummies, proof) => {
+                write!(mm0, "theorem {}{}", label, mm0_binders(&binders))?;
+                for (name, expr) in &hyps {
+                    write!(mm0, " ({}: $ {} $)", name, tr.mm0_string(expr))?;
+                }
+                writeln!(mm0, ": $ {} $;", tr.mm0_string(&concl))?;
+                let hyps: Vec<String> = hyps.iter()
+                    .map(|h| format!("({} {})", h.0, tr.mmu_string(&h.1)))
+                    .collect();
+                let dummies: Vec<String> =
+                    dummies.iter().map(|d| format!("({} {})", d.0, d.1)).collect();
+                writeln!(mmu,
+                         "(theorem {} {} ({}) {} ({})\n  {})",
+                         label,
+                         mmu_binders(&binders),
+                         hyps.join(" "),
+                         tr.mmu_string(&concl),
+                         dummies.join(" "),
+                         proof)?;
+            }
+            Decl::Skipped(label, reason) => writeln!(mm0, "-- {} skipped: {}", label, reason)?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use mm0::export_mm0;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_mm0_export() {
+    let text = format!("{}$c setvar A. $. $v x $. vx $f setvar x $.
+wal $a wff A. x ph $.
+${{ $d x ph $. ax-5 $a |- ( ph -> A. x ph ) $. $}}
+${{
+  th1.1 $e |- ph $.
+  th1 $p |- ( ps -> ph ) $= wph wps wph wi th1.1 wph wps ax-1 ax-mp $.
+$}}
+bad $p |- ph $= ? $.
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let sset = db.parse_result().clone();
+    let nset = db.name_result().clone();
+    let scope = db.scope_result().clone();
+    let (mut mm0, mut mmu) = (Vec::new(), Vec::new());
+    export_mm0(&sset, &nset, &scope, &mut mm0, &mut mmu).unwrap();
+    assert_eq!(String::from_utf8(mm0).unwrap(),
+               "provable sort wff;
+pure sort setvar;
+term wi (ph: wff) (ps: wff): wff;
+axiom ax_mp (ph: wff) (ps: wff) (min: $ ph $) (maj: $ wi ph ps $): $ ps $;
+axiom ax_1 (ph: wff) (ps: wff): $ wi ph (wi ps ph) $;
+term wal {x: setvar} (ph: wff x): wff;
+axiom ax_5 {x: setvar} (ph: wff): $ wi ph (wal x ph) $;
+theorem th1 (ph: wff) (ps: wff) (th1_1: $ ph $): $ wi ps ph $;
+-- bad skipped: the proof is incomplete or invalid
+");
+    assert_eq!(String::from_utf8(mmu).unwrap(),
+               "(sort wff provable)
+(sort setvar pure)
+(term wi ((ph wff ()) (ps wff ())) (wff ()))
+(axiom ax_mp ((ph wff ()) (ps wff ())) (ph (wi ph ps)) ps)
+(axiom ax_1 ((ph wff ()) (ps wff ())) () (wi ph (wi ps ph)))
+(term wal ((x setvar) (ph wff (x))) (wff ()))
+(axiom ax_5 ((x setvar) (ph wff ())) () (wi ph (wal x ph)))
+(theorem th1 ((ph wff ()) (ps wff ())) ((th1_1 ph)) (wi ps ph) ()
+  (ax_mp ph (wi ps ph) th1_1 (ax_1 ph ps)))
+");
+}
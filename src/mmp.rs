@@ -0,0 +1,222 @@
+//! Reading and writing mmj2 proof worksheets (`.mmp` files).
+//!
+//! A worksheet starts with a `$( <MM> <PROOF_ASST> THEOREM=label
+//! LOC_AFTER=label` header and ends with `$)`.  In between, every line which
+//! starts in the first column begins a new item, and indented lines continue
+//! it: a `*` comment, a `$d` disjoint variable restriction, a proof step, or
+//! the `$=` generated proof.  A proof step is written `name:hyps:label`
+//! followed by its formula, where `name` is `qed`, a step number, or `h`
+//! followed by a number for a hypothesis, and `hyps` is a comma-separated list
+//! of the steps it is derived from, with `?` for unknown ones.
+//!
+//! Formulas are kept as math token sequences, including the typecode; there
+//! is no grammar in this crate to parse them further.  `export::export_mmp`
+//! produces worksheets which `parse_worksheet` reads back.
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::Write;
+
+/// The error type for `parse_worksheet`.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum WorksheetError {
+    /// The text does not start with a `$( <MM> <PROOF_ASST>` header naming
+    /// the theorem
+    MissingHeader,
+    /// The step starting on the given line (counting from 1) does not have
+    /// the `name:hyps:label` form
+    MalformedStep(usize),
+    /// The worksheet is not closed by `$)`
+    Unterminated,
+}
+
+impl fmt::Display for WorksheetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WorksheetError::MissingHeader => write!(f, "Missing proof worksheet header"),
+            WorksheetError::MalformedStep(line) => write!(f, "Malformed step on line {}", line),
+            WorksheetError::Unterminated => write!(f, "Proof worksheet is not closed by $)"),
+        }
+    }
+}
+
+impl error::Error for WorksheetError {}
+
+/// One step of a worksheet.
+#[derive(Clone,Debug,Default,PartialEq,Eq)]
+pub struct WorksheetStep {
+    /// The step name: `qed`, a number, or `h` and a number for hypotheses.
+    pub name: String,
+    /// The names of the steps this one is derived from; `?` for each one
+    /// which is not known yet.
+    pub hyps: Vec<String>,
+    /// The label of the assertion or hypothesis justifying the step, if
+    /// known.
+    pub label: Option<String>,
+    /// The tokens of the formula, starting with the typecode.
+    pub math: Vec<String>,
+}
+
+impl WorksheetStep {
+    /// Returns true if the step is a hypothesis of the theorem.
+    pub fn is_hypothesis(&self) -> bool {
+        self.name.starts_with('h')
+    }
+
+    /// Returns true if the step is the final step, proving the theorem.
+    pub fn is_qed(&self) -> bool {
+        self.name == "qed"
+    }
+}
+
+/// The contents of a proof worksheet.
+#[derive(Clone,Debug,Default,PartialEq,Eq)]
+pub struct Worksheet {
+    /// The label of the theorem being proved.
+    pub theorem: String,
+    /// The label of the statement the theorem is to be inserted after, or
+    /// `None` if unspecified (`LOC_AFTER=?`).
+    pub loc_after: Option<String>,
+    /// The description of the theorem, one entry per line.
+    pub comment: Vec<String>,
+    /// The disjoint variable restrictions, one entry per `$d` line.
+    pub distinct: Vec<Vec<String>>,
+    /// The proof steps, in order.
+    pub steps: Vec<WorksheetStep>,
+    /// The tokens of the generated proof between `$=` and `$.`, if present.
+    pub proof: Option<Vec<String>>,
+}
+
+/// Parses a proof step from its text, which starts on line `line`.
+fn parse_step(text: &str, line: usize) -> Result<WorksheetStep, WorksheetError> {
+    let mut tokens = text.split_whitespace();
+    let head = tokens.next().unwrap_or("");
+    let parts: Vec<&str> = head.split(':').collect();
+    if parts.len() != 3 || parts[0].is_empty() {
+        return Err(WorksheetError::MalformedStep(line));
+    }
+    Ok(WorksheetStep {
+        name: parts[0].to_owned(),
+        hyps: if parts[1].is_empty() {
+            Vec::new()
+        } else {
+            parts[1].split(',').map(|hyp| hyp.to_owned()).collect()
+        },
+        label: if parts[2].is_empty() {
+            None
+        } else {
+            Some(parts[2].to_owned())
+        },
+        math: tokens.map(|tok| tok.to_owned()).collect(),
+    })
+}
+
+/// Parses the text of a proof worksheet.
+pub fn parse_worksheet(text: &str) -> Result<Worksheet, WorksheetError> {
+    // group lines into items, each with the line number it starts on
+    let mut items: Vec<(usize, String)> = Vec::new();
+    for (ix, line) in text.lines().enumerate() {
+        if line.starts_with(|ch: char| !ch.is_whitespace()) {
+            items.push((ix + 1, line.to_owned()));
+        } else if let Some(item) = items.last_mut() {
+            item.1.push('\n');
+            item.1.push_str(line);
+        }
+    }
+
+    let mut items = items.into_iter();
+    let mut sheet = Worksheet::default();
+    match items.next() {
+        Some((_, ref header)) if header.starts_with("$( <MM> <PROOF_ASST>") => {
+            for field in header.split_whitespace() {
+                if let Some(theorem) = field.strip_prefix("THEOREM=") {
+                    sheet.theorem = theorem.to_owned();
+                } else if let Some(loc) = field.strip_prefix("LOC_AFTER=") {
+                    if loc != "?" {
+                        sheet.loc_after = Some(loc.to_owned());
+                    }
+                }
+            }
+        }
+        _ => return Err(WorksheetError::MissingHeader),
+    }
+    if sheet.theorem.is_empty() {
+        return Err(WorksheetError::MissingHeader);
+    }
+
+    for (line, item) in items {
+        if item.starts_with("$)") {
+            return Ok(sheet);
+        } else if let Some(comment) = item.strip_prefix('*') {
+            sheet.comment = comment
+                .lines()
+                .map(|line| line.trim().to_owned())
+                .filter(|line| !line.is_empty())
+                .collect();
+        } else if item.starts_with("$d") {
+            sheet.distinct.push(item.split_whitespace().skip(1).map(|v| v.to_owned()).collect());
+        } else if item.starts_with("$=") {
+            sheet.proof = Some(item.split_whitespace()
+                .skip(1)
+                .take_while(|&tok| tok != "$.")
+                .map(|tok| tok.to_owned())
+                .collect());
+        } else {
+            sheet.steps.push(parse_step(&item, line)?);
+        }
+    }
+    Err(WorksheetError::Unterminated)
+}
+
+impl Worksheet {
+    /// Finds a step by name.
+    pub fn step(&self, name: &str) -> Option<&WorksheetStep> {
+        self.steps.iter().find(|step| step.name == name)
+    }
+
+    /// Writes the worksheet in the layout used by `export::export_mmp`,
+    /// with formulas aligned after the step prefixes and the proof wrapped
+    /// at 79 columns.
+    pub fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out,
+                 "$( <MM> <PROOF_ASST> THEOREM={}  LOC_AFTER={}\n",
+                 self.theorem,
+                 self.loc_after.as_ref().map_or("?", |loc| &loc[..]))?;
+        if !self.comment.is_empty() {
+            writeln!(out, "* {}\n", self.comment.join("\n  "))?;
+        }
+        for vars in &self.distinct {
+            writeln!(out, "$d {}", vars.join(" "))?;
+        }
+
+        let prefixes: Vec<String> = self.steps
+            .iter()
+            .map(|step| {
+                format!("{}:{}:{}",
+                        step.name,
+                        step.hyps.join(","),
+                        step.label.as_ref().map_or("", |label| &label[..]))
+            })
+            .collect();
+        let width = prefixes.iter().map(|prefix| prefix.len()).max().unwrap_or(0);
+        for (prefix, step) in prefixes.iter().zip(&self.steps) {
+            writeln!(out, "{:width$} {}", prefix, step.math.join(" "), width = width)?;
+        }
+
+        if let Some(ref proof) = self.proof {
+            writeln!(out)?;
+            let mut line = String::from("$=   ");
+            for tok in proof.iter().map(|tok| &tok[..]).chain(Some("$.")) {
+                if line.len() + 1 + tok.len() > 79 {
+                    writeln!(out, "{}", line)?;
+                    line = String::from("     ");
+                }
+                line.push(' ');
+                line.push_str(tok);
+            }
+            writeln!(out, "{}", line)?;
+        }
+        writeln!(out, "\n$)")
+    }
+}
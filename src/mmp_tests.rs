@@ -0,0 +1,47 @@
+use export::export_mmp;
+use mmp::WorksheetError;
+use mmp::parse_worksheet;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_worksheet() {
+    let text = format!("{}${{
+  th1.1 $e |- ph $.
+  $( Modus ponens
+     with an axiom. $)
+  th1 $p |- ( ps -> ph ) $= wph wps wph wi th1.1 wph wps ax-1 ax-mp $.
+$}}
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let sset = db.parse_result().clone();
+    let nset = db.name_result().clone();
+    let scope = db.scope_result().clone();
+    let mut out = Vec::new();
+    export_mmp(&sset, &nset, &scope, db.statement("th1").unwrap(), &mut out).unwrap();
+    let exported = String::from_utf8(out).unwrap();
+
+    let sheet = parse_worksheet(&exported).unwrap();
+    assert_eq!(sheet.theorem, "th1");
+    assert_eq!(sheet.loc_after, None);
+    assert_eq!(sheet.comment, vec!["Modus ponens", "with an axiom."]);
+    assert_eq!(sheet.steps.len(), 3);
+    assert!(sheet.steps[0].is_hypothesis());
+    assert_eq!(sheet.steps[0].label, Some("th1.1".to_owned()));
+    let qed = sheet.step("qed").unwrap();
+    assert_eq!(qed.hyps, vec!["1", "2"]);
+    assert_eq!(qed.label, Some("ax-mp".to_owned()));
+    assert_eq!(qed.math.join(" "), "|- ( ps -> ph )");
+    assert!(sheet.proof.is_some());
+
+    let mut out = Vec::new();
+    sheet.write(&mut out).unwrap();
+    assert_eq!(parse_worksheet(&String::from_utf8(out).unwrap()), Ok(sheet));
+
+    assert_eq!(parse_worksheet("h1::a |- ph\n$)"), Err(WorksheetError::MissingHeader));
+    assert_eq!(parse_worksheet("$( <MM> <PROOF_ASST> THEOREM=x LOC_AFTER=y\n\nqed:1 |- ph\n$)"),
+               Err(WorksheetError::MalformedStep(3)));
+    assert_eq!(parse_worksheet("$( <MM> <PROOF_ASST> THEOREM=x LOC_AFTER=y\n?:?: |- ph"),
+               Err(WorksheetError::Unterminated));
+}
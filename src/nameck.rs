@@ -32,7 +32,10 @@ use parser::TokenAddress;
 use parser::TokenPtr;
 use segment_set::SegmentSet;
 use std::borrow::Borrow;
+use std::cmp;
+use std::collections::BTreeSet;
 use std::hash::Hash;
+use std::ops::Bound;
 use std::sync::Arc;
 use std::u32;
 use util;
@@ -163,6 +166,26 @@ pub struct Nameset {
     dv_info: NameSlot<StatementAddress, Vec<Atom>>,
     labels: HashMap<Token, LabelInfo>,
     symbols: HashMap<Token, SymbolInfo>,
+    /// Defined labels in byte order, for prefix queries.
+    sorted_labels: BTreeSet<Token>,
+    /// Defined labels by their lowercase form.
+    folded_labels: HashMap<Token, Vec<Token>>,
+}
+
+/// Returns the number of single byte insertions, deletions and replacements
+/// needed to turn one string into another, ignoring case.
+fn edit_distance(first: &[u8], second: &[u8]) -> usize {
+    let mut row: Vec<usize> = (0..second.len() + 1).collect();
+    for (i, &a) in first.iter().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, &b) in second.iter().enumerate() {
+            let subst = diag + (!a.eq_ignore_ascii_case(&b)) as usize;
+            diag = row[j + 1];
+            row[j + 1] = cmp::min(subst, cmp::min(row[j], row[j + 1]) + 1);
+        }
+    }
+    row[second.len()]
 }
 
 impl Nameset {
@@ -234,6 +257,11 @@ impl Nameset {
         for &ref labdef in &seg.labels {
             let labelr = sref.statement(labdef.index).label();
             let label = copy_token(labelr);
+            if !self.labels.contains_key(labelr) {
+                self.sorted_labels.insert(label.clone());
+                autoviv(&mut self.folded_labels, labelr.to_ascii_lowercase().into_boxed_slice())
+                    .push(label.clone());
+            }
             let slot = autoviv(&mut self.labels, label);
             slot.generation = self.generation;
             if self.options.incremental && slot.atom == Atom::default() {
@@ -293,6 +321,13 @@ impl Nameset {
                     slot.generation = gen;
                     slot_remove(&mut slot.labels, StatementAddress::new(id, labdef.index));
                 });
+                if !self.labels.contains_key(label) {
+                    self.sorted_labels.remove(label);
+                    let folded = label.to_ascii_lowercase();
+                    deviv(&mut self.folded_labels, &folded[..], |same| {
+                        same.retain(|other| &other[..] != label);
+                    });
+                }
             }
 
             for &ref floatdef in &seg.floats {
@@ -338,6 +373,47 @@ impl Nameset {
         })
     }
 
+    /// Returns the labels starting with a prefix, in byte order.
+    pub fn labels_with_prefix(&self, prefix: &str) -> Vec<TokenPtr<'_>> {
+        self.sorted_labels
+            .range::<[u8], _>((Bound::Included(prefix.as_bytes()), Bound::Unbounded))
+            .take_while(|label| label.starts_with(prefix.as_bytes()))
+            .map(|label| &label[..])
+            .collect()
+    }
+
+    /// Returns the labels which are the same as `label` up to ASCII case, in
+    /// byte order.
+    pub fn lookup_label_ignore_case(&self, label: TokenPtr) -> Vec<TokenPtr<'_>> {
+        let mut out: Vec<TokenPtr> = self.folded_labels
+            .get(&label.to_ascii_lowercase()[..])
+            .map_or(Vec::new(), |same| same.iter().map(|label| &label[..]).collect());
+        out.sort();
+        out
+    }
+
+    /// Suggests up to `count` defined labels close to a label which was not
+    /// found, closest first, for messages such as "did you mean ax-mp?".
+    ///
+    /// Closeness is the edit distance ignoring case, and labels more than a
+    /// third of the length of `label` (but at least one edit) away are not
+    /// suggested.  Every label is compared, so this is meant for reporting
+    /// errors rather than for completion.
+    pub fn suggest_labels(&self, label: TokenPtr, count: usize) -> Vec<TokenPtr<'_>> {
+        let limit = cmp::max(1, label.len() / 3);
+        let mut found: Vec<(usize, TokenPtr)> = self.sorted_labels
+            .iter()
+            .filter(|other| {
+                let diff = cmp::max(other.len(), label.len()) - cmp::min(other.len(), label.len());
+                diff <= limit && &other[..] != label
+            })
+            .map(|other| (edit_distance(label, other), &other[..]))
+            .filter(|&(distance, _)| distance <= limit)
+            .collect();
+        found.sort();
+        found.into_iter().take(count).map(|(_, other)| other).collect()
+    }
+
     /// Looks up the address and type for a math symbol.
     pub fn lookup_symbol(&self, symbol: TokenPtr) -> Option<LookupSymbol> {
         self.symbols.get(symbol).and_then(|&ref syminfo| {
@@ -0,0 +1,23 @@
+use diag::DiagnosticClass;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_label_queries() {
+    let text = format!("{}Ax-1 $a |- ( ph -> ( ps -> ph ) ) $.
+th1 $p |- ( ps -> ( ph -> ps ) ) $= wps wph ax-11 $.
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let nset = db.name_result().clone();
+    assert_eq!(nset.labels_with_prefix("ax-"), vec![&b"ax-1"[..], b"ax-mp"]);
+    assert_eq!(nset.labels_with_prefix("w"), vec![&b"wi"[..], b"wph", b"wps"]);
+    assert!(nset.labels_with_prefix("x").is_empty());
+    assert_eq!(nset.lookup_label_ignore_case(b"AX-1"), vec![&b"Ax-1"[..], b"ax-1"]);
+    assert_eq!(nset.suggest_labels(b"ax-11", 5), vec![&b"Ax-1"[..], b"ax-1"]);
+    assert!(nset.suggest_labels(b"foo", 5).is_empty());
+    let notations = db.diag_notations(vec![DiagnosticClass::Verify]);
+    let messages: Vec<(&str, &[(&str, String)])> =
+        notations.iter().map(|note| (note.message, &note.args[..])).collect();
+    assert_eq!(messages[1], ("Did you mean {label}?", &[("label", "Ax-1".to_owned())][..]));
+}
@@ -0,0 +1,94 @@
+//! Index of the occurrences of math symbols in statements.
+//!
+//! `SegmentSet::statement_at` finds the math token under a source position,
+//! but highlighting every occurrence of a symbol, or renaming it, needs the
+//! reverse: where a given symbol is used.  `OccurrenceIndex` lists for each
+//! math symbol the statements whose math strings contain it, and the
+//! occurrences within one statement are found again from its token spans
+//! when asked for, which keeps the index small even for set.mm.
+//!
+//! Symbols are indexed by name, so a variable declared again by a `$v` in
+//! another group shares the list of the first one.
+
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::Span;
+use parser::Token;
+use parser::TokenAddress;
+use parser::TokenPtr;
+use parser::copy_token;
+use segment_set::SegmentSet;
+use util::HashMap;
+use util::new_map;
+
+/// One occurrence of a math symbol.
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub struct Occurrence {
+    /// The statement and index of the token in its math string.
+    pub address: TokenAddress,
+    /// The span of the token, relative to the segment like the spans of
+    /// diagnostics; `Database::lookup_position` turns it into a line and
+    /// column.
+    pub span: Span,
+}
+
+/// Lists the occurrences of a symbol in the math string of a statement, in
+/// order.
+pub fn occurrences_in(stmt: StatementRef, symbol: TokenPtr) -> Vec<Occurrence> {
+    stmt.math_iter()
+        .filter(|tok| tok.slice == symbol)
+        .map(|tok| {
+            Occurrence {
+                address: tok.address,
+                span: stmt.math_span(tok.index()),
+            }
+        })
+        .collect()
+}
+
+/// Analysis pass result mapping each math symbol to the statements which use
+/// it; see the module documentation.
+#[derive(Clone,Debug,Default)]
+pub struct OccurrenceIndex {
+    statements: HashMap<Token, Vec<StatementAddress>>,
+}
+
+impl OccurrenceIndex {
+    /// Reads the math strings of all statements, including `$c`, `$v` and
+    /// `$d` statements, to build the index.
+    pub fn new(sset: &SegmentSet) -> OccurrenceIndex {
+        let mut statements: HashMap<Token, Vec<StatementAddress>> = new_map();
+        for sref in sset.segments() {
+            for stmt in sref {
+                for tok in stmt.math_iter() {
+                    // statements are read one at a time, so a repeated symbol is last
+                    match statements.get_mut(tok.slice) {
+                        Some(stmts) => {
+                            if stmts.last() != Some(&stmt.address()) {
+                                stmts.push(stmt.address());
+                            }
+                        }
+                        None => {
+                            statements.insert(copy_token(tok.slice), vec![stmt.address()]);
+                        }
+                    }
+                }
+            }
+        }
+        OccurrenceIndex { statements: statements }
+    }
+
+    /// Returns the statements whose math strings contain a symbol, in
+    /// database order.
+    pub fn statements_using(&self, symbol: TokenPtr) -> &[StatementAddress] {
+        self.statements.get(symbol).map_or(&[], |stmts| &stmts[..])
+    }
+
+    /// Lists every occurrence of a symbol, in database order.
+    pub fn occurrences(&self, sset: &SegmentSet, symbol: TokenPtr) -> Vec<Occurrence> {
+        self.statements_using(symbol)
+            .iter()
+            .flat_map(|&addr| occurrences_in(sset.statement(addr), symbol))
+            .collect()
+    }
+}
@@ -0,0 +1,32 @@
+use occurrences::Occurrence;
+use occurrences::occurrences_in;
+use parser::as_str;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_occurrences() {
+    let text = format!("{}th1 $p |- ( ps -> ( ph -> ps ) ) $= wps wph ax-1 $.\n", DB);
+    let mut db = mkdb(text.as_bytes());
+    let sset = db.parse_result().clone();
+    let labels = |occs: &[Occurrence]| -> Vec<String> {
+        occs.iter()
+            .map(|occ| as_str(sset.statement(occ.address.statement).label()).to_owned())
+            .collect()
+    };
+    let occs = db.occurrences_of("ps");
+    assert_eq!(labels(&occs), vec!["", "wps", "wi", "maj", "ax-mp", "ax-1", "th1", "th1"]);
+    for occ in &occs {
+        let stmt = sset.statement(occ.address.statement);
+        assert_eq!(stmt.math_at(occ.address.token_index).slice, b"ps");
+        assert_eq!(occ.span.as_ref(&stmt.segment().buffer), b"ps");
+    }
+    let th1 = db.statement("th1").unwrap();
+    let indices: Vec<_> =
+        occurrences_in(th1, b"ps").iter().map(|occ| occ.address.token_index).collect();
+    assert_eq!(indices, vec![2, 7]);
+    let (_, line, col) = db.lookup_position(occs[7].address.statement, occs[7].span);
+    assert_eq!((line, col), (8, 27));
+    assert_eq!(db.occurrence_index().statements_using(b"ps").len(), 7);
+    assert!(db.occurrences_of("ch").is_empty());
+}
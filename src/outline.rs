@@ -0,0 +1,244 @@
+//! The outline of a database, as given by its heading comments.
+//!
+//! set.mm and the databases derived from it divide their statements with
+//! heading comments, which start with a line of decoration, then the title,
+//! then the same decoration again:
+//!
+//! ```text
+//! $(
+//! =-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=
+//!         Logical implication
+//! =-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=
+//! $)
+//! ```
+//!
+//! The decoration gives the level of the heading: `####` for parts, `#*#*`
+//! for chapters, `=-=-` for sections and `-.-.` for subsections.  This pass
+//! builds the tree of headings, each with the statements up to the next
+//! heading of the same or a higher level, and offers navigation between
+//! them.  Nodes are identified by their index in `Outline::nodes`, which lists
+//! them in database order; the root, index 0, stands for the whole database.
+
+use parser::Comparer;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::as_str;
+use segment_set::SegmentSet;
+use std::cmp::Ordering;
+
+/// The level of a heading in the outline.
+///
+/// Levels are ordered from the outermost to the innermost.
+#[derive(Copy,Clone,Debug,PartialEq,Eq,PartialOrd,Ord)]
+pub enum HeadingLevel {
+    /// The root of the outline, standing for the whole database.
+    Database,
+    /// A part, decorated with `####`.
+    Part,
+    /// A chapter, decorated with `#*#*`.
+    Chapter,
+    /// A section, decorated with `=-=-`.
+    Section,
+    /// A subsection, decorated with `-.-.`.
+    Subsection,
+}
+
+const DECORATIONS: [(&str, HeadingLevel); 4] = [("####", HeadingLevel::Part),
+                                                ("#*#*", HeadingLevel::Chapter),
+                                                ("=-=-", HeadingLevel::Section),
+                                                ("-.-.", HeadingLevel::Subsection)];
+
+/// Returns the level and title of a heading comment, or `None` if the
+/// comment is not a heading.
+pub fn heading(stmt: StatementRef) -> Option<(HeadingLevel, String)> {
+    let text = stmt.span().as_ref(&stmt.segment().segment.buffer);
+    let mut lines = text[2..]
+        .split(|&ch| ch == b'\n')
+        .map(|line| as_str(line).trim())
+        .filter(|line| !line.is_empty());
+    let rule = lines.next()?;
+    let level = match DECORATIONS.iter().find(|&&(deco, _)| rule.starts_with(deco)) {
+        Some(&(_, level)) => level,
+        None => return None,
+    };
+    lines.next().map(|title| (level, title.to_owned()))
+}
+
+/// A heading of the outline, with the statements it contains.
+#[derive(Clone,Debug)]
+pub struct OutlineNode {
+    /// The level of the heading.
+    pub level: HeadingLevel,
+    /// The title of the heading; empty for the root.
+    pub title: String,
+    /// The heading comment, or `None` for the root.
+    pub heading: Option<StatementAddress>,
+    /// The heading which ends this node, or `None` if it extends to the end
+    /// of the database.  The node spans the statements from its own heading
+    /// up to, but not including, this one.
+    pub end: Option<StatementAddress>,
+    /// The enclosing node, or `None` for the root.
+    pub parent: Option<usize>,
+    /// The nodes for the headings directly within this one, in database
+    /// order.
+    pub children: Vec<usize>,
+    /// The statements which belong to this node but not to any of its
+    /// children, in database order; comments are not included.
+    pub statements: Vec<StatementAddress>,
+}
+
+/// Analysis pass result for the heading structure of a database.
+#[derive(Clone,Debug)]
+pub struct Outline {
+    /// All nodes, in database order, beginning with the root.
+    pub nodes: Vec<OutlineNode>,
+}
+
+impl Outline {
+    /// Builds the outline of a database from its heading comments.
+    pub fn new(sset: &SegmentSet) -> Outline {
+        let mut nodes = vec![OutlineNode {
+                                 level: HeadingLevel::Database,
+                                 title: String::new(),
+                                 heading: None,
+                                 end: None,
+                                 parent: None,
+                                 children: Vec::new(),
+                                 statements: Vec::new(),
+                             }];
+        // the chain of nodes enclosing the current statement
+        let mut open = vec![0];
+        for sref in sset.segments() {
+            for stmt in sref {
+                if stmt.statement_type() != StatementType::Comment {
+                    nodes[*open.last().unwrap()].statements.push(stmt.address());
+                    continue;
+                }
+                let (level, title) = match heading(stmt) {
+                    Some(heading) => heading,
+                    None => continue,
+                };
+                while nodes[*open.last().unwrap()].level >= level {
+                    let closed = open.pop().unwrap();
+                    nodes[closed].end = Some(stmt.address());
+                }
+                let parent = *open.last().unwrap();
+                let ix = nodes.len();
+                nodes[parent].children.push(ix);
+                nodes.push(OutlineNode {
+                    level: level,
+                    title: title,
+                    heading: Some(stmt.address()),
+                    end: None,
+                    parent: Some(parent),
+                    children: Vec::new(),
+                    statements: Vec::new(),
+                });
+                open.push(ix);
+            }
+        }
+        Outline { nodes: nodes }
+    }
+
+    /// Returns the index of the root node.
+    pub fn root(&self) -> usize {
+        0
+    }
+
+    /// Returns a node by index.
+    pub fn node(&self, ix: usize) -> &OutlineNode {
+        &self.nodes[ix]
+    }
+
+    /// Returns the enclosing node of a node, or `None` for the root.
+    pub fn parent(&self, ix: usize) -> Option<usize> {
+        self.nodes[ix].parent
+    }
+
+    /// Returns the nodes directly within a node.
+    pub fn children(&self, ix: usize) -> &[usize] {
+        &self.nodes[ix].children
+    }
+
+    fn siblings(&self, ix: usize) -> (&[usize], usize) {
+        match self.nodes[ix].parent {
+            Some(parent) => {
+                let siblings = &self.nodes[parent].children[..];
+                (siblings, siblings.iter().position(|&sib| sib == ix).unwrap())
+            }
+            None => (&[], 0),
+        }
+    }
+
+    /// Returns the node after a node with the same parent, if any.
+    pub fn next(&self, ix: usize) -> Option<usize> {
+        let (siblings, pos) = self.siblings(ix);
+        siblings.get(pos + 1).cloned()
+    }
+
+    /// Returns the node before a node with the same parent, if any.
+    pub fn prev(&self, ix: usize) -> Option<usize> {
+        let (siblings, pos) = self.siblings(ix);
+        if pos == 0 {
+            None
+        } else {
+            siblings.get(pos - 1).cloned()
+        }
+    }
+
+    /// Returns the innermost node containing a statement.
+    ///
+    /// Since the nodes are in database order, this is the last node whose
+    /// heading is not after the statement.
+    pub fn containing(&self, sset: &SegmentSet, addr: StatementAddress) -> usize {
+        let pos = self.nodes[1..].binary_search_by(|node| {
+            match sset.order.cmp(&node.heading.unwrap(), &addr) {
+                Ordering::Greater => Ordering::Greater,
+                _ => Ordering::Less,
+            }
+        });
+        match pos {
+            Ok(ix) | Err(ix) => ix,
+        }
+    }
+
+    /// Returns all statements of a node and the nodes within it, including
+    /// the heading and other comments, in database order.  End-of-file
+    /// markers are left out.
+    pub fn section_statements(&self, sset: &SegmentSet, ix: usize) -> Vec<StatementAddress> {
+        let node = &self.nodes[ix];
+        let mut out = Vec::new();
+        for sref in sset.segments() {
+            for stmt in sref {
+                let addr = stmt.address();
+                if node.heading
+                    .is_some_and(|start| sset.order.cmp(&addr, &start) == Ordering::Less) {
+                    continue;
+                }
+                if node.end.is_some_and(|end| sset.order.cmp(&addr, &end) != Ordering::Less) {
+                    return out;
+                }
+                if stmt.statement_type() != StatementType::Eof {
+                    out.push(addr);
+                }
+            }
+        }
+        out
+    }
+
+    /// Returns the titles of a node and the nodes enclosing it, outermost
+    /// first, leaving out the root.
+    pub fn path(&self, ix: usize) -> Vec<&str> {
+        let mut titles = Vec::new();
+        let mut node = Some(ix);
+        while let Some(ix) = node {
+            if self.nodes[ix].heading.is_some() {
+                titles.push(&self.nodes[ix].title[..]);
+            }
+            node = self.nodes[ix].parent;
+        }
+        titles.reverse();
+        titles
+    }
+}
@@ -0,0 +1,53 @@
+use outline::HeadingLevel;
+use parser::StatementAddress;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_outline() {
+    let mut db = mkdb(b"$c |- A $.
+$( ####
+  Part one
+#### $)
+$( =-=-=-
+  Section one
+=-=-=- $)
+ax-a $a |- A $.
+$( -.-.-.
+  Subsection
+-.-.-. $)
+ax-b $a |- A $.
+$( =-=-=-
+  Section two
+=-=-=- $)
+$( Not a heading. $)
+ax-c $a |- A $.
+$( ####
+  Part two
+#### $)
+ax-d $a |- A $.
+");
+    let sset = db.parse_result().clone();
+    let outline = db.outline().clone();
+    let titles: Vec<&str> = outline.nodes.iter().map(|node| &node.title[..]).collect();
+    assert_eq!(titles,
+               vec!["", "Part one", "Section one", "Subsection", "Section two", "Part two"]);
+    assert_eq!(outline.node(3).level, HeadingLevel::Subsection);
+    assert_eq!(outline.children(outline.root()), &[1, 5]);
+    assert_eq!(outline.children(1), &[2, 4]);
+    assert_eq!(outline.parent(3), Some(2));
+    assert_eq!(outline.next(2), Some(4));
+    assert_eq!(outline.prev(4), Some(2));
+    assert_eq!(outline.next(4), None);
+    assert_eq!(outline.prev(1), None);
+    assert_eq!(outline.node(3).end, outline.node(4).heading);
+    assert_eq!(outline.node(2).end, outline.node(4).heading);
+    assert_eq!(outline.node(5).end, None);
+    assert_eq!(outline.path(3), vec!["Part one", "Section one", "Subsection"]);
+
+    let ax_b = db.statement("ax-b").unwrap().address();
+    let ax_c = db.statement("ax-c").unwrap().address();
+    assert_eq!(outline.node(3).statements, vec![ax_b]);
+    assert_eq!(outline.containing(&sset, ax_b), 3);
+    assert_eq!(outline.containing(&sset, ax_c), 4);
+    assert_eq!(outline.containing(&sset, StatementAddress::new(ax_b.segment_id, 0)), 0);
+}
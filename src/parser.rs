@@ -45,11 +45,24 @@
 //! `SegmentId` and `SegmentRef` cover the same use cases for segments, although
 //! it makes no sense to have a segment-local segment reference.
 
+use checkpoint::get_bytes;
+use checkpoint::get_span;
+use checkpoint::get_u32;
+use checkpoint::put_bytes;
+use checkpoint::put_span;
+use checkpoint::put_u32;
 use diag::Diagnostic;
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
 use std::cmp;
 use std::cmp::Ordering;
+#[cfg(feature = "mmap")]
+use std::fs::File;
+#[cfg(feature = "mmap")]
+use std::io;
 use std::mem;
 use std::ops::Deref;
+use std::ops::Range;
 use std::slice;
 use std::str;
 use std::sync::Arc;
@@ -62,7 +75,99 @@ use std::sync::Arc;
 /// although there would need to be a validity check (valid UTF-8 encodings are
 /// always canonical) in `Scanner::get_raw` and the eighth-bit hack in
 /// `scan_expression` would need to be reverted.
-pub type BufferRef = Arc<Vec<u8>>;
+pub type BufferRef = Arc<Buffer>;
+
+/// Memory holding the text of a source file.
+#[derive(Debug)]
+enum Storage {
+    /// Text read into memory.
+    Owned(Vec<u8>),
+    /// A file mapped into memory.
+    #[cfg(feature = "mmap")]
+    Mapped(Mmap),
+}
+
+/// The text of a source file, or of a slice of one.
+///
+/// Slices share the memory of the file, so splitting a large file for
+/// parallel parsing does not copy it.  With the `mmap` feature the memory can
+/// be a mapping of the file, which the operating system pages in and out as
+/// needed instead of holding a copy on the heap.  Buffers compare equal if
+/// their text is the same.
+#[derive(Clone,Debug)]
+pub struct Buffer {
+    storage: Arc<Storage>,
+    range: Range<usize>,
+}
+
+impl Buffer {
+    /// Wraps text read into memory.
+    pub fn new(text: Vec<u8>) -> Buffer {
+        let len = text.len();
+        Buffer {
+            storage: Arc::new(Storage::Owned(text)),
+            range: 0..len,
+        }
+    }
+
+    /// Maps a file into memory.
+    ///
+    /// # Safety
+    ///
+    /// The text changes, or reading it faults, if the file is modified or
+    /// truncated while it is mapped.  Databases are normally only changed by
+    /// replacing the file, which is safe.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn map(file: &File) -> io::Result<Buffer> {
+        let map = Mmap::map(file)?;
+        let len = map.len();
+        Ok(Buffer {
+            storage: Arc::new(Storage::Mapped(map)),
+            range: 0..len,
+        })
+    }
+
+    /// Returns a buffer for part of the text, sharing its memory.
+    pub fn slice(&self, range: Range<usize>) -> Buffer {
+        assert!(range.start <= range.end && range.end <= self.len());
+        Buffer {
+            storage: self.storage.clone(),
+            range: self.range.start + range.start..self.range.start + range.end,
+        }
+    }
+}
+
+impl Default for Buffer {
+    fn default() -> Buffer {
+        Buffer::new(Vec::new())
+    }
+}
+
+impl From<Vec<u8>> for Buffer {
+    fn from(text: Vec<u8>) -> Buffer {
+        Buffer::new(text)
+    }
+}
+
+impl Deref for Buffer {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        let all: &[u8] = match *self.storage {
+            Storage::Owned(ref text) => text,
+            #[cfg(feature = "mmap")]
+            Storage::Mapped(ref map) => map,
+        };
+        &all[self.range.clone()]
+    }
+}
+
+impl PartialEq for Buffer {
+    fn eq(&self, other: &Buffer) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for Buffer {}
 
 /// Semantic type for positions in files.
 ///
@@ -301,7 +406,7 @@ impl TokenAddress {
 }
 
 /// Expresses a valid range for a statement or token.
-#[derive(Copy,Clone,Debug,Default)]
+#[derive(Copy,Clone,Debug,Default,PartialEq,Eq)]
 pub struct GlobalRange {
     /// The starting position of the range, which is also the definition site.
     pub start: StatementAddress,
@@ -438,6 +543,195 @@ pub struct Segment {
     pub floats: Vec<FloatDef>,
 }
 
+const STATEMENT_TYPES: [StatementType; 15] = [Eof,
+                                              Invalid,
+                                              Comment,
+                                              TypesettingComment,
+                                              AdditionalInfoComment,
+                                              FileInclude,
+                                              Axiom,
+                                              Provable,
+                                              Essential,
+                                              Floating,
+                                              Disjoint,
+                                              OpenGroup,
+                                              CloseGroup,
+                                              Constant,
+                                              Variable];
+
+fn put_tokens(out: &mut Vec<u8>, tokens: &[Token]) {
+    put_u32(out, tokens.len() as u32);
+    for token in tokens {
+        put_bytes(out, token);
+    }
+}
+
+fn get_tokens(input: &mut &[u8]) -> Option<Vec<Token>> {
+    (0..get_u32(input)?).map(|_| get_bytes(input).map(copy_token)).collect()
+}
+
+impl Segment {
+    /// Returns the part of the buffer covered by the statements of the
+    /// segment, including their leading white space, or a null span if there
+    /// are none.
+    pub fn span(&self) -> Span {
+        match (self.statements.first(), self.statements.last()) {
+            (Some(first), Some(last)) => Span::new2(first.span.start, last.span.end),
+            _ => Span::null(),
+        }
+    }
+
+    /// Appends the parsed data of the segment, except its buffer and
+    /// diagnostics, to a checkpoint; see the `checkpoint` module.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        put_u32(out, self.statements.len() as u32);
+        for stmt in &self.statements {
+            let stype = STATEMENT_TYPES.iter().position(|&st| st == stmt.stype).unwrap();
+            put_u32(out, stype as u32);
+            put_span(out, stmt.span);
+            put_span(out, stmt.label);
+            put_u32(out, stmt.group as u32);
+            put_u32(out, stmt.group_end as u32);
+            put_u32(out, stmt.math_start as u32);
+            put_u32(out, stmt.proof_start as u32);
+            put_u32(out, stmt.proof_end as u32);
+        }
+        put_u32(out, self.span_pool.len() as u32);
+        for &span in &self.span_pool {
+            put_span(out, span);
+        }
+        put_span(out, self.next_file);
+        put_u32(out, self.global_dvs.len() as u32);
+        for dv in &self.global_dvs {
+            put_u32(out, dv.start as u32);
+            put_tokens(out, &dv.vars);
+        }
+        put_u32(out, self.symbols.len() as u32);
+        for sym in &self.symbols {
+            put_bytes(out, &sym.name);
+            put_u32(out, (sym.stype == SymbolType::Constant) as u32);
+            put_u32(out, sym.start as u32);
+            put_u32(out, sym.ordinal as u32);
+        }
+        put_u32(out, self.local_vars.len() as u32);
+        for var in &self.local_vars {
+            put_u32(out, var.index as u32);
+            put_u32(out, var.ordinal as u32);
+        }
+        put_u32(out, self.labels.len() as u32);
+        for label in &self.labels {
+            put_u32(out, label.index as u32);
+        }
+        put_u32(out, self.floats.len() as u32);
+        for float in &self.floats {
+            put_u32(out, float.start as u32);
+            put_bytes(out, &float.name);
+            put_bytes(out, &float.label);
+            put_bytes(out, &float.typecode);
+        }
+    }
+
+    /// Reads a segment written by `encode` for the given buffer, advancing
+    /// `input` past it.  Returns `None` if the data is malformed or refers to
+    /// text, tokens or statements outside the segment, or to math tokens
+    /// outside their statement.
+    pub fn decode(buffer: &BufferRef, input: &mut &[u8]) -> Option<Segment> {
+        let mut seg = Segment {
+            buffer: buffer.clone(),
+            statements: Vec::new(),
+            span_pool: Vec::new(),
+            diagnostics: Vec::new(),
+            next_file: Span::null(),
+            global_dvs: Vec::new(),
+            symbols: Vec::new(),
+            local_vars: Vec::new(),
+            labels: Vec::new(),
+            floats: Vec::new(),
+        };
+        for _ in 0..get_u32(input)? {
+            seg.statements.push(Statement {
+                stype: *STATEMENT_TYPES.get(get_u32(input)? as usize)?,
+                span: get_span(input)?,
+                label: get_span(input)?,
+                group: get_u32(input)? as StatementIndex,
+                group_end: get_u32(input)? as StatementIndex,
+                math_start: get_u32(input)? as usize,
+                proof_start: get_u32(input)? as usize,
+                proof_end: get_u32(input)? as usize,
+            });
+        }
+        for _ in 0..get_u32(input)? {
+            seg.span_pool.push(get_span(input)?);
+        }
+        seg.next_file = get_span(input)?;
+        for _ in 0..get_u32(input)? {
+            seg.global_dvs.push(GlobalDv {
+                start: get_u32(input)? as StatementIndex,
+                vars: get_tokens(input)?,
+            });
+        }
+        for _ in 0..get_u32(input)? {
+            seg.symbols.push(SymbolDef {
+                name: copy_token(get_bytes(input)?),
+                stype: if get_u32(input)? != 0 {
+                    SymbolType::Constant
+                } else {
+                    SymbolType::Variable
+                },
+                start: get_u32(input)? as StatementIndex,
+                ordinal: get_u32(input)? as TokenIndex,
+            });
+        }
+        for _ in 0..get_u32(input)? {
+            seg.local_vars.push(LocalVarDef {
+                index: get_u32(input)? as StatementIndex,
+                ordinal: get_u32(input)? as TokenIndex,
+            });
+        }
+        for _ in 0..get_u32(input)? {
+            seg.labels.push(LabelDef { index: get_u32(input)? as StatementIndex });
+        }
+        for _ in 0..get_u32(input)? {
+            seg.floats.push(FloatDef {
+                start: get_u32(input)? as StatementIndex,
+                name: copy_token(get_bytes(input)?),
+                label: copy_token(get_bytes(input)?),
+                typecode: copy_token(get_bytes(input)?),
+            });
+        }
+        let len = buffer.len() as FilePos;
+        let in_buffer = |span: &Span| span.start <= span.end && span.end <= len;
+        if !seg.statements.iter().all(|stmt| in_buffer(&stmt.span) && in_buffer(&stmt.label)) ||
+           !seg.span_pool.iter().all(&in_buffer) || !in_buffer(&seg.next_file) {
+            return None;
+        }
+        // a group may end just past the last statement if it is not closed
+        let count = seg.statements.len() as StatementIndex;
+        let in_segment = |index: StatementIndex| 0 <= index && index < count;
+        let in_group = |index: StatementIndex, limit| NO_STATEMENT <= index && index < limit;
+        // a symbol must be one of the math tokens of its statement
+        let has_token = |index: StatementIndex, ordinal: TokenIndex| {
+            in_segment(index) && 0 <= ordinal && {
+                let stmt = &seg.statements[index as usize];
+                (ordinal as usize) < stmt.proof_start - stmt.math_start
+            }
+        };
+        let pool = seg.span_pool.len();
+        if !seg.statements.iter().all(|stmt| {
+            in_group(stmt.group, count) && in_group(stmt.group_end, count + 1) &&
+            stmt.math_start <= stmt.proof_start && stmt.proof_start <= stmt.proof_end &&
+            stmt.proof_end <= pool
+        }) || !seg.global_dvs.iter().all(|dv| in_segment(dv.start)) ||
+           !seg.symbols.iter().all(|sym| has_token(sym.start, sym.ordinal)) ||
+           !seg.local_vars.iter().all(|var| has_token(var.index, var.ordinal)) ||
+           !seg.labels.iter().all(|label| in_segment(label.index)) ||
+           !seg.floats.iter().all(|float| in_segment(float.start)) {
+            return None;
+        }
+        Some(seg)
+    }
+}
+
 /// A pointer to a segment which knows its identity.
 ///
 /// `SegmentRef` objects are constructed from outside by the `segment_set`.
@@ -468,11 +762,130 @@ impl<'a> SegmentRef<'a> {
         }
     }
 
+    /// Fetch a single statement from this segment by its local index, or
+    /// `None` if there is no statement at the index.
+    pub fn statement_opt(self, index: StatementIndex) -> Option<StatementRef<'a>> {
+        if index < 0 {
+            return None;
+        }
+        self.segment.statements.get(index as usize).map(|statement| {
+            StatementRef {
+                segment: self,
+                statement: statement,
+                index: index,
+            }
+        })
+    }
+
+    /// Finds the statement whose span, including leading white space and
+    /// comments, contains a position in the segment's buffer.
+    pub fn statement_containing(self, pos: FilePos) -> Option<StatementRef<'a>> {
+        let index = self.segment
+            .statements
+            .binary_search_by(|stmt| {
+                if stmt.span.end <= pos {
+                    Ordering::Less
+                } else if stmt.span.start > pos {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            })
+            .ok()?;
+        Some(self.statement(index as StatementIndex))
+    }
+
     /// Returns the source size of the segment, a proxy for computational
     /// difficulty which drives the `database::Executor` bin-packing heuristics.
     pub fn bytes(self) -> usize {
         self.buffer.len()
     }
+
+    /// Lists the regions of source text whose interpretation was affected by
+    /// error recovery in the parser, in source order.
+    ///
+    /// Diagnostics reported after one of these regions may be consequences of
+    /// the recovery rather than independent errors; an editor can use this to
+    /// de-emphasize the affected text.
+    pub fn recovery_regions(self) -> Vec<RecoveryRegion> {
+        let mut out = Vec::new();
+        for stmt in self {
+            if stmt.statement_type() == Invalid {
+                out.push(RecoveryRegion {
+                    statement: stmt.index,
+                    span: stmt.span(),
+                    kind: RecoveryKind::Skipped,
+                });
+            }
+        }
+        for &(index, ref diag) in &self.diagnostics {
+            let stmt = self.statement(index);
+            let (span, kind) = match *diag {
+                Diagnostic::UnclosedMath |
+                Diagnostic::UnclosedProof |
+                Diagnostic::MidStatementInclude(_) => {
+                    if stmt.statement_type() == Invalid {
+                        continue;
+                    }
+                    // the statement span runs up to the keyword which ended
+                    // it; report only the part which was actually kept
+                    let end = if stmt.proof_len() > 0 {
+                        stmt.proof_span(stmt.proof_len() - 1).end
+                    } else if stmt.math_len() > 0 {
+                        stmt.math_span(stmt.math_len() - 1).end
+                    } else {
+                        stmt.span().end
+                    };
+                    (Span::new2(stmt.span().start, end), RecoveryKind::Truncated)
+                }
+                Diagnostic::UnclosedBeforeEof |
+                Diagnostic::UnclosedBeforeInclude(_) => {
+                    let last = self.statements.len() as StatementIndex - 1;
+                    (Span::new2(stmt.span().start, self.statement(last).span().end),
+                     RecoveryKind::ImplicitClose)
+                }
+                Diagnostic::UnclosedComment(span) => (span, RecoveryKind::UnclosedComment),
+                _ => continue,
+            };
+            out.push(RecoveryRegion {
+                statement: index,
+                span: span,
+                kind: kind,
+            });
+        }
+        out.sort_by_key(|r| (r.span.start, r.statement));
+        out
+    }
+}
+
+/// Ways in which parser error recovery can change the interpretation of the
+/// source text.
+#[derive(Copy,Clone,Debug,Eq,PartialEq)]
+pub enum RecoveryKind {
+    /// The statement was too damaged to interpret and is ignored by all later
+    /// passes; for an unknown keyword this includes the tokens skipped while
+    /// resynchronizing.
+    Skipped,
+    /// The statement was ended early at an unexpected keyword, which was then
+    /// reinterpreted as the start of the next statement.
+    Truncated,
+    /// A `${` group was still open at the end of the segment and was closed
+    /// implicitly; the region extends from the opener to the end of the
+    /// segment.
+    ImplicitClose,
+    /// A comment was never closed and swallowed the rest of the file.
+    UnclosedComment,
+}
+
+/// A region of a segment affected by parser error recovery.
+#[derive(Copy,Clone,Debug,Eq,PartialEq)]
+pub struct RecoveryRegion {
+    /// The statement to which the triggering diagnostic is attached.
+    pub statement: StatementIndex,
+    /// The affected text, relative to the segment buffer.
+    pub span: Span,
+    /// How the text was reinterpreted.
+    pub kind: RecoveryKind,
 }
 
 impl<'a> IntoIterator for SegmentRef<'a> {
@@ -504,6 +917,9 @@ pub enum StatementType {
     /// A comment which starts with a `$t` token and must be interpreted
     /// specially by the HTML generator.
     TypesettingComment,
+    /// A comment which starts with a `$j` token and carries additional
+    /// machine-readable information for tools.
+    AdditionalInfoComment,
     /// A `$[` directive; we process these as statements, and disallow them
     /// inside other statements, which violates the published Metamath spec but
     /// is allowed behavior as an erratum.
@@ -538,7 +954,8 @@ impl Default for StatementType {
 }
 
 impl StatementType {
-    fn takes_label(self) -> bool {
+    /// Returns true for statement types which carry a label.
+    pub fn takes_label(self) -> bool {
         match self {
             Axiom | Provable | Essential | Floating => true,
             _ => false,
@@ -847,6 +1264,10 @@ struct Scanner<'a> {
 const MM_VALID_SPACES: u64 = (1u64 << 9) | (1u64 << 10) | (1u64 << 12) | (1u64 << 13) |
                              (1u64 << 32);
 
+/// The UTF-8 encoding of U+FEFF, which some editors write at the start of
+/// text files.
+pub const BYTE_ORDER_MARK: &[u8] = b"\xEF\xBB\xBF";
+
 /// Check if a character which is known to be <= 32 is a valid Metamath
 /// whitespace.  May panic if out of range.
 fn is_mm_space_c0(byte: u8) -> bool {
@@ -1034,10 +1455,10 @@ impl<'a> Scanner<'a> {
             let ftok_ref = ftok.as_ref(self.buffer);
             if ftok_ref == b"$(" {
                 let ctype = self.get_comment(ftok, false);
-                let stype = if ctype == CommentType::Typesetting {
-                    TypesettingComment
-                } else {
-                    Comment
+                let stype = match ctype {
+                    CommentType::Typesetting => TypesettingComment,
+                    CommentType::Extra => AdditionalInfoComment,
+                    CommentType::Normal => Comment,
                 };
                 return Some(self.out_statement(stype, Span::new2(ftok.start, ftok.start)));
             } else {
@@ -1133,6 +1554,12 @@ impl<'a> Scanner<'a> {
                         self.diag(Diagnostic::SpuriousProof(tokn));
                     }
                     return true;
+                } else if toknref == b"$[" {
+                    // includes are only recognized between statements; end
+                    // the string here so the include is still processed
+                    self.diag(Diagnostic::MidStatementInclude(tokn));
+                    self.unget = tokn;
+                    return false;
                 } else {
                     // string is closed with no proof and with an error, whoops
                     self.unget = tokn;
@@ -1217,6 +1644,8 @@ impl<'a> Scanner<'a> {
                     self.invalidated = true;
                 } else if res.as_ref(self.buffer).contains(&b'$') {
                     self.diag(Diagnostic::FilenameDollar);
+                } else if res.as_ref(self.buffer).contains(&b'\\') {
+                    self.diag(Diagnostic::FilenameBackslash);
                 }
                 return res;
             } else if tref.len() > 0 && tref[0] == b'$' {
@@ -1233,7 +1662,9 @@ impl<'a> Scanner<'a> {
 
     /// Main function called to read a complete statement from the input buffer.
     fn get_statement(&mut self) -> Statement {
-        self.statement_start = self.position;
+        // `statement_start` was left at the end of the previous statement by
+        // `out_statement`, or at 0 for the first, so that a skipped byte order
+        // mark stays in the leading white space of the first statement
         self.statement_math_start = self.span_pool.len();
         self.statement_proof_start = self.span_pool.len();
 
@@ -1372,6 +1803,9 @@ impl<'a> Scanner<'a> {
                     }
                 }
                 FileInclude => {
+                    if top_group != NO_STATEMENT {
+                        self.diag(Diagnostic::IncludeNotTopLevel(top_group));
+                    }
                     // snag this _now_
                     seg.next_file = seg.statements[index as usize].label;
                     end_diag = Diagnostic::UnclosedBeforeInclude(index);
@@ -1480,7 +1914,7 @@ fn collect_definitions(seg: &mut Segment) {
 }
 
 /// Metamath spec valid label characters are `[-._a-zA-Z0-9]`
-fn is_valid_label(label: &[u8]) -> bool {
+pub fn is_valid_label(label: &[u8]) -> bool {
     label.iter().all(|&c| {
         c == b'.' || c == b'-' || c == b'_' || (c >= b'a' && c <= b'z') ||
         (c >= b'0' && c <= b'9') || (c >= b'A' && c <= b'Z')
@@ -1552,6 +1986,12 @@ pub fn parse_segments(input: &BufferRef) -> Vec<Arc<Segment>> {
         ..Scanner::default()
     };
     assert!(input.len() < FilePos::max_value() as usize);
+    // `IncludeOptions::strip_bom` removes the mark before parsing; otherwise
+    // it is skipped like white space, but kept in the source
+    if input.starts_with(BYTE_ORDER_MARK) {
+        scanner.diag(Diagnostic::ByteOrderMark);
+        scanner.position = BYTE_ORDER_MARK.len() as FilePos;
+    }
 
     loop {
         let (seg, last) = scanner.get_segment();
@@ -1569,7 +2009,7 @@ pub fn parse_segments(input: &BufferRef) -> Vec<Arc<Segment>> {
 /// Every error must be associated with a statement in our design, so associate
 /// it with the EOF statement of a zero-length segment.
 pub fn dummy_segment(diag: Diagnostic) -> Arc<Segment> {
-    let mut seg = parse_segments(&Arc::new(Vec::new())).pop().unwrap();
+    let mut seg = parse_segments(&Arc::new(Buffer::default())).pop().unwrap();
     Arc::get_mut(&mut seg).unwrap().diagnostics.push((0, diag));
     seg
 }
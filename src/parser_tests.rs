@@ -1,13 +1,15 @@
-use database::Database;
-use database::DbOptions;
 use diag::Diagnostic;
+use parser::Buffer;
 use parser::SegmentOrder;
 use parser::StatementAddress;
 use parser::StatementType;
 use parser::Span;
 use parser::NO_STATEMENT;
+use parser::RecoveryKind;
+use parser::RecoveryRegion;
 use parser::Comparer;
 use std::cmp::Ordering;
+use test_fixtures::mkdb;
 
 #[test]
 fn test_segment_order() {
@@ -33,14 +35,6 @@ fn test_segment_order() {
     assert_eq!(so.cmp(&f1, &c1), Ordering::Greater);
 }
 
-fn mkdb(text: &[u8]) -> Database {
-    let dbo = DbOptions::default();
-    let mut db = Database::new(dbo);
-    db.parse("test.mm".to_owned(),
-             vec![("test.mm".to_owned(), text.to_owned())]);
-    db
-}
-
 #[test]
 fn test_segref() {
     let mut db = mkdb(b"${ $}");
@@ -99,3 +93,51 @@ parse_test!(test_invalid_c0,
 parse_test!(test_invalid_del,
             b"$c X Y\x7F $.",
             [(0, Diagnostic::BadCharacter(6, 0x7F))]);
+parse_test!(test_include_mid_statement,
+            b"$c X $[ a.mm $] $.",
+            [(0, Diagnostic::MidStatementInclude(Span::new(5, 7)))]);
+parse_test!(test_include_in_group,
+            b"${ $[ a.mm $] $}",
+            [(1, Diagnostic::IncludeNotTopLevel(0)),
+             (0, Diagnostic::UnclosedBeforeInclude(1))]);
+parse_test!(test_byte_order_mark,
+            b"\xEF\xBB\xBF$c X $.",
+            [(0, Diagnostic::ByteOrderMark)]);
+parse_test!(test_include_backslash,
+            b"$[ sub\\a.mm $]",
+            [(0, Diagnostic::FilenameBackslash)]);
+
+#[test]
+fn test_buffer_slice() {
+    let buf = Buffer::new(b"$c X $. $c Y $.".to_vec());
+    let second = buf.slice(8..15);
+    assert_eq!(&second[..], b"$c Y $.");
+    assert_eq!(&second.slice(3..4)[..], b"Y");
+    assert_eq!(second, Buffer::new(b"$c Y $.".to_vec()));
+    assert!(buf != second);
+}
+
+#[test]
+fn test_recovery_regions() {
+    let mut db = mkdb(b"$c X $q Y $. $c Z $[ a.mm $] ${ $( x");
+    let regions = db.parse_result().segments()[0].recovery_regions();
+    assert_eq!(regions,
+               vec![RecoveryRegion {
+                        statement: 0,
+                        span: Span::new(0, 4),
+                        kind: RecoveryKind::Truncated,
+                    },
+                    RecoveryRegion {
+                        statement: 1,
+                        span: Span::new(5, 12),
+                        kind: RecoveryKind::Skipped,
+                    },
+                    RecoveryRegion {
+                        statement: 2,
+                        span: Span::new(13, 17),
+                        kind: RecoveryKind::Truncated,
+                    }]);
+    let regions = db.parse_result().segments()[2].recovery_regions();
+    assert_eq!(regions.iter().map(|r| r.kind).collect::<Vec<_>>(),
+               vec![RecoveryKind::ImplicitClose, RecoveryKind::UnclosedComment]);
+}
@@ -1,12 +1,21 @@
 //! The proof object model for RPN proofs used in Metamath.
 
 use diag::Diagnostic;
+use formula::Formula;
+#[cfg(feature = "grammar")]
+use grammar::SyntaxTree;
+use layout::statement_indent;
 use nameck::Nameset;
 use parser::as_str;
+use parser::copy_token;
 use parser::StatementAddress;
 use parser::StatementRef;
 use parser::StatementType::*;
+#[cfg(feature = "grammar")]
+use parser::Token;
 use parser::TokenPtr;
+use scopeck::Frame;
+use scopeck::Hyp;
 use scopeck::ScopeResult;
 use segment_set::SegmentSet;
 use std::cmp::max;
@@ -25,6 +34,7 @@ use std::u16;
 use util::HashMap;
 use util::new_map;
 use verify::ProofBuilder;
+use verify::build_steps;
 use verify::verify_one;
 
 /// A tree structure for storing proofs and grammar derivations.
@@ -136,6 +146,99 @@ impl ProofTreeArray {
         Ok(arr)
     }
 
+    /// Create a proof tree array from a NORMAL mode proof of a $p statement,
+    /// given as a list of step labels in place of its own proof, or return an
+    /// error if the proof is faulty
+    pub fn from_steps(sset: &SegmentSet,
+                      nset: &Nameset,
+                      scopes: &ScopeResult,
+                      stmt: StatementRef,
+                      steps: &[TokenPtr])
+                      -> Result<ProofTreeArray, Diagnostic> {
+        let mut arr = ProofTreeArray::default();
+        arr.qed = build_steps(sset, nset, scopes, &mut arr, stmt, steps)?;
+        arr.indent = arr.calc_indent();
+        Ok(arr)
+    }
+
+    fn insert(&mut self, tree: ProofTree, expr: Vec<u8>) -> usize {
+        let ix = self.trees.len();
+        self.map.insert(tree.hash, ix);
+        self.trees.push(tree);
+        self.exprs.push(expr);
+        ix
+    }
+
+    /// Adds a step applying a statement to earlier steps, for building
+    /// proofs other than by verification, and returns its index.  An
+    /// identical step is reused if there is one; otherwise `expr` is recorded
+    /// as its formula, in the format of `exprs`.
+    pub fn add_step(&mut self,
+                    address: StatementAddress,
+                    children: Vec<usize>,
+                    expr: Vec<u8>)
+                    -> usize {
+        let tree = ProofTree::new(self, address, children);
+        match self.index(&tree) {
+            Some(ix) => ix,
+            None => self.insert(tree, expr),
+        }
+    }
+
+    /// Adds the syntax proof of a formula, given as a parse tree, returning its
+    /// step and its math string in the format of `exprs`.  `floats` gives the
+    /// `$f` hypothesis proving each variable.  Returns `None` if a variable
+    /// has no `$f` hypothesis or the tree does not fit its syntax axioms.
+    #[cfg(feature = "grammar")]
+    pub fn add_syntax_proof(&mut self,
+                            sset: &SegmentSet,
+                            nset: &Nameset,
+                            scope: &ScopeResult,
+                            floats: &HashMap<Token, StatementAddress>,
+                            tree: &SyntaxTree)
+                            -> Option<(usize, Vec<u8>)> {
+        match *tree {
+            SyntaxTree::Var(ref name) => {
+                let addr = *floats.get(name)?;
+                let mut expr = vec![b' '];
+                expr.extend_from_slice(name);
+                Some((self.add_step(addr, Vec::new(), expr.clone()), expr))
+            }
+            SyntaxTree::App(addr, ref args) => {
+                let stmt = sset.statement(addr);
+                let frame = scope.get(stmt.label())?;
+                let mut children = Vec::new();
+                let mut exprs = new_map();
+                for hyp in frame.hypotheses.iter() {
+                    if let Hyp::Floating(_, var, _) = *hyp {
+                        let arg = args.get(children.len())?;
+                        let (step, expr) = self.add_syntax_proof(sset, nset, scope, floats, arg)?;
+                        children.push(step);
+                        exprs.insert(nset.atom_name(frame.var_list[var]), expr);
+                    }
+                }
+                let mut expr = Vec::new();
+                for tok in stmt.math_iter().skip(1) {
+                    match exprs.get(tok.slice) {
+                        Some(sub) => expr.extend_from_slice(sub),
+                        None => {
+                            expr.push(b' ');
+                            expr.extend_from_slice(tok.slice);
+                        }
+                    }
+                }
+                Some((self.add_step(addr, children, expr.clone()), expr))
+            }
+        }
+    }
+
+    /// Sets the QED step of a proof built with `add_step`, and recomputes the
+    /// distances of `indent`.
+    pub fn set_qed(&mut self, qed: usize) {
+        self.qed = qed;
+        self.indent = self.calc_indent();
+    }
+
     /// Get the minimum distance from each step to the QED step
     pub fn indent(&self) -> &[u16] {
         &self.indent
@@ -345,9 +448,6 @@ impl ProofBuilder for ProofTreeArray {
              -> usize {
         let tree = ProofTree::new(self, addr, trees);
         self.index(&tree).unwrap_or_else(|| {
-            let ix = self.trees.len();
-            self.map.insert(tree.hash, ix);
-            self.trees.push(tree);
             let mut uexpr = vec![b' '];
             for &chr in &pool[expr] {
                 if chr & 0x80 == 0 {
@@ -358,8 +458,7 @@ impl ProofBuilder for ProofTreeArray {
                 }
             }
             uexpr.pop();
-            self.exprs.push(uexpr);
-            ix
+            self.insert(tree, uexpr)
         })
     }
 }
@@ -527,16 +626,13 @@ impl<'a, 'b> ProofTreePrinterImpl<'a, 'b> {
         let frame = self.p.scope.get(self.p.thm_label).unwrap();
         for item in &rpn {
             if let &RPNStep::Normal { addr, .. } = item {
+                // mandatory hypotheses are referred to by their position
+                if frame.hypotheses.iter().any(|hyp| hyp.address() == addr) {
+                    continue;
+                }
                 let stmt = self.p.sset.statement(addr);
                 let vec = match stmt.statement_type() {
-                    Floating => {
-                        let atom = self.p.nset.var_atom(stmt).unwrap();
-                        if frame.var_list[..frame.mandatory_count].contains(&atom) {
-                            continue;
-                        }
-                        &mut proof_ordered_hyps
-                    }
-                    Essential => &mut proof_ordered_hyps,
+                    Floating | Essential => &mut proof_ordered_hyps,
                     Axiom | Provable => &mut proof_ordered,
                     _ => unreachable!(),
                 };
@@ -553,7 +649,7 @@ impl<'a, 'b> ProofTreePrinterImpl<'a, 'b> {
 
         let mut sorted_by_refs = (0..proof_ordered.len()).collect::<Vec<usize>>();
         sorted_by_refs.sort_by(|&a, &b| proof_ordered[b].1.cmp(&proof_ordered[a].1));
-        let mut i = frame.mandatory_count;
+        let mut i = frame.hypotheses.len();
         let mut cutoff = 20;
         while cutoff <= i {
             i -= cutoff;
@@ -602,23 +698,15 @@ impl<'a, 'b> ProofTreePrinterImpl<'a, 'b> {
         for item in &rpn {
             let (is_fwdref, mut letter) = match item {
                 &RPNStep::Normal { fwdref, addr, .. } => {
-                    let stmt = self.p.sset.statement(addr);
-                    let pos = if stmt.statement_type() == Floating {
-                        let atom = self.p.nset.var_atom(stmt).unwrap();
-                        frame.var_list[..frame.mandatory_count]
-                            .iter()
-                            .position(|&a| a == atom)
-                    } else {
-                        None
-                    };
+                    let pos = frame.hypotheses.iter().position(|hyp| hyp.address() == addr);
                     (fwdref != 0,
                      pos.unwrap_or_else(|| {
-                        frame.mandatory_count +
+                        frame.hypotheses.len() +
                         paren_stmt.iter().position(|s| s.address() == addr).unwrap()
                     }))
                 }
                 &RPNStep::Backref { backref, .. } => {
-                    (false, frame.mandatory_count + paren_stmt.len() + backref - 1)
+                    (false, frame.hypotheses.len() + paren_stmt.len() + backref - 1)
                 }
             };
             let code_start = letters.len();
@@ -726,3 +814,245 @@ impl<'a> fmt::Display for ProofTreePrinter<'a> {
             .fmt()
     }
 }
+
+/// Decodes the compressed proof of a `$p` statement into a `ProofTreeArray`,
+/// without verifying it.
+///
+/// Labels are resolved and each assertion is applied to as many steps as it
+/// has hypotheses, but no substitutions are made, so `exprs` holds an empty
+/// formula for every step.  Steps saved with `Z` become shared subtrees, as
+/// do identical subproofs.  Returns `None` if the statement has no compressed
+/// proof or no frame, and an error, with the diagnostic the verifier would
+/// give, if the proof is malformed or refers to unknown labels.
+pub fn decode_compressed(sset: &SegmentSet,
+                         scope: &ScopeResult,
+                         stmt: StatementRef)
+                         -> Option<Result<ProofTreeArray, Diagnostic>> {
+    if stmt.proof_len() == 0 || stmt.proof_slice_at(0) != b"(" {
+        return None;
+    }
+    scope.get(stmt.label()).map(|frame| decode_compressed_frame(sset, scope, frame, stmt))
+}
+
+fn decode_compressed_frame(sset: &SegmentSet,
+                           scope: &ScopeResult,
+                           frame: &Frame,
+                           stmt: StatementRef)
+                           -> Result<ProofTreeArray, Diagnostic> {
+    // the statements which step numbers refer to, with their hypothesis counts
+    let mut prepared: Vec<(StatementAddress, usize)> =
+        frame.hypotheses.iter().map(|hyp| (hyp.address(), 0)).collect();
+    let mut i = 1;
+    loop {
+        if i == stmt.proof_len() {
+            return Err(Diagnostic::ProofUnterminatedRoster);
+        }
+        let label = stmt.proof_slice_at(i);
+        i += 1;
+        if label == b")" {
+            break;
+        }
+        let step = match scope.get(label) {
+            Some(used) if used.stype == Floating => (used.valid.start, 0),
+            Some(used) => (used.valid.start, used.hypotheses.len()),
+            None => {
+                let hyp = frame.hypotheses.iter().find(|hyp| match **hyp {
+                    Hyp::Essential(addr, _) => sset.statement(addr).label() == label,
+                    Hyp::Floating(..) => false,
+                });
+                match hyp {
+                    Some(hyp) => (hyp.address(), 0),
+                    None => return Err(Diagnostic::StepMissing(copy_token(label))),
+                }
+            }
+        };
+        prepared.push(step);
+    }
+
+    let mut arr = ProofTreeArray::default();
+    let mut stack: Vec<usize> = Vec::new();
+    let mut saved: Vec<usize> = Vec::new();
+    let mut k = 0usize;
+    let mut can_save = false;
+    for ix in i..stmt.proof_len() {
+        for &ch in stmt.proof_slice_at(ix) {
+            if (b'A'..=b'T').contains(&ch) {
+                k = k * 20 + (ch - b'A') as usize;
+                let step = if k < prepared.len() {
+                    let (address, hyps) = prepared[k];
+                    let base = stack.len()
+                        .checked_sub(hyps)
+                        .ok_or(Diagnostic::ProofUnderflow)?;
+                    let children = stack.split_off(base);
+                    arr.add_step(address, children, Vec::new())
+                } else {
+                    *saved.get(k - prepared.len()).ok_or(Diagnostic::StepOutOfRange)?
+                };
+                stack.push(step);
+                k = 0;
+                can_save = true;
+            } else if (b'U'..=b'Y').contains(&ch) {
+                k = k * 5 + 1 + (ch - b'U') as usize;
+                if k >= (u32::MAX as usize / 20) - 1 {
+                    return Err(Diagnostic::ProofMalformedVarint);
+                }
+                can_save = false;
+            } else if ch == b'Z' {
+                if !can_save {
+                    return Err(Diagnostic::ProofInvalidSave);
+                }
+                saved.push(*stack.last().expect("can_save implies a step"));
+                can_save = false;
+            } else if ch == b'?' {
+                return Err(if k == 0 {
+                    Diagnostic::ProofIncomplete
+                } else {
+                    Diagnostic::ProofMalformedVarint
+                });
+            }
+        }
+    }
+    if k != 0 {
+        return Err(Diagnostic::ProofMalformedVarint);
+    }
+    match stack.len() {
+        0 => Err(Diagnostic::ProofNoSteps),
+        1 => {
+            arr.set_qed(stack[0]);
+            Ok(arr)
+        }
+        _ => Err(Diagnostic::ProofExcessEnd),
+    }
+}
+
+/// Writes a proof as a compressed proof of the given `$p` statement.
+///
+/// The label list is ordered by use count and packed into lines as
+/// metamath.exe does, and subtrees used more than once are shared with `Z`
+/// backreferences.  The result is the text which follows `$=`, including the
+/// final `$.`, laid out for the statement by the `format` option.
+pub fn encode_compressed(sset: &SegmentSet,
+                         nset: &Nameset,
+                         scope: &ScopeResult,
+                         stmt: StatementRef,
+                         arr: &ProofTreeArray)
+                         -> String {
+    let (indent, line_width) = sset.options.format.proof_layout(statement_indent(sset, stmt));
+    format!("{}",
+            ProofTreePrinter {
+                sset: sset,
+                nset: nset,
+                scope: scope,
+                thm_label: stmt.label(),
+                style: ProofStyle::Compressed,
+                arr: arr,
+                initial_chr: 2,
+                indent: indent,
+                line_width: line_width,
+            })
+}
+
+/// Rewrites the compressed proof of a `$p` statement as compactly as possible,
+/// laid out by the `format` option, as `SAVE PROOF / COMPRESSED` does in
+/// metamath.exe.
+///
+/// The proof is decoded without being verified, so this is cheap and also
+/// works on proofs which do not verify.  The label list is reordered so that
+/// the most used labels get the shortest step numbers, identical subproofs are
+/// shared, and the step letters are written again by `encode_compressed`.
+/// Returns `None` if the statement has no compressed proof or no frame, and
+/// an error if the proof is malformed.
+pub fn rewrap_compressed(sset: &SegmentSet,
+                         nset: &Nameset,
+                         scope: &ScopeResult,
+                         stmt: StatementRef)
+                         -> Option<Result<String, Diagnostic>> {
+    decode_compressed(sset, scope, stmt)
+        .map(|arr| arr.map(|arr| encode_compressed(sset, nset, scope, stmt, &arr)))
+}
+
+/// Re-expresses the proof of a `$p` statement as a compressed proof.
+///
+/// The existing proof may be in any format accepted by the verifier; it is
+/// verified and converted into a `ProofTreeArray`, then written with
+/// `encode_compressed`.
+pub fn compress_proof(sset: &SegmentSet,
+                      nset: &Nameset,
+                      scope: &ScopeResult,
+                      stmt: StatementRef)
+                      -> Result<String, Diagnostic> {
+    let arr = ProofTreeArray::new(sset, nset, scope, stmt)?;
+    Ok(encode_compressed(sset, nset, scope, stmt, &arr))
+}
+
+/// Compresses a NORMAL mode proof of a `$p` statement, given as a list of
+/// step labels, such as a proof found by a tool and not yet in the database.
+///
+/// The steps are verified against the frame of the statement and written
+/// with `encode_compressed`, which also takes a proof already built as a
+/// `ProofTreeArray`.
+pub fn compress_steps(sset: &SegmentSet,
+                      nset: &Nameset,
+                      scope: &ScopeResult,
+                      stmt: StatementRef,
+                      steps: &[TokenPtr])
+                      -> Result<String, Diagnostic> {
+    let arr = ProofTreeArray::from_steps(sset, nset, scope, stmt, steps)?;
+    Ok(encode_compressed(sset, nset, scope, stmt, &arr))
+}
+
+/// A step of an expanded proof, as returned by `explain_proof`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct ProofStep {
+    /// The hypothesis or assertion applied at this step.
+    pub address: StatementAddress,
+    /// The label of the statement applied.
+    pub label: String,
+    /// The steps proving the hypotheses of the statement applied, `$f` and
+    /// `$e`, in the order of its frame; indexes into the list of steps.
+    pub hyps: Vec<usize>,
+    /// The formula proved by this step.
+    pub formula: Formula,
+}
+
+/// Expands the proof of a `$p` statement into a list of steps, each with
+/// the formula it proves.
+///
+/// Steps are listed in the order the verifier evaluates them, so every step
+/// comes after its hypotheses and the last step proves the statement.  A
+/// subproof used several times, such as one saved with `Z` in a compressed
+/// proof, is listed once and referred to by all of its uses.  Returns an
+/// error if the proof does not verify.
+pub fn explain_proof(sset: &SegmentSet,
+                     nset: &Nameset,
+                     scope: &ScopeResult,
+                     stmt: StatementRef)
+                     -> Result<Vec<ProofStep>, Diagnostic> {
+    let arr = ProofTreeArray::new(sset, nset, scope, stmt)?;
+    // leave out steps which do not contribute to the result
+    let mut used = vec![false; arr.trees.len()];
+    used[arr.qed] = true;
+    for ix in (0..arr.trees.len()).rev() {
+        if used[ix] {
+            for &child in &arr.trees[ix].children {
+                used[child] = true;
+            }
+        }
+    }
+    let mut numbers = vec![0; arr.trees.len()];
+    let mut steps = Vec::new();
+    for (ix, tree) in arr.trees.iter().enumerate() {
+        if !used[ix] {
+            continue;
+        }
+        let applied = sset.statement(tree.address);
+        numbers[ix] = steps.len();
+        steps.push(ProofStep {
+            address: tree.address,
+            label: as_str(applied.label()).to_owned(),
+            hyps: tree.children.iter().map(|&child| numbers[child]).collect(),
+            formula: Formula::from_expr(applied.math_at(0).slice, &arr.exprs[ix]),
+        });
+    }
+    Ok(steps)
+}
@@ -0,0 +1,191 @@
+//! Statistics about proofs and the use of assertions.
+//!
+//! This pass computes the numbers shown on the Metamath website's "most used
+//! theorems" and axiom usage pages: for each assertion, how many theorems use
+//! it directly, and for each theorem, the size of its proof and which axioms
+//! it depends on, directly or indirectly.  (It is unrelated to the `stats`
+//! module, which times the passes.)
+//!
+//! Proof sizes are measured on the proof tree, so they do not depend on the
+//! format the proof is stored in.  The compressed length counts steps with
+//! every repeated subproof given once and then referred back to, as in a
+//! compressed proof; the expanded length counts steps with every subproof
+//! written out in full, as in a normal proof, and can be very large.  Both
+//! include the syntax-building steps.
+//!
+//! Only `$a` statements with a provable typecode, `|-` unless `$j` comments
+//! declare others, count as axioms for the dependence matrix, so definitions
+//! are included but syntax axioms are not.  An axiom depends on itself.
+
+use bit_set::Bitset;
+use extra_info::JConfig;
+use nameck::Nameset;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use proof::ProofTreeArray;
+use proof::decode_compressed;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use std::cmp;
+use std::cmp::Reverse;
+use usage::proof_labels;
+use util::HashMap;
+use util::HashSet;
+use util::new_set;
+
+/// The size of a proof; see the module documentation.
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub struct ProofSize {
+    /// The number of steps with repeated subproofs referred back to.
+    pub compressed: usize,
+    /// The number of steps with every subproof written out.  Saturates at
+    /// `u64::MAX`.
+    pub expanded: u64,
+    /// The largest number of results on the stack at once while verifying
+    /// the expanded proof.
+    pub max_stack_depth: usize,
+}
+
+/// The statistics of one assertion.
+#[derive(Clone,Debug)]
+pub struct AssertionStats {
+    /// The `$a` or `$p` statement.
+    pub address: StatementAddress,
+    /// The number of theorems whose proofs use this assertion directly.
+    pub uses: usize,
+    /// The size of the proof, or `None` for an axiom or a proof which is
+    /// incomplete or malformed.
+    pub proof: Option<ProofSize>,
+    /// The axioms this assertion depends on, as indices into
+    /// `ProofStats::axioms`.
+    pub axioms: Bitset,
+}
+
+/// Analysis pass result for the statistics of a database.
+#[derive(Clone,Debug,Default)]
+pub struct ProofStats {
+    /// The `$a` and `$p` statements, in database order.
+    pub assertions: Vec<AssertionStats>,
+    /// The axioms of the dependence matrix, in database order.
+    pub axioms: Vec<StatementAddress>,
+    index: HashMap<StatementAddress, usize>,
+}
+
+/// Measures a proof tree.
+fn proof_size(arr: &ProofTreeArray) -> ProofSize {
+    // children always come before their parents
+    let mut expanded = vec![0u64; arr.trees.len()];
+    let mut depth = vec![0usize; arr.trees.len()];
+    for (ix, tree) in arr.trees.iter().enumerate() {
+        let mut size = 1u64;
+        let mut max = 1;
+        for (i, &child) in tree.children.iter().enumerate() {
+            size = size.saturating_add(expanded[child]);
+            max = cmp::max(max, i + depth[child]);
+        }
+        expanded[ix] = size;
+        depth[ix] = max;
+    }
+    ProofSize {
+        compressed: arr.to_rpn(&arr.count_parents(), false).len(),
+        expanded: expanded[arr.qed],
+        max_stack_depth: depth[arr.qed],
+    }
+}
+
+/// Builds the proof tree of a `$p` statement, decoding a compressed proof
+/// without verifying it.
+fn proof_tree(sset: &SegmentSet,
+              nset: &Nameset,
+              scope: &ScopeResult,
+              stmt: StatementRef)
+              -> Option<ProofTreeArray> {
+    match decode_compressed(sset, scope, stmt) {
+        Some(arr) => arr.ok(),
+        None => ProofTreeArray::new(sset, nset, scope, stmt).ok(),
+    }
+}
+
+impl ProofStats {
+    /// Computes the statistics of every assertion in the database.
+    pub fn new(sset: &SegmentSet, nset: &Nameset, scope: &ScopeResult) -> ProofStats {
+        let config = JConfig::new(sset);
+        let mut stats = ProofStats::default();
+        for sref in sset.segments() {
+            for stmt in sref {
+                let axiom = match stmt.statement_type() {
+                    StatementType::Axiom => {
+                        stmt.math_len() > 0 && config.is_provable(stmt.math_at(0).slice)
+                    }
+                    StatementType::Provable => false,
+                    _ => continue,
+                };
+                let mut axioms = Bitset::new();
+                if axiom {
+                    axioms.set_bit(stats.axioms.len());
+                    stats.axioms.push(stmt.address());
+                }
+                let mut proof = None;
+                if stmt.statement_type() == StatementType::Provable {
+                    let mut used: HashSet<StatementAddress> = new_set();
+                    for (_, label) in proof_labels(stmt).0 {
+                        let frame = match scope.get(label) {
+                            Some(frame) => frame,
+                            None => continue,
+                        };
+                        // a proof can only use earlier assertions
+                        if let Some(&ix) = stats.index.get(&frame.valid.start) {
+                            if used.insert(frame.valid.start) {
+                                stats.assertions[ix].uses += 1;
+                                axioms |= &stats.assertions[ix].axioms;
+                            }
+                        }
+                    }
+                    proof = proof_tree(sset, nset, scope, stmt).map(|arr| proof_size(&arr));
+                }
+                stats.index.insert(stmt.address(), stats.assertions.len());
+                stats.assertions.push(AssertionStats {
+                    address: stmt.address(),
+                    uses: 0,
+                    proof: proof,
+                    axioms: axioms,
+                });
+            }
+        }
+        stats
+    }
+
+    /// Returns the statistics of an assertion.
+    pub fn get(&self, address: StatementAddress) -> Option<&AssertionStats> {
+        self.index.get(&address).map(|&ix| &self.assertions[ix])
+    }
+
+    /// Returns the axioms an assertion depends on, in database order; this is
+    /// a row of the dependence matrix.
+    pub fn axioms_used(&self, address: StatementAddress) -> Vec<StatementAddress> {
+        match self.get(address) {
+            Some(stats) => stats.axioms.into_iter().map(|ix| self.axioms[ix]).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns whether an assertion depends on an axiom; this is an entry of
+    /// the dependence matrix.
+    pub fn depends_on(&self, address: StatementAddress, axiom: StatementAddress) -> bool {
+        match (self.get(address), self.axioms.iter().position(|&ax| ax == axiom)) {
+            (Some(stats), Some(bit)) => stats.axioms.has_bit(bit),
+            _ => false,
+        }
+    }
+
+    /// Returns the assertions used by at least one proof, the most used
+    /// first, and in database order among those used equally often.
+    pub fn most_used(&self) -> Vec<&AssertionStats> {
+        let mut used: Vec<&AssertionStats> =
+            self.assertions.iter().filter(|stats| stats.uses > 0).collect();
+        // the sort is stable, so database order is kept among ties
+        used.sort_by_key(|stats| Reverse(stats.uses));
+        used
+    }
+}
@@ -0,0 +1,53 @@
+use proof_stats::ProofSize;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_proof_stats() {
+    let text = format!("{}th1 $p |- ( ph -> ( ps -> ph ) ) $= wph wps ax-1 $.
+${{ h1 $e |- ph $. th2 $p |- ( ps -> ph ) $= wph wps wph wi h1 wph wps th1 ax-mp $. $}}
+th3 $p |- ( ( ph -> ph ) -> ( ( ph -> ph ) -> ( ph -> ph ) ) ) $= ( wi ax-1 ) AABZDC $.
+th4 $p |- ( ph -> ph ) $= ? $.\n",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let mut addr = |label| db.statement(label).unwrap().address();
+    let (wph, wi, ax_mp, ax_1) = (addr("wph"), addr("wi"), addr("ax-mp"), addr("ax-1"));
+    let (th1, th2, th3, th4) = (addr("th1"), addr("th2"), addr("th3"), addr("th4"));
+    let stats = db.proof_stats().clone();
+
+    let size = |addr| stats.get(addr).unwrap().proof;
+    assert_eq!(size(th1),
+               Some(ProofSize {
+                   compressed: 3,
+                   expanded: 3,
+                   max_stack_depth: 2,
+               }));
+    assert_eq!(size(th2),
+               Some(ProofSize {
+                   compressed: 9,
+                   expanded: 9,
+                   max_stack_depth: 5,
+               }));
+    assert_eq!(size(th3),
+               Some(ProofSize {
+                   compressed: 5,
+                   expanded: 7,
+                   max_stack_depth: 3,
+               }));
+    assert_eq!(size(th4), None);
+    assert_eq!(size(ax_1), None);
+
+    let uses = |addr| stats.get(addr).unwrap().uses;
+    assert_eq!((uses(wi), uses(ax_mp), uses(ax_1), uses(th1), uses(th2)), (2, 1, 2, 1, 0));
+    assert!(stats.get(wph).is_none());
+    let most_used: Vec<_> = stats.most_used().iter().map(|stats| stats.address).collect();
+    assert_eq!(most_used, vec![wi, ax_1, ax_mp, th1]);
+
+    assert_eq!(stats.axioms, vec![ax_mp, ax_1]);
+    assert_eq!(stats.axioms_used(th2), vec![ax_mp, ax_1]);
+    assert_eq!(stats.axioms_used(th3), vec![ax_1]);
+    assert_eq!(stats.axioms_used(wi), vec![]);
+    assert!(stats.depends_on(th1, ax_1));
+    assert!(!stats.depends_on(th1, ax_mp));
+    assert!(stats.depends_on(ax_mp, ax_mp));
+}
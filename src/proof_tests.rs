@@ -0,0 +1,137 @@
+use database::Database;
+use database::DbOptions;
+use diag::Diagnostic;
+use diag::DiagnosticClass;
+use parser::as_str;
+use proof::compress_proof;
+use proof::compress_steps;
+use proof::decode_compressed;
+use proof::encode_compressed;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_compress_proof() {
+    let thm = "th1 $p |- ( ( ph -> ph ) -> ( ( ph -> ph ) -> ( ph -> ph ) ) ) $=";
+    let text = format!("{}{} wph wph wi wph wph wi ax-1 $.\n", DB, thm);
+    let mut db = mkdb(text.as_bytes());
+    let sset = db.parse_result().clone();
+    let nset = db.name_result().clone();
+    let scope = db.scope_result().clone();
+    let stmt = db.statement("th1").unwrap();
+    let proof = compress_proof(&sset, &nset, &scope, stmt).unwrap();
+    assert_eq!(proof.split_whitespace().collect::<Vec<_>>(),
+               vec!["(", "wi", "ax-1", ")", "AABZDC", "$."]);
+
+    // the compressed proof must verify in place of the original
+    let text = format!("{}{}{}\n", DB, thm, proof);
+    let mut db = mkdb(text.as_bytes());
+    assert!(db.diag_notations(vec![DiagnosticClass::Parse,
+                                   DiagnosticClass::Scope,
+                                   DiagnosticClass::Verify])
+        .is_empty());
+}
+
+#[test]
+fn test_compress_steps() {
+    let thm = "th1 $p |- ( ( ph -> ph ) -> ( ( ph -> ph ) -> ( ph -> ph ) ) ) $= ? $.\n";
+    let text = format!("{}{}", DB, thm);
+    let mut db = mkdb(text.as_bytes());
+    let sset = db.parse_result().clone();
+    let nset = db.name_result().clone();
+    let scope = db.scope_result().clone();
+    let stmt = db.statement("th1").unwrap();
+    let steps: Vec<&[u8]> = vec![b"wph", b"wph", b"wi", b"wph", b"wph", b"wi", b"ax-1"];
+    let proof = compress_steps(&sset, &nset, &scope, stmt, &steps).unwrap();
+    assert_eq!(proof.split_whitespace().collect::<Vec<_>>(),
+               vec!["(", "wi", "ax-1", ")", "AABZDC", "$."]);
+
+    // steps that do not prove the statement are rejected
+    let steps: Vec<&[u8]> = vec![b"wph", b"wph", b"ax-1"];
+    assert!(compress_steps(&sset, &nset, &scope, stmt, &steps).is_err());
+}
+
+#[test]
+fn test_rewrap_proof() {
+    let thm = "th1 $p |- ( ( ph -> ph ) -> ( ( ph -> ph ) -> ( ph -> ph ) ) ) $=";
+    let text = format!("{}{} ( ax-1 wi ) AAC\n  ZDB $.\n\
+                        th2 $p |- ( ph -> ( ph -> ph ) ) $= wph wph ax-1 $.\n",
+                       DB,
+                       thm);
+    let mut db = mkdb(text.as_bytes());
+    let proof = db.rewrap_proof("th1").unwrap().unwrap();
+    assert_eq!(proof.split_whitespace().collect::<Vec<_>>(),
+               vec!["(", "wi", "ax-1", ")", "AABZDC", "$."]);
+    assert!(db.rewrap_proof("th2").is_none());
+    assert!(db.rewrap_proof("ax-1").is_none());
+
+    let mut options = DbOptions::default();
+    options.format.line_width = 16;
+    let mut db = Database::new(options);
+    db.parse("test.mm".to_owned(),
+             vec![("test.mm".to_owned(), text.as_bytes().to_owned())]);
+    let proof = db.rewrap_proof("th1").unwrap().unwrap();
+    assert!(proof.lines().all(|line| line.len() <= 16));
+    let text = format!("{}{}{}\n", DB, thm, proof);
+    let mut db = mkdb(text.as_bytes());
+    assert!(db.diag_notations(vec![DiagnosticClass::Parse,
+                                   DiagnosticClass::Scope,
+                                   DiagnosticClass::Verify])
+        .is_empty());
+}
+
+#[test]
+fn test_decode_compressed() {
+    let thm = "|- ( ( ph -> ph ) -> ( ( ph -> ph ) -> ( ph -> ph ) ) ) $=";
+    let text = format!("{}th1 $p {} ( wi ax-1 ) AABZDC $.\nth2 $p {} ( wi ax-1 ) AABZEC $.\n\
+                        th3 $p {} ( wi ax-2 ) AABZDC $.\nth4 $p {} wph wph wi wph wph wi ax-1 $.\n",
+                       DB,
+                       thm,
+                       thm,
+                       thm,
+                       thm);
+    let mut db = mkdb(text.as_bytes());
+    let sset = db.parse_result().clone();
+    let nset = db.name_result().clone();
+    let scope = db.scope_result().clone();
+    let stmt = db.statement("th1").unwrap();
+    let arr = decode_compressed(&sset, &scope, stmt).unwrap().unwrap();
+    let labels: Vec<&str> =
+        arr.trees.iter().map(|tree| as_str(sset.statement(tree.address).label())).collect();
+    assert_eq!(labels, vec!["wph", "wi", "ax-1"]);
+    assert_eq!(arr.trees[1].children, vec![0, 0]);
+    assert_eq!(arr.trees[arr.qed].children, vec![1, 1]);
+    assert_eq!(encode_compressed(&sset, &nset, &scope, stmt, &arr)
+                   .split_whitespace()
+                   .collect::<Vec<_>>(),
+               vec!["(", "wi", "ax-1", ")", "AABZDC", "$."]);
+
+    let stmt = db.statement("th2").unwrap();
+    assert_eq!(decode_compressed(&sset, &scope, stmt).unwrap().err(),
+               Some(Diagnostic::StepOutOfRange));
+    let stmt = db.statement("th3").unwrap();
+    assert_eq!(decode_compressed(&sset, &scope, stmt).unwrap().err(),
+               Some(Diagnostic::StepMissing(b"ax-2".to_vec().into_boxed_slice())));
+    let stmt = db.statement("th4").unwrap();
+    assert!(decode_compressed(&sset, &scope, stmt).is_none());
+}
+
+#[test]
+fn test_explain_proof() {
+    let text = format!("{}${{ th.1 $e |- ps $.
+th $p |- ( ph -> ps ) $= wps wph wps wi th.1 wps wph ax-1 ax-mp $. $}}
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let steps = db.explain_proof("th").unwrap().unwrap();
+    let show = |ix: usize| {
+        (&steps[ix].label[..], steps[ix].hyps.clone(), steps[ix].formula.to_string())
+    };
+    assert_eq!(steps.len(), 6);
+    assert_eq!(show(0), ("wps", vec![], "wff ps".to_owned()));
+    assert_eq!(show(2), ("wi", vec![1, 0], "wff ( ph -> ps )".to_owned()));
+    assert_eq!(show(3), ("th.1", vec![], "|- ps".to_owned()));
+    assert_eq!(show(4), ("ax-1", vec![0, 1], "|- ( ps -> ( ph -> ps ) )".to_owned()));
+    assert_eq!(show(5), ("ax-mp", vec![0, 2, 3, 4], "|- ( ph -> ps )".to_owned()));
+    assert!(db.explain_proof("ax-1").is_none());
+}
@@ -0,0 +1,637 @@
+//! Bounded backward proof search.
+//!
+//! This is a simple form of metamath.exe's `improve`: given a goal formula
+//! in the frame of a theorem and a list of assertions which may be used, it
+//! searches for a proof of the goal from the theorem's `$e` hypotheses.  The
+//! search works backwards from the goal: each assertion whose conclusion
+//! unifies with the goal is tried in turn, and its hypotheses become new
+//! goals.  Variables of an assertion which do not occur in its conclusion,
+//! such as the `ph` of `ax-mp`, become unknowns which later unifications
+//! determine, so inferences like modus ponens can be searched for.
+//!
+//! The search is depth-first with iterative deepening on the depth of the
+//! proof tree, cut off after a number of unification attempts.  Formulas are
+//! compared as parse trees (see `grammar`), so this needs the syntax axioms of
+//! the database.  Assertions with disjoint variable conditions are not used,
+//! which makes this mostly useful for the propositional fragment.
+//!
+//! `applicable` is the single backward step of an interactive prover, like
+//! mmj2's unification of a step: it lists the assertions whose conclusion
+//! matches a goal, with the hypotheses which would remain to be proved.
+//! Variables of an assertion which the goal does not determine are replaced
+//! by work variables, named `&` followed by the variable and a number, as in
+//! `&ph1`, which the user fills in later.
+
+use database::Database;
+use formula::Formula;
+use grammar::SyntaxParser;
+use grammar::SyntaxTree;
+use grammar::frame_variables;
+use grammar::global_variables;
+use nameck::Nameset;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::Token;
+use parser::TokenPtr;
+use parser::as_str;
+use parser::copy_token;
+use proof::ProofTreeArray;
+use scopeck::Frame;
+use scopeck::Hyp;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use std::fmt;
+use util::HashMap;
+use util::new_map;
+
+/// An assertion which can be applied to a goal, as found by `applicable`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Application {
+    /// The assertion.
+    pub address: StatementAddress,
+    /// The `$e` hypotheses of the assertion, in frame order, with its
+    /// variables replaced by the goal's subformulas or by work variables.
+    pub hypotheses: Vec<Formula>,
+    /// The work variables introduced, each with its typecode.
+    pub work_variables: Vec<(Token, Token)>,
+    /// An estimate of the work left, for ranking: each hypothesis costs 10
+    /// plus its number of symbols, unless it is a `$e` hypothesis of the
+    /// theorem, and each work variable costs 20.
+    pub cost: usize,
+}
+
+/// Limits of the search done by `prove`.
+#[derive(Copy,Clone,Debug)]
+pub struct ProverOptions {
+    /// The maximum depth of the proof tree, not counting syntax proofs; an
+    /// assertion without hypotheses has depth 1.
+    pub max_depth: usize,
+    /// The maximum number of unifications to try overall.
+    pub max_steps: usize,
+}
+
+impl Default for ProverOptions {
+    fn default() -> ProverOptions {
+        ProverOptions {
+            max_depth: 5,
+            max_steps: 100000,
+        }
+    }
+}
+
+/// The error type for `prove`.
+#[derive(Clone,Debug)]
+pub enum ProveError {
+    /// The label does not exist.
+    UnknownLabel(String),
+    /// The label is not a `$p` statement.
+    NotProvable(String),
+    /// The goal does not parse in the frame of the theorem.
+    GoalNotParsed,
+    /// No proof was found within the limits.
+    NotFound,
+}
+
+impl fmt::Display for ProveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProveError::UnknownLabel(ref label) => write!(f, "Unknown label {}", label),
+            ProveError::NotProvable(ref label) => write!(f, "{} is not a theorem", label),
+            ProveError::GoalNotParsed => write!(f, "The goal does not parse"),
+            ProveError::NotFound => write!(f, "No proof found"),
+        }
+    }
+}
+
+/// An assertion which may be used.
+struct Rule<'a> {
+    address: StatementAddress,
+    frame: &'a Frame,
+    assertion: SyntaxTree,
+    /// The `$e` hypotheses, in frame order.
+    hyps: Vec<SyntaxTree>,
+}
+
+/// A step of a proof in the order the search makes them, which is the
+/// preorder of the proof tree.
+enum Choice {
+    /// A `$e` hypothesis of the theorem.
+    Hyp(StatementAddress),
+    /// An assertion, with the unknown replacing each of its variables.
+    Rule(usize, HashMap<Token, Token>),
+}
+
+/// A goal with the remaining depth for proving it.
+struct Goal {
+    tree: SyntaxTree,
+    depth: usize,
+}
+
+/// Unknowns are variables whose names contain a NUL byte, which cannot occur
+/// in a math symbol.
+fn is_unknown(name: &[u8]) -> bool {
+    name.contains(&0)
+}
+
+/// Follows the bindings of unknowns at the root of a tree.
+fn walk<'t>(mut tree: &'t SyntaxTree, subst: &'t HashMap<Token, SyntaxTree>) -> &'t SyntaxTree {
+    while let SyntaxTree::Var(ref name) = *tree {
+        match subst.get(name) {
+            Some(bound) => tree = bound,
+            None => break,
+        }
+    }
+    tree
+}
+
+/// Applies the bindings of unknowns throughout a tree.
+fn resolve(tree: &SyntaxTree, subst: &HashMap<Token, SyntaxTree>) -> SyntaxTree {
+    match *walk(tree, subst) {
+        SyntaxTree::Var(ref name) => SyntaxTree::Var(name.clone()),
+        SyntaxTree::App(addr, ref args) => {
+            SyntaxTree::App(addr, args.iter().map(|arg| resolve(arg, subst)).collect())
+        }
+    }
+}
+
+fn occurs(name: &[u8], tree: &SyntaxTree, subst: &HashMap<Token, SyntaxTree>) -> bool {
+    match *walk(tree, subst) {
+        SyntaxTree::Var(ref var) => &var[..] == name,
+        SyntaxTree::App(_, ref args) => args.iter().any(|arg| occurs(name, arg, subst)),
+    }
+}
+
+struct Prover<'a> {
+    sset: &'a SegmentSet,
+    nset: &'a Nameset,
+    scope: &'a ScopeResult,
+    parser: &'a SyntaxParser,
+    /// The typecode of each variable, including the unknowns.
+    vars: HashMap<Token, Token>,
+    /// The `$f` hypotheses of the theorem, proving each of its variables.
+    floats: HashMap<Token, StatementAddress>,
+    /// The `$e` hypotheses of the theorem with the goal's typecode.
+    hyps: Vec<(StatementAddress, SyntaxTree)>,
+    rules: Vec<Rule<'a>>,
+    unknowns: usize,
+    steps: usize,
+    max_steps: usize,
+}
+
+impl<'a> Prover<'a> {
+    fn parse(&self, vars: &HashMap<Token, Token>, stmt: StatementRef) -> Option<SyntaxTree> {
+        parse_statement(self.parser, vars, stmt)
+    }
+
+    /// Unifies two trees, extending the bindings of unknowns; on failure the
+    /// bindings may have been partially extended.
+    fn unify(&self,
+             first: &SyntaxTree,
+             second: &SyntaxTree,
+             subst: &mut HashMap<Token, SyntaxTree>)
+             -> bool {
+        let first = walk(first, subst).clone();
+        let second = walk(second, subst).clone();
+        match (&first, &second) {
+            (SyntaxTree::Var(v1), SyntaxTree::Var(v2)) if v1 == v2 => true,
+            (SyntaxTree::Var(var), tree) | (tree, SyntaxTree::Var(var)) if is_unknown(var) => {
+                let typecode = self.parser.typecode_of(tree, &self.vars);
+                if typecode.as_ref() != self.vars.get(var) || occurs(var, tree, subst) {
+                    return false;
+                }
+                subst.insert(var.clone(), tree.clone());
+                true
+            }
+            (SyntaxTree::App(a1, args1), SyntaxTree::App(a2, args2)) => {
+                a1 == a2 && args1.len() == args2.len() &&
+                args1.iter().zip(args2).all(|(x, y)| self.unify(x, y, subst))
+            }
+            _ => false,
+        }
+    }
+
+    /// Replaces the variables of an assertion by fresh unknowns.
+    fn rename(&mut self, rule: usize) -> HashMap<Token, Token> {
+        let frame = self.rules[rule].frame;
+        let mut renaming = new_map();
+        for hyp in frame.hypotheses.iter() {
+            if let Hyp::Floating(_, var, typecode) = *hyp {
+                let name = self.nset.atom_name(frame.var_list[var]);
+                let mut unknown = name.to_vec();
+                unknown.push(0);
+                unknown.extend_from_slice(self.unknowns.to_string().as_bytes());
+                self.unknowns += 1;
+                let unknown = unknown.into_boxed_slice();
+                self.vars.insert(unknown.clone(), self.nset.atom_name(typecode).into());
+                renaming.insert(name.into(), unknown);
+            }
+        }
+        renaming
+    }
+
+    /// Proves the goals on the stack, last first, recording the steps.
+    fn solve(&mut self,
+             goals: &mut Vec<Goal>,
+             subst: &mut HashMap<Token, SyntaxTree>,
+             trace: &mut Vec<Choice>)
+             -> bool {
+        let goal = match goals.pop() {
+            Some(goal) => goal,
+            None => return true,
+        };
+        for ix in 0..self.hyps.len() {
+            if self.steps >= self.max_steps {
+                break;
+            }
+            self.steps += 1;
+            let saved = subst.clone();
+            let (addr, ref hyp) = self.hyps[ix];
+            if self.unify(&goal.tree, hyp, subst) {
+                trace.push(Choice::Hyp(addr));
+                if self.solve(goals, subst, trace) {
+                    return true;
+                }
+                trace.pop();
+            }
+            *subst = saved;
+        }
+        if goal.depth > 0 {
+            for rule in 0..self.rules.len() {
+                if self.steps >= self.max_steps {
+                    break;
+                }
+                self.steps += 1;
+                let renaming = self.rename(rule);
+                let conclusion = self.rules[rule].assertion.substitute(&renamed(&renaming));
+                let saved = subst.clone();
+                if self.unify(&goal.tree, &conclusion, subst) {
+                    let pending = goals.len();
+                    for hyp in self.rules[rule].hyps.iter().rev() {
+                        goals.push(Goal {
+                            tree: hyp.substitute(&renamed(&renaming)),
+                            depth: goal.depth - 1,
+                        });
+                    }
+                    trace.push(Choice::Rule(rule, renaming));
+                    if self.solve(goals, subst, trace) {
+                        return true;
+                    }
+                    trace.pop();
+                    goals.truncate(pending);
+                }
+                *subst = saved;
+            }
+        }
+        goals.push(goal);
+        false
+    }
+
+    /// Replaces the unknowns which no unification determined by variables of
+    /// the theorem of the same typecode, preferring the variable the unknown
+    /// was made for.
+    fn ground(&self, tree: &SyntaxTree, subst: &HashMap<Token, SyntaxTree>) -> Option<SyntaxTree> {
+        match resolve(tree, subst) {
+            SyntaxTree::Var(ref name) if is_unknown(name) => {
+                let original = name.split(|&ch| ch == 0).next().expect("split is not empty");
+                if self.floats.contains_key(original) {
+                    return Some(SyntaxTree::Var(original.into()));
+                }
+                let typecode = self.vars.get(name)?;
+                self.floats
+                    .keys()
+                    .filter(|var| self.vars.get(*var) == Some(typecode))
+                    .min()
+                    .map(|var| SyntaxTree::Var(var.clone()))
+            }
+            SyntaxTree::Var(name) => Some(SyntaxTree::Var(name)),
+            SyntaxTree::App(addr, args) => {
+                let args: Option<Vec<SyntaxTree>> =
+                    args.iter().map(|arg| self.ground(arg, subst)).collect();
+                Some(SyntaxTree::App(addr, args?))
+            }
+        }
+    }
+
+    /// Adds the steps of a proof found by the search, taking them from the
+    /// trace in order, and returns the last.
+    fn build(&self,
+             trace: &mut ::std::slice::Iter<Choice>,
+             subst: &HashMap<Token, SyntaxTree>,
+             arr: &mut ProofTreeArray)
+             -> Option<usize> {
+        match *trace.next()? {
+            Choice::Hyp(addr) => {
+                let mut expr = Vec::new();
+                for tok in self.sset.statement(addr).math_iter().skip(1) {
+                    expr.push(b' ');
+                    expr.extend_from_slice(tok.slice);
+                }
+                Some(arr.add_step(addr, Vec::new(), expr))
+            }
+            Choice::Rule(ix, ref renaming) => {
+                let rule = &self.rules[ix];
+                let mut children = Vec::new();
+                for hyp in rule.frame.hypotheses.iter() {
+                    children.push(match *hyp {
+                        Hyp::Floating(_, var, _) => {
+                            let name = self.nset.atom_name(rule.frame.var_list[var]);
+                            let tree = SyntaxTree::Var(renaming.get(name)?.clone());
+                            let tree = self.ground(&tree, subst)?;
+                            let proof = arr.add_syntax_proof(self.sset,
+                                                             self.nset,
+                                                             self.scope,
+                                                             &self.floats,
+                                                             &tree);
+                            proof?.0
+                        }
+                        Hyp::Essential(..) => self.build(trace, subst, arr)?,
+                    });
+                }
+                let conclusion = rule.assertion.substitute(&renamed(renaming));
+                let conclusion = self.ground(&conclusion, subst)?;
+                let formula = self.parser.to_formula(&conclusion, &self.vars)?;
+                let mut expr = Vec::new();
+                for sym in &formula.symbols {
+                    expr.push(b' ');
+                    expr.extend_from_slice(sym);
+                }
+                Some(arr.add_step(rule.address, children, expr))
+            }
+        }
+    }
+}
+
+/// Turns a renaming of variables into a substitution.
+fn renamed(renaming: &HashMap<Token, Token>) -> HashMap<Token, SyntaxTree> {
+    renaming.iter().map(|(var, unknown)| (var.clone(), SyntaxTree::Var(unknown.clone()))).collect()
+}
+
+/// Searches for a proof of a goal in the frame of a theorem, using its `$e`
+/// hypotheses and the allowed assertions; see the module documentation.
+///
+/// Allowed assertions which come after the theorem, have a different typecode
+/// from the goal or have disjoint variable conditions are ignored.  The proof
+/// is returned with its last step as QED, and can be printed with
+/// `ProofTreePrinter`.
+pub fn prove(db: &mut Database,
+             label: &str,
+             goal: &Formula,
+             allowed: &[String],
+             options: &ProverOptions)
+             -> Result<ProofTreeArray, ProveError> {
+    let sset = db.parse_result().clone();
+    let nset = db.name_result().clone();
+    let scope = db.scope_result().clone();
+    let parser = db.syntax_parser().clone();
+    let stmt = match db.statement(label) {
+        Some(stmt) => sset.statement(stmt.address()),
+        None => return Err(ProveError::UnknownLabel(label.to_owned())),
+    };
+    let frame = match scope.get(stmt.label()) {
+        Some(frame) if stmt.statement_type() == StatementType::Provable => frame,
+        _ => return Err(ProveError::NotProvable(label.to_owned())),
+    };
+    for name in allowed {
+        if db.statement(name).is_none() {
+            return Err(ProveError::UnknownLabel(name.clone()));
+        }
+    }
+
+    let mut vars = global_variables(&sset);
+    vars.extend(frame_variables(&nset, frame));
+    let mut floats = new_map();
+    for hyp in frame.hypotheses.iter() {
+        if let Hyp::Floating(addr, var, _) = *hyp {
+            floats.insert(nset.atom_name(frame.var_list[var]).into(), addr);
+        }
+    }
+    let mut prover = Prover {
+        sset: &sset,
+        nset: &nset,
+        scope: &scope,
+        parser: &parser,
+        vars: vars,
+        floats: floats,
+        hyps: Vec::new(),
+        rules: Vec::new(),
+        unknowns: 0,
+        steps: 0,
+        max_steps: options.max_steps,
+    };
+    let tree = match goal.parse(&parser, &prover.vars) {
+        Some((_, tree)) => tree,
+        None => return Err(ProveError::GoalNotParsed),
+    };
+    let provable = |stmt: StatementRef| {
+        stmt.math_len() > 0 && stmt.math_at(0).slice == &goal.typecode[..]
+    };
+    for hyp in frame.hypotheses.iter() {
+        if let Hyp::Essential(addr, _) = *hyp {
+            let hyp = sset.statement(addr);
+            if provable(hyp) {
+                if let Some(tree) = prover.parse(&prover.vars, hyp) {
+                    prover.hyps.push((addr, tree));
+                }
+            }
+        }
+    }
+    'segments: for sref in sset.segments() {
+        for rule in sref {
+            if rule.address() == stmt.address() {
+                break 'segments;
+            }
+            match rule.statement_type() {
+                StatementType::Axiom | StatementType::Provable => {}
+                _ => continue,
+            }
+            if !provable(rule) || !allowed.iter().any(|name| name.as_bytes() == rule.label()) {
+                continue;
+            }
+            let frame = match scope.get(rule.label()) {
+                Some(frame) if frame.mandatory_dv.is_empty() => frame,
+                _ => continue,
+            };
+            let vars = frame_variables(&nset, frame);
+            let assertion = match prover.parse(&vars, rule) {
+                Some(tree) => tree,
+                None => continue,
+            };
+            let hyps: Option<Vec<SyntaxTree>> = frame.hypotheses
+                .iter()
+                .filter_map(|hyp| match *hyp {
+                    Hyp::Essential(addr, _) => {
+                        let hyp = sset.statement(addr);
+                        Some(if provable(hyp) { prover.parse(&vars, hyp) } else { None })
+                    }
+                    Hyp::Floating(..) => None,
+                })
+                .collect();
+            if let Some(hyps) = hyps {
+                prover.rules.push(Rule {
+                    address: rule.address(),
+                    frame: frame,
+                    assertion: assertion,
+                    hyps: hyps,
+                });
+            }
+        }
+    }
+
+    for depth in 0..options.max_depth + 1 {
+        let mut goals = vec![Goal {
+                                 tree: tree.clone(),
+                                 depth: depth,
+                             }];
+        let mut subst = new_map();
+        let mut trace = Vec::new();
+        if prover.solve(&mut goals, &mut subst, &mut trace) {
+            let mut arr = ProofTreeArray::default();
+            let qed = prover.build(&mut trace.iter(), &subst, &mut arr)
+                .ok_or(ProveError::NotFound)?;
+            arr.set_qed(qed);
+            return Ok(arr);
+        }
+        if prover.steps >= prover.max_steps {
+            break;
+        }
+    }
+    Err(ProveError::NotFound)
+}
+
+/// Parses the math string of a statement with the given variables.
+fn parse_statement(parser: &SyntaxParser,
+                   vars: &HashMap<Token, Token>,
+                   stmt: StatementRef)
+                   -> Option<SyntaxTree> {
+    let math: Vec<TokenPtr> = stmt.math_iter().map(|tok| tok.slice).collect();
+    parser.parse_math(vars, &math).map(|(_, tree)| tree)
+}
+
+/// A goal for `applicable`, in the frame of a theorem.
+struct Target<'a> {
+    sset: &'a SegmentSet,
+    nset: &'a Nameset,
+    parser: &'a SyntaxParser,
+    /// The variables of the theorem.
+    vars: HashMap<Token, Token>,
+    tree: SyntaxTree,
+    /// The `$e` hypotheses of the theorem, which cost nothing.
+    hyps: Vec<SyntaxTree>,
+}
+
+impl<'a> Target<'a> {
+    /// Matches the conclusion of an assertion onto the goal.
+    fn apply(&self, rule: StatementRef, frame: &Frame) -> Option<Application> {
+        let (sset, nset, parser) = (self.sset, self.nset, self.parser);
+        let rule_vars = frame_variables(nset, frame);
+        let mut subst = parse_statement(parser, &rule_vars, rule)?.match_onto(&self.tree)?;
+        let mut vars = self.vars.clone();
+        let mut work_variables = Vec::new();
+        for hyp in frame.hypotheses.iter() {
+            if let Hyp::Floating(_, var, typecode) = *hyp {
+                let name = nset.atom_name(frame.var_list[var]);
+                if subst.contains_key(name) {
+                    continue;
+                }
+                let work = (1..)
+                    .map(|n| format!("&{}{}", as_str(name), n).into_bytes().into_boxed_slice())
+                    .find(|work| !vars.contains_key(work))
+                    .expect("some name is unused");
+                let typecode: Token = nset.atom_name(typecode).into();
+                vars.insert(work.clone(), typecode.clone());
+                subst.insert(name.into(), SyntaxTree::Var(work.clone()));
+                work_variables.push((work, typecode));
+            }
+        }
+        let mut hypotheses = Vec::new();
+        let mut cost = 20 * work_variables.len();
+        for hyp in frame.hypotheses.iter() {
+            if let Hyp::Essential(addr, _) = *hyp {
+                let tree = parse_statement(parser, &rule_vars, sset.statement(addr))?
+                    .substitute(&subst);
+                let mut formula = parser.to_formula(&tree, &vars)?;
+                // the typecode of a $e hypothesis is not part of its parse
+                formula.typecode = copy_token(sset.statement(addr).math_at(0).slice);
+                if !self.hyps.contains(&tree) {
+                    cost += 10 + formula.symbols.len();
+                }
+                hypotheses.push(formula);
+            }
+        }
+        Some(Application {
+            address: rule.address(),
+            hypotheses: hypotheses,
+            work_variables: work_variables,
+            cost: cost,
+        })
+    }
+}
+
+/// Finds the assertions which can prove a goal in the frame of a theorem;
+/// see the module documentation.
+///
+/// Only assertions which come before the theorem and have the goal's
+/// typecode are considered, and disjoint variable conditions are not checked.
+/// The results are sorted by cost, then in database order.
+pub fn applicable(db: &mut Database,
+                  label: &str,
+                  goal: &Formula)
+                  -> Result<Vec<Application>, ProveError> {
+    let sset = db.parse_result().clone();
+    let nset = db.name_result().clone();
+    let scope = db.scope_result().clone();
+    let parser = db.syntax_parser().clone();
+    let stmt = match db.statement(label) {
+        Some(stmt) => sset.statement(stmt.address()),
+        None => return Err(ProveError::UnknownLabel(label.to_owned())),
+    };
+    let frame = match scope.get(stmt.label()) {
+        Some(frame) if stmt.statement_type() == StatementType::Provable => frame,
+        _ => return Err(ProveError::NotProvable(label.to_owned())),
+    };
+
+    let mut vars = global_variables(&sset);
+    vars.extend(frame_variables(&nset, frame));
+    let tree = match goal.parse(&parser, &vars) {
+        Some((_, tree)) => tree,
+        None => return Err(ProveError::GoalNotParsed),
+    };
+    let hyps: Vec<SyntaxTree> = frame.hypotheses
+        .iter()
+        .filter_map(|hyp| match *hyp {
+            Hyp::Essential(addr, _) => parse_statement(&parser, &vars, sset.statement(addr)),
+            Hyp::Floating(..) => None,
+        })
+        .collect();
+    let target = Target {
+        sset: &sset,
+        nset: &nset,
+        parser: &parser,
+        vars: vars,
+        tree: tree,
+        hyps: hyps,
+    };
+
+    let mut found = Vec::new();
+    'segments: for sref in sset.segments() {
+        for rule in sref {
+            if rule.address() == stmt.address() {
+                break 'segments;
+            }
+            match rule.statement_type() {
+                StatementType::Axiom | StatementType::Provable => {}
+                _ => continue,
+            }
+            if rule.math_len() == 0 || rule.math_at(0).slice != &goal.typecode[..] {
+                continue;
+            }
+            if let Some(rule_frame) = scope.get(rule.label()) {
+                found.extend(target.apply(rule, rule_frame));
+            }
+        }
+    }
+    // the sort is stable, so database order is kept among ties
+    found.sort_by_key(|application| application.cost);
+    Ok(found)
+}
@@ -0,0 +1,87 @@
+use database::Database;
+use diag::DiagnosticClass;
+use formula::Formula;
+use parser::as_str;
+use parser::copy_token;
+use proof::ProofStyle;
+use proof::ProofTreeArray;
+use proof::ProofTreePrinter;
+use prover::ProverOptions;
+use prover::applicable;
+use prover::prove;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_applicable() {
+    let text = format!("{}${{ a3 $e |- ph $. ax-3 $a |- ( ps -> ph ) $. $}}
+${{ h1 $e |- ph $. th1 $p |- ( ps -> ph ) $= ? $. $}}\n",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let goal = Formula::from_statement(db.statement("th1").unwrap());
+    let found = applicable(&mut db, "th1", &goal).unwrap();
+    let sset = db.parse_result().clone();
+    let summary: Vec<(String, Vec<String>, usize)> = found.iter()
+        .map(|app| {
+            (as_str(sset.statement(app.address).label()).to_owned(),
+             app.hypotheses.iter().map(|hyp| hyp.to_string()).collect(),
+             app.cost)
+        })
+        .collect();
+    assert_eq!(summary,
+               vec![("ax-3".to_owned(), vec!["|- ph".to_owned()], 0),
+                    ("ax-mp".to_owned(),
+                     vec!["|- &ph1".to_owned(), "|- ( &ph1 -> ( ps -> ph ) )".to_owned()],
+                     50)]);
+    assert_eq!(found[1].work_variables, vec![(copy_token(b"&ph1"), copy_token(b"wff"))]);
+
+    let goal = Formula::from_statement(db.statement("ax-1").unwrap());
+    assert!(applicable(&mut db, "ax-1", &goal).is_err());
+}
+
+#[test]
+fn test_prove() {
+    let text = format!("{}ax-2 $a |- ( ( ph -> ( ps -> ch ) ) -> ( ( ph -> ps ) -> ( ph -> ch ) ) ) $.
+${{ h1 $e |- ph $. th1 $p |- ( ps -> ph ) $= ? $. $}}
+th2 $p |- ( ph -> ph ) $= ? $.
+",
+                       DB.replace("$v ph ps $.", "$v ph ps ch $.\nwch $f wff ch $."));
+    let mut db = mkdb(text.as_bytes());
+    let allowed: Vec<String> = vec!["ax-mp".to_owned(), "ax-1".to_owned(), "ax-2".to_owned()];
+    let options = ProverOptions::default();
+    let print = |db: &mut Database, label: &str, arr: &ProofTreeArray| {
+        let sset = db.parse_result().clone();
+        let nset = db.name_result().clone();
+        let scope = db.scope_result().clone();
+        let proof = format!("{}",
+                            ProofTreePrinter {
+                                sset: &sset,
+                                nset: &nset,
+                                scope: &scope,
+                                thm_label: label.as_bytes(),
+                                style: ProofStyle::Normal,
+                                arr: arr,
+                                initial_chr: 0,
+                                indent: 1,
+                                line_width: 1000,
+                            });
+        proof.trim().trim_end_matches("$.").trim().to_owned()
+    };
+
+    let goal = Formula::from_statement(db.statement("th1").unwrap());
+    let arr = prove(&mut db, "th1", &goal, &allowed, &options).unwrap();
+    let proof1 = print(&mut db, "th1", &arr);
+    assert_eq!(proof1, "wph wps wph wi h1 wph wps ax-1 ax-mp");
+
+    let goal = Formula::from_statement(db.statement("th2").unwrap());
+    let arr = prove(&mut db, "th2", &goal, &allowed, &options).unwrap();
+    let proof2 = print(&mut db, "th2", &arr);
+    let text = text.replace("( ps -> ph ) $= ?", &format!("( ps -> ph ) $= {}", proof1))
+        .replace("( ph -> ph ) $= ?", &format!("( ph -> ph ) $= {}", proof2));
+    let mut checked = mkdb(text.as_bytes());
+    assert!(checked.diag_notations(vec![DiagnosticClass::Verify]).is_empty());
+
+    let mut options = options;
+    options.max_depth = 2;
+    assert!(prove(&mut db, "th2", &goal, &allowed, &options).is_err());
+}
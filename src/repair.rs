@@ -0,0 +1,320 @@
+//! Suggested repairs for proofs which fail verification.
+//!
+//! When the verifier rejects a proof because a step is applied to a
+//! hypothesis of the wrong type (`StepFloatWrongType`), to the wrong formula
+//! (`StepEssenWrong`), or to fewer results than it has hypotheses
+//! (`ProofUnderflow`), the cause is often a small slip: a syntax-building step
+//! left out, the subproofs of two hypotheses given in the wrong order, or an
+//! assertion used in place of another which states the same thing with its
+//! hypotheses in a different order.  This module tries each such edit at the
+//! failing step and keeps those which make the proof verify.
+//!
+//! The proof is handled as a list of step labels in normal format, so
+//! compressed proofs are decoded first and the repaired proofs are given in
+//! normal format.  Normal proofs using local labels (`3:label`) or explicit
+//! hypothesis names (`hyp=label`) are not repaired.
+
+use diag::Diagnostic;
+use formula::Formula;
+use nameck::Nameset;
+use parser::Comparer;
+use parser::NO_STATEMENT;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::Token;
+use parser::as_str;
+use parser::copy_token;
+use proof::RPNStep;
+use proof::decode_compressed;
+use scopeck::Frame;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Range;
+use verify::StepObserver;
+use verify::trace_steps;
+use verify::verify_steps;
+
+/// An edit to a proof.  Steps are numbered from zero in normal format.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum RepairKind {
+    /// Insert a step, usually a syntax-building one, before the given step.
+    InsertStep(usize, Token),
+    /// Swap the subproofs of two hypotheses, given by their indices, of the
+    /// assertion applied at the given step.
+    SwapHypotheses(usize, usize, usize),
+    /// Apply another assertion with the same conclusion at the given step.
+    ReplaceLabel(usize, Token),
+}
+
+impl fmt::Display for RepairKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RepairKind::InsertStep(step, ref label) => {
+                write!(f, "Insert {} before step {}", as_str(label), step + 1)
+            }
+            RepairKind::SwapHypotheses(step, first, second) => {
+                write!(f,
+                       "Swap hypotheses {} and {} of step {}",
+                       first + 1,
+                       second + 1,
+                       step + 1)
+            }
+            RepairKind::ReplaceLabel(step, ref label) => {
+                write!(f, "Use {} at step {}", as_str(label), step + 1)
+            }
+        }
+    }
+}
+
+/// A suggested repair of a proof.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Repair {
+    /// The edit made.
+    pub kind: RepairKind,
+    /// The repaired proof, in normal format, as the text between `$=` and
+    /// `$.`.
+    pub proof: String,
+}
+
+/// The error of a proof, with the repairs which fix it.
+#[derive(Clone,Debug)]
+pub struct RepairReport {
+    /// The error reported by the verifier.
+    pub diagnostic: Diagnostic,
+    /// The step at which the error occurred.
+    pub step: usize,
+    /// The repairs found, possibly none.
+    pub repairs: Vec<Repair>,
+}
+
+/// Counts the steps executed before an error.
+struct StepCounter(usize);
+
+impl StepObserver for StepCounter {
+    fn step(&mut self, count: usize, _: &[Formula]) {
+        self.0 = count;
+    }
+}
+
+/// Returns the proof of a statement as a list of step labels, if it is a
+/// compressed proof which decodes or a normal proof without extensions.
+fn proof_steps(sset: &SegmentSet, scope: &ScopeResult, stmt: StatementRef) -> Option<Vec<Token>> {
+    if let Some(arr) = decode_compressed(sset, scope, stmt) {
+        let arr = arr.ok()?;
+        return Some(arr.normal_iter(false)
+            .map(|step| match step {
+                RPNStep::Normal { addr, .. } => copy_token(sset.statement(addr).label()),
+                RPNStep::Backref { .. } => unreachable!("normal_iter makes no backrefs"),
+            })
+            .collect());
+    }
+    let mut steps = Vec::new();
+    for index in 0..stmt.proof_len() {
+        let chunk = stmt.proof_slice_at(index);
+        if chunk.iter().any(|&ch| ch == b':' || ch == b'=') {
+            return None;
+        }
+        steps.push(copy_token(chunk));
+    }
+    Some(steps)
+}
+
+/// Returns whether a frame can be used in the proof of a statement.
+fn in_scope(sset: &SegmentSet, frame: &Frame, stmt: StatementRef) -> bool {
+    let pos = stmt.address();
+    let valid = frame.valid;
+    sset.order.cmp(&valid.start, &pos) == Ordering::Less &&
+    (valid.end == NO_STATEMENT ||
+     pos.segment_id == valid.start.segment_id && pos.index < valid.end)
+}
+
+struct Repairer<'a> {
+    sset: &'a SegmentSet,
+    nset: &'a Nameset,
+    scope: &'a ScopeResult,
+    stmt: StatementRef<'a>,
+    steps: Vec<Token>,
+    repairs: Vec<Repair>,
+}
+
+impl<'a> Repairer<'a> {
+    /// Returns the number of results a step pops off the stack.
+    fn arity(&self, label: &[u8]) -> usize {
+        match self.scope.get(label) {
+            Some(frame) if frame.stype == StatementType::Axiom ||
+                           frame.stype == StatementType::Provable => frame.hypotheses.len(),
+            _ => 0,
+        }
+    }
+
+    /// Returns the steps of the subproofs of the hypotheses of a step, in
+    /// order, or `None` if there are too few steps before it.
+    fn arguments(&self, step: usize) -> Option<Vec<Range<usize>>> {
+        let mut out = Vec::new();
+        let mut end = step;
+        for _ in 0..self.arity(&self.steps[step]) {
+            let mut need = 1;
+            let mut start = end;
+            while need > 0 {
+                if start == 0 {
+                    return None;
+                }
+                start -= 1;
+                need = need - 1 + self.arity(&self.steps[start]);
+            }
+            out.push(start..end);
+            end = start;
+        }
+        out.reverse();
+        Some(out)
+    }
+
+    /// Records a repair if the edited proof verifies.
+    fn check(&mut self, kind: RepairKind, steps: Vec<Token>) {
+        let ok = {
+            let labels: Vec<&[u8]> = steps.iter().map(|step| &step[..]).collect();
+            verify_steps(self.sset, self.nset, self.scope, self.stmt, &labels).is_ok()
+        };
+        if ok {
+            let labels: Vec<&str> = steps.iter().map(|step| as_str(step)).collect();
+            self.repairs.push(Repair {
+                kind: kind,
+                proof: labels.join(" "),
+            });
+        }
+    }
+
+    /// Tries inserting each active `$f` hypothesis before the failing step
+    /// and in the places of its hypotheses.
+    fn insert_steps(&mut self, step: usize) {
+        let mut floats = Vec::new();
+        for sref in self.sset.segments() {
+            for hyp in sref {
+                if hyp.statement_type() == StatementType::Floating {
+                    if let Some(frame) = self.scope.get(hyp.label()) {
+                        if in_scope(self.sset, frame, self.stmt) {
+                            floats.push(copy_token(hyp.label()));
+                        }
+                    }
+                }
+            }
+        }
+        let first = step.saturating_sub(self.arity(&self.steps[step]));
+        for position in first..step + 1 {
+            for label in &floats {
+                let mut steps = self.steps.clone();
+                steps.insert(position, label.clone());
+                self.check(RepairKind::InsertStep(position, label.clone()), steps);
+            }
+        }
+    }
+
+    /// Tries swapping the subproofs of each pair of hypotheses of the failing
+    /// step.
+    fn swap_hypotheses(&mut self, step: usize) {
+        let args = match self.arguments(step) {
+            Some(args) => args,
+            None => return,
+        };
+        for first in 0..args.len() {
+            for second in first + 1..args.len() {
+                let mut steps = self.steps[..args[0].start].to_vec();
+                for (index, arg) in args.iter().enumerate() {
+                    let arg = if index == first {
+                        &args[second]
+                    } else if index == second {
+                        &args[first]
+                    } else {
+                        arg
+                    };
+                    steps.extend_from_slice(&self.steps[arg.clone()]);
+                }
+                steps.extend_from_slice(&self.steps[step..]);
+                self.check(RepairKind::SwapHypotheses(step, first, second), steps);
+            }
+        }
+    }
+
+    /// Tries each other assertion with the same conclusion and number of
+    /// hypotheses as the one applied at the failing step.
+    fn replace_label(&mut self, step: usize) {
+        let frame = match self.scope.get(&self.steps[step]) {
+            Some(frame) if frame.stype == StatementType::Axiom ||
+                           frame.stype == StatementType::Provable => frame,
+            _ => return,
+        };
+        let assertion = frame.assertion(self.nset);
+        let mut labels = Vec::new();
+        for sref in self.sset.segments() {
+            for other in sref {
+                if other.label() == &self.steps[step][..] {
+                    continue;
+                }
+                let alt = match self.scope.get(other.label()) {
+                    Some(alt) if alt.stype == StatementType::Axiom ||
+                                 alt.stype == StatementType::Provable => alt,
+                    _ => continue,
+                };
+                if alt.hypotheses.len() == frame.hypotheses.len() &&
+                   in_scope(self.sset, alt, self.stmt) &&
+                   alt.assertion(self.nset) == assertion {
+                    labels.push(copy_token(other.label()));
+                }
+            }
+        }
+        for label in labels {
+            let mut steps = self.steps.clone();
+            steps[step] = label.clone();
+            self.check(RepairKind::ReplaceLabel(step, label), steps);
+        }
+    }
+}
+
+/// Verifies the proof of a `$p` statement and, if it fails with one of the
+/// errors above, tries the simple repairs described in the module
+/// documentation.
+///
+/// Returns `None` if the proof is correct, fails with another error, or is
+/// not in a form which can be repaired.
+pub fn suggest_repairs(sset: &SegmentSet,
+                       nset: &Nameset,
+                       scope: &ScopeResult,
+                       stmt: StatementRef)
+                       -> Option<RepairReport> {
+    scope.get(stmt.label())?;
+    let steps = proof_steps(sset, scope, stmt)?;
+    let mut counter = StepCounter(0);
+    let diagnostic = {
+        let labels: Vec<&[u8]> = steps.iter().map(|step| &step[..]).collect();
+        trace_steps(sset, nset, scope, stmt, &labels, &mut counter).err()?
+    };
+    match diagnostic {
+        Diagnostic::ProofUnderflow |
+        Diagnostic::StepEssenWrong |
+        Diagnostic::StepFloatWrongType => {}
+        _ => return None,
+    }
+    let step = counter.0;
+    if step >= steps.len() {
+        return None;
+    }
+
+    let mut repairer = Repairer {
+        sset: sset,
+        nset: nset,
+        scope: scope,
+        stmt: stmt,
+        steps: steps,
+        repairs: Vec::new(),
+    };
+    repairer.insert_steps(step);
+    repairer.swap_hypotheses(step);
+    repairer.replace_label(step);
+    Some(RepairReport {
+        diagnostic: diagnostic,
+        step: step,
+        repairs: repairer.repairs,
+    })
+}
@@ -0,0 +1,47 @@
+use diag::Diagnostic;
+use parser::copy_token;
+use repair::Repair;
+use repair::RepairKind;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_suggest_repairs() {
+    let text = format!("{}${{ maj2 $e |- ( ph -> ps ) $. min2 $e |- ph $. ax-mp2 $a |- ps $. $}}
+${{ h1 $e |- ph $. h2 $e |- ( ph -> ps ) $.
+th1 $p |- ps $= wph h1 h2 ax-mp $.
+th2 $p |- ps $= wph wps h2 h1 ax-mp $.
+th3 $p |- ps $= ( ax-mp ) ABDCE $.
+th4 $p |- ps $= wph wps h1 h2 ax-mp $.
+th5 $p |- ps $= wph wps ax-1 $. $}}\n",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+
+    let report = db.suggest_repairs("th1").unwrap();
+    assert_eq!(report.diagnostic, Diagnostic::ProofUnderflow);
+    assert_eq!(report.step, 3);
+    assert_eq!(report.repairs,
+               vec![Repair {
+                        kind: RepairKind::InsertStep(1, copy_token(b"wps")),
+                        proof: "wph wps h1 h2 ax-mp".to_owned(),
+                    }]);
+
+    for label in &["th2", "th3"] {
+        let report = db.suggest_repairs(label).unwrap();
+        assert_eq!(report.diagnostic, Diagnostic::StepEssenWrong);
+        assert_eq!(report.step, 4);
+        assert_eq!(report.repairs,
+                   vec![Repair {
+                            kind: RepairKind::SwapHypotheses(4, 2, 3),
+                            proof: "wph wps h1 h2 ax-mp".to_owned(),
+                        },
+                        Repair {
+                            kind: RepairKind::ReplaceLabel(4, copy_token(b"ax-mp2")),
+                            proof: "wph wps h2 h1 ax-mp2".to_owned(),
+                        }]);
+    }
+
+    assert!(db.suggest_repairs("th4").is_none());
+    assert!(db.suggest_repairs("th5").is_none());
+    assert!(db.suggest_repairs("ax-mp").is_none());
+}
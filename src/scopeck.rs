@@ -30,6 +30,7 @@
 
 use bit_set::Bitset;
 use diag::Diagnostic;
+use formula::Formula;
 use nameck::Atom;
 use nameck::NameReader;
 use nameck::Nameset;
@@ -55,8 +56,11 @@ use parser::TokenRef;
 use segment_set::SegmentSet;
 use std::cmp::Ordering;
 use std::collections::VecDeque;
+use std::mem;
 use std::ops::Range;
 use std::sync::Arc;
+#[cfg(feature = "stats")]
+use std::time::Instant;
 use util::fast_extend;
 use util::HashMap;
 use util::HashSet;
@@ -111,7 +115,7 @@ struct LocalEssentialInfo<'a> {
 pub type VarIndex = usize;
 
 /// A literal-variable pair, used in the frame expressions.
-#[derive(Clone,Debug)]
+#[derive(Clone,Debug,PartialEq,Eq)]
 pub struct ExprFragment {
     /// Pointer into the frame's constant pool for a literal span consisting of
     /// zero or more constant symbols.
@@ -131,7 +135,7 @@ pub struct ExprFragment {
 /// between math symbols are indicated by setting the 8th bit on the last byte
 /// of each symbol.  The same compressed representation is used in literal
 /// segments so that execution can be simple copying.
-#[derive(Clone,Debug)]
+#[derive(Clone,Debug,PartialEq,Eq)]
 pub struct VerifyExpr {
     /// Atom representation of the first constant symbol in the expression.
     pub typecode: Atom,
@@ -154,7 +158,7 @@ impl Default for VerifyExpr {
 }
 
 /// Representation of a hypothesis in a frame program.
-#[derive(Clone,Debug)]
+#[derive(Clone,Debug,PartialEq,Eq)]
 pub enum Hyp {
     /// An `$e` hypothesis, which is substituted and then compared to a stack
     /// slot.
@@ -211,7 +215,7 @@ impl Hyp {
 ///
 /// Variable names are replaced with small integers so that the substitution
 /// being built can be maintained as an array.
-#[derive(Clone,Debug,Default)]
+#[derive(Clone,Debug,Default,PartialEq,Eq)]
 pub struct Frame {
     /// Type of this statement, will be `StatementType::Axiom`,
     /// `StatementType::Floating`, or `StatementType::Provable`.
@@ -254,6 +258,66 @@ pub struct Frame {
     pub optional_dv: Box<[Bitset]>,
 }
 
+/// Appends the symbols of a packed math string, each terminated by a byte
+/// with the high bit set, as found in constant pools and verifier results.
+pub fn unpack_symbols(packed: &[u8], out: &mut Vec<Token>) {
+    let mut symbol = Vec::new();
+    for &chr in packed {
+        symbol.push(chr & 0x7F);
+        if chr & 0x80 != 0 {
+            out.push(mem::take(&mut symbol).into_boxed_slice());
+        }
+    }
+}
+
+impl Frame {
+    /// Returns the formula of an expression of the frame, such as its target
+    /// or an essential hypothesis, with the names of its variables.
+    pub fn formula(&self, nset: &Nameset, expr: &VerifyExpr) -> Formula {
+        let mut symbols = Vec::new();
+        for part in expr.tail.iter() {
+            unpack_symbols(&self.const_pool[part.prefix.clone()], &mut symbols);
+            symbols.push(copy_token(nset.atom_name(self.var_list[part.var])));
+        }
+        unpack_symbols(&self.const_pool[expr.rump.clone()], &mut symbols);
+        Formula {
+            typecode: copy_token(nset.atom_name(expr.typecode)),
+            symbols: symbols,
+        }
+    }
+
+    /// Returns the formula of the statement itself.
+    pub fn assertion(&self, nset: &Nameset) -> Formula {
+        self.formula(nset, &self.target)
+    }
+
+    /// Returns the labels and formulas of the essential hypotheses, in the
+    /// order they are matched against the stack.
+    pub fn essential_hypotheses(&self, sset: &SegmentSet, nset: &Nameset) -> Vec<(Token, Formula)> {
+        self.hypotheses
+            .iter()
+            .filter_map(|hyp| match *hyp {
+                Hyp::Essential(addr, ref expr) => {
+                    Some((copy_token(sset.statement(addr).label()), self.formula(nset, expr)))
+                }
+                Hyp::Floating(..) => None,
+            })
+            .collect()
+    }
+
+    /// Returns the mandatory variables, in the order of their slots in a
+    /// substitution.
+    pub fn mandatory_variables(&self) -> &[Atom] {
+        &self.var_list[..self.mandatory_count]
+    }
+
+    /// Returns the pairs of variables in the mandatory disjoint variable
+    /// conditions.
+    pub fn mandatory_dv_pairs(&self) -> Vec<(Atom, Atom)> {
+        self.mandatory_dv.iter().map(|&(a, b)| (self.var_list[a], self.var_list[b])).collect()
+    }
+}
+
 /// Data which is tracked during scope checking, but discarded when done.
 struct ScopeState<'a> {
     /// Accumulated errors for this segment.
@@ -846,7 +910,11 @@ fn scope_check_single(sset: &SegmentSet, names: &Nameset, seg: SegmentRef) -> Se
         frames_out: Vec::new(),
     };
 
+    #[cfg(feature = "stats")]
+    let mut times = Vec::new();
     for sref in seg {
+        #[cfg(feature = "stats")]
+        let start = Instant::now();
         match sref.statement_type() {
             StatementType::Axiom => scope_check_assert(&mut state, sref),
             StatementType::Constant => scope_check_constant(&mut state, sref),
@@ -857,6 +925,12 @@ fn scope_check_single(sset: &SegmentSet, names: &Nameset, seg: SegmentRef) -> Se
             StatementType::Variable => scope_check_variable(&mut state, sref),
             _ => {}
         }
+        #[cfg(feature = "stats")]
+        times.push((sref.index(), start.elapsed()));
+    }
+    #[cfg(feature = "stats")]
+    if let Some(ref stats) = sset.stats {
+        stats.record_segment("scopeck", seg.id, times);
     }
 
     state.frames_out.shrink_to_fit();
@@ -878,7 +952,11 @@ pub struct ScopeResult {
     incremental: bool,
     generation: usize,
     segments: Vec<Option<Arc<SegmentScopeResult>>>,
+    /// For each label, the generation in which its frame last changed, and
+    /// the location of the frame.
     frame_index: HashMap<Token, (usize, usize, usize)>,
+    /// Labels whose frames were added, changed or removed by the last update.
+    changed: Vec<Token>,
 }
 
 impl ScopeResult {
@@ -898,6 +976,18 @@ impl ScopeResult {
         out
     }
 
+    /// Returns the labels whose frames were added, changed or removed by the
+    /// last call to `scope_check`, in no particular order.
+    ///
+    /// Frames of a segment which is checked again keep their generation if
+    /// they come out the same, so users of a `ScopeUsage`, such as the
+    /// verifier, only need to redo their work if they read one of these
+    /// frames.  Any change to a statement before a frame in the same segment
+    /// changes the statement addresses in the frame.
+    pub fn changed_frames(&self) -> &[Token] {
+        &self.changed
+    }
+
     /// Fetch a frame.
     pub fn get(&self, name: TokenPtr) -> Option<&Frame> {
         self.frame_index
@@ -964,17 +1054,22 @@ pub fn scope_check(result: &mut ScopeResult, segments: &Arc<SegmentSet>, names:
         }
     }
 
+    // frames of the stale segments, to find those which are unchanged
+    let mut old_frames = new_map();
     for stale_id in stale_ids {
         let oseg = result.segments[stale_id.0 as usize].take().unwrap();
         let sref = SegmentRef {
             segment: &oseg.source,
             id: stale_id,
         };
-        for frame in &oseg.frames_out {
+        for (index, frame) in oseg.frames_out.iter().enumerate() {
             let label = sref.statement(frame.valid.start.index).label();
-            result.frame_index.remove(label).expect("check_label_dup should prevent this");
+            let (old_gen, _, _) =
+                result.frame_index.remove(label).expect("check_label_dup should prevent this");
+            old_frames.insert(copy_token(label), (old_gen, oseg.clone(), index));
         }
     }
+    result.changed.clear();
 
     for res_new in to_add {
         let seg_index = res_new.id.0 as usize;
@@ -985,12 +1080,51 @@ pub fn scope_check(result: &mut ScopeResult, segments: &Arc<SegmentSet>, names:
         let sref = segments.segment(res_new.id);
         for (index, frame) in res_new.frames_out.iter().enumerate() {
             let label = copy_token(sref.statement(frame.valid.start.index).label());
-            let old = result.frame_index.insert(label, (gen, seg_index, index));
+            let frame_gen = match old_frames.remove(&label) {
+                Some((old_gen, ref oseg, old_index)) if oseg.frames_out[old_index] == *frame => {
+                    old_gen
+                }
+                _ => {
+                    result.changed.push(label.clone());
+                    gen
+                }
+            };
+            let old = result.frame_index.insert(label, (frame_gen, seg_index, index));
             assert!(old.is_none(), "check_label_dup should prevent this");
         }
 
         result.segments[seg_index] = Some(res_new);
     }
+    result.changed.extend(old_frames.into_keys());
+}
+
+/// Extracts scope data for some segments of a database only.
+///
+/// The result has the frames and diagnostics of the listed segments, and is
+/// enough to verify a proof if they include every segment which declares an
+/// assertion or hypothesis it references.  It cannot be updated by
+/// `scope_check`.
+pub fn scope_check_segments(segments: &SegmentSet,
+                            names: &Nameset,
+                            ids: &[SegmentId])
+                            -> ScopeResult {
+    let mut result = ScopeResult {
+        generation: 1,
+        ..ScopeResult::default()
+    };
+    for &id in ids {
+        let sref = segments.segment(id);
+        let res = scope_check_single(segments, names, sref);
+        for (index, frame) in res.frames_out.iter().enumerate() {
+            let label = copy_token(sref.statement(frame.valid.start.index).label());
+            result.frame_index.insert(label, (1, id.0 as usize, index));
+        }
+        if id.0 as usize >= result.segments.len() {
+            result.segments.resize(id.0 as usize + 1, None);
+        }
+        result.segments[id.0 as usize] = Some(Arc::new(res));
+    }
+    result
 }
 
 /// Handle to scope results which can fetch frames while tracking dependencies.
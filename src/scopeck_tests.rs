@@ -0,0 +1,65 @@
+use database::Database;
+use database::DbOptions;
+use parser::as_str;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_frame_accessors() {
+    let text = format!("{}${{ $d ph ps $. ax-2 $a |- ( ps -> ph ) $. $}}\n", DB);
+    let mut db = mkdb(text.as_bytes());
+    let nset = db.name_result().clone();
+    let sset = db.parse_result().clone();
+    let scope = db.scope_result().clone();
+    let name = |atom| as_str(nset.atom_name(atom)).to_owned();
+
+    let frame = scope.get(b"ax-mp").unwrap();
+    assert_eq!(frame.assertion(&nset).to_string(), "|- ps");
+    let hyps: Vec<(String, String)> = frame.essential_hypotheses(&sset, &nset)
+        .into_iter()
+        .map(|(label, formula)| (as_str(&label).to_owned(), formula.to_string()))
+        .collect();
+    assert_eq!(hyps,
+               vec![("min".to_owned(), "|- ph".to_owned()),
+                    ("maj".to_owned(), "|- ( ph -> ps )".to_owned())]);
+    let vars: Vec<String> = frame.mandatory_variables().iter().map(|&atom| name(atom)).collect();
+    assert_eq!(vars, vec!["ph", "ps"]);
+    assert!(frame.mandatory_dv_pairs().is_empty());
+
+    let frame = scope.get(b"ax-2").unwrap();
+    let dvs: Vec<(String, String)> = frame.mandatory_dv_pairs()
+        .into_iter()
+        .map(|(a, b)| (name(a), name(b)))
+        .collect();
+    assert_eq!(dvs.len(), 1);
+    assert!(dvs[0] == ("ph".to_owned(), "ps".to_owned()) ||
+            dvs[0] == ("ps".to_owned(), "ph".to_owned()));
+}
+
+#[test]
+fn test_changed_frames() {
+    let dbo = DbOptions {
+        incremental: true,
+        ..DbOptions::default()
+    };
+    let mut db = Database::new(dbo);
+    let load = |db: &mut Database, thm: &str| {
+        let text = format!("{}th1 $p |- ( ph -> ( ph -> ph ) ) $= {} $.\n", DB, thm);
+        db.parse("test.mm".to_owned(), vec![("test.mm".to_owned(), text.into_bytes())]);
+        let mut changed: Vec<String> = db.scope_result()
+            .changed_frames()
+            .iter()
+            .map(|label| as_str(label).to_owned())
+            .collect();
+        changed.sort();
+        changed
+    };
+    assert_eq!(load(&mut db, "wph wph ax-1").len(), 6);
+    // a new proof leaves every frame as it was
+    assert!(load(&mut db, "wph wph wph wi ax-1").is_empty());
+    assert!(!db.verify_result().diagnostics().is_empty());
+    let text = format!("{}th1 $p |- ( ph -> ( ps -> ph ) ) $= wph wps ax-1 $.\n", DB);
+    db.parse("test.mm".to_owned(), vec![("test.mm".to_owned(), text.into_bytes())]);
+    assert_eq!(db.scope_result().changed_frames(), &[b"th1".to_vec().into_boxed_slice()]);
+    assert!(db.verify_result().diagnostics().is_empty());
+}
@@ -0,0 +1,283 @@
+//! Searching the statements of a database.
+//!
+//! Two kinds of search are provided.  The text queries `search_labels`,
+//! `search_math` and `search_comments` correspond to metamath.exe's `search`
+//! command: they find statements by label glob, by a run of math tokens, or
+//! by a regular expression over comments, and return the spans of the
+//! matches.
+//!
+//! `search_formula` finds assertions matching a pattern, in the style of
+//! mmj2's search assistant.  A pattern is a math string in which every token
+//! declared as a variable is a metavariable.  There is no grammar in this
+//! crate, so a metavariable matches any non-empty run of tokens which is
+//! balanced with respect to the bracket pairs `( )`, `[ ]`, `{ }` and
+//! `<. >.`, rather than a syntactically well-formed subexpression; for set.mm
+//! notation this gives the same matches in nearly all cases.  Repeated
+//! metavariables must match identical runs.  Theorem stubs are matched like
+//! any other assertion, but flagged.
+
+use nameck::Nameset;
+use parser::Span;
+use parser::StatementAddress;
+use parser::StatementType;
+use parser::SymbolType;
+use parser::Token;
+use parser::TokenIndex;
+use parser::copy_token;
+use regex::bytes::Regex;
+use segment_set::SegmentSet;
+use stubs::is_stub;
+
+/// A match of a text query.
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub struct TextMatch {
+    /// The statement containing the match.
+    pub address: StatementAddress,
+    /// The matched text, relative to the statement's segment buffer as for
+    /// all parser spans.
+    pub span: Span,
+}
+
+/// Matches a label against a glob in which `*` matches any sequence of
+/// characters and `?` matches any single character.
+fn glob_match(glob: &[u8], text: &[u8]) -> bool {
+    match glob.split_first() {
+        None => text.is_empty(),
+        Some((&b'*', rest)) => (0..text.len() + 1).any(|skip| glob_match(rest, &text[skip..])),
+        Some((&ch, rest)) => {
+            !text.is_empty() && (ch == b'?' || ch == text[0]) && glob_match(rest, &text[1..])
+        }
+    }
+}
+
+/// Finds the statements whose labels match a glob such as `ax-*` or `?bi*`.
+pub fn search_labels(sset: &SegmentSet, glob: &str) -> Vec<TextMatch> {
+    let mut out = Vec::new();
+    for sref in sset.segments() {
+        for stmt in sref {
+            if stmt.statement_type().takes_label() && glob_match(glob.as_bytes(), stmt.label()) {
+                out.push(TextMatch {
+                    address: stmt.address(),
+                    span: Span::new(stmt.span().start as usize,
+                                    stmt.span().start as usize + stmt.label().len()),
+                });
+            }
+        }
+    }
+    out
+}
+
+/// Finds every occurrence of a run of math tokens, given as a
+/// whitespace-separated string, in the math strings of all statements.
+pub fn search_math(sset: &SegmentSet, tokens: &str) -> Vec<TextMatch> {
+    let needle: Vec<&[u8]> = tokens.split_whitespace().map(|tok| tok.as_bytes()).collect();
+    let mut out = Vec::new();
+    if needle.is_empty() {
+        return out;
+    }
+    for sref in sset.segments() {
+        for stmt in sref {
+            let math: Vec<&[u8]> = stmt.math_iter().map(|tok| tok.slice).collect();
+            if math.len() < needle.len() {
+                continue;
+            }
+            for start in 0..math.len() - needle.len() + 1 {
+                if math[start..start + needle.len()] == needle[..] {
+                    let last = (start + needle.len() - 1) as TokenIndex;
+                    out.push(TextMatch {
+                        address: stmt.address(),
+                        span: Span::new(stmt.math_span(start as TokenIndex).start as usize,
+                                        stmt.math_span(last).end as usize),
+                    });
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Finds every match of a regular expression in the text of all comments,
+/// including `$t` and `$j` comments.
+pub fn search_comments(sset: &SegmentSet, regex: &Regex) -> Vec<TextMatch> {
+    let mut out = Vec::new();
+    for sref in sset.segments() {
+        for stmt in sref {
+            match stmt.statement_type() {
+                StatementType::Comment |
+                StatementType::TypesettingComment |
+                StatementType::AdditionalInfoComment => {}
+                _ => continue,
+            }
+            let span = stmt.span();
+            let text = span.as_ref(&sref.segment.buffer);
+            for (start, end) in regex.find_iter(text) {
+                out.push(TextMatch {
+                    address: stmt.address(),
+                    span: Span::new(span.start as usize + start, span.start as usize + end),
+                });
+            }
+        }
+    }
+    out
+}
+
+/// Options for `search_formula`.
+#[derive(Copy,Clone,Debug,Default)]
+pub struct SearchOptions {
+    /// If true, the pattern may match any balanced run of tokens within a
+    /// statement; otherwise it must match the entire math string, including
+    /// the typecode.
+    pub subformulas: bool,
+    /// Stop after this many matches.
+    pub max_results: Option<usize>,
+}
+
+/// A statement matching a search pattern.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct SearchMatch {
+    /// The matching `$a` or `$p` statement.
+    pub address: StatementAddress,
+    /// The index in the statement's math string where the match starts.
+    pub start: TokenIndex,
+    /// The run of tokens matched by each metavariable of the pattern, in
+    /// order of first occurrence in the pattern.
+    pub substitution: Vec<(Token, Vec<Token>)>,
+    /// True if the statement is a stub whose proof is not yet complete; see
+    /// the `stubs` module.
+    pub stub: bool,
+}
+
+fn bracket_depth(tok: &[u8]) -> i32 {
+    match tok {
+        b"(" | b"[" | b"{" | b"<." => 1,
+        b")" | b"]" | b"}" | b">." => -1,
+        _ => 0,
+    }
+}
+
+/// An element of a compiled pattern.
+#[derive(Copy,Clone)]
+enum Elem<'a> {
+    Const(&'a [u8]),
+    /// A metavariable, by index in order of first occurrence.
+    Var(usize),
+}
+
+/// Tries to match `pattern[pi..]` against a prefix of `text[ti..]`, given the
+/// ranges of `text` already bound to metavariables.  Returns the end of the
+/// first complete match for which `accept` returns true.
+fn match_from(pattern: &[Elem],
+              pi: usize,
+              text: &[&[u8]],
+              ti: usize,
+              bound: &mut [Option<(usize, usize)>],
+              accept: &dyn Fn(usize) -> bool)
+              -> Option<usize> {
+    if pi == pattern.len() {
+        return if accept(ti) { Some(ti) } else { None };
+    }
+    match pattern[pi] {
+        Elem::Const(tok) => {
+            if ti < text.len() && text[ti] == tok {
+                return match_from(pattern, pi + 1, text, ti + 1, bound, accept);
+            }
+            None
+        }
+        Elem::Var(k) => {
+            if let Some((start, end)) = bound[k] {
+                let len = end - start;
+                if ti + len <= text.len() && text[ti..ti + len] == text[start..end] {
+                    return match_from(pattern, pi + 1, text, ti + len, bound, accept);
+                }
+                return None;
+            }
+            let mut depth = 0;
+            for end in ti..text.len() {
+                depth += bracket_depth(text[end]);
+                if depth < 0 {
+                    break;
+                }
+                if depth == 0 {
+                    bound[k] = Some((ti, end + 1));
+                    let found = match_from(pattern, pi + 1, text, end + 1, bound, accept);
+                    if found.is_some() {
+                        return found;
+                    }
+                }
+            }
+            bound[k] = None;
+            None
+        }
+    }
+}
+
+/// Scans all `$a` and `$p` statements for matches of a pattern, given as a
+/// whitespace-separated math string.
+///
+/// At most one match is reported per statement, the one starting earliest in
+/// its math string.
+pub fn search_formula(sset: &SegmentSet,
+                      nset: &Nameset,
+                      pattern: &str,
+                      options: &SearchOptions)
+                      -> Vec<SearchMatch> {
+    let mut vars: Vec<&[u8]> = Vec::new();
+    let mut compiled = Vec::new();
+    for tok in pattern.split_whitespace() {
+        let tok = tok.as_bytes();
+        let is_var = nset.lookup_symbol(tok)
+            .is_some_and(|sym| sym.stype == SymbolType::Variable);
+        if is_var {
+            let k = match vars.iter().position(|&v| v == tok) {
+                Some(k) => k,
+                None => {
+                    vars.push(tok);
+                    vars.len() - 1
+                }
+            };
+            compiled.push(Elem::Var(k));
+        } else {
+            compiled.push(Elem::Const(tok));
+        }
+    }
+
+    let mut out = Vec::new();
+    if compiled.is_empty() {
+        return out;
+    }
+    for sref in sset.segments() {
+        for stmt in sref {
+            match stmt.statement_type() {
+                StatementType::Axiom | StatementType::Provable => {}
+                _ => continue,
+            }
+            if options.max_results.is_some_and(|max| out.len() >= max) {
+                return out;
+            }
+            let text: Vec<&[u8]> = stmt.math_iter().map(|tok| tok.slice).collect();
+            let starts = if options.subformulas { 0..text.len() } else { 0..1 };
+            for start in starts {
+                let mut bound = vec![None; vars.len()];
+                let whole = !options.subformulas;
+                let accept = |end: usize| !whole || end == text.len();
+                if match_from(&compiled, 0, &text, start, &mut bound, &accept).is_some() {
+                    let substitution = vars.iter()
+                        .zip(&bound)
+                        .map(|(&var, range)| {
+                            let (s, e) = range.expect("all metavariables are bound");
+                            (copy_token(var), text[s..e].iter().map(|&t| copy_token(t)).collect())
+                        })
+                        .collect();
+                    out.push(SearchMatch {
+                        address: stmt.address(),
+                        start: start as TokenIndex,
+                        substitution: substitution,
+                        stub: is_stub(stmt),
+                    });
+                    break;
+                }
+            }
+        }
+    }
+    out
+}
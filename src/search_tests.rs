@@ -0,0 +1,61 @@
+use parser::as_str;
+use search::SearchMatch;
+use search::SearchOptions;
+use search::TextMatch;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_search_formula() {
+    let mut db = mkdb(DB.as_bytes());
+    let tok = |s: &str| s.as_bytes().to_vec().into_boxed_slice();
+    let ax1 = db.statement("ax-1").unwrap().address();
+    let ax_mp = db.statement("ax-mp").unwrap().address();
+
+    let found = db.search_formula("|- ( ph -> ps )", &SearchOptions::default());
+    assert_eq!(found,
+               vec![SearchMatch {
+                        address: ax1,
+                        start: 0,
+                        substitution: vec![(tok("ph"), vec![tok("ph")]),
+                                           (tok("ps"), vec![tok("("), tok("ps"), tok("->"),
+                                                            tok("ph"), tok(")")])],
+                        stub: false,
+                    }]);
+    assert!(db.search_formula("|- ( ph -> ph )", &SearchOptions::default()).is_empty());
+
+    let mut options = SearchOptions {
+        subformulas: true,
+        ..SearchOptions::default()
+    };
+    let found = db.search_formula("( ps -> ph )", &options);
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0].address, db.statement("wi").unwrap().address());
+    assert_eq!(found[0].start, 1);
+    assert_eq!(found[1].address, ax1);
+    assert_eq!(found[1].start, 1);
+    assert!(db.search_formula("ps", &options).iter().any(|m| m.address == ax_mp));
+    options.max_results = Some(1);
+    assert_eq!(db.search_formula("ph", &options).len(), 1);
+}
+
+#[test]
+fn test_text_search() {
+    let text = format!("{}$( A comment about modus ponens. $)\nth1 $p |- ( ph -> ph ) $= ? $.\n",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let sset = db.parse_result().clone();
+    let slice = |m: &TextMatch| {
+        let stmt = sset.statement(m.address);
+        as_str(m.span.as_ref(&stmt.segment().segment.buffer)).to_owned()
+    };
+    let labels: Vec<String> = db.search_labels("ax-*").iter().map(&slice).collect();
+    assert_eq!(labels, vec!["ax-mp", "ax-1"]);
+    assert_eq!(db.search_labels("w?").len(), 1);
+    let math: Vec<String> = db.search_math("ph -> ps").iter().map(&slice).collect();
+    assert_eq!(math, vec!["ph -> ps", "ph -> ps"]);
+    assert_eq!(db.search_math("-> ph )").len(), 2);
+    let comments = db.search_comments("modus +ponens").unwrap();
+    assert_eq!(comments.iter().map(&slice).collect::<Vec<_>>(), vec!["modus ponens"]);
+    assert!(db.search_comments("(").is_err());
+}
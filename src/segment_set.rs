@@ -47,14 +47,31 @@
 //! most IDs will change and require new work from analysis passes.  A full LCS
 //! would make changing the beginning and end at the same time faster, and is
 //! attractive future work.
+//!
+//! # Portability
+//!
+//! Databases developed on Windows may include files using `\\` as the path
+//! separator, with names differing in case from the files on disk, and with a
+//! byte order mark or CRLF line endings.  The parser warns about backslashes
+//! in include names and skips a leading byte order mark with a warning (CR is
+//! ordinary whitespace in Metamath).  `IncludeOptions` in `DbOptions` enables
+//! normalizing each of these explicitly, so that the database loads the same
+//! way everywhere.
 
+use checkpoint;
 use database::DbOptions;
 use database::Executor;
 use database::Promise;
 use diag::Diagnostic;
+use emit::EmitError;
 use filetime::FileTime;
 use parser;
+use parser::BYTE_ORDER_MARK;
+use parser::Buffer;
+use parser::BufferRef;
 use parser::Comparer;
+use parser::FilePos;
+use parser::RecoveryRegion;
 use parser::Segment;
 use parser::SegmentId;
 use parser::SegmentOrder;
@@ -62,6 +79,11 @@ use parser::SegmentRef;
 use parser::Span;
 use parser::StatementAddress;
 use parser::StatementRef;
+use parser::StatementType;
+use parser::TokenIndex;
+#[cfg(feature = "stats")]
+use stats::Stats;
+use std::cmp::Ordering;
 use std::collections::VecDeque;
 use std::fs;
 use std::fs::File;
@@ -69,14 +91,14 @@ use std::hash::Hash;
 use std::hash::Hasher;
 use std::io;
 use std::io::Read;
+use std::io::Write;
 use std::mem;
+use std::ops::Range;
 use std::str;
 use std::sync::Arc;
 use util::find_chapter_header;
 use util::HashMap;
-use util::HashSet;
 use util::new_map;
-use util::new_set;
 use util::ptr_eq;
 
 /// Memory buffer wrapper which hashes by length.
@@ -85,7 +107,7 @@ use util::ptr_eq;
 /// segments are long enough that their lengths are likely to contain enough
 /// entropy already.
 #[derive(Eq,PartialEq,Clone,Debug)]
-struct LongBuf(Arc<Vec<u8>>);
+struct LongBuf(BufferRef);
 
 impl Hash for LongBuf {
     fn hash<H: Hasher>(&self, state: &mut H) {
@@ -106,12 +128,137 @@ pub struct SourceInfo {
     /// Name of the source file as loaded.
     pub name: String,
     /// Reference to the full unsliced source buffer.
-    pub text: Arc<Vec<u8>>,
+    pub text: BufferRef,
     /// Span of the parser input within the file; all spans reported by the
     /// parser are relative to this.
     pub span: Span,
 }
 
+/// Normalizations applied to included file names and file contents while
+/// loading.  All are disabled by default.
+///
+/// Normalized contents are what the parser sees and what `SourceInfo::text`
+/// holds, so they are also what `emit::emit_file` writes back.
+#[derive(Copy,Clone,Debug,Default)]
+pub struct IncludeOptions {
+    /// Treat `\\` in file names as a path separator.
+    pub backslash_separators: bool,
+    /// Treat file names differing only in ASCII case as the same file, as on
+    /// case-insensitive filesystems; a later inclusion which differs in case
+    /// from an earlier one is skipped with a warning.
+    pub case_insensitive: bool,
+    /// Remove a UTF-8 byte order mark from the start of each file.
+    pub strip_bom: bool,
+    /// Convert CRLF line endings to LF.
+    pub normalize_crlf: bool,
+}
+
+impl IncludeOptions {
+    /// Returns options with every normalization enabled.
+    pub fn all() -> IncludeOptions {
+        IncludeOptions {
+            backslash_separators: true,
+            case_insensitive: true,
+            strip_bom: true,
+            normalize_crlf: true,
+        }
+    }
+
+    /// Applies the file name normalizations to the name given in an include
+    /// statement.
+    fn normalize_path(&self, path: String) -> String {
+        if self.backslash_separators {
+            path.replace('\\', "/")
+        } else {
+            path
+        }
+    }
+
+    /// Returns true if some normalization of file contents is enabled.
+    fn normalizes_text(&self) -> bool {
+        self.strip_bom || self.normalize_crlf
+    }
+
+    /// Applies the content normalizations to the text of a file.
+    fn normalize_text(&self, mut buf: Vec<u8>) -> Vec<u8> {
+        if self.strip_bom && buf.starts_with(BYTE_ORDER_MARK) {
+            buf.drain(..BYTE_ORDER_MARK.len());
+        }
+        if self.normalize_crlf && buf.contains(&b'\r') {
+            let mut out = Vec::with_capacity(buf.len());
+            for (ix, &ch) in buf.iter().enumerate() {
+                if ch != b'\r' || buf.get(ix + 1) != Some(&b'\n') {
+                    out.push(ch);
+                }
+            }
+            buf = out;
+        }
+        buf
+    }
+}
+
+/// A change to the text of one statement, for `SegmentSet::edit_statements`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum StatementEdit {
+    /// Replace the text of the statement, not including any whitespace
+    /// before it.
+    Replace(Vec<u8>),
+    /// Insert text immediately before the statement, after any whitespace
+    /// preceding it.
+    InsertBefore(Vec<u8>),
+    /// Delete the statement together with the whitespace preceding it.
+    Delete,
+}
+
+/// A range of bytes of a file and the text replacing them.
+type Splice<'a> = (Range<usize>, &'a [u8]);
+
+/// Identifies the part of a statement found at a source position by
+/// `SegmentSet::statement_at`.
+#[derive(Copy,Clone,Debug,Eq,PartialEq)]
+pub enum StatementPart {
+    /// The statement label.
+    Label,
+    /// A math token, by index within the math string.
+    Math(TokenIndex),
+    /// A proof token, by index within the proof string.
+    Proof(TokenIndex),
+    /// Anything else: keywords, whitespace, or comments within or before the
+    /// statement.
+    Other,
+}
+
+fn statement_part(stmt: StatementRef, pos: FilePos) -> StatementPart {
+    fn find(len: TokenIndex, span_at: &dyn Fn(TokenIndex) -> Span, pos: FilePos) -> Option<TokenIndex> {
+        let (mut lo, mut hi) = (0, len);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let span = span_at(mid);
+            if span.end <= pos {
+                lo = mid + 1;
+            } else if span.start > pos {
+                hi = mid;
+            } else {
+                return Some(mid);
+            }
+        }
+        None
+    }
+
+    let label = stmt.span();
+    let label_end = label.start + stmt.label().len() as FilePos;
+    if !stmt.label().is_empty() && label.start <= pos && pos < label_end {
+        return StatementPart::Label;
+    }
+    if let Some(ix) = find(stmt.math_len(), &|ix| stmt.math_span(ix), pos) {
+        return StatementPart::Math(ix);
+    }
+    if let Some(ix) = find(stmt.proof_len(), &|ix| stmt.proof_span(ix), pos) {
+        return StatementPart::Proof(ix);
+    }
+    StatementPart::Other
+}
+
 /// The result of parsing one or more segments from a single slice of a source
 /// file is a segment collection, a source file reference, and possibly text to
 /// use for inserting into the second cache.  If this parsing result applies to
@@ -143,12 +290,21 @@ pub struct SegmentSet {
     pub options: Arc<DbOptions>,
     /// The work queue for use with this database.
     pub exec: Executor,
+    /// Recorder for the statement times of each pass, if `DbOptions::stats`
+    /// is set.
+    #[cfg(feature = "stats")]
+    pub stats: Option<Arc<Stats>>,
     /// Order structure which records the relative order of segment IDs created
     /// by the SegmentSet.
     pub order: Arc<SegmentOrder>,
     /// Track segment and source info in parallel so they can be updated
     /// independently in the slicing case and if a file is renamed.
     segments: HashMap<SegmentId, (Arc<Segment>, Arc<SourceInfo>)>,
+    /// Name of the file most recently passed to `read`.
+    start: String,
+    /// In-memory file contents which take precedence over the filesystem,
+    /// from the last `read` plus any edits made since.
+    preload: HashMap<String, BufferRef>,
     /// First cache as described in the module comment.
     file_cache: HashMap<(String, FileTime), FileSR>,
     /// Second cache as described in the module comment.
@@ -162,13 +318,39 @@ impl SegmentSet {
         SegmentSet {
             options: opts,
             exec: exec.clone(),
+            #[cfg(feature = "stats")]
+            stats: None,
             order: Arc::new(SegmentOrder::new()),
             segments: new_map(),
+            start: String::new(),
+            preload: new_map(),
             parse_cache: new_map(),
             file_cache: new_map(),
         }
     }
 
+    /// Writes the parsed segments of the current source slices to a
+    /// checkpoint; see the `checkpoint` module.
+    pub fn write_checkpoint<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let slices: Vec<_> = self.parse_cache
+            .iter()
+            .map(|(text, segments)| (text.0.clone(), segments.clone()))
+            .collect();
+        checkpoint::write_checkpoint(&slices, out)
+    }
+
+    /// Adds the slices of a checkpoint to the parse cache, so that the next
+    /// `read` will not parse them again if their text is unchanged.  Returns
+    /// the number of slices loaded.
+    pub fn load_checkpoint(&mut self, data: &[u8]) -> io::Result<usize> {
+        let slices = checkpoint::read_checkpoint(data)?;
+        let count = slices.len();
+        for (text, segments) in slices {
+            self.parse_cache.insert(LongBuf(text), segments);
+        }
+        Ok(count)
+    }
+
     /// Iterates over all loaded segments in logical order.
     pub fn segments(&self) -> Vec<SegmentRef> {
         // this might be an actual iterator in the future if needs be
@@ -208,11 +390,35 @@ impl SegmentSet {
         &self.segments[&seg_id].1
     }
 
+    /// Lists the names of the source files in the order their text appears in
+    /// the logical database, after resolving inclusions.
+    ///
+    /// A file which includes another file is listed once for each run of
+    /// segments it contributes, so `a.mm` including `b.mm` in the middle
+    /// yields `["a.mm", "b.mm", "a.mm"]`.  Files which were skipped because
+    /// they had already been included, or which could not be read, do not
+    /// appear.
+    pub fn file_order(&self) -> Vec<&str> {
+        let mut out: Vec<&str> = Vec::new();
+        for sref in self.segments() {
+            let name = &self.source_info(sref.id).name;
+            if out.last() != Some(&&name[..]) {
+                out.push(name);
+            }
+        }
+        out
+    }
+
     /// Fetches a handle to a statement given a global address.
     pub fn statement(&self, addr: StatementAddress) -> StatementRef {
         self.segment(addr.segment_id).statement(addr.index)
     }
 
+    /// Fetch a handle to a statement given a possibly stale address.
+    pub fn statement_opt(&self, addr: StatementAddress) -> Option<StatementRef<'_>> {
+        self.segment_opt(addr.segment_id).and_then(|sref| sref.statement_opt(addr.index))
+    }
+
     /// Reports any parse errors associated with loaded segments.
     pub fn parse_diagnostics(&self) -> Vec<(StatementAddress, Diagnostic)> {
         let mut out = Vec::new();
@@ -224,6 +430,128 @@ impl SegmentSet {
         out
     }
 
+    /// Finds the statement containing a byte offset in a source file, and the
+    /// part of the statement at that offset.
+    ///
+    /// The offset is relative to the start of the named file.  Whitespace and
+    /// comments before a statement are considered part of it (as in
+    /// `StatementRef::span_full`); returns `None` if the file is not loaded or
+    /// the offset is past its end.
+    pub fn statement_at(&self, name: &str, offset: usize) -> Option<(StatementAddress, StatementPart)> {
+        // the segments of a file are in file order and cover adjacent text;
+        // the slices they were parsed from are offset within the file
+        let segments: Vec<(SegmentRef, usize, Range<usize>)> = self.segments()
+            .into_iter()
+            .filter_map(|sref| {
+                let sinfo = self.source_info(sref.id);
+                let span = sref.span();
+                if sinfo.name != name || span.is_null() {
+                    return None;
+                }
+                let base = sinfo.span.start as usize;
+                Some((sref, base, base + span.start as usize..base + span.end as usize))
+            })
+            .collect();
+        let ix = segments.binary_search_by(|(_, _, range)| {
+                if range.end <= offset {
+                    Ordering::Less
+                } else if range.start > offset {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            })
+            .ok()?;
+        let (sref, base, _) = segments[ix].clone();
+        let local = (offset - base) as FilePos;
+        let stmt = sref.statement_containing(local)?;
+        Some((stmt.address(), statement_part(stmt, local)))
+    }
+
+    /// Lists the statements overlapping a range of byte offsets in a source
+    /// file, in database order.
+    ///
+    /// As with `statement_at`, leading whitespace and comments count as part
+    /// of the following statement.
+    pub fn statements_in_range(&self, name: &str, range: Range<usize>) -> Vec<StatementAddress> {
+        let mut out = Vec::new();
+        for sref in self.segments() {
+            let sinfo = self.source_info(sref.id);
+            if sinfo.name != name {
+                continue;
+            }
+            let base = sinfo.span.start as usize;
+            for stmt in sref {
+                let span = stmt.span_full();
+                let (start, end) = (base + span.start as usize, base + span.end as usize);
+                if start < range.end && range.start < end ||
+                   start == end && range.start <= start && start < range.end {
+                    out.push(stmt.address());
+                }
+            }
+        }
+        out
+    }
+
+    /// Writes the source text of a single segment, reconstructed from its
+    /// statements.
+    pub fn write_segment<W: Write>(&self, seg_id: SegmentId, out: &mut W) -> io::Result<()> {
+        let sref = self.segment(seg_id);
+        for stmt in sref {
+            out.write_all(stmt.span_full().as_ref(&sref.buffer))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the source text of one of the loaded files, reconstructed from
+    /// the segments it generated.
+    ///
+    /// File inclusion statements are written as is, so the result is a
+    /// replacement for the named file only.  Nothing is written if no
+    /// segments came from a file of that name.
+    pub fn write_file<W: Write>(&self, name: &str, out: &mut W) -> io::Result<()> {
+        for sref in self.segments() {
+            if self.source_info(sref.id).name == name {
+                self.write_segment(sref.id, out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the whole database as a single `.mm` source with all file
+    /// inclusions expanded.
+    ///
+    /// Comments and whitespace are preserved; each `$[ $]` statement is
+    /// replaced by the text of the included file (or nothing, if the file was
+    /// already included earlier).
+    pub fn write_mm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for sref in self.segments() {
+            for stmt in sref {
+                let text = stmt.span_full().as_ref(&sref.buffer);
+                if stmt.statement_type() == StatementType::FileInclude {
+                    // keep the whitespace before the `$[`
+                    let lead = text.iter().position(|&ch| ch > 32).unwrap_or(text.len());
+                    out.write_all(&text[..lead])?;
+                } else {
+                    out.write_all(text)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reports the regions of all loaded segments affected by parser error
+    /// recovery; see `SegmentRef::recovery_regions`.
+    pub fn recovery_regions(&self) -> Vec<(SegmentId, RecoveryRegion)> {
+        let mut out = Vec::new();
+        for sref in self.segments() {
+            for region in sref.recovery_regions() {
+                out.push((sref.id, region));
+            }
+        }
+        out
+    }
+
     /// Replaces the content of the `SegmentSet` with data loaded from disk
     /// files or memory.
     ///
@@ -231,6 +559,132 @@ impl SegmentSet {
     /// purposes of file inclusion statements.  If a match is not made in
     /// `data`, it will be accessed as a file relative to the current directory.
     pub fn read(&mut self, path: String, data: Vec<(String, Vec<u8>)>) {
+        self.start = path;
+        self.preload = data.into_iter().map(|(name, text)| (name, Arc::new(Buffer::new(text)))).collect();
+        self.reload();
+    }
+
+    /// Replaces the text of a statement, not including any whitespace before
+    /// it, and reparses.
+    ///
+    /// Edits are made by splicing the text of the source file containing the
+    /// statement and rereading the database with the result as an in-memory
+    /// override for that file, so the usual caching applies: unchanged files
+    /// and slices are not reparsed, and segment IDs are reused where possible.
+    /// All `StatementAddress` values should be considered stale afterward.
+    /// Edits are discarded by the next call to `read`.
+    ///
+    /// Fails with `EmitError::UnknownStatement`, changing nothing, if the
+    /// address is stale or out of range.
+    pub fn replace_statement(&mut self,
+                             addr: StatementAddress,
+                             text: &[u8])
+                             -> Result<(), EmitError> {
+        self.edit_statements(&[(addr, StatementEdit::Replace(text.to_vec()))])
+    }
+
+    /// Inserts text immediately before a statement (after any whitespace
+    /// preceding it) and reparses.
+    ///
+    /// The text is inserted verbatim, so it should normally end with
+    /// whitespace to separate it from the following statement.  See
+    /// `replace_statement` for details on how edits are processed and on
+    /// errors.
+    pub fn insert_statement(&mut self,
+                            addr: StatementAddress,
+                            text: &[u8])
+                            -> Result<(), EmitError> {
+        self.edit_statements(&[(addr, StatementEdit::InsertBefore(text.to_vec()))])
+    }
+
+    /// Deletes a statement, together with the whitespace preceding it, and
+    /// reparses.  See `replace_statement` for details on how edits are
+    /// processed and on errors.
+    pub fn delete_statement(&mut self, addr: StatementAddress) -> Result<(), EmitError> {
+        self.edit_statements(&[(addr, StatementEdit::Delete)])
+    }
+
+    /// Applies several statement edits at once, with a single reparse.
+    ///
+    /// All addresses refer to the database before any of the edits.  Several
+    /// insertions before the same statement are made in the order given,
+    /// followed by a replacement of it if any; otherwise edits of the same
+    /// text region are rejected with `EmitError::OverlappingEdits`, giving
+    /// the overlapping spans as file offsets, and nothing is changed.  The
+    /// same holds for an address which is stale or out of range, which is
+    /// rejected with `EmitError::UnknownStatement`.
+    pub fn edit_statements(&mut self,
+                           edits: &[(StatementAddress, StatementEdit)])
+                           -> Result<(), EmitError> {
+        let mut by_file: HashMap<String, Vec<Splice>> = new_map();
+        for &(addr, ref edit) in edits {
+            let (start, end) = self.statement_text_range(addr)?;
+            let splice = match *edit {
+                StatementEdit::Replace(ref text) => (start.1..end, &text[..]),
+                StatementEdit::InsertBefore(ref text) => (start.1..start.1, &text[..]),
+                StatementEdit::Delete => (start.0..end, &b""[..]),
+            };
+            let name = self.source_info(addr.segment_id).name.clone();
+            by_file.entry(name).or_default().push(splice);
+        }
+
+        let mut texts = Vec::new();
+        for (name, mut splices) in by_file {
+            // stable, so insertions at one offset stay in order
+            splices.sort_by_key(|(range, _)| (range.start, range.end));
+            for pair in splices.windows(2) {
+                if pair[1].0.start < pair[0].0.end {
+                    return Err(EmitError::OverlappingEdits(Span::new(pair[0].0.start,
+                                                                     pair[0].0.end),
+                                                           Span::new(pair[1].0.start,
+                                                                     pair[1].0.end)));
+                }
+            }
+            let old = self.segments()
+                .iter()
+                .map(|sref| self.source_info(sref.id))
+                .find(|sinfo| sinfo.name == name)
+                .expect("edited statements are loaded")
+                .text
+                .clone();
+            let mut buf = Vec::with_capacity(old.len());
+            let mut pos = 0;
+            for (range, text) in splices {
+                buf.extend_from_slice(&old[pos..range.start]);
+                buf.extend_from_slice(text);
+                pos = range.end;
+            }
+            buf.extend_from_slice(&old[pos..]);
+            texts.push((name, buf));
+        }
+        for (name, buf) in texts {
+            self.preload.insert(name, Arc::new(Buffer::new(buf)));
+        }
+        self.reload();
+        Ok(())
+    }
+
+    /// Finds the file offsets of a statement's leading whitespace, its first
+    /// non-whitespace byte, and its end.
+    fn statement_text_range(&self,
+                            addr: StatementAddress)
+                            -> Result<((usize, usize), usize), EmitError> {
+        let stmt = self.statement_opt(addr).ok_or(EmitError::UnknownStatement(addr))?;
+        let base = self.source_info(addr.segment_id).span.start as usize;
+        let span = stmt.span_full();
+        let text = span.as_ref(&stmt.segment().segment.buffer);
+        let lead = text.iter().position(|&ch| ch > 32).unwrap_or(text.len());
+        Ok(((base + span.start as usize, base + span.start as usize + lead),
+            base + span.end as usize))
+    }
+
+    /// Rereads the database from the start file and in-memory overrides
+    /// recorded by the last `read` or edit.
+    ///
+    /// Files on disk whose modification time has not changed are not read
+    /// again.
+    pub fn reload(&mut self) {
+        let path = self.start.clone();
         // data which is kept during the recursive load process, which does
         // _not_ have access to the SegmentSet
         struct RecState {
@@ -245,8 +699,9 @@ impl SegmentSet {
             new_by_time: HashMap<(String, FileTime), FileSR>,
             /// segments which have been placed in the order so far
             segments: SegList,
-            included: HashSet<String>,
-            preload: HashMap<String, Vec<u8>>,
+            /// files included so far, by normalized name for comparison
+            included: HashMap<String, String>,
+            preload: HashMap<String, BufferRef>,
             exec: Executor,
         }
 
@@ -258,10 +713,9 @@ impl SegmentSet {
         fn split_and_parse(state: &RecState,
                            path: String,
                            timestamp: Option<FileTime>,
-                           buf: Vec<u8>)
+                           buf: BufferRef)
                            -> Promise<FileSR> {
             let mut parts = Vec::new();
-            let buf = Arc::new(buf);
             // see if we need to parse this file in multiple slices.  the
             // slicing is a slight incompatibility (no chapter headers inside
             // groups) but is needed for full parallelism
@@ -285,7 +739,7 @@ impl SegmentSet {
                 let partbuf = if range == (0..buf.len()) {
                     buf.clone()
                 } else {
-                    Arc::new(buf[range.clone()].to_owned())
+                    Arc::new(buf.slice(range.clone()))
                 };
 
                 let srcinfo = Arc::new(SourceInfo {
@@ -322,62 +776,155 @@ impl SegmentSet {
                 .map(move |srlist| FileSR(timestamp.map(move |s| (path, s)), srlist))
         }
 
-        // read a file from disk (intercessions have already been checked, but
-        // the first cache has not) and split/parse it;
-        // returns by Result Error on I/O error
-        fn canonicalize_and_read(state: &mut RecState,
-                                 path: String)
-                                 -> io::Result<Promise<FileSR>> {
+        /// A file which is being read on a worker thread, or which needed no
+        /// reading.
+        enum PendingRead {
+            Done(Promise<FileSR>),
+            Reading(String, FileTime, Promise<io::Result<Buffer>>),
+        }
+
+        /// The include statement which led to a file being read, as the
+        /// source of the including segment and the span of the file name.
+        type IncludeSite = Option<(Arc<SourceInfo>, Span)>;
+
+        // insert a bogus segment so we have a place to hang a read error.  if
+        // the file was included, the error is reported on the include
+        // statement
+        fn read_error(path: String, site: &IncludeSite, err: io::Error) -> Promise<FileSR> {
+            let (seg, sinfo) = match *site {
+                Some((ref sinfo, span)) => {
+                    (parser::dummy_segment(Diagnostic::IncludeReadError(span, err.to_string())),
+                     sinfo.clone())
+                }
+                None => {
+                    let sinfo = SourceInfo {
+                        name: path,
+                        text: Arc::new(Buffer::default()),
+                        span: Span::null(),
+                    };
+                    (parser::dummy_segment(From::from(err)), Arc::new(sinfo))
+                }
+            };
+            // cache keys are None so this won't pollute any caches
+            Promise::new(FileSR(None, vec![SliceSR(None, vec![seg], sinfo)]))
+        }
+
+        // stat a file on disk (intercessions have already been checked) and
+        // probe the first cache; on a miss, read the file on a worker thread
+        // so that sibling includes are read in parallel
+        fn canonicalize_and_read(state: &mut RecState, path: String) -> io::Result<PendingRead> {
             let metadata = fs::metadata(&path)?;
             let time = FileTime::from_last_modification_time(&metadata);
 
             // probe 1st cache
             match state.old_by_time.get(&(path.clone(), time)) {
-                Some(old_fsr) => Ok(Promise::new(old_fsr.clone())),
+                Some(old_fsr) => Ok(PendingRead::Done(Promise::new(old_fsr.clone()))),
                 None => {
-                    // miss, but we have the file size, so try to read in one
-                    // call to a buffer we won't have to move
-                    let mut fh = File::open(&path)?;
-                    let mut buf = Vec::with_capacity(metadata.len() as usize + 1);
-                    // note: File's read_to_end uses the buffer capacity to choose how much to read
-                    fh.read_to_end(&mut buf)?;
-
-                    Ok(split_and_parse(state, path, Some(time), buf))
+                    let includes = state.options.includes;
+                    #[cfg(feature = "mmap")]
+                    let mmap = state.options.mmap;
+                    let fpath = path.clone();
+                    let read = state.exec.exec(metadata.len() as usize, move || {
+                        let mut fh = File::open(&fpath)?;
+                        // mapping an empty file fails on some systems
+                        #[cfg(feature = "mmap")]
+                        {
+                            if mmap && metadata.len() > 0 && !includes.normalizes_text() {
+                                return unsafe { Buffer::map(&fh) };
+                            }
+                        }
+                        // we have the file size, so try to read in one call to
+                        // a buffer we won't have to move
+                        let mut buf = Vec::with_capacity(metadata.len() as usize + 1);
+                        // note: File's read_to_end uses the buffer capacity to choose how much to read
+                        fh.read_to_end(&mut buf)?;
+                        Ok(Buffer::new(includes.normalize_text(buf)))
+                    });
+                    Ok(PendingRead::Reading(path, time, read))
                 }
             }
         }
 
-        // We have a filename and an incomplete database in the RecState, read
-        // it and queue tasks to parse
-        fn read_and_parse(state: &mut RecState, path: String) -> Promise<FileSR> {
+        // We have a filename and an incomplete database in the RecState; start
+        // reading it.  `finish_read` then queues tasks to parse it
+        fn start_read(state: &mut RecState, path: String, site: &IncludeSite) -> PendingRead {
             // THIS IS WRONG: https://github.com/sorear/smetamath-rs/issues/22
 
             // We do need to avoid issuing multiple parses for the same file,
             // but catching it here leads to misassociation
-            if !state.included.insert(path.clone()) {
-                return Promise::new(FileSR(None, Vec::new()));
+            let includes = state.options.includes;
+            let path = includes.normalize_path(path);
+            let key = if includes.case_insensitive {
+                path.to_ascii_lowercase()
+            } else {
+                path.clone()
+            };
+            if let Some(earlier) = state.included.get(&key).cloned() {
+                if earlier == path {
+                    return PendingRead::Done(Promise::new(FileSR(None, Vec::new())));
+                }
+                // same file under a different spelling; insert a segment to
+                // hang the warning on, reported on the include statement
+                let (span, sinfo) = match *site {
+                    Some((ref sinfo, span)) => (span, sinfo.clone()),
+                    None => {
+                        let sinfo = SourceInfo {
+                            name: path,
+                            text: Arc::new(Buffer::default()),
+                            span: Span::null(),
+                        };
+                        (Span::null(), Arc::new(sinfo))
+                    }
+                };
+                let seg = parser::dummy_segment(Diagnostic::IncludeCaseMismatch(span, earlier));
+                let slice = SliceSR(None, vec![seg], sinfo);
+                return PendingRead::Done(Promise::new(FileSR(None, vec![slice])));
             }
+            state.included.insert(key, path.clone());
             // check intercessions
-            match state.preload.get(&path).cloned() {
+            let preloaded = if includes.case_insensitive {
+                state.preload
+                    .iter()
+                    .find(|&(name, _)| name.eq_ignore_ascii_case(&path))
+                    .map(|(_, data)| data.clone())
+            } else {
+                state.preload.get(&path).cloned()
+            };
+            match preloaded {
                 None => {
                     // read from FS
-                    canonicalize_and_read(state, path.clone()).unwrap_or_else(|cerr| {
-                        // read failed, insert a bogus segment so we have a
-                        // place to hang the errors
-                        let sinfo = SourceInfo {
-                            name: path.clone(),
-                            text: Arc::new(Vec::new()),
-                            span: Span::null(),
-                        };
-                        let seg = parser::dummy_segment(From::from(cerr));
-                        // cache keys are None so this won't pollute any caches
-                        Promise::new(FileSR(None, vec![SliceSR(None, vec![seg], Arc::new(sinfo))]))
-                    })
+                    canonicalize_and_read(state, path.clone())
+                        .unwrap_or_else(|err| PendingRead::Done(read_error(path, site, err)))
+                }
+                Some(data) => {
+                    let data = if includes.normalizes_text() {
+                        Arc::new(Buffer::new(includes.normalize_text(data.to_vec())))
+                    } else {
+                        data
+                    };
+                    PendingRead::Done(split_and_parse(state, path, None, data))
                 }
-                Some(data) => split_and_parse(state, path, None, data),
             }
         }
 
+        // Wait for a file to be read, and queue tasks to parse it
+        fn finish_read(state: &mut RecState, read: PendingRead, site: &IncludeSite) -> Promise<FileSR> {
+            match read {
+                PendingRead::Done(promise) => promise,
+                PendingRead::Reading(path, time, read) => {
+                    match read.wait() {
+                        Ok(buf) => split_and_parse(state, path, Some(time), Arc::new(buf)),
+                        Err(err) => read_error(path, site, err),
+                    }
+                }
+            }
+        }
+
+        fn read_and_parse(state: &mut RecState, path: String) -> Promise<FileSR> {
+            let read = start_read(state, path, &None);
+            finish_read(state, read, &None)
+        }
+
         // File data has come back from the worker thread, make sure it's in the
         // first and second caches as appropriate, even if it came from a hit
         // earlier
@@ -404,17 +951,23 @@ impl SegmentSet {
         // incorporate it into the database under construction while parsing
         // includes
         fn recurse(state: &mut RecState, segments: SegList) {
-            let mut promises = VecDeque::new();
+            let mut reads = Vec::new();
 
             for seg in &segments {
                 if seg.0.next_file != Span::null() {
                     let chain = str::from_utf8(seg.0.next_file.as_ref(&seg.0.buffer))
                         .expect("parser verified ASCII")
                         .to_owned();
-                    // parse this include in the background
-                    promises.push_back(read_and_parse(state, chain));
+                    // read this include in the background
+                    let site = Some((seg.1.clone(), seg.0.next_file));
+                    let read = start_read(state, chain, &site);
+                    reads.push((read, site));
                 }
             }
+            // parse the includes in the background as they are read
+            let mut promises: VecDeque<Promise<FileSR>> = reads.into_iter()
+                .map(|(read, site)| finish_read(state, read, &site))
+                .collect();
             for seg in segments {
                 if seg.0.next_file != Span::null() {
                     state.segments.push(seg);
@@ -439,8 +992,8 @@ impl SegmentSet {
             old_by_time: mem::replace(&mut self.file_cache, new_map()),
             new_by_time: new_map(),
             segments: Vec::new(),
-            included: new_set(),
-            preload: data.into_iter().collect(),
+            included: new_map(),
+            preload: self.preload.clone(),
             exec: self.exec.clone(),
         };
 
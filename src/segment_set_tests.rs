@@ -0,0 +1,157 @@
+use database::Database;
+use database::DbOptions;
+use diag::Diagnostic;
+use diag::DiagnosticClass;
+use emit::EmitError;
+use parser::Span;
+use parser::StatementAddress;
+use segment_set::IncludeOptions;
+use segment_set::StatementPart;
+#[cfg(feature = "mmap")]
+use std::env;
+#[cfg(feature = "mmap")]
+use std::fs;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_file_order() {
+    let dbo = DbOptions::default();
+    let mut db = Database::new(dbo);
+    db.parse("a.mm".to_owned(),
+             vec![("a.mm".to_owned(), b"$c X $. $[ b.mm $] $c Y $.".to_vec()),
+                  ("b.mm".to_owned(), b"$c Z $.".to_vec())]);
+    assert_eq!(db.parse_result().file_order(), vec!["a.mm", "b.mm", "a.mm"]);
+}
+
+#[test]
+fn test_include_normalization() {
+    let files = vec![("a.mm".to_owned(),
+                      b"$c X $.\r\n$[ Sub\\B.mm $]\r\n$[ sub/b.mm $]\r\n".to_vec()),
+                     ("sub/b.mm".to_owned(), b"\xEF\xBB\xBF$c Z $.".to_vec())];
+    let mut db = Database::new(DbOptions::default());
+    db.parse("a.mm".to_owned(), files.clone());
+    assert_eq!(db.parse_result().file_order(), vec!["a.mm", "sub/b.mm", "a.mm"]);
+
+    let dbo = DbOptions {
+        includes: IncludeOptions::all(),
+        ..DbOptions::default()
+    };
+    let mut db = Database::new(dbo);
+    db.parse("a.mm".to_owned(), files);
+    assert_eq!(db.parse_result().file_order(), vec!["a.mm", "Sub/B.mm", "a.mm"]);
+    let diags: Vec<Diagnostic> = db.parse_result()
+        .parse_diagnostics()
+        .into_iter()
+        .map(|(_, diag)| diag)
+        .collect();
+    assert_eq!(diags,
+               vec![Diagnostic::FilenameBackslash,
+                    Diagnostic::IncludeCaseMismatch(Span::new(26, 34), "Sub/B.mm".to_owned())]);
+    let notations = db.diag_notations(vec![DiagnosticClass::Parse]);
+    assert_eq!(notations[1].source.name, "a.mm");
+    assert_eq!(notations[1].span.as_ref(&notations[1].source.text), b"sub/b.mm");
+    let mut out = Vec::new();
+    db.parse_result().write_file("a.mm", &mut out).unwrap();
+    assert_eq!(out, b"$c X $.\n$[ Sub\\B.mm $]\n$[ sub/b.mm $]\n".to_vec());
+}
+
+#[test]
+#[cfg(feature = "mmap")]
+fn test_mmap() {
+    let path = env::temp_dir().join(format!("metamath-knife-mmap-{}.mm", ::std::process::id()));
+    fs::write(&path, b"$c X $.\n$[ empty.mm $]\n$c Y $.\n").unwrap();
+    let dbo = DbOptions {
+        mmap: true,
+        ..DbOptions::default()
+    };
+    let mut db = Database::new(dbo);
+    let name = path.to_str().unwrap().to_owned();
+    db.parse(name.clone(), vec![("empty.mm".to_owned(), Vec::new())]);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(db.parse_result().file_order(), vec![&name[..], "empty.mm", &name[..]]);
+    let mut out = Vec::new();
+    db.parse_result().write_file(&name, &mut out).unwrap();
+    assert_eq!(out, b"$c X $.\n$[ empty.mm $]\n$c Y $.\n".to_vec());
+}
+
+#[test]
+fn test_include_read_error() {
+    let mut db = mkdb(b"$c X $.\n$[ missing-include.mm $]\n$c Y $.\n");
+    let notations = db.diag_notations(vec![DiagnosticClass::Parse]);
+    assert_eq!(notations.len(), 1);
+    assert_eq!(notations[0].message,
+               "Included file could not be read (error: {error})");
+    assert_eq!(notations[0].source.name, "test.mm");
+    assert_eq!(notations[0].span.as_ref(&notations[0].source.text),
+               b"missing-include.mm");
+    assert_eq!(db.parse_result().file_order(), vec!["test.mm"]);
+}
+
+#[test]
+fn test_write_mm() {
+    let dbo = DbOptions::default();
+    let mut db = Database::new(dbo);
+    let a = b"$( head $)\n$c X $.\n  $[ b.mm $]\n${ $v Y $. $}\n\n$( tail $) \n";
+    let b = b"$c Z $. $( in b $)";
+    db.parse("a.mm".to_owned(),
+             vec![("a.mm".to_owned(), a.to_vec()), ("b.mm".to_owned(), b.to_vec())]);
+    let mut out = Vec::new();
+    db.parse_result().write_file("a.mm", &mut out).unwrap();
+    assert_eq!(out, a.to_vec());
+    let mut out = Vec::new();
+    db.write_mm(&mut out).unwrap();
+    assert_eq!(out,
+               b"$( head $)\n$c X $.\n  $c Z $. $( in b $)\n${ $v Y $. $}\n\n$( tail $) \n"
+                   .to_vec());
+}
+
+#[test]
+fn test_statement_edits() {
+    let mut db = mkdb(b"$c X $.\n$c Y $.\n$c Z $.\n");
+    let seg = db.parse_result().segments()[0].id;
+    db.replace_statement(StatementAddress::new(seg, 1), b"$c W $.").unwrap();
+    db.insert_statement(StatementAddress::new(seg, 0), b"$( new $)\n").unwrap();
+    let seg = db.parse_result().segments()[0].id;
+    match db.replace_statement(StatementAddress::new(seg, 9), b"") {
+        Err(EmitError::UnknownStatement(addr)) => assert_eq!(addr.index, 9),
+        _ => panic!("an address out of range must be rejected"),
+    }
+    db.delete_statement(StatementAddress::new(seg, 3)).unwrap();
+    let mut out = Vec::new();
+    db.write_mm(&mut out).unwrap();
+    assert_eq!(out, b"$( new $)\n$c X $.\n$c W $.\n".to_vec());
+    assert!(db.diag_notations(vec![DiagnosticClass::Parse, DiagnosticClass::Scope]).is_empty());
+}
+
+#[test]
+fn test_statement_at() {
+    let mut db = mkdb(b"$c X Y $.\n${ ax $p X Y $= ? $. $}");
+    let seg = db.parse_result().segments()[0].id;
+    assert_eq!(db.statement_at("test.mm", 5),
+               Some((StatementAddress::new(seg, 0), StatementPart::Math(1))));
+    assert_eq!(db.statement_at("test.mm", 9),
+               Some((StatementAddress::new(seg, 1), StatementPart::Other)));
+    assert_eq!(db.statement_at("test.mm", 14),
+               Some((StatementAddress::new(seg, 2), StatementPart::Label)));
+    assert_eq!(db.statement_at("test.mm", 26),
+               Some((StatementAddress::new(seg, 2), StatementPart::Proof(0))));
+    assert_eq!(db.statement_at("other.mm", 0), None);
+    assert_eq!(db.statements_in_range("test.mm", 8..14),
+               vec![StatementAddress::new(seg, 0),
+                    StatementAddress::new(seg, 1),
+                    StatementAddress::new(seg, 2)]);
+
+    // a file split by an inclusion has a segment on either side of it
+    let mut db = Database::new(DbOptions::default());
+    db.parse("a.mm".to_owned(),
+             vec![("a.mm".to_owned(), b"$c X $.\n$[ b.mm $]\n$c Y $.\n".to_vec()),
+                  ("b.mm".to_owned(), b"$c Z $.\n".to_vec())]);
+    let segs: Vec<_> = db.parse_result().segments().iter().map(|sref| sref.id).collect();
+    assert_eq!(db.statement_at("a.mm", 3),
+               Some((StatementAddress::new(segs[0], 0), StatementPart::Math(0))));
+    assert_eq!(db.statement_at("a.mm", 22),
+               Some((StatementAddress::new(segs[2], 0), StatementPart::Math(0))));
+    assert_eq!(db.statement_at("b.mm", 3),
+               Some((StatementAddress::new(segs[1], 0), StatementPart::Math(0))));
+    assert_eq!(db.statement_at("a.mm", 100), None);
+}
@@ -0,0 +1,263 @@
+//! A JSON-RPC server answering queries about a database.
+//!
+//! Loading and checking a large database takes seconds, which is too slow to
+//! repeat for every query of an editor or web front-end.  The server keeps
+//! one `Database` in memory and answers JSON-RPC 2.0 requests, one per line,
+//! writing one response per line.  Requests without an `id` are
+//! notifications and get no response.
+//!
+//! The methods are:
+//!
+//! * `statement` `{"label"}`: the statement as described by
+//!   `json::StatementJson`.
+//! * `parse` `{"formula", "label"?}`: the syntactic typecode (`wff` for
+//!   `|- ( ph -> ps )`) and parse tree of a math string, or `null` if it
+//!   does not parse.  The variables are those of the frame of `label` if
+//!   given, and otherwise those of every `$f` statement.
+//! * `unify` `{"formula", "label"}`: the substitution making the assertion
+//!   `label` equal to the formula, as a map from variables to parse trees,
+//!   or `null` if there is none.
+//! * `verify` `{"label", "proof"?}`: whether the proof of a `$p` statement is
+//!   valid, with the diagnostics if not.  If `proof` is given, it is checked
+//!   in place of the current proof, which is restored afterwards.
+//!
+//! The `formula` of `parse` and `unify` is rejected with an error if it has
+//! more than 10000 symbols, or is nested too deeply for the parser.
+
+use database::Database;
+use diag;
+use diag::Diagnostic;
+use grammar::SyntaxParser;
+use grammar::SyntaxTree;
+use grammar::frame_variables;
+use grammar::global_variables;
+use json::StatementJson;
+use parser::StatementAddress;
+use parser::Token;
+use parser::TokenPtr;
+use parser::as_str;
+use serde_json;
+use serde_json::Value;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use transaction::Change;
+use transaction::Transaction;
+use util::HashMap;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Application error code for queries which cannot be answered, such as
+/// those naming unknown labels.
+const QUERY_FAILED: i64 = -32000;
+/// The most symbols a formula parameter may have; longer ones are rejected
+/// before parsing.
+const MAX_FORMULA_SYMBOLS: usize = 10000;
+
+type MethodResult = Result<Value, (i64, String)>;
+
+fn string_param<'a>(params: &'a Value, name: &str) -> Result<&'a str, (i64, String)> {
+    params.get(name)
+        .and_then(|value| value.as_str())
+        .ok_or_else(|| (INVALID_PARAMS, format!("Missing string parameter {}", name)))
+}
+
+/// Splits the `formula` parameter into its symbols, rejecting a formula too
+/// long to parse.
+fn formula_param(params: &Value) -> Result<Vec<TokenPtr<'_>>, (i64, String)> {
+    let math: Vec<TokenPtr> = string_param(params, "formula")?
+        .split_whitespace()
+        .map(|tok| tok.as_bytes())
+        .collect();
+    if math.len() > MAX_FORMULA_SYMBOLS {
+        return Err((INVALID_PARAMS,
+                    format!("Formula has more than {} symbols", MAX_FORMULA_SYMBOLS)));
+    }
+    Ok(math)
+}
+
+/// Parses a formula given as a parameter, returning `None` if it does not
+/// parse and an error if it is nested too deeply to parse.
+fn parse_formula(parser: &SyntaxParser,
+                 vars: &HashMap<Token, Token>,
+                 math: &[TokenPtr])
+                 -> Result<Option<(Token, SyntaxTree)>, (i64, String)> {
+    match parser.parse_math_checked(vars, math) {
+        Ok(parse) => Ok(Some(parse)),
+        Err(Diagnostic::FormulaTooComplex(_)) => {
+            Err((INVALID_PARAMS, "Formula is nested too deeply".to_owned()))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+fn lookup(db: &mut Database, label: &str) -> Result<StatementAddress, (i64, String)> {
+    db.statement(label)
+        .map(|stmt| stmt.address())
+        .ok_or_else(|| (QUERY_FAILED, format!("Unknown label {}", label)))
+}
+
+fn statement(db: &mut Database, params: &Value) -> MethodResult {
+    let addr = lookup(db, string_param(params, "label")?)?;
+    let parser = db.syntax_parser().clone();
+    let sset = db.parse_result().clone();
+    let nset = db.name_result().clone();
+    let scope = db.scope_result().clone();
+    let stmt = StatementJson::new(&parser, &sset, &nset, &scope, sset.statement(addr));
+    Ok(serde_json::to_value(stmt).expect("statements serialize"))
+}
+
+fn parse(db: &mut Database, params: &Value) -> MethodResult {
+    let math = formula_param(params)?;
+    let parser = db.syntax_parser().clone();
+    let sset = db.parse_result().clone();
+    let vars = match params.get("label") {
+        Some(label) => {
+            let label = label.as_str()
+                .ok_or_else(|| (INVALID_PARAMS, "Label must be a string".to_owned()))?;
+            lookup(db, label)?;
+            let nset = db.name_result().clone();
+            let scope = db.scope_result().clone();
+            let frame = scope.get(label.as_bytes())
+                .ok_or_else(|| (QUERY_FAILED, format!("{} has no frame", label)))?;
+            frame_variables(&nset, frame)
+        }
+        None => global_variables(&sset),
+    };
+    Ok(match parse_formula(&parser, &vars, &math)? {
+        Some((typecode, tree)) => {
+            json!({"typecode": as_str(&typecode), "parse": tree.to_sexpr(&sset)})
+        }
+        None => Value::Null,
+    })
+}
+
+fn unify(db: &mut Database, params: &Value) -> MethodResult {
+    let math = formula_param(params)?;
+    let label = string_param(params, "label")?;
+    let addr = lookup(db, label)?;
+    let parser = db.syntax_parser().clone();
+    let sset = db.parse_result().clone();
+    let nset = db.name_result().clone();
+    let scope = db.scope_result().clone();
+    let frame = scope.get(label.as_bytes())
+        .ok_or_else(|| (QUERY_FAILED, format!("{} is not an assertion", label)))?;
+
+    let assertion: Vec<TokenPtr> = sset.statement(addr).math_iter().map(|tok| tok.slice).collect();
+    let pattern = parser.parse_math(&frame_variables(&nset, frame), &assertion)
+        .ok_or_else(|| (QUERY_FAILED, format!("The assertion of {} does not parse", label)))?;
+    let target = parse_formula(&parser, &global_variables(&sset), &math)?
+        .ok_or_else(|| (QUERY_FAILED, "The formula does not parse".to_owned()))?;
+
+    if pattern.0 != target.0 || math.first() != assertion.first() {
+        return Ok(Value::Null);
+    }
+    Ok(match pattern.1.match_onto(&target.1) {
+        Some(subst) => {
+            let mut map = serde_json::Map::new();
+            for (var, tree) in subst {
+                map.insert(as_str(&var).to_owned(), Value::String(tree.to_sexpr(&sset)));
+            }
+            json!({ "substitution": map })
+        }
+        None => Value::Null,
+    })
+}
+
+/// Checks a statement, returning its verification diagnostics as JSON.
+fn check(db: &mut Database, label: &str) -> MethodResult {
+    let addr = lookup(db, label)?;
+    let mut diags = db.scope_result().diagnostics();
+    diags.extend(db.verify_result().diagnostics());
+    diags.retain(|&(daddr, _)| daddr == addr);
+    let name = db.name_result().clone();
+    let notations = diag::to_annotations(db.parse_result(), &name, diags);
+    let diagnostics: Vec<Value> = notations.into_iter()
+        .map(|notation| {
+            let mut args = serde_json::Map::new();
+            for (name, value) in notation.args {
+                args.insert(name.to_owned(), Value::String(value));
+            }
+            json!({
+                "level": format!("{:?}", notation.level),
+                "code": notation.code,
+                "message": notation.message,
+                "args": args,
+            })
+        })
+        .collect();
+    Ok(json!({ "valid": diagnostics.is_empty(), "diagnostics": diagnostics }))
+}
+
+fn verify(db: &mut Database, params: &Value) -> MethodResult {
+    let label = string_param(params, "label")?;
+    match params.get("proof") {
+        None => check(db, label),
+        Some(proof) => {
+            let proof = proof.as_str()
+                .ok_or_else(|| (INVALID_PARAMS, "Proof must be a string".to_owned()))?;
+            let addr = lookup(db, label)?;
+            let mut txn = Transaction::new(db);
+            txn.apply(&[Change::ReplaceProof(addr, proof.to_owned())])
+                .map_err(|err| (QUERY_FAILED, err.to_string()))?;
+            check(txn.database(), label)
+            // dropping the transaction restores the original proof
+        }
+    }
+}
+
+/// Handles one request, given as the text of a JSON-RPC message, and returns
+/// the text of the response, or `None` for a notification.
+pub fn handle_request(db: &mut Database, request: &str) -> Option<String> {
+    let response = |id: Value, result: MethodResult| {
+        let body = match result {
+            Ok(value) => json!({"jsonrpc": "2.0", "id": id, "result": value}),
+            Err((code, message)) => {
+                json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+            }
+        };
+        Some(body.to_string())
+    };
+
+    let request: Value = match serde_json::from_str(request) {
+        Ok(value) => value,
+        Err(err) => return response(Value::Null, Err((PARSE_ERROR, err.to_string()))),
+    };
+    let id = request.get("id").cloned();
+    let method = match request.get("method").and_then(|method| method.as_str()) {
+        Some(method) => method,
+        None => {
+            return response(id.unwrap_or(Value::Null),
+                            Err((INVALID_REQUEST, "Missing method".to_owned())))
+        }
+    };
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    let result = match method {
+        "statement" => statement(db, &params),
+        "parse" => parse(db, &params),
+        "unify" => unify(db, &params),
+        "verify" => verify(db, &params),
+        _ => Err((METHOD_NOT_FOUND, format!("Unknown method {}", method))),
+    };
+    match id {
+        Some(id) => response(id, result),
+        None => None,
+    }
+}
+
+/// Answers requests read from `input`, one per line, until it ends.
+pub fn serve<R: BufRead, W: Write>(db: &mut Database, input: R, output: &mut W) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = handle_request(db, &line) {
+            writeln!(output, "{}", response)?;
+            output.flush()?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use server::handle_request;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_server_requests() {
+    let text = format!("{}th1.1 $e |- ph $.
+  th1 $p |- ( ps -> ph ) $= wph wps wph wi th1.1 wph wps ax-1 ax-mp $.
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let mut call = |request: &str| handle_request(&mut db, request);
+    assert_eq!(call(r#"{"jsonrpc":"2.0","id":1,"method":"parse","params":{"formula":"|- ( ph -> ps )"}}"#),
+               Some(r#"{"id":1,"jsonrpc":"2.0","result":{"parse":"(wi ph ps)","typecode":"wff"}}"#
+                   .to_owned()));
+    assert_eq!(call(r#"{"jsonrpc":"2.0","id":2,"method":"unify","params":{"formula":"|- ( ph -> ( ( ph -> ps ) -> ph ) )","label":"ax-1"}}"#),
+               Some(r#"{"id":2,"jsonrpc":"2.0","result":{"substitution":{"ph":"ph","ps":"(wi ph ps)"}}}"#
+                   .to_owned()));
+    assert_eq!(call(r#"{"jsonrpc":"2.0","id":3,"method":"verify","params":{"label":"th1"}}"#),
+               Some(r#"{"id":3,"jsonrpc":"2.0","result":{"diagnostics":[],"valid":true}}"#
+                   .to_owned()));
+    let response = call(r#"{"jsonrpc":"2.0","id":4,"method":"verify","params":{"label":"th1","proof":"wph wps ax-1"}}"#)
+        .unwrap();
+    assert!(response.contains(r#""valid":false"#));
+    // the trial proof is not kept
+    assert_eq!(call(r#"{"jsonrpc":"2.0","id":5,"method":"verify","params":{"label":"th1"}}"#),
+               Some(r#"{"id":5,"jsonrpc":"2.0","result":{"diagnostics":[],"valid":true}}"#
+                   .to_owned()));
+    assert_eq!(call(r#"{"jsonrpc":"2.0","id":6,"method":"statement","params":{"label":"nope"}}"#),
+               Some(r#"{"error":{"code":-32000,"message":"Unknown label nope"},"id":6,"jsonrpc":"2.0"}"#
+                   .to_owned()));
+    assert_eq!(call(r#"{"jsonrpc":"2.0","id":7,"method":"frobnicate"}"#),
+               Some(r#"{"error":{"code":-32601,"message":"Unknown method frobnicate"},"id":7,"jsonrpc":"2.0"}"#
+                   .to_owned()));
+    assert_eq!(call(r#"{"jsonrpc":"2.0","method":"verify","params":{"label":"th1"}}"#), None);
+}
+
+#[test]
+fn test_server_formula_limits() {
+    let mut db = mkdb(DB.as_bytes());
+    let mut call = |method: &str, formula: String| {
+        handle_request(&mut db,
+                       &format!(r#"{{"jsonrpc":"2.0","id":1,"method":"{}","params":{{"formula":"{}","label":"ax-1"}}}}"#,
+                                method,
+                                formula))
+            .unwrap()
+    };
+    let long = format!("|- {}", "ph ".repeat(10000));
+    for method in &["parse", "unify"] {
+        assert_eq!(call(method, long.clone()),
+                   r#"{"error":{"code":-32602,"message":"Formula has more than 10000 symbols"},"id":1,"jsonrpc":"2.0"}"#);
+    }
+    let nested = format!("|- {}ph{}", "( ph -> ".repeat(2000), " )".repeat(2000));
+    for method in &["parse", "unify"] {
+        assert_eq!(call(method, nested.clone()),
+                   r#"{"error":{"code":-32602,"message":"Formula is nested too deeply"},"id":1,"jsonrpc":"2.0"}"#);
+    }
+}
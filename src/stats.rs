@@ -0,0 +1,81 @@
+//! Timing of passes and of individual statements.
+//!
+//! With the `stats` feature and `DbOptions::stats` set, every pass run through
+//! the database records its duration, and the scope checker, grammar check and
+//! verifier record the time spent on each statement they process.  The parser is only timed as a
+//! whole, since statements only get their addresses once the segments are
+//! assembled.  The data is kept by the `Database`, which shares it with the
+//! passes through their `SegmentSet` so that they can reach it from any
+//! thread.
+//!
+//! Statement times are stored by segment and replaced whenever a segment is
+//! processed again, so after an incremental reload they describe the latest
+//! run of each segment.  The overhead is two clock reads per statement.
+
+use parser::Comparer;
+use parser::SegmentId;
+use parser::StatementAddress;
+use parser::StatementIndex;
+use segment_set::SegmentSet;
+use std::sync::Mutex;
+use std::time::Duration;
+use util::HashMap;
+use util::new_map;
+
+/// Times of statements in one segment, in processing order.
+pub type SegmentTimes = Vec<(StatementIndex, Duration)>;
+
+/// Recorded durations of passes and statements.
+#[derive(Debug,Default)]
+pub struct Stats {
+    passes: Mutex<Vec<(String, Duration)>>,
+    statements: Mutex<HashMap<&'static str, HashMap<SegmentId, SegmentTimes>>>,
+}
+
+impl Stats {
+    /// Records a run of a pass.
+    pub fn record_pass(&self, name: &str, time: Duration) {
+        self.passes.lock().unwrap().push((name.to_owned(), time));
+    }
+
+    /// Records the statement times of a segment for a pass, replacing those of
+    /// an earlier run.
+    pub fn record_segment(&self, pass: &'static str, id: SegmentId, times: SegmentTimes) {
+        self.statements
+            .lock()
+            .unwrap()
+            .entry(pass)
+            .or_insert_with(new_map)
+            .insert(id, times);
+    }
+
+    /// Returns the runs of each pass, in the order they finished.
+    pub fn pass_times(&self) -> Vec<(String, Duration)> {
+        self.passes.lock().unwrap().clone()
+    }
+
+    /// Returns the `count` statements of the database on which a pass
+    /// (`"scopeck"`, `"grammar"` or `"verify"`) spent the most time, slowest
+    /// first.  Segments which are no longer part of the database are skipped.
+    pub fn slowest(&self,
+                   sset: &SegmentSet,
+                   pass: &str,
+                   count: usize)
+                   -> Vec<(StatementAddress, Duration)> {
+        let statements = self.statements.lock().unwrap();
+        let segments = match statements.get(pass) {
+            Some(segments) => segments,
+            None => return Vec::new(),
+        };
+        let mut out = Vec::new();
+        for sref in sset.segments() {
+            if let Some(times) = segments.get(&sref.id) {
+                out.extend(times.iter()
+                    .map(|&(index, time)| (StatementAddress::new(sref.id, index), time)));
+            }
+        }
+        out.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| sset.order.cmp(&a.0, &b.0)));
+        out.truncate(count);
+        out
+    }
+}
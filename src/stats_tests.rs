@@ -0,0 +1,34 @@
+use database::Database;
+use database::DbOptions;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+fn mkdb_stats(text: &[u8]) -> Database {
+    let mut db = Database::new(DbOptions { stats: true, ..DbOptions::default() });
+    db.parse("test.mm".to_owned(),
+             vec![("test.mm".to_owned(), text.to_owned())]);
+    db
+}
+
+#[test]
+fn test_slowest_proofs() {
+    let text = format!("{}th1 $p |- ( ph -> ( ph -> ph ) ) $= wph wph ax-1 $.
+th2 $p |- ( ps -> ( ph -> ps ) ) $= wps wph ax-1 $.
+",
+                       DB);
+    let mut db = mkdb_stats(text.as_bytes());
+    let slowest = db.slowest_proofs(5);
+    let mut labels: Vec<&str> = slowest.iter().map(|(label, _)| &label[..]).collect();
+    labels.sort();
+    assert_eq!(labels, vec!["th1", "th2"]);
+    assert_eq!(db.slowest_proofs(1).len(), 1);
+    assert!(db.pass_times().iter().any(|(pass, _)| pass == "verify"));
+}
+
+#[test]
+fn test_stats_off() {
+    let mut db = mkdb(DB.as_bytes());
+    db.verify_result();
+    assert!(db.slowest_proofs(5).is_empty());
+    assert!(db.pass_times().is_empty());
+}
@@ -0,0 +1,140 @@
+//! Theorem stubs, for top-down development of new theories.
+//!
+//! A stub is a `$p` statement which is planned but not yet proved: its proof
+//! contains `?` steps, typically being just `$= ? $.`.  Stubs can be used in
+//! the proofs of later theorems like any other assertion, which lets a theory
+//! be written from the main results down; every theorem whose proof depends
+//! on a stub, directly or through other theorems, is pending until the stub
+//! is proved.
+//!
+//! `draft_stub` produces the source text registering a new stub, and
+//! `StubReport` lists the stubs of a database ordered by how much pending
+//! work proving each one would unblock.  Formula search flags matches which
+//! are stubs; see `search::SearchMatch`.
+
+use emit;
+use emit::Edit;
+use parser::Comparer;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use usage::proof_labels;
+use util::HashMap;
+use util::HashSet;
+use util::new_map;
+use util::new_set;
+
+/// Returns true if a statement is a stub: a `$p` statement whose proof
+/// contains `?` steps.
+pub fn is_stub(stmt: StatementRef) -> bool {
+    stmt.statement_type() == StatementType::Provable && proof_labels(stmt).1
+}
+
+/// Drafts a new stub, to be inserted before an existing statement.
+///
+/// The math string is given without the typecode `|-`.  Returns the name of
+/// the file to edit and an edit inserting the stub with a placeholder
+/// description comment.
+pub fn draft_stub(sset: &SegmentSet,
+                  before: StatementAddress,
+                  label: &str,
+                  math: &str)
+                  -> (String, Edit) {
+    let lines = [format!("$( Planned theorem {}. $)", label),
+                 format!("{} $p |- {} $= ? $.", label, math.trim())];
+    emit::insert_before(sset, sset.statement(before), &lines)
+}
+
+/// A stub and the theorems waiting for it.
+#[derive(Clone,Debug)]
+pub struct Stub {
+    /// The stub statement.
+    pub address: StatementAddress,
+    /// Every other `$p` statement whose proof uses the stub, directly or
+    /// transitively, in database order.
+    pub dependents: Vec<StatementAddress>,
+    /// The number of dependents which would become complete if this stub
+    /// were proved: those which are not stubs themselves and depend on no
+    /// other stub.
+    pub unblocks: usize,
+}
+
+/// The stubs of a database.
+#[derive(Clone,Debug,Default)]
+pub struct StubReport {
+    /// All stubs, the ones unblocking the most theorems first; ties are
+    /// broken by the total number of dependents and then database order.
+    pub stubs: Vec<Stub>,
+}
+
+impl StubReport {
+    /// Finds all stubs and the theorems depending on them, following proof
+    /// references resolved by the scope pass.
+    pub fn new(sset: &SegmentSet, scope: &ScopeResult) -> StubReport {
+        let mut users: HashMap<StatementAddress, Vec<StatementAddress>> = new_map();
+        let mut stubs = Vec::new();
+        for sref in sset.segments() {
+            for stmt in sref {
+                if stmt.statement_type() != StatementType::Provable {
+                    continue;
+                }
+                let (labels, incomplete) = proof_labels(stmt);
+                if incomplete {
+                    stubs.push(stmt.address());
+                }
+                for (_, label) in labels {
+                    if let Some(frame) = scope.get(label) {
+                        if frame.stype == StatementType::Provable {
+                            let used = users.entry(frame.valid.start).or_default();
+                            if used.last() != Some(&stmt.address()) {
+                                used.push(stmt.address());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut blockers: HashMap<StatementAddress, usize> = new_map();
+        let mut report = StubReport::default();
+        for &addr in &stubs {
+            let mut seen: HashSet<StatementAddress> = new_set();
+            let mut queue = vec![addr];
+            seen.insert(addr);
+            while let Some(cur) = queue.pop() {
+                for &user in users.get(&cur).map_or(&[][..], |v| &v[..]) {
+                    if seen.insert(user) {
+                        queue.push(user);
+                    }
+                }
+            }
+            seen.remove(&addr);
+            let mut dependents: Vec<StatementAddress> = seen.into_iter().collect();
+            dependents.sort_by(|x, y| sset.order.cmp(x, y));
+            for &dep in &dependents {
+                *blockers.entry(dep).or_insert(0) += 1;
+            }
+            report.stubs.push(Stub {
+                address: addr,
+                dependents: dependents,
+                unblocks: 0,
+            });
+        }
+
+        for stub in &mut report.stubs {
+            stub.unblocks = stub.dependents
+                .iter()
+                .filter(|dep| blockers[dep] == 1 && !stubs.contains(dep))
+                .count();
+        }
+        report.stubs.sort_by(|a, b| {
+            b.unblocks
+                .cmp(&a.unblocks)
+                .then_with(|| b.dependents.len().cmp(&a.dependents.len()))
+                .then_with(|| sset.order.cmp(&a.address, &b.address))
+        });
+        report
+    }
+}
@@ -0,0 +1,44 @@
+use database::Database;
+use emit::emit_file;
+use stubs::draft_stub;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_stubs() {
+    let text = format!("{}s1 $p |- ( ph -> ph ) $= ? $.
+s2 $p |- ps $= ? $.
+t1 $p |- ( ph -> ph ) $= wph s1 $.
+t2 $p |- ( ph -> ph ) $= wph t1 $.
+t3 $p |- ps $= wps s2 $.
+t4 $p |- ps $= wph s1 wps s2 $.
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let addr = |db: &mut Database, label: &str| db.statement(label).unwrap().address();
+    let (s1, s2) = (addr(&mut db, "s1"), addr(&mut db, "s2"));
+    let (t1, t2, t3, t4) = (addr(&mut db, "t1"),
+                            addr(&mut db, "t2"),
+                            addr(&mut db, "t3"),
+                            addr(&mut db, "t4"));
+    let report = db.stub_report();
+    assert_eq!(report.stubs.len(), 2);
+    assert_eq!(report.stubs[0].address, s1);
+    assert_eq!(report.stubs[0].dependents, vec![t1, t2, t4]);
+    assert_eq!(report.stubs[0].unblocks, 2);
+    assert_eq!(report.stubs[1].address, s2);
+    assert_eq!(report.stubs[1].dependents, vec![t3, t4]);
+    assert_eq!(report.stubs[1].unblocks, 1);
+
+    let sset = db.parse_result().clone();
+    let (file, edit) = draft_stub(&sset, t1, "s3", "( ps -> ps ) ");
+    assert_eq!(file, "test.mm");
+    let mut out = Vec::new();
+    emit_file(&sset, "test.mm", &[edit], &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("s2 $p |- ps $= ? $.
+$( Planned theorem s3. $)
+s3 $p |- ( ps -> ps ) $= ? $.
+
+t1 $p"));
+}
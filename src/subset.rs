@@ -0,0 +1,182 @@
+//! Extraction of a self-contained sub-database.
+//!
+//! Given some theorems, `extract_text` writes the smallest database in which
+//! they still verify: the assertions their proofs use, directly or
+//! indirectly, with the hypotheses of each, the `$f` statements of variables
+//! used only inside proofs, the `$d` statements in effect, and declarations of
+//! the math symbols which remain.  If the database has syntax axioms, those
+//! needed to parse the statements kept are included too, so that the result
+//! passes the grammar check as well.  Each assertion keeps its description
+//! comment, and `$j` comments are kept since they configure the grammar.
+//!
+//! Statements are written in their original order with their original text,
+//! except that `$c` and `$v` statements are cut down to the symbols still
+//! used.  The `${ $}` groups are kept, minus those which end up empty, so that
+//! scoping is unchanged.
+
+use grammar::SyntaxParser;
+use grammar::SyntaxTree;
+use grammar::SyntaxVisitor;
+use grammar::frame_variables;
+use grammar::global_variables;
+use layout::statement_indent;
+use nameck::Nameset;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::TokenPtr;
+use parser::as_str;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use usage::proof_labels;
+use util::HashSet;
+use util::new_set;
+use view::in_effect;
+
+/// Collects the syntax axioms of a parse tree.
+struct SyntaxAxioms<'a>(&'a mut Vec<StatementAddress>);
+
+impl<'a> SyntaxVisitor for SyntaxAxioms<'a> {
+    fn pre(&mut self, _: &[usize], tree: &SyntaxTree) -> bool {
+        if let SyntaxTree::App(address, _) = *tree {
+            self.0.push(address);
+        }
+        true
+    }
+}
+
+/// Computes the statements to keep for the given theorems: assertions,
+/// hypotheses and `$d` statements, but not declarations or comments.
+fn closure(sset: &SegmentSet,
+           nset: &Nameset,
+           scope: &ScopeResult,
+           parser: Option<&SyntaxParser>,
+           roots: &[StatementAddress])
+           -> HashSet<StatementAddress> {
+    let mut keep = new_set();
+    let mut queue = roots.to_vec();
+    let mut assertions = Vec::new();
+    while let Some(addr) = queue.pop() {
+        if !keep.insert(addr) {
+            continue;
+        }
+        let stmt = sset.statement(addr);
+        let frame = match scope.get(stmt.label()) {
+            Some(frame) => frame,
+            None => continue,
+        };
+        if stmt.statement_type() != StatementType::Axiom &&
+           stmt.statement_type() != StatementType::Provable {
+            continue;
+        }
+        assertions.push(addr);
+        let mut statements: Vec<StatementRef> =
+            frame.hypotheses.iter().map(|hyp| sset.statement(hyp.address())).collect();
+        statements.push(stmt);
+        queue.extend(statements.iter().map(|hyp| hyp.address()));
+        // the assertions used by the proof, and the $f statements of its
+        // dummy variables, which are not in the frame; $e hypotheses have no
+        // frame of their own
+        for (_, label) in proof_labels(stmt).0 {
+            if let Some(used) = scope.get(label) {
+                queue.push(used.valid.start);
+            }
+        }
+        if let Some(parser) = parser {
+            let mut vars = global_variables(sset);
+            vars.extend(frame_variables(nset, frame));
+            let mut syntax = Vec::new();
+            for stmt in statements {
+                let math: Vec<TokenPtr> = stmt.math_iter().map(|tok| tok.slice).collect();
+                if let Some((_, tree)) = parser.parse_math(&vars, &math) {
+                    tree.visit(&mut SyntaxAxioms(&mut syntax));
+                }
+            }
+            queue.extend(syntax);
+        }
+    }
+
+    for sref in sset.segments() {
+        for stmt in sref {
+            if stmt.statement_type() == StatementType::Disjoint &&
+               assertions.iter().any(|&addr| in_effect(sset, stmt, addr)) {
+                keep.insert(stmt.address());
+            }
+        }
+    }
+    keep
+}
+
+/// Writes a database containing only the given theorems and what they need;
+/// see the module documentation.
+pub fn extract_text(sset: &SegmentSet,
+                    nset: &Nameset,
+                    scope: &ScopeResult,
+                    parser: Option<&SyntaxParser>,
+                    roots: &[StatementAddress])
+                    -> Vec<u8> {
+    let keep = closure(sset, nset, scope, parser, roots);
+    let mut symbols = new_set();
+    for &addr in &keep {
+        symbols.extend(sset.statement(addr).math_iter().map(|tok| tok.slice));
+    }
+
+    // pieces of text, with whether each opens a group, so that empty groups
+    // can be dropped when they are closed
+    let mut out: Vec<(bool, Vec<u8>)> = Vec::new();
+    for sref in sset.segments() {
+        for stmt in sref {
+            let text = stmt.span_full().as_ref(&sref.buffer);
+            match stmt.statement_type() {
+                StatementType::OpenGroup => out.push((true, text.to_vec())),
+                StatementType::CloseGroup => {
+                    if let Some(&(true, _)) = out.last() {
+                        out.pop();
+                    } else {
+                        out.push((false, text.to_vec()));
+                    }
+                }
+                StatementType::AdditionalInfoComment => out.push((false, text.to_vec())),
+                StatementType::Constant | StatementType::Variable => {
+                    let used: Vec<TokenPtr> = stmt.math_iter()
+                        .map(|tok| tok.slice)
+                        .filter(|tok| symbols.contains(tok))
+                        .collect();
+                    if !used.is_empty() {
+                        let keyword = if stmt.statement_type() == StatementType::Constant {
+                            &b"$c"[..]
+                        } else {
+                            b"$v"
+                        };
+                        let mut words = vec![as_str(keyword)];
+                        words.extend(used.into_iter().map(as_str));
+                        words.push("$.");
+                        let indent = " ".repeat(statement_indent(sset, stmt));
+                        let mut decl = String::new();
+                        for line in sset.options.format.statement_lines(indent.len(), &words) {
+                            decl.push('\n');
+                            decl.push_str(&indent);
+                            decl.push_str(&line);
+                        }
+                        out.push((false, decl.into_bytes()));
+                    }
+                }
+                _ if keep.contains(&stmt.address()) => {
+                    if let Some(comment) = stmt.associated_comment() {
+                        if stmt.statement_type() == StatementType::Axiom ||
+                           stmt.statement_type() == StatementType::Provable {
+                            out.push((false,
+                                      comment.span_full().as_ref(&sref.buffer).to_vec()));
+                        }
+                    }
+                    out.push((false, text.to_vec()));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut text: Vec<u8> = out.into_iter().flat_map(|piece| piece.1).collect();
+    text.push(b'\n');
+    text
+}
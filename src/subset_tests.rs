@@ -0,0 +1,34 @@
+use diag::DiagnosticClass;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_extract() {
+    let text = format!("{}$v ch $.\nwch $f wff ch $.
+${{ $d ph ch $. th1 $p |- ( ch -> ( ph -> ch ) ) $= wch wph ax-1 $. $}}
+${{ h1 $e |- ph $.
+$( A simple use of ~ ax-mp . $)
+th2 $p |- ( ps -> ph ) $= wph wps wph wi h1 wph wps ax-1 ax-mp $. $}}\n",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    assert!(db.extract(&["th3"]).is_none());
+    let mut sub = db.extract(&["th2"]).unwrap();
+    assert!(sub.statement("th1").is_none());
+    assert!(sub.statement("wch").is_none());
+    for label in &["wph", "wps", "wi", "min", "maj", "ax-mp", "ax-1", "h1", "th2"] {
+        assert!(sub.statement(label).is_some(), "{} is missing", label);
+    }
+    assert!(sub.name_result().lookup_symbol(b"ch").is_none());
+    assert!(sub.diag_notations(vec![DiagnosticClass::Parse,
+                                    DiagnosticClass::Scope,
+                                    DiagnosticClass::Verify])
+        .is_empty());
+
+    let mut sub = db.extract(&["th1"]).unwrap();
+    assert!(sub.statement("ax-mp").is_none());
+    assert!(sub.statement("wch").is_some());
+    assert!(sub.diag_notations(vec![DiagnosticClass::Parse,
+                                    DiagnosticClass::Scope,
+                                    DiagnosticClass::Verify])
+        .is_empty());
+}
@@ -0,0 +1,21 @@
+use database::Database;
+use database::DbOptions;
+
+/// Parses a single-file database named `test.mm`.
+pub fn mkdb(text: &[u8]) -> Database {
+    let dbo = DbOptions::default();
+    let mut db = Database::new(dbo);
+    db.parse("test.mm".to_owned(),
+             vec![("test.mm".to_owned(), text.to_owned())]);
+    db
+}
+
+/// A minimal propositional logic with implication and modus ponens.
+pub const DB: &str = "$c |- wff ( -> ) $.
+$v ph ps $.
+wph $f wff ph $.
+wps $f wff ps $.
+wi $a wff ( ph -> ps ) $.
+${ min $e |- ph $. maj $e |- ( ph -> ps ) $. ax-mp $a |- ps $. $}
+ax-1 $a |- ( ph -> ( ps -> ph ) ) $.
+";
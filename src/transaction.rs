@@ -0,0 +1,521 @@
+//! Atomic editing of a database.
+//!
+//! A `Transaction` groups edits to the source of a database with the
+//! re-analysis needed to check them.  Edits are applied in batches, each with a
+//! single incremental reparse; `validate` reruns the parse, scope and verify
+//! passes (and any others requested), which only recompute the segments
+//! affected by the edits.  `commit` then returns the new text of every
+//! changed file for the caller to write out, while `rollback`, or dropping the
+//! transaction, restores the database to its state before the transaction
+//! began.
+
+use comment_parser::CommentItem;
+use comment_parser::comment_items;
+use database::Database;
+use diag::DiagnosticClass;
+use diag::Notation;
+use emit::EmitError;
+use nameck::Nameset;
+use occurrences::occurrences_in;
+use parser::BufferRef;
+use parser::Comparer;
+use parser::Span;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::as_str;
+use parser::is_valid_label;
+use segment_set::SegmentSet;
+use segment_set::StatementEdit;
+use std::cmp::Ordering;
+use std::error;
+use std::fmt;
+use std::sync::Arc;
+use typesetting::definition_tokens;
+use util::HashMap;
+use util::new_map;
+
+/// A change to apply in a transaction.
+///
+/// Addresses refer to the database as it was before the batch of changes
+/// containing them was applied.  Each statement can only be changed once per
+/// batch; for instance, renaming a label and replacing a proof which uses it
+/// must be done in separate batches.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Change {
+    /// Edit the text of a statement.
+    Edit(StatementAddress, StatementEdit),
+    /// Replace the proof of a `$p` statement, given as the text between `$=`
+    /// and `$.`.
+    ReplaceProof(StatementAddress, String),
+    /// Rename a label, in its defining statement, in every proof which refers
+    /// to it, and in the `~ label` references of comments.
+    Rename(String, String),
+    /// Rename several labels at once, as `Rename` does.  The new names must
+    /// be unused and distinct.
+    RenameAll(Vec<(String, String)>),
+    /// Rename a math symbol, in its `$c` or `$v` declarations, in every math
+    /// string and `$d` statement, in the math text of comments, and in the
+    /// `htmldef`, `althtmldef` and `latexdef` commands of `$t` comments.
+    RenameSymbol(String, String),
+    /// Move statements, in the order given, to just before another statement.
+    ///
+    /// The move is rejected if a proof would then use a statement defined
+    /// after it.  Scoping is not checked: moving an assertion away from the
+    /// hypotheses or variables it needs is reported by `validate`.
+    Move(Vec<StatementAddress>, StatementAddress),
+}
+
+/// The error type for `Transaction::apply`.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum TransactionError {
+    /// A label to rename does not exist
+    UnknownLabel(String),
+    /// The new name of a label is already used by another statement or by a
+    /// math symbol
+    LabelInUse(String),
+    /// The new name of a label is empty, or contains characters other than
+    /// letters, digits, `-`, `_` and `.`
+    InvalidLabel(String),
+    /// A math symbol to rename is not declared
+    UnknownSymbol(String),
+    /// The new name of a math symbol is already used by a symbol or a label
+    SymbolInUse(String),
+    /// The new name of a math symbol is empty, or contains `$`, whitespace or
+    /// characters outside of ASCII
+    InvalidSymbol(String),
+    /// A statement cannot be moved, or moved to the given place: it is the
+    /// end of a file or the target of the move itself
+    InvalidMove(StatementAddress),
+    /// After a move, the proof of the first statement would use the second
+    /// before its definition
+    ForwardReference(String, String),
+    /// A proof replacement targets a statement other than `$p`
+    NotProvable(StatementAddress),
+    /// Two changes edit the same text, given as file offsets
+    OverlappingEdits(Span, Span),
+    /// A change refers to a statement address which is stale or out of range
+    UnknownStatement(StatementAddress),
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TransactionError::UnknownLabel(ref label) => write!(f, "Unknown label {}", label),
+            TransactionError::LabelInUse(ref label) => write!(f, "Label {} is already in use", label),
+            TransactionError::InvalidLabel(ref label) => {
+                write!(f, "{:?} is not a valid label", label)
+            }
+            TransactionError::UnknownSymbol(ref symbol) => {
+                write!(f, "Unknown math symbol {}", symbol)
+            }
+            TransactionError::SymbolInUse(ref symbol) => {
+                write!(f, "Math symbol {} is already in use", symbol)
+            }
+            TransactionError::InvalidSymbol(ref symbol) => {
+                write!(f, "{:?} is not a valid math symbol", symbol)
+            }
+            TransactionError::InvalidMove(_) => write!(f, "Statement cannot be moved there"),
+            TransactionError::ForwardReference(ref user, ref used) => {
+                write!(f, "Proof of {} would use {} before its definition", user, used)
+            }
+            TransactionError::NotProvable(_) => write!(f, "Statement has no proof to replace"),
+            TransactionError::OverlappingEdits(a, b) => {
+                write!(f,
+                       "Edits {}..{} and {}..{} overlap",
+                       a.start,
+                       a.end,
+                       b.start,
+                       b.end)
+            }
+            TransactionError::UnknownStatement(_) => write!(f, "Statement is not loaded"),
+        }
+    }
+}
+
+impl error::Error for TransactionError {}
+
+/// Returns the text of a statement with some of its spans replaced.
+fn splice_statement(stmt: StatementRef, mut splices: Vec<(Span, &[u8])>) -> Vec<u8> {
+    let span = stmt.span();
+    let text = span.as_ref(&stmt.segment().segment.buffer);
+    splices.sort_by_key(|&(span, _)| span.start);
+    let mut out = Vec::with_capacity(text.len());
+    let mut pos = 0;
+    for (sub, new) in splices {
+        out.extend_from_slice(&text[pos..(sub.start - span.start) as usize]);
+        out.extend_from_slice(new);
+        pos = (sub.end - span.start) as usize;
+    }
+    out.extend_from_slice(&text[pos..]);
+    out
+}
+
+/// Finds the labels used in the proof of a statement, as their spans within
+/// the proof tokens.  In normal proofs a label may be preceded by a local
+/// label (`3:label`) or a hypothesis name (`hyp=label`); in compressed proofs
+/// only the label list is searched.
+fn proof_labels(stmt: StatementRef) -> Vec<Span> {
+    let mut out = Vec::new();
+    let compressed = stmt.proof_len() > 0 && stmt.proof_slice_at(0) == b"(";
+    for ix in 0..stmt.proof_len() {
+        let tok = stmt.proof_slice_at(ix);
+        if compressed {
+            if tok == b")" {
+                break;
+            } else if ix == 0 {
+                continue;
+            }
+        }
+        let start = if compressed {
+            0
+        } else {
+            tok.iter().rposition(|&ch| ch == b':' || ch == b'=').map_or(0, |pos| pos + 1)
+        };
+        let span = stmt.proof_span(ix);
+        out.push(Span::new(span.start as usize + start, span.end as usize));
+    }
+    out
+}
+
+/// Checks that no proof uses a statement defined after it once the given
+/// statements are moved before `target`.
+fn check_move(sset: &SegmentSet,
+              nset: &Nameset,
+              moved: &[StatementAddress],
+              target: StatementAddress)
+              -> Result<(), TransactionError> {
+    let moved_index: HashMap<StatementAddress, usize> =
+        moved.iter().enumerate().map(|(ix, &addr)| (addr, ix)).collect();
+    // position after the move: moved statements sit just before the target,
+    // in the order given
+    let position = |addr: StatementAddress| match moved_index.get(&addr) {
+        Some(&ix) => (target, 0, ix),
+        None => (addr, 1, 0),
+    };
+    let precedes = |a: (StatementAddress, usize, usize), b: (StatementAddress, usize, usize)| {
+        sset.order.cmp(&a.0, &b.0).then((a.1, a.2).cmp(&(b.1, b.2))) == Ordering::Less
+    };
+    for sref in sset.segments() {
+        for stmt in sref {
+            if stmt.statement_type() != StatementType::Provable {
+                continue;
+            }
+            let user_moved = moved_index.contains_key(&stmt.address());
+            for span in proof_labels(stmt) {
+                let label = span.as_ref(&sref.buffer);
+                let used = match nset.lookup_label(label) {
+                    Some(lookup) => lookup.address,
+                    None => continue,
+                };
+                if !user_moved && !moved_index.contains_key(&used) {
+                    continue;
+                }
+                if !precedes(position(used), position(stmt.address())) {
+                    return Err(TransactionError::ForwardReference(as_str(stmt.label())
+                                                                      .to_owned(),
+                                                                  as_str(label).to_owned()));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Translates renames of labels into statement edits.
+///
+/// Labels and math symbols share a namespace, so the new names must not be
+/// used by either, as for `symbol_rename_edits`.
+fn rename_edits(sset: &SegmentSet,
+                db: &mut Database,
+                renames: &[(String, String)],
+                out: &mut Vec<(StatementAddress, StatementEdit)>)
+                -> Result<(), TransactionError> {
+    let nset = db.name_result().clone();
+    let index = db.occurrence_index().clone();
+    let mut map: HashMap<&[u8], &[u8]> = new_map();
+    for rename in renames {
+        if db.statement(&rename.0).is_none() {
+            return Err(TransactionError::UnknownLabel(rename.0.clone()));
+        }
+        let new = rename.1.as_bytes();
+        if new.is_empty() || !is_valid_label(new) {
+            return Err(TransactionError::InvalidLabel(rename.1.clone()));
+        }
+        if db.statement(&rename.1).is_some() || nset.lookup_symbol(new).is_some() ||
+           !index.statements_using(new).is_empty() ||
+           renames.iter().filter(|other| other.1 == rename.1).count() > 1 {
+            return Err(TransactionError::LabelInUse(rename.1.clone()));
+        }
+        map.insert(rename.0.as_bytes(), rename.1.as_bytes());
+    }
+    for sref in sset.segments() {
+        for stmt in sref {
+            let mut splices = Vec::new();
+            if stmt.statement_type().takes_label() {
+                if let Some(new) = map.get(stmt.label()) {
+                    let start = stmt.span().start as usize;
+                    splices.push((Span::new(start, start + stmt.label().len()), *new));
+                }
+            }
+            let buf = &sref.buffer;
+            match stmt.statement_type() {
+                StatementType::Provable => {
+                    for span in proof_labels(stmt) {
+                        if let Some(new) = map.get(span.as_ref(buf)) {
+                            splices.push((span, *new));
+                        }
+                    }
+                }
+                StatementType::Comment => {
+                    for item in comment_items(stmt) {
+                        if let CommentItem::Label(span) = item {
+                            if let Some(new) = map.get(span.as_ref(buf)) {
+                                splices.push((span, *new));
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+            if !splices.is_empty() {
+                let text = splice_statement(stmt, splices);
+                out.push((stmt.address(), StatementEdit::Replace(text)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Translates the rename of a math symbol into statement edits.
+///
+/// Symbols and labels share a namespace, so the new name must not be used by
+/// either; local `$v` declarations are not in the nameset, so any use of the
+/// new name in a math string also counts.
+fn symbol_rename_edits(sset: &SegmentSet,
+                       db: &mut Database,
+                       old: &str,
+                       new: &str,
+                       out: &mut Vec<(StatementAddress, StatementEdit)>)
+                       -> Result<(), TransactionError> {
+    if new.is_empty() || new.bytes().any(|ch| ch <= b' ' || ch == b'$' || ch > b'~') {
+        return Err(TransactionError::InvalidSymbol(new.to_owned()));
+    }
+    let nset = db.name_result().clone();
+    let index = db.occurrence_index().clone();
+    let declared = |symbol: &str| {
+        nset.lookup_symbol(symbol.as_bytes()).is_some() ||
+        index.statements_using(symbol.as_bytes()).iter().any(|&addr| {
+            let stype = sset.statement(addr).statement_type();
+            stype == StatementType::Constant || stype == StatementType::Variable
+        })
+    };
+    if !declared(old) {
+        return Err(TransactionError::UnknownSymbol(old.to_owned()));
+    }
+    if declared(new) || nset.lookup_label(new.as_bytes()).is_some() ||
+       !index.statements_using(new.as_bytes()).is_empty() {
+        return Err(TransactionError::SymbolInUse(new.to_owned()));
+    }
+
+    let (old, new) = (old.as_bytes(), new.as_bytes());
+    let mut quoted: HashMap<u8, Vec<u8>> = new_map();
+    for &quote in b"\"'" {
+        let mut text = vec![quote];
+        for &ch in new {
+            text.push(ch);
+            if ch == quote {
+                text.push(ch);
+            }
+        }
+        text.push(quote);
+        quoted.insert(quote, text);
+    }
+    for sref in sset.segments() {
+        for stmt in sref {
+            let buf = &sref.buffer;
+            let mut splices: Vec<(Span, &[u8])> = Vec::new();
+            match stmt.statement_type() {
+                StatementType::Comment => {
+                    for item in comment_items(stmt) {
+                        if let CommentItem::MathToken(span) = item {
+                            if span.as_ref(buf) == old {
+                                splices.push((span, new));
+                            }
+                        }
+                    }
+                }
+                StatementType::TypesettingComment => {
+                    for (span, token) in definition_tokens(stmt) {
+                        if token == old {
+                            splices.push((span, &quoted[&span.as_ref(buf)[0]]));
+                        }
+                    }
+                }
+                _ => {
+                    for occ in occurrences_in(stmt, old) {
+                        splices.push((occ.span, new));
+                    }
+                }
+            }
+            if !splices.is_empty() {
+                let text = splice_statement(stmt, splices);
+                out.push((stmt.address(), StatementEdit::Replace(text)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Translates a change into statement edits against the current database.
+fn change_edits(sset: &SegmentSet,
+                db: &mut Database,
+                change: &Change,
+                out: &mut Vec<(StatementAddress, StatementEdit)>)
+                -> Result<(), TransactionError> {
+    match *change {
+        Change::Edit(addr, ref edit) => out.push((addr, edit.clone())),
+        Change::ReplaceProof(addr, ref proof) => {
+            let stmt = sset.statement_opt(addr).ok_or(TransactionError::UnknownStatement(addr))?;
+            if stmt.statement_type() != StatementType::Provable {
+                return Err(TransactionError::NotProvable(addr));
+            }
+            let span = stmt.span();
+            // everything from the first proof token up to the closing `$.`
+            let start = if stmt.proof_len() > 0 {
+                stmt.proof_span(0).start
+            } else {
+                span.end - 2
+            };
+            let mut new_proof = proof.trim().as_bytes().to_vec();
+            new_proof.push(b' ');
+            let old_proof = Span::new(start as usize, span.end as usize - 2);
+            let text = splice_statement(stmt, vec![(old_proof, &new_proof[..])]);
+            out.push((addr, StatementEdit::Replace(text)));
+        }
+        Change::Rename(ref old, ref new) => {
+            rename_edits(sset, db, &[(old.clone(), new.clone())], out)?;
+        }
+        Change::RenameAll(ref renames) => rename_edits(sset, db, renames, out)?,
+        Change::RenameSymbol(ref old, ref new) => symbol_rename_edits(sset, db, old, new, out)?,
+        Change::Move(ref moved, target) => {
+            for &addr in moved.iter().chain(Some(&target)) {
+                let stmt = sset.statement_opt(addr)
+                    .ok_or(TransactionError::UnknownStatement(addr))?;
+                let invalid = stmt.statement_type() == StatementType::Eof && addr != target;
+                if invalid || (addr == target && moved.contains(&target)) {
+                    return Err(TransactionError::InvalidMove(addr));
+                }
+            }
+            check_move(sset, &db.name_result().clone(), moved, target)?;
+            // the statements keep the whitespace before them, except that
+            // the whitespace of the first goes between the block and the target
+            let mut text = Vec::new();
+            let mut first_space = &b"\n"[..];
+            for (ix, &addr) in moved.iter().enumerate() {
+                let stmt = sset.statement(addr);
+                let full = stmt.span_full().as_ref(&stmt.segment().segment.buffer);
+                let lead = full.iter().position(|&ch| ch > b' ').unwrap_or(full.len());
+                if ix == 0 {
+                    if lead > 0 {
+                        first_space = &full[..lead];
+                    }
+                    text.extend_from_slice(&full[lead..]);
+                } else {
+                    text.extend_from_slice(full);
+                }
+                out.push((addr, StatementEdit::Delete));
+            }
+            text.extend_from_slice(first_space);
+            out.push((target, StatementEdit::InsertBefore(text)));
+        }
+    }
+    Ok(())
+}
+
+/// An atomic set of edits to a database; see the module documentation.
+pub struct Transaction<'a> {
+    db: &'a mut Database,
+    saved: Arc<SegmentSet>,
+    committed: bool,
+}
+
+impl<'a> Transaction<'a> {
+    /// Begins a transaction, remembering the current state of the database
+    /// to restore on rollback.
+    pub fn new(db: &'a mut Database) -> Transaction<'a> {
+        let saved = db.parse_result().clone();
+        Transaction {
+            db: db,
+            saved: saved,
+            committed: false,
+        }
+    }
+
+    /// Gives access to the database with the changes applied so far, for
+    /// example to look up statements for the next batch of changes.
+    pub fn database(&mut self) -> &mut Database {
+        self.db
+    }
+
+    /// Applies a batch of changes with a single reparse.
+    ///
+    /// Either all of the changes are applied or, if an error is returned,
+    /// none of them.
+    pub fn apply(&mut self, changes: &[Change]) -> Result<(), TransactionError> {
+        let sset = self.db.parse_result().clone();
+        let mut edits = Vec::new();
+        for change in changes {
+            change_edits(&sset, self.db, change, &mut edits)?;
+        }
+        self.db.edit_statements(&edits).map_err(|err| match err {
+            EmitError::OverlappingEdits(a, b) => TransactionError::OverlappingEdits(a, b),
+            EmitError::UnknownStatement(addr) => TransactionError::UnknownStatement(addr),
+            _ => unreachable!("statement edits only report overlaps and unknown statements"),
+        })
+    }
+
+    /// Checks the database with the changes applied so far, running the
+    /// parse, scope and verify passes and any others given, and returns all
+    /// of their findings.
+    pub fn validate(&mut self, extra: &[DiagnosticClass]) -> Vec<Notation> {
+        let mut classes = vec![DiagnosticClass::Parse,
+                               DiagnosticClass::Scope,
+                               DiagnosticClass::Verify];
+        classes.extend_from_slice(extra);
+        self.db.diag_notations(classes)
+    }
+
+    /// Ends the transaction, keeping the changes, and returns the name and
+    /// new text of each file which was changed.
+    pub fn commit(mut self) -> Vec<(String, Vec<u8>)> {
+        self.committed = true;
+        let mut old: HashMap<&str, &BufferRef> = new_map();
+        for sref in self.saved.segments() {
+            let sinfo = self.saved.source_info(sref.id);
+            old.insert(&sinfo.name, &sinfo.text);
+        }
+        let sset = self.db.parse_result().clone();
+        let mut out: Vec<(String, Vec<u8>)> = Vec::new();
+        for sref in sset.segments() {
+            let sinfo = sset.source_info(sref.id);
+            let changed = old.get(&sinfo.name[..]).is_none_or(|text| **text != sinfo.text);
+            if changed && !out.iter().any(|(name, _)| *name == sinfo.name) {
+                out.push((sinfo.name.clone(), sinfo.text.to_vec()));
+            }
+        }
+        out
+    }
+
+    /// Ends the transaction, restoring the database to its state before the
+    /// transaction began.
+    pub fn rollback(self) {}
+}
+
+impl<'a> Drop for Transaction<'a> {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.db.restore_parse_result(self.saved.clone());
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use database::Database;
+use database::DbOptions;
+use diag::DiagnosticClass;
+use parser::StatementAddress;
+use segment_set::StatementEdit;
+use test_fixtures::mkdb;
+use transaction::Change;
+use transaction::Transaction;
+use transaction::TransactionError;
+
+#[test]
+fn test_transaction() {
+    let text = b"$c |- wff ( -> ) $.\n$v ph ps $.\nwph $f wff ph $.\nwps $f wff ps $.\n\
+                 wi $a wff ( ph -> ps ) $.\nax-1 $a |- ( ph -> ( ps -> ph ) ) $.\n\
+                 th1 $p |- ( ph -> ( ph -> ph ) ) $= wph wph ax-1 $.\n\
+                 th2 $p |- ( ph -> ( ph -> ph ) ) $= ( ax-1 ) AAB $.\n";
+    let mut db = mkdb(text);
+    let th1 = db.statement("th1").unwrap().address();
+    let th2 = db.statement("th2").unwrap().address();
+
+    {
+        let mut tx = Transaction::new(&mut db);
+        tx.apply(&[Change::Rename("ax-1".to_owned(), "ax-simp".to_owned()),
+                   Change::Edit(th1, StatementEdit::InsertBefore(b"$( New. $)\n".to_vec()))])
+            .unwrap();
+        assert!(tx.validate(&[]).is_empty());
+        assert!(tx.database().statement("ax-1").is_none());
+        let th2 = tx.database().statement("th2").unwrap().address();
+        tx.apply(&[Change::ReplaceProof(th2, "wph wps ax-simp".to_owned())]).unwrap();
+        assert_eq!(tx.validate(&[]).len(), 1);
+        tx.rollback();
+    }
+    assert!(db.statement("ax-1").is_some());
+    assert!(db.diag_notations(vec![DiagnosticClass::Verify]).is_empty());
+
+    let mut tx = Transaction::new(&mut db);
+    assert_eq!(tx.apply(&[Change::Rename("ax-2".to_owned(), "ax-3".to_owned())]),
+               Err(TransactionError::UnknownLabel("ax-2".to_owned())));
+    assert_eq!(tx.apply(&[Change::ReplaceProof(StatementAddress::new(th2.segment_id, 0),
+                                               "?".to_owned())]),
+               Err(TransactionError::NotProvable(StatementAddress::new(th2.segment_id, 0))));
+    match tx.apply(&[Change::Edit(th1, StatementEdit::Delete),
+                     Change::Edit(th1, StatementEdit::InsertBefore(b"x".to_vec()))]) {
+        Err(TransactionError::OverlappingEdits(..)) => {}
+        _ => panic!("overlapping edits must be rejected"),
+    }
+    let missing = StatementAddress::new(th2.segment_id, 100);
+    assert_eq!(tx.apply(&[Change::ReplaceProof(missing, "?".to_owned())]),
+               Err(TransactionError::UnknownStatement(missing)));
+    assert_eq!(tx.apply(&[Change::Edit(missing, StatementEdit::Delete)]),
+               Err(TransactionError::UnknownStatement(missing)));
+    tx.apply(&[Change::ReplaceProof(th2, "wph wph ax-1".to_owned())]).unwrap();
+    tx.apply(&[Change::Rename("ax-1".to_owned(), "ax-simp".to_owned())]).unwrap();
+    assert!(tx.validate(&[]).is_empty());
+    let files = tx.commit();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].0, "test.mm");
+    let out = String::from_utf8(files[0].1.clone()).unwrap();
+    assert!(out.contains("ax-simp $a |- ( ph -> ( ps -> ph ) ) $.\n\
+                          th1 $p |- ( ph -> ( ph -> ph ) ) $= wph wph ax-simp $.\n\
+                          th2 $p |- ( ph -> ( ph -> ph ) ) $= wph wph ax-simp $.\n"));
+    assert!(db.statement("ax-simp").is_some());
+}
+
+#[test]
+fn test_rename_label() {
+    let a = b"$c |- wff ( -> ) ~ $.\n$v ph ps $.\nwph $f wff ph $.\nwps $f wff ps $.\n\
+              wi $a wff ( ph -> ps ) $.\n$( Axiom. $)\nax-1 $a |- ( ph -> ( ps -> ph ) ) $.\n\
+              $[ b.mm $]\n";
+    let b = b"$( Uses ~ ax-1 , not ` ~ ax-1 ` or ~ ax-10 . $)\n\
+              th1 $p |- ( ph -> ( ph -> ph ) ) $= ( ax-1 ) AAB $.\n";
+    let mut db = Database::new(DbOptions::default());
+    db.parse("a.mm".to_owned(),
+             vec![("a.mm".to_owned(), a.to_vec()), ("b.mm".to_owned(), b.to_vec())]);
+    assert_eq!(db.rename_label("ax-2", "ax-3"),
+               Err(TransactionError::UnknownLabel("ax-2".to_owned())));
+    assert_eq!(db.rename_label("ax-1", "wi"),
+               Err(TransactionError::LabelInUse("wi".to_owned())));
+    assert_eq!(db.rename_label("ax-1", "ph"),
+               Err(TransactionError::LabelInUse("ph".to_owned())));
+    for &new in &["", "foo bar", "a$b"] {
+        assert_eq!(db.rename_label("ax-1", new),
+                   Err(TransactionError::InvalidLabel(new.to_owned())));
+    }
+
+    let changed = db.rename_label("ax-1", "ax-simp").unwrap();
+    let sset = db.parse_result().clone();
+    let names: Vec<&str> = changed.iter().map(|&id| &sset.source_info(id).name[..]).collect();
+    assert_eq!(names, vec!["a.mm", "b.mm"]);
+    assert!(db.statement("ax-simp").is_some());
+    assert!(db.diag_notations(vec![DiagnosticClass::Verify]).is_empty());
+    let mut out = Vec::new();
+    db.write_mm(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("$( Uses ~ ax-simp , not ` ~ ax-1 ` or ~ ax-10 . $)\n\
+                          th1 $p |- ( ph -> ( ph -> ph ) ) $= ( ax-simp ) AAB $.\n"));
+}
+
+#[test]
+fn test_rename_math_symbol() {
+    let text = b"$( $t htmldef \"->\" as \" &rarr; \"; htmldef '<->' as ' &harr; '; $)\n\
+                 $c |- wff ( -> <-> ) $.\n$v ph ps $.\nwph $f wff ph $.\nwps $f wff ps $.\n\
+                 wi $a wff ( ph -> ps ) $.\nwb $a wff ( ph <-> ps ) $.\n\
+                 $( Implication ` ( ph -> ps ) ` is not ` <-> ` . $)\n\
+                 ax-1 $a |- ( ph -> ( ps -> ph ) ) $.\n";
+    let mut db = mkdb(text);
+    assert_eq!(db.rename_math_symbol("=>", "->>"),
+               Err(TransactionError::UnknownSymbol("=>".to_owned())));
+    assert_eq!(db.rename_math_symbol("->", "<->"),
+               Err(TransactionError::SymbolInUse("<->".to_owned())));
+    assert_eq!(db.rename_math_symbol("->", "wi"),
+               Err(TransactionError::SymbolInUse("wi".to_owned())));
+    assert_eq!(db.rename_math_symbol("->", "= >"),
+               Err(TransactionError::InvalidSymbol("= >".to_owned())));
+
+    assert_eq!(db.rename_math_symbol("->", "=>").unwrap().len(), 1);
+    assert!(db.diag_notations(vec![DiagnosticClass::Parse,
+                                   DiagnosticClass::Scope,
+                                   DiagnosticClass::Verify])
+        .is_empty());
+    let mut out = Vec::new();
+    db.write_mm(&mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "$( $t htmldef \"=>\" as \" &rarr; \"; htmldef '<->' as ' &harr; '; $)\n\
+                $c |- wff ( => <-> ) $.\n$v ph ps $.\nwph $f wff ph $.\nwps $f wff ps $.\n\
+                wi $a wff ( ph => ps ) $.\nwb $a wff ( ph <-> ps ) $.\n\
+                $( Implication ` ( ph => ps ) ` is not ` <-> ` . $)\n\
+                ax-1 $a |- ( ph => ( ps => ph ) ) $.\n");
+    assert_eq!(db.typesetting_result().html_def(b"=>"), Some(" &rarr; "));
+}
+
+#[test]
+fn test_move_statements() {
+    let text = b"$c |- wff ( -> ) $.\n$v ph ps $.\nwph $f wff ph $.\nwps $f wff ps $.\n\
+                 wi $a wff ( ph -> ps ) $.\n\n$( =-=-=-\n  Axioms\n=-=-=- $)\n\
+                 ax-1 $a |- ( ph -> ( ps -> ph ) ) $.\n\n$( =-=-=-\n  Theorems\n=-=-=- $)\n\
+                 $( A theorem. $)\nth1 $p |- ( ph -> ( ph -> ph ) ) $= ( ax-1 ) AAB $.\n";
+    let mut db = mkdb(text);
+    let outline = db.outline().clone();
+    let sset = db.parse_result().clone();
+    assert_eq!(outline.node(2).title, "Theorems");
+    let theorems = outline.section_statements(&sset, 2);
+    assert_eq!(theorems.len(), 3);
+    let axioms = outline.node(1).heading.unwrap();
+    let th1 = db.statement("th1").unwrap().address();
+    let wi = db.statement("wi").unwrap().address();
+    let ax_1 = db.statement("ax-1").unwrap().address();
+    assert_eq!(db.move_statements(&theorems, axioms),
+               Err(TransactionError::ForwardReference("th1".to_owned(), "ax-1".to_owned())));
+    assert_eq!(db.move_statements(&[th1, ax_1], wi),
+               Err(TransactionError::ForwardReference("th1".to_owned(), "ax-1".to_owned())));
+    assert_eq!(db.move_statements(&[th1], th1),
+               Err(TransactionError::InvalidMove(th1)));
+
+    let files = db.move_statements(&[ax_1], wi).unwrap();
+    assert_eq!(files.len(), 1);
+    let out = String::from_utf8(files[0].1.clone()).unwrap();
+    assert!(out.contains("wps $f wff ps $.\nax-1 $a |- ( ph -> ( ps -> ph ) ) $.\n\
+                          wi $a wff ( ph -> ps ) $.\n\n$( =-=-=-\n  Axioms\n=-=-=- $)\n\n$( =-=-=-"));
+    assert!(db.diag_notations(vec![DiagnosticClass::Parse,
+                                   DiagnosticClass::Scope,
+                                   DiagnosticClass::Verify])
+        .is_empty());
+}
@@ -0,0 +1,396 @@
+//! Extraction of typesetting information from `$t` comments.
+//!
+//! Typesetting comments contain a small command language used by the HTML and
+//! LaTeX generators of metamath.exe; for instance `htmldef "->" as " &rarr; ";`
+//! says how to render the `->` token on HTML pages.  We collect the
+//! `htmldef`, `althtmldef` and `latexdef` commands into per-token maps, and
+//! keep the string arguments of any other command (`htmltitle`, `htmlcss`,
+//! etc.) in a generic settings table keyed by command name.
+//!
+//! The grammar is:
+//!
+//! ```text
+//! command := keyword (string ('+' string)* | word)* ';'
+//! string := '"' ... '"' | "'" ... "'"
+//! ```
+//!
+//! A quote character is included in a string by doubling it, and C-style
+//! `/* */` comments may appear between tokens.  Malformed commands are skipped
+//! up to the next `;`.  The same language is used by `$j` comments, so the
+//! lexer is shared with `extra_info`.
+
+use parser::Span;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::Token;
+use parser::as_str;
+use segment_set::SegmentSet;
+use util::HashMap;
+use util::new_map;
+
+/// Lexical elements of the typesetting command language.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Lexeme {
+    /// A bare word such as a command keyword or `as`.
+    Word(Vec<u8>),
+    /// A quoted string, with doubled quotes already collapsed.
+    Str(Vec<u8>),
+    /// The `+` concatenation operator.
+    Plus,
+    /// The `;` command terminator.
+    Semi,
+}
+
+/// Splits the body of a `$t` or `$j` comment into lexemes.
+pub fn lex(text: &[u8]) -> Vec<Lexeme> {
+    lex_spans(text).into_iter().map(|(_, lex)| lex).collect()
+}
+
+/// Splits the body of a `$t` or `$j` comment into lexemes, each with its span
+/// in the body.
+pub fn lex_spans(text: &[u8]) -> Vec<(Span, Lexeme)> {
+    let mut out = Vec::new();
+    let mut ix = 0;
+    while ix < text.len() {
+        let ch = text[ix];
+        let start = ix;
+        if ch <= 32 {
+            ix += 1;
+        } else if text[ix..].starts_with(b"/*") {
+            ix += 2;
+            while ix < text.len() && !text[ix..].starts_with(b"*/") {
+                ix += 1;
+            }
+            ix += 2;
+        } else if ch == b'+' {
+            ix += 1;
+            out.push((Span::new(start, ix), Lexeme::Plus));
+        } else if ch == b';' {
+            ix += 1;
+            out.push((Span::new(start, ix), Lexeme::Semi));
+        } else if ch == b'"' || ch == b'\'' {
+            let mut buf = Vec::new();
+            ix += 1;
+            while ix < text.len() {
+                if text[ix] == ch {
+                    if ix + 1 < text.len() && text[ix + 1] == ch {
+                        buf.push(ch);
+                        ix += 2;
+                    } else {
+                        ix += 1;
+                        break;
+                    }
+                } else {
+                    buf.push(text[ix]);
+                    ix += 1;
+                }
+            }
+            out.push((Span::new(start, ix), Lexeme::Str(buf)));
+        } else {
+            while ix < text.len() && text[ix] > 32 && text[ix] != b';' && text[ix] != b'+' &&
+                  text[ix] != b'"' && text[ix] != b'\'' {
+                ix += 1;
+            }
+            out.push((Span::new(start, ix), Lexeme::Word(text[start..ix].to_owned())));
+        }
+    }
+    out
+}
+
+/// Concatenates a `string ('+' string)*` sequence, returning `None` if the
+/// command is malformed.
+pub fn concat(args: &[Lexeme]) -> Option<String> {
+    let mut buf = Vec::new();
+    let mut want_str = true;
+    for lex in args {
+        match (want_str, lex) {
+            (true, Lexeme::Str(s)) => buf.extend_from_slice(s),
+            (false, Lexeme::Plus) => {}
+            _ => return None,
+        }
+        want_str = !want_str;
+    }
+    if want_str {
+        None
+    } else {
+        Some(String::from_utf8_lossy(&buf).into_owned())
+    }
+}
+
+/// Splits a comment statement which starts with the given marker (`$t` or
+/// `$j`) into commands, each a list of lexemes without the terminating `;`.
+pub fn comment_commands(stmt: StatementRef, marker: &[u8]) -> Vec<Vec<Lexeme>> {
+    comment_command_spans(stmt, marker).into_iter().map(|(_, command)| command).collect()
+}
+
+/// Splits a comment statement like `comment_commands`, returning each command
+/// with its span in the segment buffer, from its first lexeme to its last.
+pub fn comment_command_spans(stmt: StatementRef, marker: &[u8]) -> Vec<(Span, Vec<Lexeme>)> {
+    let (base, body) = match comment_body(stmt, marker) {
+        Some(body) => body,
+        None => return Vec::new(),
+    };
+    lex_spans(body)
+        .split(|(_, lex)| *lex == Lexeme::Semi)
+        .filter(|command| !command.is_empty())
+        .map(|command| {
+            let span = Span::new(base + command[0].0.start as usize,
+                                 base + command[command.len() - 1].0.end as usize);
+            (span, command.iter().map(|(_, lex)| lex.clone()).collect())
+        })
+        .collect()
+}
+
+/// Returns the text of a comment statement after the marker and before the
+/// closing `$)`, with its offset in the segment buffer.
+fn comment_body<'a>(stmt: StatementRef<'a>, marker: &[u8]) -> Option<(usize, &'a [u8])> {
+    let span = stmt.span();
+    let text = span.as_ref(&stmt.segment().segment.buffer);
+    // strip the `$(` and `$)` delimiters, then the marker
+    let body = &text[2..text.len() - 2];
+    body.windows(marker.len()).position(|w| w == marker).map(|pos| {
+        let start = pos + marker.len();
+        (span.start as usize + 2 + start, &body[start..])
+    })
+}
+
+/// Finds the math tokens defined by the `htmldef`, `althtmldef` and
+/// `latexdef` commands of a `$t` comment statement, with the spans of their
+/// quoted strings in the segment buffer, quotes included.
+pub fn definition_tokens(stmt: StatementRef) -> Vec<(Span, Vec<u8>)> {
+    let (base, body) = match comment_body(stmt, b"$t") {
+        Some(body) => body,
+        None => return Vec::new(),
+    };
+    let lexemes = lex_spans(body);
+    let mut out = Vec::new();
+    for command in lexemes.split(|(_, lex)| *lex == Lexeme::Semi) {
+        match command {
+            [(_, Lexeme::Word(keyword)), (span, Lexeme::Str(token)), ..]
+                if &keyword[..] == b"htmldef" || &keyword[..] == b"althtmldef" ||
+                   &keyword[..] == b"latexdef" => {
+                let span = Span::new(base + span.start as usize, base + span.end as usize);
+                out.push((span, token.clone()));
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// How to render math tokens as text.
+#[derive(Copy,Clone,Debug)]
+pub enum TypesettingMode<'a> {
+    /// The tokens themselves separated by single spaces, as in a canonically
+    /// formatted source file.
+    Ascii,
+    /// HTML using the `htmldef` strings.
+    Html(&'a TypesettingData),
+    /// HTML using the `althtmldef` strings.
+    AltHtml(&'a TypesettingData),
+    /// LaTeX using the `latexdef` strings.
+    Latex(&'a TypesettingData),
+}
+
+impl<'a> TypesettingMode<'a> {
+    /// Renders a sequence of math tokens.
+    pub fn render<'b, I>(&self, tokens: I) -> String
+        where I: IntoIterator<Item = &'b [u8]>
+    {
+        match *self {
+            TypesettingMode::Ascii => {
+                let tokens: Vec<&str> = tokens.into_iter().map(as_str).collect();
+                tokens.join(" ")
+            }
+            TypesettingMode::Html(data) => data.render_html(tokens),
+            TypesettingMode::AltHtml(data) => data.render_alt_html(tokens),
+            TypesettingMode::Latex(data) => data.render_latex(tokens),
+        }
+    }
+}
+
+/// Typesetting definitions collected from all `$t` comments in a database.
+///
+/// Later definitions for the same token override earlier ones, matching the
+/// behavior of metamath.exe.
+#[derive(Clone,Debug,Default)]
+pub struct TypesettingData {
+    html_defs: HashMap<Token, String>,
+    alt_html_defs: HashMap<Token, String>,
+    latex_defs: HashMap<Token, String>,
+    settings: HashMap<Token, String>,
+}
+
+impl TypesettingData {
+    /// Collects typesetting data from every `$t` comment in the database, in
+    /// database order.
+    pub fn new(sset: &SegmentSet) -> TypesettingData {
+        let mut data = TypesettingData {
+            html_defs: new_map(),
+            alt_html_defs: new_map(),
+            latex_defs: new_map(),
+            settings: new_map(),
+        };
+        for segment in sset.segments() {
+            for stmt in segment {
+                if stmt.statement_type() == StatementType::TypesettingComment {
+                    data.add_comment(stmt);
+                }
+            }
+        }
+        data
+    }
+
+    /// Parses the commands of a single `$t` comment statement.
+    fn add_comment(&mut self, stmt: StatementRef) {
+        for command in comment_commands(stmt, b"$t") {
+            self.add_command(&command);
+        }
+    }
+
+    fn add_command(&mut self, command: &[Lexeme]) {
+        let keyword = match command.first() {
+            Some(Lexeme::Word(word)) => word,
+            _ => return,
+        };
+        let map = match &keyword[..] {
+            b"htmldef" => &mut self.html_defs,
+            b"althtmldef" => &mut self.alt_html_defs,
+            b"latexdef" => &mut self.latex_defs,
+            _ => {
+                if let Some(value) = concat(&command[1..]) {
+                    self.settings.insert(keyword.clone().into_boxed_slice(), value);
+                }
+                return;
+            }
+        };
+        if command.len() < 4 || command[2] != Lexeme::Word(b"as".to_vec()) {
+            return;
+        }
+        if let (Lexeme::Str(token), Some(value)) = (&command[1], concat(&command[3..])) {
+            map.insert(token.clone().into_boxed_slice(), value);
+        }
+    }
+
+    /// Returns the `htmldef` string for a math token, if one was given.
+    pub fn html_def(&self, token: &[u8]) -> Option<&str> {
+        self.html_defs.get(token).map(|s| &s[..])
+    }
+
+    /// Returns the `althtmldef` string for a math token, if one was given.
+    pub fn alt_html_def(&self, token: &[u8]) -> Option<&str> {
+        self.alt_html_defs.get(token).map(|s| &s[..])
+    }
+
+    /// Returns the `latexdef` string for a math token, if one was given.
+    pub fn latex_def(&self, token: &[u8]) -> Option<&str> {
+        self.latex_defs.get(token).map(|s| &s[..])
+    }
+
+    /// Returns true if some math token has an `althtmldef` string.
+    pub fn has_alt_html(&self) -> bool {
+        !self.alt_html_defs.is_empty()
+    }
+
+    /// Returns the argument of any other typesetting command, such as
+    /// `htmltitle` or `htmlcss`.
+    pub fn setting(&self, keyword: &[u8]) -> Option<&str> {
+        self.settings.get(keyword).map(|s| &s[..])
+    }
+
+    /// Renders a sequence of math tokens as HTML using the `htmldef` strings.
+    ///
+    /// Tokens without a definition are rendered as their escaped text,
+    /// followed by a space.
+    pub fn render_html<'b, I>(&self, tokens: I) -> String
+        where I: IntoIterator<Item = &'b [u8]>
+    {
+        render_html_with(&self.html_defs, tokens)
+    }
+
+    /// Renders a sequence of math tokens as HTML using the `althtmldef`
+    /// strings (typically Unicode rather than GIF images).
+    pub fn render_alt_html<'b, I>(&self, tokens: I) -> String
+        where I: IntoIterator<Item = &'b [u8]>
+    {
+        render_html_with(&self.alt_html_defs, tokens)
+    }
+
+    /// Renders a sequence of math tokens as LaTeX using the `latexdef`
+    /// strings, separated by spaces.
+    ///
+    /// Tokens without a definition are rendered in `\mathrm{}` with LaTeX
+    /// special characters escaped.
+    pub fn render_latex<'b, I>(&self, tokens: I) -> String
+        where I: IntoIterator<Item = &'b [u8]>
+    {
+        let mut out = String::new();
+        for token in tokens {
+            if !out.is_empty() {
+                out.push(' ');
+            }
+            match self.latex_defs.get(token) {
+                Some(def) => out.push_str(def),
+                None => {
+                    out.push_str("\\mathrm{");
+                    for ch in String::from_utf8_lossy(token).chars() {
+                        match ch {
+                            '#' | '$' | '%' | '&' | '_' | '{' | '}' => {
+                                out.push('\\');
+                                out.push(ch);
+                            }
+                            '\\' => out.push_str("\\backslash "),
+                            '^' => out.push_str("\\hat{}"),
+                            '~' => out.push_str("\\sim "),
+                            _ => out.push(ch),
+                        }
+                    }
+                    out.push('}');
+                }
+            }
+        }
+        out
+    }
+
+    /// Renders the math string of a statement, including its typecode, as
+    /// HTML.
+    pub fn statement_as_html(&self, stmt: StatementRef) -> String {
+        self.render_html(stmt.math_iter().map(|t| t.slice))
+    }
+
+    /// Renders the math string of a statement, including its typecode, as
+    /// HTML using the alternate definitions.
+    pub fn statement_as_alt_html(&self, stmt: StatementRef) -> String {
+        self.render_alt_html(stmt.math_iter().map(|t| t.slice))
+    }
+
+    /// Renders the math string of a statement, including its typecode, as
+    /// LaTeX.
+    pub fn statement_as_latex(&self, stmt: StatementRef) -> String {
+        self.render_latex(stmt.math_iter().map(|t| t.slice))
+    }
+}
+
+fn render_html_with<'b, I>(defs: &HashMap<Token, String>, tokens: I) -> String
+    where I: IntoIterator<Item = &'b [u8]>
+{
+    let mut out = String::new();
+    for token in tokens {
+        match defs.get(token) {
+            Some(def) => out.push_str(def),
+            None => {
+                for ch in String::from_utf8_lossy(token).chars() {
+                    match ch {
+                        '<' => out.push_str("&lt;"),
+                        '>' => out.push_str("&gt;"),
+                        '&' => out.push_str("&amp;"),
+                        '"' => out.push_str("&quot;"),
+                        _ => out.push(ch),
+                    }
+                }
+                out.push(' ');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,67 @@
+use formula::Formula;
+use test_fixtures::mkdb;
+use typesetting::TypesettingMode;
+
+const TYPESETTING: &[u8] = b"$c |- ( ) -> wff $.
+$v ph ps $.
+$( $t
+  /* comment */ htmldef \"->\" as ' &rarr; ';
+  htmldef \"(\" as \"(\"; htmldef \")\" as \")\";
+  althtmldef \"->\" as '<span>' + '&#8594;' + \"</span>\";
+  latexdef \"->\" as \"\\rightarrow\";
+  latexdef \"|-\" as \"\\vdash\";
+  htmltitle \"Test \"\"quoted\"\"\";
+  htmldef \"ph\" bogus;
+$)
+wph $f wff ph $.
+wps $f wff ps $.
+ax $a |- ( ph -> ps ) $.
+";
+
+#[test]
+fn test_typesetting_defs() {
+    let mut db = mkdb(TYPESETTING);
+    let ts = db.typesetting_result().clone();
+    assert_eq!(ts.html_def(b"->"), Some(" &rarr; "));
+    assert_eq!(ts.alt_html_def(b"->"), Some("<span>&#8594;</span>"));
+    assert_eq!(ts.latex_def(b"|-"), Some("\\vdash"));
+    assert_eq!(ts.setting(b"htmltitle"), Some("Test \"quoted\""));
+    assert_eq!(ts.html_def(b"ph"), None);
+}
+
+#[test]
+fn test_typesetting_render() {
+    let mut db = mkdb(TYPESETTING);
+    let ts = db.typesetting_result().clone();
+    let stmt = db.statement("ax").unwrap();
+    assert_eq!(ts.statement_as_latex(stmt),
+               "\\vdash \\mathrm{(} \\mathrm{ph} \\rightarrow \\mathrm{ps} \\mathrm{)}");
+    assert_eq!(ts.statement_as_html(stmt), "|- (ph  &rarr; ps )");
+}
+
+#[test]
+fn test_formula_to_string_with() {
+    let mut db = mkdb(TYPESETTING);
+    let ts = db.typesetting_result().clone();
+    let formula = Formula::from_statement(db.statement("ax").unwrap());
+    assert_eq!(formula.to_tokens().len(), 6);
+    assert_eq!(formula.to_string_with(&TypesettingMode::Ascii), "|- ( ph -> ps )");
+    assert_eq!(formula.to_string_with(&TypesettingMode::Ascii), formula.to_string());
+    assert_eq!(formula.to_string_with(&TypesettingMode::AltHtml(&ts)),
+               "|- ( ph <span>&#8594;</span>ps ) ");
+    assert_eq!(formula.to_alt_html(&ts), formula.to_string_with(&TypesettingMode::AltHtml(&ts)));
+    assert_eq!(formula.to_html(&ts), ts.statement_as_html(db.statement("ax").unwrap()));
+    assert_eq!(formula.to_latex(&ts), ts.statement_as_latex(db.statement("ax").unwrap()));
+    assert_eq!(formula.to_string_with(&TypesettingMode::Latex(&ts)),
+               ts.statement_as_latex(db.statement("ax").unwrap()));
+}
+
+#[test]
+fn test_typesetting_unicode_fallback() {
+    // the parser only accepts ASCII, but formulas can be built by clients
+    let mut db = mkdb(TYPESETTING);
+    let ts = db.typesetting_result().clone();
+    let formula = Formula::from_expr(b"|-", "\u{2192} <\u{e9}>".as_bytes());
+    assert_eq!(formula.to_html(&ts), "|- \u{2192} &lt;\u{e9}&gt; ");
+    assert_eq!(formula.to_latex(&ts), "\\vdash \\mathrm{\u{2192}} \\mathrm{<\u{e9}>}");
+}
@@ -0,0 +1,188 @@
+//! Dependency tracing between assertions, as done by metamath.exe's
+//! `show trace_back`.
+//!
+//! Dependencies are read directly from the proof text of each `$p` statement
+//! rather than by running the verifier, so incomplete proofs are traced as far
+//! as they go, and a proof which references a label that does not resolve to
+//! an assertion is reported rather than aborting the trace.
+//!
+//! The reverse direction, which theorems use a given statement, is answered
+//! by `UsageIndex`, built once for the whole database.
+
+use parser::Comparer;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::Token;
+use parser::TokenIndex;
+use parser::TokenPtr;
+use parser::copy_token;
+use scopeck::Hyp;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use util::HashMap;
+use util::HashSet;
+use util::new_set;
+
+/// The transitive dependencies of an assertion.
+///
+/// All address lists are in database order.
+#[derive(Clone,Debug,Default)]
+pub struct Usage {
+    /// `$a` statements used directly or indirectly, including syntax axioms
+    /// and definitions.
+    pub axioms: Vec<StatementAddress>,
+    /// `$p` statements used directly or indirectly, not including the traced
+    /// statement itself.
+    pub theorems: Vec<StatementAddress>,
+    /// `$p` statements in the closure (possibly including the traced
+    /// statement) whose proofs contain `?` steps.
+    pub incomplete: Vec<StatementAddress>,
+    /// Labels referenced from proofs in the closure which are neither
+    /// assertions nor hypotheses in scope.
+    pub unresolved: Vec<Token>,
+}
+
+/// Extracts the labels referenced by a proof, in either normal or compressed
+/// format.
+///
+/// For normal proofs, packed-format local labels (`3:label`) and explicit
+/// hypothesis names (`hyp=label`) are stripped, and backreferences to local
+/// labels are dropped.  Returns the labels with the indices of the proof
+/// tokens they were found in, and whether the proof is incomplete.
+pub fn proof_labels<'a>(stmt: StatementRef<'a>) -> (Vec<(TokenIndex, TokenPtr<'a>)>, bool) {
+    let mut labels = Vec::new();
+    let mut incomplete = false;
+    if stmt.proof_len() > 0 && stmt.proof_slice_at(0) == b"(" {
+        let mut i = 1;
+        while i < stmt.proof_len() {
+            let tok = stmt.proof_slice_at(i);
+            i += 1;
+            if tok == b")" {
+                break;
+            }
+            labels.push((i - 1, tok));
+        }
+        while i < stmt.proof_len() {
+            if stmt.proof_slice_at(i).contains(&b'?') {
+                incomplete = true;
+            }
+            i += 1;
+        }
+    } else {
+        let mut locals: Vec<TokenPtr> = Vec::new();
+        for i in 0..stmt.proof_len() {
+            let mut tok = stmt.proof_slice_at(i);
+            if let Some(n) = tok.iter().position(|&x| x == b'=') {
+                tok = &tok[n + 1..];
+            }
+            if let Some(m) = tok.iter().position(|&x| x == b':') {
+                locals.push(&tok[..m]);
+                tok = &tok[m + 1..];
+            }
+            if tok == b"?" {
+                incomplete = true;
+            } else if !locals.contains(&tok) {
+                labels.push((i, tok));
+            }
+        }
+    }
+    (labels, incomplete)
+}
+
+/// Computes the transitive closure of assertions used by the proof of a
+/// statement.
+///
+/// Hypotheses are not reported; a statement which is not a `$p` has an empty
+/// closure.
+pub fn trace_usage(sset: &SegmentSet, scope: &ScopeResult, stmt: StatementRef) -> Usage {
+    let mut usage = Usage::default();
+    let mut seen: HashSet<StatementAddress> = new_set();
+    let mut unresolved: HashSet<Token> = new_set();
+    let mut queue = vec![stmt.address()];
+    seen.insert(stmt.address());
+
+    while let Some(addr) = queue.pop() {
+        let cur = sset.statement(addr);
+        if cur.statement_type() != StatementType::Provable {
+            continue;
+        }
+        let (labels, incomplete) = proof_labels(cur);
+        if incomplete {
+            usage.incomplete.push(addr);
+        }
+        for (_, label) in labels {
+            match scope.get(label) {
+                Some(frame) => {
+                    let dep = frame.valid.start;
+                    if !seen.insert(dep) {
+                        continue;
+                    }
+                    match frame.stype {
+                        StatementType::Axiom => usage.axioms.push(dep),
+                        StatementType::Provable => {
+                            usage.theorems.push(dep);
+                            queue.push(dep);
+                        }
+                        _ => {}
+                    }
+                }
+                None => {
+                    // $e hypotheses have no frame; only complain about labels
+                    // which are not hypotheses of the statement being traced
+                    let is_hyp = scope.get(cur.label()).is_some_and(|frame| {
+                        frame.hypotheses.iter().any(|hyp| match *hyp {
+                            Hyp::Essential(haddr, _) => sset.statement(haddr).label() == label,
+                            Hyp::Floating(..) => false,
+                        })
+                    });
+                    if !is_hyp && unresolved.insert(copy_token(label)) {
+                        usage.unresolved.push(copy_token(label));
+                    }
+                }
+            }
+        }
+    }
+
+    usage.axioms.sort_by(|x, y| sset.order.cmp(x, y));
+    usage.theorems.sort_by(|x, y| sset.order.cmp(x, y));
+    usage.incomplete.sort_by(|x, y| sset.order.cmp(x, y));
+    usage
+}
+
+/// Analysis pass result mapping each statement to the theorems whose proofs
+/// use it directly.
+#[derive(Clone,Debug,Default)]
+pub struct UsageIndex {
+    users: HashMap<StatementAddress, Vec<StatementAddress>>,
+}
+
+impl UsageIndex {
+    /// Reads the proofs of all `$p` statements to build the index.
+    pub fn new(sset: &SegmentSet, scope: &ScopeResult) -> UsageIndex {
+        let mut index = UsageIndex::default();
+        for sref in sset.segments() {
+            for stmt in sref {
+                if stmt.statement_type() != StatementType::Provable {
+                    continue;
+                }
+                for (_, label) in proof_labels(stmt).0 {
+                    if let Some(frame) = scope.get(label) {
+                        let users = index.users.entry(frame.valid.start).or_insert_with(Vec::new);
+                        // proofs are read one at a time, so a repeated use is last
+                        if users.last() != Some(&stmt.address()) {
+                            users.push(stmt.address());
+                        }
+                    }
+                }
+            }
+        }
+        index
+    }
+
+    /// Returns the theorems whose proofs use an assertion or `$f` hypothesis
+    /// directly, in database order.
+    pub fn users_of(&self, address: StatementAddress) -> &[StatementAddress] {
+        self.users.get(&address).map_or(&[], |users| &users[..])
+    }
+}
@@ -0,0 +1,47 @@
+use database::Database;
+use parser::StatementAddress;
+use parser::as_str;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_usages_of() {
+    let text = format!("{}th1 $p |- ( ph -> ( ps -> ph ) ) $= wph wps ax-1 $.
+${{ h1 $e |- ph $. th2 $p |- ( ps -> ph ) $= wph wps wph wi h1 wph wps ax-1 ax-mp $. $}}\n",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let th1 = db.statement("th1").unwrap().address();
+    let th2 = db.statement("th2").unwrap().address();
+    assert_eq!(db.usages_of("ax-1"), &[th1, th2]);
+    assert_eq!(db.usages_of("wi"), &[th2]);
+    assert_eq!(db.usages_of("wph"), &[th1, th2]);
+    assert!(db.usages_of("th2").is_empty());
+    assert!(db.usages_of("th3").is_empty());
+
+    let text = format!("{}th3 $p |- ( ps -> ( ph -> ps ) ) $= wps wph ax-1 $.\n", text);
+    db.parse("test.mm".to_owned(), vec![("test.mm".to_owned(), text.into_bytes())]);
+    let th3 = db.statement("th3").unwrap().address();
+    assert_eq!(db.usages_of("ax-1"), &[th1, th2, th3]);
+}
+
+#[test]
+fn test_usage() {
+    let text = format!("{}th1 $p |- ( ph -> ( ps -> ph ) ) $= wph wps ax-1 $.
+${{ th2.1 $e |- ph $. th2 $p |- ( ps -> ph ) $= wph wps wi wph wps th1 th2.1 ax-mp $. $}}
+th3 $p |- ph $= ? $.
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let usage = db.usage("th2").unwrap();
+    let labels = |addrs: &[StatementAddress], db: &mut Database| {
+        addrs.iter()
+            .map(|&a| as_str(db.parse_result().statement(a).label()).to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(labels(&usage.axioms, &mut db), vec!["wi", "ax-mp", "ax-1"]);
+    assert_eq!(labels(&usage.theorems, &mut db), vec!["th1"]);
+    assert!(usage.incomplete.is_empty());
+    assert!(usage.unresolved.is_empty());
+    let usage = db.usage("th3").unwrap();
+    assert_eq!(labels(&usage.incomplete, &mut db), vec!["th3"]);
+}
@@ -26,6 +26,7 @@
 
 use bit_set::Bitset;
 use diag::Diagnostic;
+use formula::Formula;
 use nameck::Atom;
 use nameck::Nameset;
 use parser;
@@ -40,6 +41,7 @@ use parser::Span;
 use parser::StatementAddress;
 use parser::StatementRef;
 use parser::StatementType;
+use parser::Token;
 use parser::TokenPtr;
 use scopeck;
 use scopeck::ExprFragment;
@@ -48,13 +50,17 @@ use scopeck::Hyp::*;
 use scopeck::ScopeReader;
 use scopeck::ScopeResult;
 use scopeck::ScopeUsage;
+use scopeck::VarIndex;
 use scopeck::VerifyExpr;
+use scopeck::unpack_symbols;
 use segment_set::SegmentSet;
 use std::cmp::Ordering;
 use std::mem;
 use std::ops::Range;
 use std::result;
 use std::sync::Arc;
+#[cfg(feature = "stats")]
+use std::time::Instant;
 use std::u32;
 use std::usize;
 use util::copy_portion;
@@ -74,12 +80,87 @@ macro_rules! try_assert {
     }
 }
 
+/// Options for verifying a single proof: limits on the resources used, and
+/// whether to go on after an error.
+///
+/// Services which check databases from untrusted sources can set the limits
+/// to guard against proofs, particularly compressed ones, which are crafted
+/// to exhaust memory or time.  A proof exceeding a limit fails verification
+/// with `ProofTooLarge`, `ProofStackTooDeep` or `ProofBufferTooLarge`.  There
+/// are no limits by default.
+#[derive(Default,Debug,Clone,Copy)]
+pub struct VerifyOptions {
+    /// Maximum number of steps executed, counting each use of a saved step.
+    pub max_proof_steps: Option<usize>,
+    /// Maximum number of results on the stack at any time.
+    pub max_stack_depth: Option<usize>,
+    /// Maximum total size in bytes of the math strings of the results,
+    /// which grows with each substitution.
+    pub max_buffer_size: Option<usize>,
+    /// Report every error of a proof which can be recovered from, rather than
+    /// only the first; see `verify_soft`.
+    pub continue_after_errors: bool,
+}
+
+/// The disjoint variable conditions a proof relies on, as found by
+/// `dv_usage`.  Variables are given as indices into the `var_list` of the
+/// frame of the proved statement.
+#[derive(Clone,Debug)]
+pub struct DvUsage {
+    /// Pairs of variables which must be disjoint for the proof to be valid,
+    /// with the smaller index first; sorted, without duplicates.
+    pub required: Vec<(VarIndex, VarIndex)>,
+    /// The variables which occur in a substitution made by the proof.
+    pub variables: Bitset,
+}
+
+/// A `?` step of an incomplete proof, as found by `verify_incomplete`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Goal {
+    /// The index of the step, counting each use of a saved step.
+    pub step: usize,
+    /// The typecode of the result the step must give, or `None` if the
+    /// result is never used.
+    pub typecode: Option<Token>,
+    /// The symbols after the typecode of the formula the step must prove, or
+    /// `None` if they are not known: the step fills a `$f` hypothesis, so it
+    /// chooses a substitution rather than proving a formula, or the formula
+    /// involves a substitution which is itself a `?` step.
+    pub symbols: Option<Vec<Token>>,
+}
+
+impl Goal {
+    /// Returns the formula the step must prove, if it is known.
+    pub fn formula(&self) -> Option<Formula> {
+        match (&self.typecode, &self.symbols) {
+            (Some(typecode), Some(symbols)) => {
+                Some(Formula {
+                    typecode: typecode.clone(),
+                    symbols: symbols.clone(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What is known of a result in an incomplete proof.
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+enum Known {
+    /// The result is known.
+    Yes,
+    /// The result is that of a `?` step, given as an index into the goals.
+    Goal(usize),
+    /// The result depends on a `?` step used as a substitution.
+    No,
+}
+
 /// Preparing a step means that it can be referenced using a varint in a
 /// compressed proof.  Compressed steps are either saved prior
 /// results/hypotheses, which are copied directly onto the stack, or previously
 /// proved assertions which require substitution before use.
 enum PreparedStep<'a, D> {
-    Hyp(Bitset, Atom, Range<usize>, D),
+    Hyp(Bitset, Atom, Range<usize>, Known, D),
     Assert(&'a Frame),
 }
 use self::PreparedStep::*;
@@ -95,6 +176,7 @@ pub struct StackSlot {
     vars: Bitset,
     code: Atom,
     expr: Range<usize>,
+    known: Known,
 }
 
 /// A constructor trait for plugging in to the verifier, to collect extra data during the
@@ -130,6 +212,14 @@ impl ProofBuilder for () {
     fn build(&mut self, _: StatementAddress, _: (), _: &[u8], _: Range<usize>) -> () {}
 }
 
+/// An observer of the steps of a proof, for debuggers and teaching tools which
+/// show the stack as the proof is executed.
+pub trait StepObserver {
+    /// Called after each executed step, with the number of steps executed so
+    /// far (counting each use of a saved step) and the stack, bottom first.
+    fn step(&mut self, count: usize, stack: &[Formula]);
+}
+
 /// Working memory used by the verifier on a segment.  This expands for the
 /// first few proofs and the rest can be handled without allocation.
 struct VerifyState<'a, P: 'a + ProofBuilder> {
@@ -161,10 +251,88 @@ struct VerifyState<'a, P: 'a + ProofBuilder> {
     var2bit: HashMap<Atom, usize>,
     /// Disjoint variable conditions in the current extended frame
     dv_map: &'a [Bitset],
+    /// Resource limits for each proof
+    limits: VerifyOptions,
+    /// Number of steps executed so far in the current proof
+    steps: usize,
+    /// Disjoint variable conditions used so far, if they are being tracked
+    dv_usage: Option<DvUsage>,
+    /// Observer shown the stack after each step, if any
+    observer: Option<&'a mut dyn StepObserver>,
+    /// Errors recovered from in the current proof, with the index of the step,
+    /// if the proof is checked past its first error
+    errors: Option<Vec<(usize, Diagnostic)>>,
+    /// The `?` steps of the current proof, if incomplete proofs are accepted
+    goals: Option<Vec<Goal>>,
+    /// Variables of the assertion being applied whose substitutions are not
+    /// known, in an incomplete proof
+    unknown_vars: Bitset,
 }
 
 type Result<T> = result::Result<T, Diagnostic>;
 
+impl<'a, P: 'a + ProofBuilder> VerifyState<'a, P> {
+    /// Makes the working memory for the proof of a single $p statement, which
+    /// must have a valid frame.  Nothing beyond the proof result is recorded
+    /// until the caller sets one of the optional fields.
+    fn new(sset: &'a SegmentSet,
+           nset: &'a Nameset,
+           scopes: &'a ScopeResult,
+           stmt: StatementRef<'a>,
+           builder: &'a mut P)
+           -> Self {
+        assert!(stmt.statement_type() == StatementType::Provable);
+        let mut scoper = ScopeReader::new(scopes);
+        let frame = scoper.get(stmt.label()).unwrap();
+        VerifyState::with_frame(sset, nset, scoper, stmt.segment(), frame, builder)
+    }
+
+    /// Makes the working memory for the proofs of a segment, starting on the
+    /// given frame.
+    fn with_frame(sset: &'a SegmentSet,
+                  nset: &'a Nameset,
+                  scoper: ScopeReader<'a>,
+                  sref: SegmentRef<'a>,
+                  frame: &'a Frame,
+                  builder: &'a mut P)
+                  -> Self {
+        VerifyState {
+            this_seg: sref,
+            scoper: scoper,
+            nameset: nset,
+            builder: builder,
+            order: &sset.order,
+            cur_frame: frame,
+            stack: Vec::new(),
+            stack_buffer: Vec::new(),
+            prepared: Vec::new(),
+            temp_buffer: Vec::new(),
+            subst_info: Vec::new(),
+            var2bit: new_map(),
+            dv_map: &frame.optional_dv,
+            limits: sset.options.verify,
+            steps: 0,
+            dv_usage: None,
+            observer: None,
+            errors: None,
+            goals: None,
+            unknown_vars: Bitset::new(),
+        }
+    }
+}
+
+/// Records an error at the current step and continues if the proof is
+/// checked past its first error, otherwise fails with it.
+fn soft_error<P: ProofBuilder>(state: &mut VerifyState<P>, diag: Diagnostic) -> Result<()> {
+    match state.errors {
+        Some(ref mut errors) => {
+            errors.push((state.steps, diag));
+            Ok(())
+        }
+        None => Err(diag),
+    }
+}
+
 /// Variables are added lazily to the extended frame.  All variables which are
 /// associated with hypotheses or $d constraints are numbered by scopeck, but if
 /// a dummy variable is used in a proof without a $d constraint it won't be
@@ -214,6 +382,7 @@ fn prepare_hypothesis<'a, P: ProofBuilder>(state: &mut VerifyState<P>, hyp: &'a
         .push(Hyp(vars,
                   hyp.typecode(),
                   tos..ntos,
+                  Known::Yes,
                   state.builder.build(hyp.address(),
                                       Default::default(),
                                       &state.stack_buffer,
@@ -339,6 +508,7 @@ fn prepare_step<'a, P: ProofBuilder>(state: &mut VerifyState<P>,
             .push(Hyp(vars,
                       frame.target.typecode,
                       tos..ntos,
+                      Known::Yes,
                       state.builder.build(valid.start,
                                           Default::default(),
                                           &state.stack_buffer,
@@ -419,6 +589,48 @@ fn do_substitute_vars(expr: &[ExprFragment], vars: &[(Range<usize>, Bitset)]) ->
     out
 }
 
+/// Records what a `?` step must prove, once it is used.
+fn set_goal(goals: Option<&mut Vec<Goal>>,
+            goal: usize,
+            nameset: &Nameset,
+            typecode: Atom,
+            symbols: Option<Vec<Token>>) {
+    if let Some(goals) = goals {
+        goals[goal].typecode = Some(copy_token(nameset.atom_name(typecode)));
+        goals[goal].symbols = symbols;
+    }
+}
+
+/// Pushes the unknown result of a `?` step, or fails if the proof must be
+/// complete.
+fn push_unknown<P: ProofBuilder>(state: &mut VerifyState<P>) -> Result<()> {
+    let goal = match state.goals {
+        Some(ref mut goals) => {
+            goals.push(Goal {
+                step: state.steps,
+                typecode: None,
+                symbols: None,
+            });
+            goals.len() - 1
+        }
+        None => return Err(Diagnostic::ProofIncomplete),
+    };
+    // incomplete proofs are only accepted with the null builder, so the data
+    // is never looked at
+    let data = state.builder.build(state.cur_frame.valid.start,
+                                   Default::default(),
+                                   &state.stack_buffer,
+                                   0..0);
+    state.stack.push((data,
+                      StackSlot {
+        vars: Bitset::new(),
+        code: state.cur_frame.target.typecode,
+        expr: 0..0,
+        known: Known::Goal(goal),
+    }));
+    Ok(())
+}
+
 /// Process the hypotheses of the assertion we're about to apply.  `$f` hyps
 /// allow the caller to define a replacement for a variable; `$e` hyps are
 /// logical hypotheses that must have been proved; the result is then
@@ -434,24 +646,64 @@ fn process_hyp<P: ProofBuilder>(state: &mut VerifyState<P>,
                                 ix: usize,
                                 hyp: &scopeck::Hyp)
                                 -> Result<()> {
-    let (ref data, ref slot): (P::Item, StackSlot) = state.stack[ix];
-    state.builder.push(datavec, data.clone());
-    match hyp {
-        &Floating(_addr, var_index, typecode) => {
-            try_assert!(slot.code == typecode, Diagnostic::StepFloatWrongType);
-            state.subst_info[var_index] = (slot.expr.clone(), slot.vars.clone());
-        }
-        &Essential(_addr, ref expr) => {
-            try_assert!(slot.code == expr.typecode, Diagnostic::StepEssenWrongType);
-            try_assert!(do_substitute_eq(&state.stack_buffer[slot.expr.clone()],
-                                         frame,
-                                         &expr,
-                                         &state.subst_info,
-                                         &state.stack_buffer),
-                        Diagnostic::StepEssenWrong);
+    let error = {
+        let (ref data, ref slot): (P::Item, StackSlot) = state.stack[ix];
+        state.builder.push(datavec, data.clone());
+        match hyp {
+            &Floating(_addr, var_index, typecode) => {
+                // substituted even if mistyped, so that the step still gives
+                // the right result when continuing after errors
+                state.subst_info[var_index] = (slot.expr.clone(), slot.vars.clone());
+                if slot.known != Known::Yes {
+                    state.unknown_vars.set_bit(var_index);
+                }
+                if let Known::Goal(goal) = slot.known {
+                    set_goal(state.goals.as_mut(), goal, state.nameset, typecode, None);
+                    None
+                } else if slot.code != typecode {
+                    Some(Diagnostic::StepFloatWrongType)
+                } else {
+                    None
+                }
+            }
+            &Essential(_addr, ref expr) => {
+                let unknown = state.goals.is_some() &&
+                              expr.tail.iter().any(|part| state.unknown_vars.has_bit(part.var));
+                if let Known::Goal(goal) = slot.known {
+                    let mut symbols = None;
+                    if !unknown {
+                        // substitutions are ranges of the stack buffer, so the
+                        // formula is built at its end and then removed
+                        let tos = state.stack_buffer.len();
+                        do_substitute(&mut state.stack_buffer, frame, expr, &state.subst_info);
+                        let mut tokens = Vec::new();
+                        unpack_symbols(&state.stack_buffer[tos..], &mut tokens);
+                        state.stack_buffer.truncate(tos);
+                        symbols = Some(tokens);
+                    }
+                    set_goal(state.goals.as_mut(), goal, state.nameset, expr.typecode, symbols);
+                    None
+                } else if slot.code != expr.typecode {
+                    Some(Diagnostic::StepEssenWrongType)
+                } else if slot.known == Known::No || unknown {
+                    // nothing to compare with
+                    None
+                } else if !do_substitute_eq(&state.stack_buffer[slot.expr.clone()],
+                                            frame,
+                                            &expr,
+                                            &state.subst_info,
+                                            &state.stack_buffer) {
+                    Some(Diagnostic::StepEssenWrong)
+                } else {
+                    None
+                }
+            }
         }
+    };
+    match error {
+        Some(diag) => soft_error(state, diag),
+        None => Ok(()),
     }
-    Ok(())
 }
 
 /// This is the main "VM" function, and responsible for ~30% of CPU time during
@@ -463,7 +715,7 @@ fn execute_step<P: ProofBuilder>(state: &mut VerifyState<P>,
                                  -> Result<()> {
     try_assert!(index < state.prepared.len(), Diagnostic::StepOutOfRange);
     let fref = match state.prepared[index] {
-        Hyp(ref vars, code, ref expr, ref data) => {
+        Hyp(ref vars, code, ref expr, known, ref data) => {
             // hypotheses/saved steps are the easy case.  unfortunately, this is
             // also a very unpredictable branch
             state.stack.push((data.clone(),
@@ -471,6 +723,7 @@ fn execute_step<P: ProofBuilder>(state: &mut VerifyState<P>,
                 vars: vars.clone(),
                 code: code,
                 expr: expr.clone(),
+                known: known,
             }));
             return Ok(());
         }
@@ -489,6 +742,9 @@ fn execute_step<P: ProofBuilder>(state: &mut VerifyState<P>,
     }
 
     let mut datavec = Default::default();
+    if state.goals.is_some() {
+        state.unknown_vars = Bitset::new();
+    }
 
     // This branch is optimized out of compressed proof processing
     if let Some(explicit_stack) = explicit {
@@ -560,6 +816,12 @@ fn execute_step<P: ProofBuilder>(state: &mut VerifyState<P>,
                   &fref.target,
                   &state.subst_info);
     let ntos = state.stack_buffer.len();
+    let known = if state.goals.is_some() &&
+                   fref.target.tail.iter().any(|part| state.unknown_vars.has_bit(part.var)) {
+        Known::No
+    } else {
+        Known::Yes
+    };
 
     state.stack.truncate(sbase);
     state.stack
@@ -568,55 +830,136 @@ fn execute_step<P: ProofBuilder>(state: &mut VerifyState<P>,
             code: fref.target.typecode,
             vars: do_substitute_vars(&fref.target.tail, &state.subst_info),
             expr: tos..ntos,
+            known: known,
         }));
 
     // check $d constraints on the used assertion now that the dust has settled.
     // Remember that we might have variable indexes allocated during the proof
     // that are out of range for dv_map
-    for &(ix1, ix2) in &*fref.mandatory_dv {
-        for var1 in &state.subst_info[ix1].1 {
-            for var2 in &state.subst_info[ix2].1 {
-                try_assert!(var1 < state.dv_map.len() && state.dv_map[var1].has_bit(var2),
-                            Diagnostic::ProofDvViolation);
+    let dv_ok = fref.mandatory_dv.iter().all(|&(ix1, ix2)| {
+        state.subst_info[ix1].1.into_iter().all(|var1| {
+            state.subst_info[ix2].1.into_iter().all(|var2| {
+                var1 < state.dv_map.len() && state.dv_map[var1].has_bit(var2)
+            })
+        })
+    });
+    if !dv_ok {
+        soft_error(state, Diagnostic::ProofDvViolation)?;
+    }
+
+    if let Some(ref mut usage) = state.dv_usage {
+        for &(ix1, ix2) in &*fref.mandatory_dv {
+            for var1 in &state.subst_info[ix1].1 {
+                for var2 in &state.subst_info[ix2].1 {
+                    usage.required.push((var1.min(var2), var1.max(var2)));
+                }
             }
         }
+        for &(_, ref vars) in &state.subst_info[..fref.mandatory_count] {
+            usage.variables |= vars;
+        }
     }
 
     Ok(())
 }
 
+/// Checks the resource limits after executing a step.  The buffer may exceed
+/// its limit by the result of one step before this is caught.
+fn check_limits<P: ProofBuilder>(state: &mut VerifyState<P>) -> Result<()> {
+    state.steps += 1;
+    let limits = state.limits;
+    if let Some(max) = limits.max_proof_steps {
+        try_assert!(state.steps <= max, Diagnostic::ProofTooLarge(max));
+    }
+    if let Some(max) = limits.max_stack_depth {
+        try_assert!(state.stack.len() <= max, Diagnostic::ProofStackTooDeep(max));
+    }
+    if let Some(max) = limits.max_buffer_size {
+        try_assert!(state.stack_buffer.len() <= max,
+                    Diagnostic::ProofBufferTooLarge(max));
+    }
+    Ok(())
+}
+
+/// Shows the stack to the observer, if any, after executing a step.
+fn observe_step<P: ProofBuilder>(state: &mut VerifyState<P>) {
+    let buffer = &state.stack_buffer;
+    let nameset = state.nameset;
+    if let Some(ref mut observer) = state.observer {
+        let stack: Vec<Formula> = state.stack
+            .iter()
+            .map(|entry| {
+                let mut symbols = Vec::new();
+                unpack_symbols(&buffer[entry.1.expr.clone()], &mut symbols);
+                Formula {
+                    typecode: copy_token(nameset.atom_name(entry.1.code)),
+                    symbols: symbols,
+                }
+            })
+            .collect();
+        observer.step(state.steps, &stack);
+    }
+}
+
 fn finalize_step<P: ProofBuilder>(state: &mut VerifyState<P>) -> Result<P::Item> {
     // if we get here, it's a valid proof, but was it the _right_ valid proof?
-    try_assert!(state.stack.len() <= 1, Diagnostic::ProofExcessEnd);
-    let &(ref data, ref tos) = state.stack.last().ok_or(Diagnostic::ProofNoSteps)?;
+    if state.stack.len() > 1 {
+        soft_error(state, Diagnostic::ProofExcessEnd)?;
+    }
+    let (data, code, expr, known) = match state.stack.last() {
+        Some((data, tos)) => (data.clone(), tos.code, tos.expr.clone(), tos.known),
+        None => return Err(Diagnostic::ProofNoSteps),
+    };
 
-    try_assert!(tos.code == state.cur_frame.target.typecode,
-                Diagnostic::ProofWrongTypeEnd);
+    if code != state.cur_frame.target.typecode {
+        soft_error(state, Diagnostic::ProofWrongTypeEnd)?;
+    }
 
     fast_clear(&mut state.temp_buffer);
     do_substitute_raw(&mut state.temp_buffer, &state.cur_frame, state.nameset);
 
-    try_assert!(state.stack_buffer[tos.expr.clone()] == state.temp_buffer[..],
-                Diagnostic::ProofWrongExprEnd);
+    match known {
+        Known::Yes => {
+            if state.stack_buffer[expr] != state.temp_buffer[..] {
+                soft_error(state, Diagnostic::ProofWrongExprEnd)?;
+            }
+        }
+        Known::Goal(goal) => {
+            let mut symbols = Vec::new();
+            unpack_symbols(&state.temp_buffer, &mut symbols);
+            set_goal(state.goals.as_mut(),
+                     goal,
+                     state.nameset,
+                     state.cur_frame.target.typecode,
+                     Some(symbols));
+        }
+        Known::No => {}
+    }
 
-    Ok(data.clone())
+    Ok(data)
 }
 
 fn save_step<P: ProofBuilder>(state: &mut VerifyState<P>) {
     let &(ref data, ref top) = state.stack.last().expect("can_save should prevent getting here");
-    state.prepared.push(Hyp(top.vars.clone(), top.code, top.expr.clone(), data.clone()));
+    state.prepared
+        .push(Hyp(top.vars.clone(), top.code, top.expr.clone(), top.known, data.clone()));
 }
 
-// proofs are not self-synchronizing, so it's not likely to get >1 usable error
-fn verify_proof<'a, P: ProofBuilder>(state: &mut VerifyState<'a, P>,
-                                     stmt: StatementRef<'a>)
-                                     -> Result<P::Item> {
+/// Resets the state for a proof of the current frame.
+fn begin_proof<P: ProofBuilder>(state: &mut VerifyState<P>) {
     // clear, but do not free memory
     state.stack.clear();
     fast_clear(&mut state.stack_buffer);
     state.prepared.clear();
     state.var2bit.clear();
     state.dv_map = &state.cur_frame.optional_dv;
+    state.steps = 0;
+    if let Some(ref mut errors) = state.errors {
+        errors.clear();
+    }
+    if let Some(ref mut goals) = state.goals {
+        goals.clear();
+    }
     // temp_buffer is cleared before use; subst_info should be overwritten
     // before use if scopeck is working correctly
 
@@ -626,6 +969,68 @@ fn verify_proof<'a, P: ProofBuilder>(state: &mut VerifyState<'a, P>,
     for (index, &tokr) in state.cur_frame.var_list.iter().enumerate() {
         state.var2bit.insert(tokr, index);
     }
+}
+
+/// Executes the steps of a NORMAL mode proof, given with their spans.
+fn execute_normal<'a, P, I>(state: &mut VerifyState<'a, P>, steps: I) -> Result<()>
+    where P: ProofBuilder,
+          I: Iterator<Item = (Span, TokenPtr<'a>)>
+{
+    let mut count = 0;
+    let mut backrefs: HashMap<TokenPtr, usize> = new_map();
+    let mut explicit_stack: Option<Vec<Option<TokenPtr<'a>>>> = None;
+
+    // NORMAL mode proofs are just a list of steps, with no saving provision
+    for (span, chunk) in steps {
+        if chunk == b"?" {
+            push_unknown(state)?;
+            check_limits(state)?;
+            observe_step(state);
+            if let Some(ref mut explicit_stack) = explicit_stack {
+                explicit_stack.push(None);
+            }
+            continue;
+        }
+        let step = prepare_step(state, chunk, Some(span))?;
+        if let Some(label) = step.label {
+            try_assert!(step.fwdref.is_none(), Diagnostic::ChainBackref(span));
+            let &ix = backrefs.get(label)
+                .ok_or_else(|| Diagnostic::StepMissing(copy_token(label)))?;
+            execute_step(state, ix, explicit_stack.as_mut())?;
+            check_limits(state)?;
+            observe_step(state);
+        } else {
+            execute_step(state, count, explicit_stack.as_mut())?;
+            check_limits(state)?;
+            observe_step(state);
+            if let Some(fwdref) = step.fwdref {
+                state.prepared.pop();
+                save_step(state);
+                try_assert!(backrefs.insert(fwdref, count).is_none(),
+                            Diagnostic::LocalLabelDuplicate(span));
+            }
+            count += 1;
+        }
+        if step.hyptok.is_some() {
+            if explicit_stack.is_none() {
+                // lazy initialization so that we don't need to maintain
+                // this parallel stack if we are not in explicit mode
+                explicit_stack = Some(vec![None; state.stack.len()-1]);
+            }
+            explicit_stack.as_mut().unwrap().push(step.hyptok);
+        }
+    }
+    Ok(())
+}
+
+// proofs are not self-synchronizing, so it's not likely to get >1 usable error
+// in general.  the errors which can be continued after are those where a step
+// still has a well-defined result: a hypothesis which does not match, which
+// does not affect the substitution, or a violated $d condition
+fn verify_proof<'a, P: ProofBuilder>(state: &mut VerifyState<'a, P>,
+                                     stmt: StatementRef<'a>)
+                                     -> Result<P::Item> {
+    begin_proof(state);
 
     if stmt.proof_len() > 0 && stmt.proof_slice_at(0) == b"(" {
         // this is a compressed proof
@@ -661,6 +1066,8 @@ fn verify_proof<'a, P: ProofBuilder>(state: &mut VerifyState<'a, P>,
                 if ch >= b'A' && ch <= b'T' {
                     k = k * 20 + (ch - b'A') as usize;
                     execute_step(state, k, None)?;
+                    check_limits(state)?;
+                    observe_step(state);
                     k = 0;
                     can_save = true;
                 } else if ch >= b'U' && ch <= b'Y' {
@@ -674,7 +1081,10 @@ fn verify_proof<'a, P: ProofBuilder>(state: &mut VerifyState<'a, P>,
                     can_save = false;
                 } else if ch == b'?' {
                     try_assert!(k == 0, Diagnostic::ProofMalformedVarint);
-                    return Err(Diagnostic::ProofIncomplete);
+                    push_unknown(state)?;
+                    check_limits(state)?;
+                    observe_step(state);
+                    can_save = true;
                 }
             }
             i += 1;
@@ -682,40 +1092,8 @@ fn verify_proof<'a, P: ProofBuilder>(state: &mut VerifyState<'a, P>,
 
         try_assert!(k == 0, Diagnostic::ProofMalformedVarint);
     } else {
-        let mut count = 0;
-        let mut backrefs: HashMap<TokenPtr, usize> = new_map();
-        let mut explicit_stack: Option<Vec<Option<TokenPtr<'a>>>> = None;
-
-        // NORMAL mode proofs are just a list of steps, with no saving provision
-        for i in 0..stmt.proof_len() {
-            let span = stmt.proof_span(i);
-            let chunk = stmt.proof_slice_at(i);
-            try_assert!(chunk != b"?", Diagnostic::ProofIncomplete);
-            let step = prepare_step(state, chunk, Some(span))?;
-            if let Some(label) = step.label {
-                try_assert!(step.fwdref.is_none(), Diagnostic::ChainBackref(span));
-                let &ix = backrefs.get(label)
-                    .ok_or_else(|| Diagnostic::StepMissing(copy_token(label)))?;
-                execute_step(state, ix, explicit_stack.as_mut())?;
-            } else {
-                execute_step(state, count, explicit_stack.as_mut())?;
-                if let Some(fwdref) = step.fwdref {
-                    state.prepared.pop();
-                    save_step(state);
-                    try_assert!(backrefs.insert(fwdref, count).is_none(),
-                                Diagnostic::LocalLabelDuplicate(span));
-                }
-                count += 1;
-            }
-            if step.hyptok.is_some() {
-                if explicit_stack.is_none() {
-                    // lazy initialization so that we don't need to maintain
-                    // this parallel stack if we are not in explicit mode
-                    explicit_stack = Some(vec![None; state.stack.len()-1]);
-                }
-                explicit_stack.as_mut().unwrap().push(step.hyptok);
-            }
-        }
+        let steps = (0..stmt.proof_len()).map(|i| (stmt.proof_span(i), stmt.proof_slice_at(i)));
+        execute_normal(state, steps)?;
     }
 
     finalize_step(state)
@@ -725,7 +1103,7 @@ fn verify_proof<'a, P: ProofBuilder>(state: &mut VerifyState<'a, P>,
 struct VerifySegment {
     source: Arc<Segment>,
     scope_usage: ScopeUsage,
-    diagnostics: HashMap<StatementAddress, Diagnostic>,
+    diagnostics: HashMap<StatementAddress, Vec<(usize, Diagnostic)>>,
 }
 
 /// Analysis pass result for the verifier.
@@ -739,8 +1117,26 @@ impl VerifyResult {
     pub fn diagnostics(&self) -> Vec<(StatementAddress, Diagnostic)> {
         let mut out = Vec::new();
         for vsr in self.segments.values() {
-            for (&sa, &ref diag) in &vsr.diagnostics {
-                out.push((sa, diag.clone()));
+            for (&sa, diags) in &vsr.diagnostics {
+                for (_, diag) in diags {
+                    out.push((sa, diag.clone()));
+                }
+            }
+        }
+        out
+    }
+
+    /// Reports errors found during database verification with the index of
+    /// the proof step where each occurred, as `verify_soft` does.  A proof has
+    /// more than one error only if `VerifyOptions::continue_after_errors` is
+    /// set.
+    pub fn step_diagnostics(&self) -> Vec<(StatementAddress, usize, Diagnostic)> {
+        let mut out = Vec::new();
+        for vsr in self.segments.values() {
+            for (&sa, diags) in &vsr.diagnostics {
+                for (step, diag) in diags {
+                    out.push((sa, *step, diag.clone()));
+                }
             }
         }
         out
@@ -756,21 +1152,18 @@ fn verify_segment(sset: &SegmentSet,
     let mut diagnostics = new_map();
     let dummy_frame = Frame::default();
     let sref = sset.segment(sid);
-    let mut state = VerifyState {
-        this_seg: sref,
-        scoper: ScopeReader::new(scopes),
-        nameset: nset,
-        builder: &mut (),
-        order: &sset.order,
-        cur_frame: &dummy_frame,
-        stack: Vec::new(),
-        stack_buffer: Vec::new(),
-        prepared: Vec::new(),
-        temp_buffer: Vec::new(),
-        subst_info: Vec::new(),
-        var2bit: new_map(),
-        dv_map: &dummy_frame.optional_dv,
-    };
+    let builder = &mut ();
+    let mut state = VerifyState::with_frame(sset,
+                                            nset,
+                                            ScopeReader::new(scopes),
+                                            sref,
+                                            &dummy_frame,
+                                            builder);
+    if sset.options.verify.continue_after_errors {
+        state.errors = Some(Vec::new());
+    }
+    #[cfg(feature = "stats")]
+    let mut times = Vec::new();
     // use the _same_ VerifyState so that memory can be reused
     for stmt in sref {
         // only intend to check $p statements
@@ -778,13 +1171,29 @@ fn verify_segment(sset: &SegmentSet,
             // no valid frame -> no use checking
             // may wish to record a secondary error?
             if let Some(frame) = state.scoper.get(stmt.label()) {
+                #[cfg(feature = "stats")]
+                let start = Instant::now();
                 state.cur_frame = frame;
-                if let Err(diag) = verify_proof(&mut state, stmt) {
-                    diagnostics.insert(stmt.address(), diag);
+                let result = verify_proof(&mut state, stmt);
+                let mut diags = match state.errors {
+                    Some(ref mut errors) => mem::take(errors),
+                    None => Vec::new(),
+                };
+                if let Err(diag) = result {
+                    diags.push((state.steps, diag));
                 }
+                if !diags.is_empty() {
+                    diagnostics.insert(stmt.address(), diags);
+                }
+                #[cfg(feature = "stats")]
+                times.push((stmt.index(), start.elapsed()));
             }
         }
     }
+    #[cfg(feature = "stats")]
+    if let Some(ref stats) = sset.stats {
+        stats.record_segment("verify", sid, times);
+    }
     VerifySegment {
         source: (*sref).clone(),
         diagnostics: diagnostics,
@@ -835,25 +1244,148 @@ pub fn verify_one<P: ProofBuilder>(sset: &SegmentSet,
                                    builder: &mut P,
                                    stmt: StatementRef)
                                    -> result::Result<P::Item, Diagnostic> {
-    let dummy_frame = Frame::default();
-    let mut state = VerifyState {
-        this_seg: stmt.segment(),
-        scoper: ScopeReader::new(scopes),
-        nameset: nset,
-        builder: builder,
-        order: &sset.order,
-        cur_frame: &dummy_frame,
-        stack: Vec::new(),
-        stack_buffer: Vec::new(),
-        prepared: Vec::new(),
-        temp_buffer: Vec::new(),
-        subst_info: Vec::new(),
-        var2bit: new_map(),
-        dv_map: &dummy_frame.optional_dv,
-    };
+    let mut state = VerifyState::new(sset, nset, scopes, stmt, builder);
+    verify_proof(&mut state, stmt)
+}
+
+/// Verifies the proof of a single $p statement, recording the disjoint
+/// variable conditions which its steps rely on, or returns an error if the
+/// proof is faulty
+pub fn dv_usage(sset: &SegmentSet,
+                nset: &Nameset,
+                scopes: &ScopeResult,
+                stmt: StatementRef)
+                -> result::Result<DvUsage, Diagnostic> {
+    let builder = &mut ();
+    let mut state = VerifyState::new(sset, nset, scopes, stmt, builder);
+    state.dv_usage = Some(DvUsage {
+        required: Vec::new(),
+        variables: Bitset::new(),
+    });
+    verify_proof(&mut state, stmt)?;
+    let mut usage = state.dv_usage.take().unwrap();
+    usage.required.sort();
+    usage.required.dedup();
+    Ok(usage)
+}
 
-    assert!(stmt.statement_type() == StatementType::Provable);
-    let frame = state.scoper.get(stmt.label()).unwrap();
-    state.cur_frame = frame;
+/// Verifies the proof of a single $p statement without stopping at the first
+/// error, returning every error found with the index of the step where it
+/// occurred; an empty list means the proof is correct.
+///
+/// A step whose hypotheses do not match those of its assertion, or which
+/// violates a `$d` condition, is recorded and then treated as if it were
+/// correct, so that it pushes the substituted conclusion of the assertion and
+/// the stack stays in step with the proof.  The same holds for a wrong final
+/// result.  Errors in the structure of the proof, such as a stack underflow or
+/// an unknown label, leave nothing to go on with and end the list.
+pub fn verify_soft(sset: &SegmentSet,
+                   nset: &Nameset,
+                   scopes: &ScopeResult,
+                   stmt: StatementRef)
+                   -> Vec<(usize, Diagnostic)> {
+    let builder = &mut ();
+    let mut state = VerifyState::new(sset, nset, scopes, stmt, builder);
+    state.errors = Some(Vec::new());
+    let result = verify_proof(&mut state, stmt);
+    let mut errors = state.errors.take().unwrap();
+    if let Err(diag) = result {
+        errors.push((state.steps, diag));
+    }
+    errors
+}
+
+/// Verifies a proof which may contain `?` steps, standing for parts still to
+/// be proved, and returns those steps with the formulas they must prove, in
+/// proof order, or an error if the rest of the proof is faulty.  An empty list
+/// means the proof is complete and correct.
+///
+/// A `?` step pushes an unknown result.  Where it is used for a `$e`
+/// hypothesis or is the final step, the formula it must prove is known.
+/// Where it is used for a `$f` hypothesis, the substitution is unknown, and so
+/// are the results which depend on it; these are not checked against the
+/// hypotheses they are used for.
+pub fn verify_incomplete(sset: &SegmentSet,
+                         nset: &Nameset,
+                         scopes: &ScopeResult,
+                         stmt: StatementRef)
+                         -> result::Result<Vec<Goal>, Diagnostic> {
+    let builder = &mut ();
+    let mut state = VerifyState::new(sset, nset, scopes, stmt, builder);
+    state.goals = Some(Vec::new());
+    verify_proof(&mut state, stmt)?;
+    Ok(state.goals.take().unwrap())
+}
+
+/// Verifies the proof of a single $p statement, showing the stack to an
+/// observer after each step, or returns an error if the proof is faulty.  The
+/// observer has seen every step which was executed before the error.
+pub fn trace_proof(sset: &SegmentSet,
+                   nset: &Nameset,
+                   scopes: &ScopeResult,
+                   stmt: StatementRef,
+                   observer: &mut dyn StepObserver)
+                   -> result::Result<(), Diagnostic> {
+    let builder = &mut ();
+    let mut state = VerifyState::new(sset, nset, scopes, stmt, builder);
+    state.observer = Some(observer);
     verify_proof(&mut state, stmt)
 }
+
+fn check_steps<P: ProofBuilder>(sset: &SegmentSet,
+                                nset: &Nameset,
+                                scopes: &ScopeResult,
+                                builder: &mut P,
+                                stmt: StatementRef,
+                                steps: &[TokenPtr],
+                                observer: Option<&mut dyn StepObserver>)
+                                -> result::Result<P::Item, Diagnostic> {
+    let mut state = VerifyState::new(sset, nset, scopes, stmt, builder);
+    state.observer = match observer {
+        // reborrowed to shorten the lifetime of the trait object
+        Some(observer) => Some(observer),
+        None => None,
+    };
+    begin_proof(&mut state);
+    execute_normal(&mut state, steps.iter().map(|&step| (Span::null(), step)))?;
+    finalize_step(&mut state)
+}
+
+/// Verifies a NORMAL mode proof, given as a list of step labels, against the
+/// frame of a $p statement instead of the statement's own proof, or returns an
+/// error if it is faulty.
+///
+/// Errors which would point into the proof text, such as `ChainBackref`, have
+/// a null span.
+pub fn verify_steps(sset: &SegmentSet,
+                    nset: &Nameset,
+                    scopes: &ScopeResult,
+                    stmt: StatementRef,
+                    steps: &[TokenPtr])
+                    -> result::Result<(), Diagnostic> {
+    check_steps(sset, nset, scopes, &mut (), stmt, steps, None)
+}
+
+/// Verifies a NORMAL mode proof given as a list of step labels, as
+/// `verify_steps` does, returning the result of the given proof builder.
+pub fn build_steps<P: ProofBuilder>(sset: &SegmentSet,
+                                    nset: &Nameset,
+                                    scopes: &ScopeResult,
+                                    builder: &mut P,
+                                    stmt: StatementRef,
+                                    steps: &[TokenPtr])
+                                    -> result::Result<P::Item, Diagnostic> {
+    check_steps(sset, nset, scopes, builder, stmt, steps, None)
+}
+
+/// Verifies a NORMAL mode proof given as a list of step labels, as
+/// `verify_steps` does, showing the stack to an observer after each step.
+pub fn trace_steps(sset: &SegmentSet,
+                   nset: &Nameset,
+                   scopes: &ScopeResult,
+                   stmt: StatementRef,
+                   steps: &[TokenPtr],
+                   observer: &mut dyn StepObserver)
+                   -> result::Result<(), Diagnostic> {
+    check_steps(sset, nset, scopes, &mut (), stmt, steps, Some(observer))
+}
@@ -0,0 +1,180 @@
+use database::Database;
+use database::DbOptions;
+use diag::Diagnostic;
+use formula::Formula;
+use parser::as_str;
+use parser::copy_token;
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+use verify::Goal;
+use verify::StepObserver;
+use verify::VerifyOptions;
+use verify::trace_proof;
+
+#[test]
+fn test_verify_one() {
+    let text = "$[ ax.mm $]
+${ h1 $e |- ph $. h2 $e |- ( ph -> ps ) $.
+th1 $p |- ps $= wph wps h1 h2 ax-mp $.
+th2 $p |- ph $= wph wps h1 h2 ax-mp $. $}
+";
+    let mut db = Database::new(DbOptions::default());
+    db.parse("test.mm".to_owned(),
+             vec![("test.mm".to_owned(), text.as_bytes().to_owned()),
+                  ("ax.mm".to_owned(), DB.as_bytes().to_owned())]);
+    assert_eq!(db.verify_one("th1"), Some(Ok(())));
+    assert_eq!(db.verify_one("th2"), Some(Err(Diagnostic::ProofWrongExprEnd)));
+    assert_eq!(db.verify_one("ax-mp"), None);
+    assert_eq!(db.verify_one("th3"), None);
+
+    db.verify_result();
+    assert_eq!(db.verify_one("th1"), Some(Ok(())));
+    assert_eq!(db.verify_one("th2"), Some(Err(Diagnostic::ProofWrongExprEnd)));
+}
+
+struct Trace(Vec<(usize, Vec<String>)>);
+
+impl StepObserver for Trace {
+    fn step(&mut self, count: usize, stack: &[Formula]) {
+        self.0.push((count, stack.iter().map(|formula| formula.to_string()).collect()));
+    }
+}
+
+#[test]
+fn test_trace_proof() {
+    let text = format!("{}${{ h1 $e |- ph $. h2 $e |- ( ph -> ps ) $.
+th1 $p |- ps $= wph wps h1 h2 ax-mp $.
+th2 $p |- ps $= ( ax-mp ) ABCDE $. $}}\n",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let sset = db.parse_result().clone();
+    let nset = db.name_result().clone();
+    let scope = db.scope_result().clone();
+    let expected = vec![(1, vec!["wff ph"]),
+                        (2, vec!["wff ph", "wff ps"]),
+                        (3, vec!["wff ph", "wff ps", "|- ph"]),
+                        (4, vec!["wff ph", "wff ps", "|- ph", "|- ( ph -> ps )"]),
+                        (5, vec!["|- ps"])];
+    for label in &[&b"th1"[..], b"th2"] {
+        let stmt = sset.statement(nset.lookup_label(label).unwrap().address);
+        let mut trace = Trace(Vec::new());
+        assert_eq!(trace_proof(&sset, &nset, &scope, stmt, &mut trace), Ok(()));
+        let steps: Vec<(usize, Vec<&str>)> = trace.0
+            .iter()
+            .map(|step| (step.0, step.1.iter().map(|s| s.as_str()).collect()))
+            .collect();
+        assert_eq!(steps, expected);
+    }
+}
+
+#[test]
+fn test_verify_limits() {
+    let text = format!("{}th1 $p |- ( ph -> ( ph -> ph ) ) $= wph wph ax-1 $.
+th2 $p |- ( ( ph -> ph ) -> ( ( ph -> ph ) -> ( ph -> ph ) ) ) $=
+  ( wi ax-1 ) AABZDC $.
+",
+                       DB);
+    let verify = |limits: VerifyOptions| {
+        let options = DbOptions {
+            verify: limits,
+            ..DbOptions::default()
+        };
+        let mut db = Database::new(options);
+        db.parse("test.mm".to_owned(),
+                 vec![("test.mm".to_owned(), text.as_bytes().to_owned())]);
+        let sset = db.parse_result().clone();
+        let mut diags: Vec<(String, Diagnostic)> = db.verify_result()
+            .diagnostics()
+            .into_iter()
+            .map(|(addr, diag)| (as_str(sset.statement(addr).label()).to_owned(), diag))
+            .collect();
+        diags.sort_by(|a, b| a.0.cmp(&b.0));
+        diags
+    };
+    assert_eq!(verify(VerifyOptions::default()), vec![]);
+
+    let limits = VerifyOptions {
+        max_proof_steps: Some(4),
+        ..VerifyOptions::default()
+    };
+    assert_eq!(verify(limits),
+               vec![("th2".to_owned(), Diagnostic::ProofTooLarge(4))]);
+
+    let limits = VerifyOptions {
+        max_stack_depth: Some(1),
+        ..VerifyOptions::default()
+    };
+    assert_eq!(verify(limits),
+               vec![("th1".to_owned(), Diagnostic::ProofStackTooDeep(1)),
+                    ("th2".to_owned(), Diagnostic::ProofStackTooDeep(1))]);
+
+    let limits = VerifyOptions {
+        max_buffer_size: Some(8),
+        ..VerifyOptions::default()
+    };
+    assert_eq!(verify(limits),
+               vec![("th1".to_owned(), Diagnostic::ProofBufferTooLarge(8)),
+                    ("th2".to_owned(), Diagnostic::ProofBufferTooLarge(8))]);
+}
+
+#[test]
+fn test_verify_soft() {
+    let text = format!("{}${{ h1 $e |- ph $. th1 $p |- ps $= wph wps h1 h1 wph ax-1 ax-mp $. $}}
+th2 $p |- ( ph -> ( ph -> ph ) ) $= wph wph ax-1 $.
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    assert_eq!(db.verify_soft("th1"),
+               Some(vec![(5, Diagnostic::StepFloatWrongType), (6, Diagnostic::StepEssenWrong)]));
+    assert_eq!(db.verify_soft("th2"), Some(vec![]));
+    assert_eq!(db.verify_soft("ax-1"), None);
+    assert_eq!(db.verify_result().diagnostics().len(), 1);
+
+    let mut options = DbOptions::default();
+    options.verify.continue_after_errors = true;
+    let mut db = Database::new(options);
+    db.parse("test.mm".to_owned(),
+             vec![("test.mm".to_owned(), text.as_bytes().to_owned())]);
+    let diags: Vec<Diagnostic> =
+        db.verify_result().diagnostics().into_iter().map(|(_, diag)| diag).collect();
+    assert_eq!(diags, vec![Diagnostic::StepFloatWrongType, Diagnostic::StepEssenWrong]);
+    let th1 = db.statement("th1").unwrap().address();
+    assert_eq!(db.verify_result().step_diagnostics(),
+               vec![(th1, 5, Diagnostic::StepFloatWrongType),
+                    (th1, 6, Diagnostic::StepEssenWrong)]);
+}
+
+#[test]
+fn test_verify_incomplete() {
+    let text = format!("{}th1 $p |- ps $= wph wps ? ? ax-mp $.
+th2 $p |- ( ph -> ( ph -> ph ) ) $= ( ax-1 ) ?AB $.
+th3 $p |- ps $= ? $.
+th4 $p |- ( ph -> ( ph -> ph ) ) $= wph wph ax-1 $.
+th5 $p |- ps $= wph ? ax-mp $.
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let goals = |db: &mut Database, label: &str| {
+        db.verify_incomplete(label).unwrap().map(|goals| {
+            goals.iter()
+                .map(|goal| (goal.step, goal.formula().map(|formula| formula.to_string())))
+                .collect::<Vec<_>>()
+        })
+    };
+    assert_eq!(goals(&mut db, "th1"),
+               Ok(vec![(2, Some("|- ph".to_owned())), (3, Some("|- ( ph -> ps )".to_owned()))]));
+    assert_eq!(db.verify_incomplete("th2").unwrap().unwrap(),
+               vec![Goal {
+                        step: 0,
+                        typecode: Some(copy_token(b"wff")),
+                        symbols: None,
+                    }]);
+    assert_eq!(goals(&mut db, "th3"), Ok(vec![(0, Some("|- ps".to_owned()))]));
+    assert_eq!(goals(&mut db, "th4"), Ok(vec![]));
+    assert_eq!(goals(&mut db, "th5"), Err(Diagnostic::ProofUnderflow));
+    assert_eq!(db.verify_incomplete("wi"), None);
+    assert!(db.verify_result()
+        .diagnostics()
+        .iter()
+        .any(|(_, diag)| *diag == Diagnostic::ProofIncomplete));
+}
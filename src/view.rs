@@ -0,0 +1,120 @@
+//! A view of the database as it stands at one statement.
+//!
+//! Editors which complete or search labels and math symbols while a proof is
+//! edited should only offer those the proof can use.  A `DatabaseView` answers
+//! the name and frame queries of `Nameset` and `ScopeResult` as they would be
+//! answered at a given statement: statements and symbols declared after it,
+//! and the hypotheses and variables of `${ $}` groups which have been closed
+//! before it, are not visible.  A statement does not see itself, so the view
+//! at a theorem is exactly what its proof may refer to.
+//!
+//! The view holds the analysis results it was made from, so it stays usable
+//! while the database changes, but it is not updated.
+
+use nameck::LookupLabel;
+use nameck::LookupSymbol;
+use nameck::Nameset;
+use parser::Comparer;
+use parser::NO_STATEMENT;
+use parser::StatementAddress;
+use parser::StatementRef;
+use parser::StatementType;
+use parser::SymbolType;
+use parser::TokenAddress;
+use parser::TokenPtr;
+use scopeck::Frame;
+use scopeck::ScopeResult;
+use segment_set::SegmentSet;
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+/// Returns whether a statement is in effect at another one, that is, the
+/// second comes after the first and, unless the first is an assertion, before
+/// the end of its group.
+pub fn in_effect(sset: &SegmentSet, stmt: StatementRef, at: StatementAddress) -> bool {
+    let range = stmt.scope_range();
+    let global = range.end == NO_STATEMENT || stmt.statement_type() == StatementType::Axiom ||
+                 stmt.statement_type() == StatementType::Provable;
+    sset.order.cmp(&range.start, &at) == Ordering::Less &&
+    (global || at.segment_id == range.start.segment_id && at.index < range.end)
+}
+
+/// The database as seen from one statement; see the module documentation.
+#[derive(Clone)]
+pub struct DatabaseView {
+    sset: Arc<SegmentSet>,
+    nset: Arc<Nameset>,
+    scope: Arc<ScopeResult>,
+    at: StatementAddress,
+}
+
+impl DatabaseView {
+    /// Makes the view at a statement from the results of the analysis passes.
+    pub fn new(sset: Arc<SegmentSet>,
+               nset: Arc<Nameset>,
+               scope: Arc<ScopeResult>,
+               at: StatementAddress)
+               -> DatabaseView {
+        DatabaseView {
+            sset: sset,
+            nset: nset,
+            scope: scope,
+            at: at,
+        }
+    }
+
+    /// Returns the statement the view is taken at.
+    pub fn address(&self) -> StatementAddress {
+        self.at
+    }
+
+    /// Returns whether a statement is visible: it comes before the statement
+    /// of the view, and the group it was declared in, if any, is still open.
+    pub fn is_visible(&self, address: StatementAddress) -> bool {
+        in_effect(&self.sset, self.sset.statement(address), self.at)
+    }
+
+    /// Looks up the address and atom for a statement label, if the statement
+    /// is visible.
+    pub fn lookup_label(&self, label: TokenPtr) -> Option<LookupLabel> {
+        self.nset.lookup_label(label).filter(|lookup| self.is_visible(lookup.address))
+    }
+
+    /// Returns the labels of visible statements starting with a prefix, in
+    /// byte order.
+    pub fn labels_with_prefix(&self, prefix: &str) -> Vec<TokenPtr<'_>> {
+        self.nset
+            .labels_with_prefix(prefix)
+            .into_iter()
+            .filter(|label| self.lookup_label(label).is_some())
+            .collect()
+    }
+
+    /// Fetches the frame of a visible `$a`, `$p` or `$f` statement.
+    pub fn get(&self, label: TokenPtr) -> Option<&Frame> {
+        self.scope.get(label).filter(|frame| self.is_visible(frame.valid.start))
+    }
+
+    /// Looks up a math symbol declared by a visible `$c` or `$v` statement,
+    /// which may be a `$v` inside an open group.
+    pub fn lookup_symbol(&self, symbol: TokenPtr) -> Option<LookupSymbol> {
+        if let Some(lookup) = self.nset.lookup_symbol(symbol) {
+            if self.is_visible(lookup.address.statement) {
+                return Some(lookup);
+            }
+        }
+        let sref = self.sset.segment(self.at.segment_id);
+        for local in &sref.local_vars {
+            let stmt = sref.statement(local.index);
+            if stmt.math_at(local.ordinal).slice == symbol && in_effect(&self.sset, stmt, self.at) {
+                return Some(LookupSymbol {
+                    stype: SymbolType::Variable,
+                    atom: self.nset.get_atom(symbol),
+                    address: TokenAddress::new3(self.at.segment_id, local.index, local.ordinal),
+                    const_address: None,
+                });
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,32 @@
+use test_fixtures::DB;
+use test_fixtures::mkdb;
+
+#[test]
+fn test_view_at() {
+    let text = format!("{}${{ h1 $e |- ph $. $v ch $. wch $f wff ch $. th1 $p |- ph $= h1 $. $}}
+th2 $p |- ( ph -> ( ph -> ph ) ) $= wph wph ax-1 $.
+",
+                       DB);
+    let mut db = mkdb(text.as_bytes());
+    let th1 = db.statement("th1").unwrap().address();
+    let th2 = db.statement("th2").unwrap().address();
+
+    let view = db.view_at(th1);
+    assert_eq!(view.address(), th1);
+    assert!(view.lookup_label(b"h1").is_some());
+    assert!(view.lookup_label(b"ax-1").is_some());
+    assert!(view.lookup_label(b"th1").is_none());
+    assert!(view.lookup_label(b"th2").is_none());
+    assert!(view.get(b"wch").is_some());
+    assert!(view.lookup_symbol(b"ch").is_some());
+    assert!(view.lookup_symbol(b"ph").is_some());
+    assert_eq!(view.labels_with_prefix("w"), vec![&b"wch"[..], b"wi", b"wph", b"wps"]);
+
+    let view = db.view_at(th2);
+    assert!(view.lookup_label(b"h1").is_none());
+    assert!(view.get(b"wch").is_none());
+    assert!(view.get(b"th1").is_some());
+    assert!(view.get(b"th2").is_none());
+    assert!(view.lookup_symbol(b"ch").is_none());
+    assert_eq!(view.labels_with_prefix("th"), vec![&b"th1"[..]]);
+}
@@ -0,0 +1,104 @@
+//! Reanalysis of a database when its source files change.
+//!
+//! This is the building block for an editor integration which keeps the
+//! diagnostics of a database up to date: `watch` registers the source files
+//! with the operating system's change notifications through the `notify`
+//! crate, and reloads the database each time one of them is written.
+//! Reloading goes through the caches of `segment_set`, so only changed files
+//! are read and parsed again, and the analysis passes only redo the work for
+//! the segments which changed.
+//!
+//! The directories containing the files are watched rather than the files
+//! themselves, since many editors save by writing a new file and renaming it
+//! over the old one.  Events are debounced, so a burst of writes leads to a
+//! single reanalysis.
+
+use database::Database;
+use diag::DiagnosticClass;
+use diag::Notation;
+use notify;
+use notify::DebouncedEvent;
+use notify::RecursiveMode;
+use notify::Watcher;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc::channel;
+use std::time::Duration;
+use util::new_set;
+
+/// How long to wait for further changes before reanalyzing.
+const DEBOUNCE_MS: u64 = 200;
+
+/// Returns a path with its directory canonicalized, which is how the paths
+/// of events are reported.  The file itself need not exist.
+fn canonical(path: &Path) -> PathBuf {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    match (fs::canonicalize(dir), path.file_name()) {
+        (Ok(dir), Some(name)) => dir.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Watches source files and reanalyzes the database whenever one changes.
+///
+/// If `paths` is empty, all files read by the database are watched, including
+/// files which new inclusions add later.  The database must already have been
+/// parsed.  After each reanalysis the callback receives the diagnostics of the
+/// given classes, and returns false to stop watching.  Returns an error if the
+/// files cannot be watched.
+pub fn watch<F>(db: &mut Database,
+                paths: &[String],
+                classes: Vec<DiagnosticClass>,
+                mut callback: F)
+                -> notify::Result<()>
+    where F: FnMut(&mut Database, Vec<Notation>) -> bool
+{
+    let (tx, rx) = channel();
+    let mut watcher = notify::watcher(tx, Duration::from_millis(DEBOUNCE_MS))?;
+    let mut files = new_set();
+    let mut dirs = new_set();
+    loop {
+        let names: Vec<String> = if paths.is_empty() {
+            db.parse_result().file_order().iter().map(|name| name.to_string()).collect()
+        } else {
+            paths.to_vec()
+        };
+        for name in names {
+            let file = canonical(Path::new(&name));
+            if let Some(dir) = file.parent() {
+                if dir.is_dir() && dirs.insert(dir.to_path_buf()) {
+                    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+                }
+            }
+            files.insert(file);
+        }
+
+        loop {
+            let changed = match rx.recv() {
+                Ok(DebouncedEvent::Create(path)) |
+                Ok(DebouncedEvent::Write(path)) |
+                Ok(DebouncedEvent::Remove(path)) => files.contains(&canonical(&path)),
+                Ok(DebouncedEvent::Rename(from, to)) => {
+                    files.contains(&canonical(&from)) || files.contains(&canonical(&to))
+                }
+                Ok(DebouncedEvent::Rescan) => true,
+                Ok(DebouncedEvent::Error(err, _)) => return Err(err),
+                Ok(_) => false,
+                // the watcher has shut down
+                Err(_) => return Ok(()),
+            };
+            if changed {
+                break;
+            }
+        }
+        db.reload();
+        let notations = db.diag_notations(classes.clone());
+        if !callback(db, notations) {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use database::Database;
+use database::DbOptions;
+use diag::DiagnosticClass;
+use std::env;
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+fn axiom(label: &str) -> String {
+    format!("$c X $.\n{} $a X $.\n", label)
+}
+
+#[test]
+fn test_watch_reload() {
+    let dir = env::temp_dir().join(format!("metamath-knife-watch-{}", ::std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("a.mm");
+    fs::write(&path, axiom("ax0")).unwrap();
+    let mut db = Database::new(DbOptions { incremental: true, ..DbOptions::default() });
+    db.parse(path.to_str().unwrap().to_owned(), Vec::new());
+    assert!(db.statement("ax0").is_some());
+
+    let writer = {
+        let path = path.clone();
+        thread::spawn(move || {
+            // leave time to register the watch, then write a burst which is
+            // debounced into one reload, and a later change for a second one
+            thread::sleep(Duration::from_millis(500));
+            for label in &["ax1", "ax2", "ax3"] {
+                fs::write(&path, axiom(label)).unwrap();
+                thread::sleep(Duration::from_millis(20));
+            }
+            thread::sleep(Duration::from_millis(1000));
+            fs::write(&path, axiom("ax4")).unwrap();
+        })
+    };
+    let labels = ["ax0", "ax1", "ax2", "ax3", "ax4"];
+    let mut seen: Vec<&str> = Vec::new();
+    db.watch(&[], vec![DiagnosticClass::Parse], |db, notations| {
+            assert!(notations.is_empty());
+            seen.extend(labels.iter().filter(|label| db.statement(label).is_some()));
+            seen.len() < 2
+        })
+        .unwrap();
+    writer.join().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(seen, vec!["ax3", "ax4"]);
+}